@@ -0,0 +1,48 @@
+//! Time recognising a long input with the items of the parse in ordinary
+//! vectors and in a bump arena. Run with
+//! `cargo bench --bench arena --features arena`.
+
+use std::time::Instant;
+
+use parsey::{grammar, recognise, recognise_in, Bump};
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let grammar = grammar! {
+        Sum -> Sum ["+-"] Product;
+        Sum -> Product;
+        Product -> Product ["*/"] Factor;
+        Product -> Factor;
+        Factor -> "(" Sum ")";
+        Factor -> Number;
+        Number -> ["0123456789"] Number;
+        Number -> ["0123456789"];
+    };
+    let input = format!("1{}", "+(23*4-5)/67".repeat(5_000));
+
+    let start = Instant::now();
+    let mut accepted = false;
+    for _ in 0..ITERATIONS {
+        accepted = recognise(&grammar, &input);
+    }
+    println!(
+        "recognise: {} for {} characters in {:?} per iteration",
+        accepted,
+        input.len(),
+        start.elapsed() / ITERATIONS
+    );
+
+    let start = Instant::now();
+    let mut arena = Bump::new();
+    for _ in 0..ITERATIONS {
+        accepted = recognise_in(&arena, &grammar, &input);
+        arena.reset();
+    }
+    println!(
+        "recognise_in: {} for {} characters in {:?} per iteration",
+        accepted,
+        input.len(),
+        start.elapsed() / ITERATIONS
+    );
+}
@@ -0,0 +1,56 @@
+//! Time recognising many small inputs one after another, comparing
+//! `recognise` with a `Parser` which keeps its buffers between calls. Run with
+//! `cargo bench --bench recognise`.
+
+use std::time::Instant;
+
+use parsey::{grammar, recognise, Parser};
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let grammar = grammar! {
+        Sum -> Sum ["+-"] Product;
+        Sum -> Product;
+        Product -> Product ["*/"] Factor;
+        Product -> Factor;
+        Factor -> "(" Sum ")";
+        Factor -> Number;
+        Number -> ["0123456789"] Number;
+        Number -> ["0123456789"];
+    };
+    // Short expressions like the requests a server might see
+    let inputs = (0..10_000)
+        .map(|i| format!("{}+({}*{})-{}", i, i % 7, i % 13, i / 3))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut accepted = 0;
+    for _ in 0..ITERATIONS {
+        accepted = inputs
+            .iter()
+            .filter(|input| recognise(&grammar, input))
+            .count();
+    }
+    println!(
+        "recognise: {} of {} inputs in {:?} per iteration",
+        accepted,
+        inputs.len(),
+        start.elapsed() / ITERATIONS
+    );
+
+    let start = Instant::now();
+    let mut parser = Parser::new();
+    for _ in 0..ITERATIONS {
+        accepted = inputs
+            .iter()
+            .filter(|input| parser.recognise(&grammar, input))
+            .count();
+    }
+    println!(
+        "Parser::recognise: {} of {} inputs in {:?} per iteration",
+        accepted,
+        inputs.len(),
+        start.elapsed() / ITERATIONS
+    );
+}
@@ -0,0 +1,65 @@
+//! Time recognising single characters, which skips the parser, against
+//! building the parse state for the same input. The grammar is a deep chain of
+//! precedence levels with a digit at the bottom, so every level can make up the
+//! input on its own. Run with `cargo bench --bench short`.
+
+use std::time::Instant;
+
+use parsey::grammar::{Grammar, Rule, Symbol};
+use parsey::{recognise, ParseState};
+
+const ITERATIONS: u32 = 20;
+const LEVELS: usize = 200;
+
+fn level(i: usize) -> Symbol {
+    Symbol::Rule(format!("Level{}", i))
+}
+
+fn main() {
+    // Level0 -> Level0 "~" Level1 | Level1, down to Level200 -> [0-9]
+    let mut rules = Vec::new();
+    for i in 0..LEVELS {
+        let name = format!("Level{}", i);
+        rules.push(Rule::new(
+            name.clone(),
+            vec![level(i), Symbol::Literal('~'), level(i + 1)],
+        ));
+        rules.push(Rule::new(name, vec![level(i + 1)]));
+    }
+    rules.push(Rule::new(
+        format!("Level{}", LEVELS),
+        vec![Symbol::Range('0', '9')],
+    ));
+    let grammar = Grammar::new(rules);
+
+    let inputs = (0..1_000).map(|i| (i % 10).to_string()).collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut accepted = 0;
+    for _ in 0..ITERATIONS {
+        accepted = inputs
+            .iter()
+            .filter(|input| recognise(&grammar, input))
+            .count();
+    }
+    println!(
+        "recognise: {} of {} inputs in {:?} per iteration",
+        accepted,
+        inputs.len(),
+        start.elapsed() / ITERATIONS
+    );
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        accepted = inputs
+            .iter()
+            .filter(|input| ParseState::build(&grammar, input).accepts())
+            .count();
+    }
+    println!(
+        "ParseState::build: {} of {} inputs in {:?} per iteration",
+        accepted,
+        inputs.len(),
+        start.elapsed() / ITERATIONS
+    );
+}
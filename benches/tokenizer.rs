@@ -0,0 +1,42 @@
+//! Time tokenizing input where the tokenizer completes on almost every
+//! character, the case where saving the restart point on each completion
+//! matters most. Run with `cargo bench --bench tokenizer`.
+
+use std::time::Instant;
+
+use parsey::tokenizer::{eat, from_fn, literal, tokenize, State, Token};
+use parsey::{firstof, tokenizers};
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    // Runs of digits separated by spaces, the number tokenizer completes after
+    // every digit and only fails at the next space
+    let input = "1234567890 ".repeat(10_000);
+    let numbers = || {
+        firstof!(
+            from_fn(
+                "number",
+                || (),
+                |(), c| if c.is_ascii_digit() {
+                    State::Completed
+                } else {
+                    State::Failed
+                }
+            ),
+            eat::<Token, _>(literal("space", " "))
+        )
+    };
+
+    let start = Instant::now();
+    let mut tokens = 0;
+    for _ in 0..ITERATIONS {
+        tokens = tokenize(&input, numbers()).unwrap().len();
+    }
+    println!(
+        "tokenize: {} tokens from {} characters in {:?} per iteration",
+        tokens,
+        input.len(),
+        start.elapsed() / ITERATIONS
+    );
+}
@@ -1,15 +1,31 @@
 //! Abstract Syntax Tree construction and manipulation
 
+use std::cell::{Cell, OnceCell};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::iter::empty;
+use std::iter::FusedIterator;
 use std::rc::Rc;
 
-use crate::grammar::{Rule, Symbol};
+use crate::grammar::{Associativity, Grammar, Repetition, Rule, Symbol};
 use crate::state::StateSet;
 use crate::utils::Uncertain;
 
+pub use forest::Forest;
+
+mod forest;
+
 /// A parse tree node
-#[derive(Clone, PartialEq)]
+///
+/// Trees can be as deep as the input is long (e.g. `S -> "a" S;`), so
+/// dropping, cloning, comparing and `Debug` formatting nodes don't recurse.
+/// The other methods which walk the whole tree (e.g. [`Node::map_names`],
+/// [`Node::outline`]) recurse once per level and can overflow the stack for
+/// trees tens of thousands of levels deep.
+///
+/// Because `Node` implements [`Drop`] a pattern can't move the name, children
+/// or grapheme cluster out of a node (`if let Node::Internal { name, children }
+/// = node` is rejected with E0509). Use [`Node::into_internal`] and
+/// [`Node::into_grapheme`] to take a tree apart by value.
 pub enum Node {
     /// An internal tree node, created from a grammar rule
     Internal {
@@ -20,62 +36,689 @@ pub enum Node {
     },
     /// A leaf node, created from a terminal (Literal or OneOf)
     Leaf(char),
+    /// A leaf node created from a Grapheme terminal, holds every character in
+    /// the grapheme cluster
+    Grapheme(String),
 }
 
 impl Node {
     /// Construct an iterator of parse trees from the Earley algorithm state
     pub(crate) fn from_parse_state<'a>(
-        start_symbol: &str,
+        grammar: &Grammar,
         parse_state: &[StateSet<'a>],
         input: Vec<char>,
-    ) -> impl Iterator<Item = Node> + 'a {
-        // To appease the borrow checker
-        let len = input.len();
-        NodeIterator::new(
-            &Rc::new(transpose(parse_state)),
-            &Rc::new(input),
-            start_symbol,
-            0,
-            Uncertain::Known(len),
-        )
+    ) -> impl FusedIterator<Item = Node> + 'a {
+        NodeIterator::new(grammar, parse_state, input, None)
+    }
+
+    /// As `from_parse_state` but trees with more than `max_depth` levels of
+    /// internal nodes are skipped. The flag is set once a tree has been
+    /// skipped because of the limit.
+    pub(crate) fn from_parse_state_with_depth_limit<'a>(
+        grammar: &Grammar,
+        parse_state: &[StateSet<'a>],
+        input: Vec<char>,
+        max_depth: usize,
+    ) -> (impl Iterator<Item = Node> + 'a, Rc<Cell<bool>>) {
+        let limit = DepthLimit {
+            max: max_depth,
+            exceeded: Rc::new(Cell::new(false)),
+        };
+        let exceeded = Rc::clone(&limit.exceeded);
+        let nodes = NodeIterator::new(grammar, parse_state, input, Some(limit));
+        (nodes, exceeded)
+    }
+
+    /// The name and children of an internal node, `None` for a leaf
+    #[must_use]
+    pub fn into_internal(mut self) -> Option<(String, Vec<Node>)> {
+        match &mut self {
+            Node::Internal { name, children } => {
+                Some((std::mem::take(name), std::mem::take(children)))
+            }
+            Node::Leaf(_) | Node::Grapheme(_) => None,
+        }
+    }
+
+    /// The characters of a grapheme cluster leaf, `None` for any other node
+    #[must_use]
+    pub fn into_grapheme(mut self) -> Option<String> {
+        match &mut self {
+            Node::Grapheme(cluster) => Some(std::mem::take(cluster)),
+            Node::Internal { .. } | Node::Leaf(_) => None,
+        }
+    }
+
+    /// Compare the structure of two trees, internal nodes must have the same
+    /// name and children but the characters in leaf nodes are ignored (any
+    /// leaf matches any other leaf). Useful for checking a parse has the
+    /// expected shape without pinning down every character.
+    #[must_use]
+    pub fn same_shape(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Node::Leaf(_) | Node::Grapheme(_), Node::Leaf(_) | Node::Grapheme(_)) => true,
+            (
+                Node::Internal { name, children },
+                Node::Internal {
+                    name: other_name,
+                    children: other_children,
+                },
+            ) => {
+                name == other_name
+                    && children.len() == other_children.len()
+                    && children
+                        .iter()
+                        .zip(other_children)
+                        .all(|(child, other_child)| child.same_shape(other_child))
+            }
+            _ => false,
+        }
+    }
+
+    /// A copy of the tree with the name of every internal node passed through
+    /// `f`, leaves are unchanged. Useful for removing the prefixes added by
+    /// [`Grammar::with_prefix`](crate::grammar::Grammar::with_prefix).
+    #[must_use]
+    pub fn map_names<F>(&self, f: F) -> Node
+    where
+        F: Fn(&str) -> String,
+    {
+        self.map_names_ref(&f)
     }
 
-    // Calculate the length in characters of the node
-    fn len(&self) -> usize {
+    // Takes the function by reference so recursion doesn't instantiate a new
+    // copy of map_names for each level of &F
+    fn map_names_ref<F>(&self, f: &F) -> Node
+    where
+        F: Fn(&str) -> String,
+    {
         match self {
-            // Leaf nodes each hold one character
-            Node::Leaf(_) => 1,
-            // The length of an internal node is the sum of the length of its
-            // children
-            Node::Internal { name: _, children } => children.iter().map(Node::len).sum(),
+            Node::Internal { name, children } => Node::Internal {
+                name: f(name),
+                children: children
+                    .iter()
+                    .map(|child| child.map_names_ref(f))
+                    .collect(),
+            },
+            Node::Leaf(_) | Node::Grapheme(_) => self.clone(),
+        }
+    }
+
+    /// A copy of the tree where every internal node called `name` that is a
+    /// direct child of another node called `name` is replaced by its
+    /// children. This collapses list-like recursive rules such as
+    /// ```text
+    /// Number -> ["0123456789"] Number;
+    /// Number -> ["0123456789"];
+    /// ```
+    /// into a single `Number` node with one child per digit. Left and right
+    /// recursion both flatten, nodes with other names are left in place.
+    #[must_use]
+    pub fn flatten_rule(&self, name: &str) -> Node {
+        match self {
+            Node::Internal {
+                name: node_name,
+                children,
+            } => {
+                let mut flattened = Vec::with_capacity(children.len());
+                for mut child in children.iter().map(|child| child.flatten_rule(name)) {
+                    match &mut child {
+                        // The child has already been flattened so its children
+                        // can be spliced straight in
+                        Node::Internal {
+                            name: child_name,
+                            children,
+                        } if node_name == name && child_name == name => flattened.append(children),
+                        _ => flattened.push(child),
+                    }
+                }
+                Node::Internal {
+                    name: node_name.clone(),
+                    children: flattened,
+                }
+            }
+            Node::Leaf(_) | Node::Grapheme(_) => self.clone(),
+        }
+    }
+
+    /// Render the tree as an indented outline, one node per line with each
+    /// child indented one level further than its parent. Easier to read than
+    /// the `Debug` format for deep trees. Leaf characters are escaped as in
+    /// [`char::escape_debug`] so whitespace such as `\n` stays on one line.
+    /// ```
+    /// # use parsey::{grammar, parse};
+    /// let grammar = grammar! {
+    ///     Pair -> Digit Digit;
+    ///     Digit -> ["0123456789"];
+    /// };
+    /// let tree = parse(&grammar, "42").unwrap().next().unwrap();
+    /// assert_eq!(tree.outline(), "Pair\n    Digit\n        4\n    Digit\n        2");
+    /// ```
+    #[must_use]
+    pub fn outline(&self) -> String {
+        let mut outline = String::new();
+        self.write_outline(&mut outline, 0);
+        outline
+    }
+
+    fn write_outline(&self, outline: &mut String, depth: usize) {
+        outline.push_str(&"    ".repeat(depth));
+        match self {
+            Node::Leaf(c) => outline.extend(c.escape_debug()),
+            Node::Grapheme(cluster) => outline.extend(cluster.escape_debug()),
+            Node::Internal { name, children } => {
+                outline.push_str(name);
+                for child in children {
+                    outline.push('\n');
+                    child.write_outline(outline, depth + 1);
+                }
+            }
+        }
+    }
+
+    /// The leaf node for the characters matched by a terminal
+    fn leaf(matched: &[char]) -> Node {
+        match matched {
+            [c] => Node::Leaf(*c),
+            _ => Node::Grapheme(matched.iter().collect()),
         }
     }
 }
 
-/// Helper function to format a tree
-fn format_node(f: &mut fmt::Formatter<'_>, node: &Node, id: usize) -> fmt::Result {
-    let indent = if id == 0 && !f.alternate() {
-        String::from(" ")
-    } else {
-        "    ".repeat(id)
+/// Select the tree with the requested associativity from the parse forest of
+/// a naturally ambiguous binary operator grammar such as
+/// ```text
+/// E -> E "-" E;
+/// E -> Number;
+/// ```
+/// A binary operator node is an internal node whose first and last children
+/// are internal nodes with the same name as itself. For left associativity the
+/// selected tree has no binary operator node as the last child of another with
+/// the same name, for right associativity none as the first child. Returns
+/// `None` if no tree qualifies.
+pub fn disambiguate_binop<I>(trees: I, associativity: Associativity) -> Option<Node>
+where
+    I: IntoIterator<Item = Node>,
+{
+    trees
+        .into_iter()
+        .find(|tree| has_associativity(tree, associativity))
+}
+
+/// True if none of the binary operator nodes in the tree group the wrong way
+fn has_associativity(node: &Node, associativity: Associativity) -> bool {
+    let (name, children) = match node {
+        Node::Leaf(_) | Node::Grapheme(_) => return true,
+        Node::Internal { name, children } => (name, children),
     };
-    let indent = String::from(if id == 0 { "" } else { "\n" }) + &indent;
+
+    if is_binop(node) {
+        let inner = match associativity {
+            Associativity::Left => children.last(),
+            Associativity::Right => children.first(),
+        };
+        if let Some(
+            inner @ Node::Internal {
+                name: inner_name, ..
+            },
+        ) = inner
+        {
+            if inner_name == name && is_binop(inner) {
+                return false;
+            }
+        }
+    }
+
+    children
+        .iter()
+        .all(|child| has_associativity(child, associativity))
+}
+
+/// True if the node has at least three children and the first and last are
+/// internal nodes with the same name as the node itself
+fn is_binop(node: &Node) -> bool {
     match node {
-        Node::Leaf(c) => write!(f, "{}{}", indent, c),
-        Node::Internal { name, children } => {
-            write!(f, "{}{} {{", indent, name)?;
-            let id = if f.alternate() { id + 1 } else { id };
-            for child in children {
-                format_node(f, child, id)?;
+        Node::Internal { name, children } if children.len() >= 3 => {
+            let same_name = |child: Option<&Node>| matches!(child, Some(Node::Internal { name: n, .. }) if n == name);
+            same_name(children.first()) && same_name(children.last())
+        }
+        _ => false,
+    }
+}
+
+/// A parse tree node annotated with its provenance in the parse state. Each
+/// internal node carries the `start` and `end` of the item it was derived
+/// from, so nodes can be correlated back to the Earley chart.
+///
+/// Dropping a node doesn't recurse, so as with [`Node`] the fields can't be
+/// moved out by a pattern. Cloning, comparing and `Debug` formatting recurse
+/// once per level.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpannedNode {
+    /// An internal tree node, created from a grammar rule
+    Internal {
+        /// The name of the node
+        name: String,
+        /// The start position of the node in the input
+        start: usize,
+        /// The end position (exclusive) of the node in the input
+        end: usize,
+        /// Child nodes
+        children: Vec<SpannedNode>,
+    },
+    /// A leaf node, created from a terminal (`Literal` or `OneOf`)
+    Leaf(char),
+    /// A leaf node created from a `Grapheme` terminal
+    Grapheme(String),
+}
+
+impl SpannedNode {
+    /// Construct an iterator of parse trees from the Earley algorithm state,
+    /// the spans are those of the items each node was produced from
+    pub(crate) fn from_parse_state<'a>(
+        grammar: &Grammar,
+        parse_state: &[StateSet<'a>],
+        input: Vec<char>,
+    ) -> impl FusedIterator<Item = SpannedNode> + 'a {
+        SpannedNodeIterator(NodeIterator::new(grammar, parse_state, input, None))
+    }
+}
+
+impl Drop for SpannedNode {
+    // As for `Node`, the default drop recurses once per level
+    fn drop(&mut self) {
+        if let SpannedNode::Internal { children, .. } = self {
+            let mut stack = std::mem::take(children);
+            while let Some(mut child) = stack.pop() {
+                if let SpannedNode::Internal { children, .. } = &mut child {
+                    stack.append(children);
+                }
             }
-            write!(f, "{}}}", indent)
         }
     }
 }
 
+/// The number of distinct derivations of a rule over a span of the input
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivationCount {
+    /// The name of the rule
+    pub name: String,
+    /// The start position of the span in the input
+    pub start: usize,
+    /// The end position (exclusive) of the span in the input
+    pub end: usize,
+    /// The number of distinct ways the rule can produce the span
+    pub count: usize,
+}
+
+impl DerivationCount {
+    /// True if the rule can produce the span in more than one way
+    #[must_use]
+    pub fn is_ambiguous(&self) -> bool {
+        self.count > 1
+    }
+
+    /// Count the derivations of every rule completed in the parse state,
+    /// sorted by span then rule name. Derivations which pass through a cycle
+    /// (e.g. `A -> B`, `B -> A`) aren't counted and completions left without
+    /// any derivation are omitted.
+    pub(crate) fn from_parse_state(parse_state: &[StateSet<'_>], input: &[char]) -> Vec<Self> {
+        let parse_state = transpose(parse_state);
+        let mut counter = Counter::new(&parse_state, input, HashMap::new());
+
+        let mut result = Vec::new();
+        for (start, items) in parse_state.iter().enumerate() {
+            for item in items {
+                let name = item.rule.name();
+                if result.iter().any(|c: &DerivationCount| {
+                    c.name == name && c.start == start && c.end == item.end
+                }) {
+                    continue;
+                }
+                let count = counter.count(name, start, item.end);
+                if count == 0 {
+                    continue;
+                }
+                result.push(DerivationCount {
+                    name: String::from(name),
+                    start,
+                    end: item.end,
+                    count,
+                });
+            }
+        }
+
+        result.sort_by(|a, b| (a.start, a.end, &a.name).cmp(&(b.start, b.end, &b.name)));
+        result
+    }
+}
+
+/// Memoized derivation counting over the transposed parse state
+struct Counter<'a, 'b> {
+    parse_state: &'b [Vec<Item<'a>>],
+    input: &'b [char],
+    /// `None` marks a count which is currently being computed, encountering
+    /// one means the derivation is cyclic
+    memo: HashMap<(&'a str, usize, usize), Option<usize>>,
+    /// Counts which were in progress when something tried to use them, see
+    /// `skipped_cycles`
+    cycles: Vec<(&'a str, usize, usize)>,
+    /// The number of ways the rest of a body can produce a span from part
+    /// way through a `Symbol::Repeat`. Splitting a span between repetitions
+    /// would otherwise take exponential time
+    repeats: HashMap<(*const Symbol, Cursor, usize, usize), usize>,
+    /// The number of counting steps left, once it reaches 0 every count is 0.
+    /// Also bounds the depth of recursion, which is at most one level per
+    /// step
+    budget: usize,
+}
+
+impl<'a, 'b> Counter<'a, 'b> {
+    fn new(
+        parse_state: &'b [Vec<Item<'a>>],
+        input: &'b [char],
+        memo: HashMap<(&'a str, usize, usize), Option<usize>>,
+    ) -> Self {
+        Counter {
+            parse_state,
+            input,
+            memo,
+            cycles: Vec::new(),
+            repeats: HashMap::new(),
+            budget: usize::MAX,
+        }
+    }
+
+    /// Give up counting after `budget` steps, see `Counter::gave_up`
+    fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// True if the budget ran out, the counts are then meaningless
+    fn gave_up(&self) -> bool {
+        self.budget == 0
+    }
+
+    /// Use up one step of the budget, false if there are none left
+    fn step(&mut self) -> bool {
+        if self.budget == 0 {
+            return false;
+        }
+        self.budget -= 1;
+        true
+    }
+
+    /// True if derivations passing through a cycle have been skipped, so the
+    /// counts are lower than the number of trees the tree iterator produces.
+    /// Cycles through spans the rule can't produce (which the parse state
+    /// can contain) don't skip anything
+    fn skipped_cycles(&self) -> bool {
+        self.cycles
+            .iter()
+            .any(|key| self.memo.get(key).copied().flatten().unwrap_or(0) > 0)
+    }
+
+    /// The number of ways the rule called `name` can produce `start..end`
+    fn count(&mut self, name: &'a str, start: usize, end: usize) -> usize {
+        if let Some(count) = self.memo.get(&(name, start, end)) {
+            return count.unwrap_or(0);
+        }
+        if !self.step() {
+            return 0;
+        }
+        let _ = self.memo.insert((name, start, end), None);
+
+        let parse_state = self.parse_state;
+        let count = parse_state[start]
+            .iter()
+            .filter(|item| item.rule.name() == name && item.end == end)
+            .fold(0_usize, |acc, item| {
+                acc.saturating_add(self.ways(item.rule.body(), Cursor::default(), start, end))
+            });
+
+        let _ = self.memo.insert((name, start, end), Some(count));
+        count
+    }
+
+    /// The number of ways the rest of `body` from `cursor` can produce
+    /// `start..end`
+    fn ways(&mut self, body: &'a [Symbol], cursor: Cursor, start: usize, end: usize) -> usize {
+        let Some(symbol) = body.get(cursor.index) else {
+            return usize::from(start == end);
+        };
+        if !self.step() {
+            return 0;
+        }
+        let key = (std::ptr::from_ref(symbol), cursor, start, end);
+        let repeat = matches!(symbol, Symbol::Repeat { .. });
+        if let Some(&ways) = self.repeats.get(&key).filter(|_| repeat) {
+            return ways;
+        }
+
+        // Matching a repeat again is tried before moving past it
+        let mut ways = match cursor.expected(body) {
+            Some(expected) => self.ways_matching(body, cursor, expected, start, end),
+            None => 0,
+        };
+        if let Some(stopped) = cursor.stopped(body) {
+            ways = ways.saturating_add(self.ways(body, stopped, start, end));
+        }
+
+        if repeat {
+            let _ = self.repeats.insert(key, ways);
+        }
+        ways
+    }
+
+    /// The number of ways `body` can produce `start..end` with `expected`
+    /// (the symbol due at `cursor`) matching first
+    fn ways_matching(
+        &mut self,
+        body: &'a [Symbol],
+        cursor: Cursor,
+        expected: &'a Symbol,
+        start: usize,
+        end: usize,
+    ) -> usize {
+        match expected {
+            Symbol::Rule(name) => {
+                // Try every possible end position for the symbol, spans the
+                // rule can't produce have no derivations
+                let mut ways = 0_usize;
+                for mid in start..=end {
+                    let Some(next) = cursor.matched(body, mid - start) else {
+                        continue;
+                    };
+                    // Trying every end position runs into counts in progress
+                    // that can't be followed by the rest of the body, those
+                    // cycles don't lose any derivations
+                    if let Some(None) = self.memo.get(&(name.as_str(), start, mid)) {
+                        if self.ways(body, next, mid, end) > 0 {
+                            self.cycles.push((name, start, mid));
+                        }
+                        continue;
+                    }
+                    let first = self.count(name, start, mid);
+                    if first == 0 {
+                        continue;
+                    }
+                    ways =
+                        ways.saturating_add(first.saturating_mul(self.ways(body, next, mid, end)));
+                }
+                ways
+            }
+            // Terminals match the same way as when the parse state was built
+            terminal => terminal
+                .scan(&self.input[start..end])
+                .and_then(|width| Some((width, cursor.matched(body, width)?)))
+                .map_or(0, |(width, next)| self.ways(body, next, start + width, end)),
+        }
+    }
+}
+
+/// How far through a rule body the children found for a node have got
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+struct Cursor {
+    /// The position of the symbol being matched in the body
+    index: usize,
+    /// How far matching has got through the symbol at `index`, see
+    /// `Symbol::Repeat`
+    repetition: Repetition,
+    /// Set while an optional repetition of a `Symbol::Repeat` hasn't matched
+    /// any input yet, see `Symbol::is_optional_repetition`
+    empty: bool,
+}
+
+impl Cursor {
+    /// The symbol to match next, `None` at the end of the body or once a
+    /// repeat can't match again
+    fn expected(self, body: &[Symbol]) -> Option<&Symbol> {
+        body.get(self.index)?.expected(self.repetition)
+    }
+
+    /// The cursor after the symbol from `expected` matches `width`
+    /// characters. `None` if that finishes an optional repetition without
+    /// matching anything, nothing could tell the trees with and without it
+    /// apart
+    fn matched(self, body: &[Symbol], width: usize) -> Option<Self> {
+        let symbol = &body[self.index];
+        let empty = width == 0 && (self.empty || symbol.is_optional_repetition(self.repetition));
+        if empty && symbol.expects_inner(self.repetition) {
+            return None;
+        }
+        Some(match symbol.matched(self.repetition) {
+            Some(repetition) => Cursor {
+                index: self.index,
+                repetition,
+                empty,
+            },
+            None => self.next(),
+        })
+    }
+
+    /// The cursor after moving past a `Symbol::Repeat` without matching it
+    /// again, `None` if that isn't allowed here
+    fn stopped(self, body: &[Symbol]) -> Option<Self> {
+        body.get(self.index)?
+            .can_stop(self.repetition)
+            .then(|| self.next())
+    }
+
+    /// The cursor at the start of the next symbol
+    fn next(self) -> Self {
+        Cursor {
+            index: self.index + 1,
+            ..Cursor::default()
+        }
+    }
+}
+
+/// Helper function to format a tree. The nodes still to be written are kept
+/// on a stack along with their depth (always 0 unless formatting with `{:#?}`),
+/// `None` stands for the closing brace of an internal node
+fn format_node(f: &mut fmt::Formatter<'_>, node: &Node) -> fmt::Result {
+    let mut stack = vec![(Some(node), 0)];
+    while let Some((node, id)) = stack.pop() {
+        let indent = if id == 0 && !f.alternate() {
+            String::from(" ")
+        } else {
+            "    ".repeat(id)
+        };
+        let indent = String::from(if id == 0 { "" } else { "\n" }) + &indent;
+        match node {
+            Some(Node::Leaf(c)) => write!(f, "{}{}", indent, c)?,
+            Some(Node::Grapheme(cluster)) => write!(f, "{}{}", indent, cluster)?,
+            Some(Node::Internal { name, children }) => {
+                write!(f, "{}{} {{", indent, name)?;
+                stack.push((None, id));
+                let id = if f.alternate() { id + 1 } else { id };
+                stack.extend(children.iter().rev().map(|child| (Some(child), id)));
+            }
+            None => write!(f, "{}}}", indent)?,
+        }
+    }
+    Ok(())
+}
+
 impl fmt::Debug for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        format_node(f, self, 0)
+        format_node(f, self)
+    }
+}
+
+impl Clone for Node {
+    // The derived clone recurses once per level, copy without recursion as in
+    // `Partial::to_node`
+    fn clone(&self) -> Self {
+        // The ancestors of the node being copied, each with the copies of its
+        // children made so far
+        let mut stack = Vec::new();
+        let mut current = (self, Vec::new());
+        loop {
+            let (node, copied) = current;
+            let copy = match node {
+                Node::Leaf(c) => Node::Leaf(*c),
+                Node::Grapheme(cluster) => Node::Grapheme(cluster.clone()),
+                Node::Internal { name, children } => {
+                    if let Some(child) = children.get(copied.len()) {
+                        stack.push((node, copied));
+                        current = (child, Vec::new());
+                        continue;
+                    }
+                    Node::Internal {
+                        name: name.clone(),
+                        children: copied,
+                    }
+                }
+            };
+            match stack.pop() {
+                Some((parent, mut copied)) => {
+                    copied.push(copy);
+                    current = (parent, copied);
+                }
+                None => return copy,
+            }
+        }
+    }
+}
+
+impl PartialEq for Node {
+    // Compares pairs of nodes from a stack rather than recursing
+    fn eq(&self, other: &Self) -> bool {
+        let mut pairs = vec![(self, other)];
+        while let Some(pair) = pairs.pop() {
+            match pair {
+                (Node::Leaf(c), Node::Leaf(other)) if c == other => (),
+                (Node::Grapheme(cluster), Node::Grapheme(other)) if cluster == other => (),
+                (
+                    Node::Internal { name, children },
+                    Node::Internal {
+                        name: other_name,
+                        children: other_children,
+                    },
+                ) if name == other_name && children.len() == other_children.len() => {
+                    pairs.extend(children.iter().zip(other_children));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Drop for Node {
+    // The default drop recurses once per level. Children are moved onto a
+    // stack so each node is dropped with no children left to recurse into
+    fn drop(&mut self) {
+        if let Node::Internal { children, .. } = self {
+            let mut stack = std::mem::take(children);
+            while let Some(mut child) = stack.pop() {
+                if let Node::Internal { children, .. } = &mut child {
+                    stack.append(children);
+                }
+            }
+        }
     }
 }
 
@@ -108,199 +751,714 @@ fn transpose<'a>(state: &[StateSet<'a>]) -> Vec<Vec<Item<'a>>> {
     result
 }
 
+/// The complete items in the parse state as `(rule, start, end)` grouped by
+/// start position, see [`transpose`]
+pub(crate) fn completions_by_start(state: &[StateSet<'_>]) -> Vec<Vec<(String, usize, usize)>> {
+    transpose(state)
+        .into_iter()
+        .enumerate()
+        .map(|(start, items)| {
+            items
+                .into_iter()
+                .map(|item| (String::from(item.rule.name()), start, item.end))
+                .collect()
+        })
+        .collect()
+}
+
+/// Maximum depth of the trees being enumerated
+struct DepthLimit {
+    /// The maximum number of nested internal nodes
+    max: usize,
+    /// Set if a node was skipped for being too deep
+    exceeded: Rc<Cell<bool>>,
+}
+
 /// Iterator which lazily computes possible parse trees from the transposed
 /// parse state
+///
+/// Each node under construction has a frame, when a frame needs a child node
+/// for a rule it pushes a new frame rather than recursing. Deep trees use heap
+/// rather than call stack, so they can't overflow it.
 struct NodeIterator<'a> {
+    /// The nodes under construction with the root at the bottom. Only the top
+    /// frame is worked on, the one below it is waiting for it to produce a
+    /// node or run out of candidates. Empty once the iterator ends
+    stack: Vec<Frame<'a>>,
+    /// The complete items in the parse state
+    candidates: Candidates<'a>,
+    /// The original input (used for testing leaf node candidates)
+    input: Vec<char>,
+    /// Limit on the depth of the stack, if any
+    limit: Option<DepthLimit>,
+    /// The items being used by the frames on the stack, see `Frame::key`
+    active: HashSet<Key>,
+    /// The transposed parse state, kept to count the trees for `size_hint`
+    parse_state: Vec<Vec<Item<'a>>>,
+    /// The start symbol as it appears in the parse state, `None` if it never
+    /// started (so there are no trees)
+    start_symbol: Option<&'a str>,
+    /// The number of trees produced so far
+    produced: usize,
+    /// The total number of trees, `None` if they can't be counted. Computed
+    /// on the first call to `size_hint`
+    total: OnceCell<Option<usize>>,
+}
+
+/// The most steps spent counting trees for `size_hint`. `collect` asks for the
+/// hint before the first tree is built, for long inputs an exact count can
+/// cost far more than building the parse state did (every split of every
+/// span is tried) so it is abandoned and the hint is left unknown
+const SIZE_HINT_BUDGET: usize = 1 << 12;
+
+/// Identifies an item in the parse state, the rule is compared by address
+type Key = (*const Rule, usize, usize);
+
+/// The complete items in the parse state indexed for finding candidates. Items
+/// covering the same span are in the order their rules were declared, items
+/// with different spans are ordered by end position
+struct Candidates<'a> {
+    /// Items by start position and name
+    by_name: HashMap<(usize, &'a str), Vec<Item<'a>>>,
+    /// Items by start position, name and end position. Looking up items with
+    /// a known end doesn't have to skip all of those ending elsewhere, for
+    /// left recursion that's every prefix of the input
+    by_span: HashMap<(usize, &'a str, usize), Vec<Item<'a>>>,
+}
+
+impl<'a> Candidates<'a> {
+    fn new(grammar: &Grammar, parse_state: &[Vec<Item<'a>>]) -> Self {
+        let mut by_name = HashMap::<_, Vec<_>>::new();
+        let mut by_span = HashMap::<_, Vec<_>>::new();
+        for (start, items) in parse_state.iter().enumerate() {
+            for &item in items {
+                let name = item.rule.name();
+                by_name.entry((start, name)).or_default().push(item);
+                by_span
+                    .entry((start, name, item.end))
+                    .or_default()
+                    .push(item);
+            }
+        }
+
+        // Items are added to the parse state in the order they complete,
+        // which needn't be the order the rules were declared in (e.g. with
+        // `S -> X; S -> Y; X -> Z;` S -> Y completes first as it doesn't wait
+        // for Z). Sort so the first declared alternative is always tried
+        // first. The sorts are stable and transpose orders items by end
+        // position already
+        let order = grammar.rule_order();
+        let declared = |item: &Item<'_>| order.get(&std::ptr::from_ref(item.rule)).copied();
+        for items in by_name.values_mut() {
+            items.sort_by_key(|item| (item.end, declared(item)));
+        }
+        for items in by_span.values_mut() {
+            items.sort_by_key(declared);
+        }
+
+        Candidates { by_name, by_span }
+    }
+
+    /// Items for rule `name` starting at `start` that end at `end`
+    fn find(&self, name: &str, start: usize, end: Uncertain) -> Vec<Item<'a>> {
+        match end {
+            Uncertain::Known(end) => self
+                .by_span
+                .get(&(start, name, end))
+                .cloned()
+                .unwrap_or_default(),
+            Uncertain::Unknown(_) => self
+                .by_name
+                .get(&(start, name))
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter(|item| end.admits(item.end))
+                        .copied()
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A node under construction
+struct Frame<'a> {
     /// The current rule we're trying to produce a node for. If this ever
-    /// becomes `None` the iterator ends
+    /// becomes `None` the frame is exhausted
     current: Option<Item<'a>>,
     /// Any other rules that could produce the target node type
     candidates: Vec<Item<'a>>,
     /// A selection of candidates for the child nodes of this node, acts as a
     /// stack of child nodes in the same order the children appear in the rule
     /// definition. If we find one for each actual child we'll construct and
-    /// yield a node. The iterator attached to each node is used to compute new
-    /// candidates after yielding a node or running out of candidates for a
-    /// later child.
-    progress: Vec<(Node, Box<dyn Iterator<Item = Node> + 'a>)>,
-    /// Shared reference to the transposed parse state
-    parse_state: Rc<Vec<Vec<Item<'a>>>>,
-    /// Shared reference to the original input (used for testing leaf node
-    /// candidates)
-    input: Rc<Vec<char>>,
+    /// produce a node. The frame attached to each internal node is used to
+    /// compute new candidates after producing a node or running out of
+    /// candidates for a later child.
+    progress: Vec<Child<'a>>,
     /// The start position of the text covered by this node in the input
     start: usize,
-    /// The (potentially uncertain) end position of the text covered by this
-    /// node in the input
-    end: Uncertain,
+    /// Set when the current selection of children has been used up (either
+    /// produced a node or failed), the next step is to backtrack
+    backtrack: bool,
+}
+
+/// A node produced by a frame. Children are shared with the frames that
+/// produced them so producing a node doesn't copy the subtree below it, the
+/// tree is only copied into `Node`s once the root is complete
+enum Partial<'a> {
+    Internal {
+        name: &'a str,
+        /// The span of the item the node was produced from
+        start: usize,
+        end: usize,
+        children: Vec<Rc<Partial<'a>>>,
+    },
+    Leaf(Node),
+}
+
+impl Partial<'_> {
+    /// Copy the tree into `Node`s, without recursion
+    fn to_node(&self) -> Node {
+        self.copy(Node::clone, |name, _, _, children| Node::Internal {
+            name: String::from(name),
+            children,
+        })
+    }
+
+    /// Copy the tree into `SpannedNode`s, without recursion
+    fn to_spanned_node(&self) -> SpannedNode {
+        self.copy(
+            |leaf| match leaf {
+                Node::Leaf(c) => SpannedNode::Leaf(*c),
+                Node::Grapheme(cluster) => SpannedNode::Grapheme(cluster.clone()),
+                Node::Internal { .. } => unreachable!("Leaves are never internal nodes"),
+            },
+            |name, start, end, children| SpannedNode::Internal {
+                name: String::from(name),
+                start,
+                end,
+                children,
+            },
+        )
+    }
+
+    /// Copy the tree using `leaf` for the leaves and `internal` to combine the
+    /// copies of a node's children with its name and span
+    fn copy<T>(
+        &self,
+        leaf: impl Fn(&Node) -> T,
+        internal: impl Fn(&str, usize, usize, Vec<T>) -> T,
+    ) -> T {
+        // The ancestors of the node being copied, each with the copies of
+        // its children made so far
+        let mut stack = Vec::new();
+        let mut current = (self, Vec::new());
+        loop {
+            let (partial, copied) = current;
+            let node = match partial {
+                Partial::Leaf(node) => leaf(node),
+                Partial::Internal {
+                    name,
+                    start,
+                    end,
+                    children,
+                } => {
+                    if let Some(child) = children.get(copied.len()) {
+                        stack.push((partial, copied));
+                        current = (&**child, Vec::new());
+                        continue;
+                    }
+                    internal(name, *start, *end, copied)
+                }
+            };
+            match stack.pop() {
+                Some((parent, mut copied)) => {
+                    copied.push(node);
+                    current = (parent, copied);
+                }
+                None => return node,
+            }
+        }
+    }
+}
+
+impl Drop for Partial<'_> {
+    // The default drop recurses once per level
+    fn drop(&mut self) {
+        if let Partial::Internal { children, .. } = self {
+            let mut stack = std::mem::take(children);
+            while let Some(child) = stack.pop() {
+                // Only take the children of nodes nothing else refers to,
+                // shared nodes are dropped by their last owner
+                if let Ok(Partial::Internal { children, .. }) = Rc::try_unwrap(child).as_mut() {
+                    stack.append(children);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Frame<'_> {
+    // Suspended frames are nested once per level of the tree, the default
+    // drop would recurse through all of them
+    fn drop(&mut self) {
+        let mut frames = self
+            .progress
+            .drain(..)
+            .filter_map(|child| child.frame)
+            .collect::<Vec<_>>();
+        while let Some(mut frame) = frames.pop() {
+            frames.extend(frame.progress.drain(..).filter_map(|child| child.frame));
+        }
+    }
+}
+
+/// A child node chosen for a frame
+struct Child<'a> {
+    /// `None` for a choice made at a `Symbol::Repeat` that could either
+    /// match again or stop, see `stop`
+    node: Option<Rc<Partial<'a>>>,
+    /// Length of `node` in characters, kept to avoid walking the node
+    len: usize,
+    /// The frame that produced the node if it was internal, suspended until
+    /// another choice is needed
+    frame: Option<Frame<'a>>,
+    /// For a choice at a `Symbol::Repeat`, whether it stops rather than
+    /// matching again
+    stop: bool,
+}
+
+impl Child<'_> {
+    fn leaf(input: &[char]) -> Self {
+        Child {
+            node: Some(Rc::new(Partial::Leaf(Node::leaf(input)))),
+            len: input.len(),
+            frame: None,
+            stop: false,
+        }
+    }
+
+    /// The choice to either match a `Symbol::Repeat` again or stop
+    fn choice(stop: bool) -> Self {
+        Child {
+            node: None,
+            len: 0,
+            frame: None,
+            stop,
+        }
+    }
+
+    /// True for the choice to match a `Symbol::Repeat` again
+    fn is_again(&self) -> bool {
+        self.node.is_none() && !self.stop
+    }
+}
+
+/// The result of working on the top frame
+enum Step<'a> {
+    /// A new frame is needed for a child node produced by the named rule
+    /// between the positions given
+    Descend(&'a str, usize, Uncertain),
+    /// A suspended frame for a child node needs to run again to produce
+    /// another node
+    Resume(Frame<'a>),
+    /// The frame produced a node covering the given number of characters
+    Produced(Rc<Partial<'a>>, usize),
+    /// The frame has no more nodes to produce
+    Exhausted,
 }
 
 impl<'a> NodeIterator<'a> {
     fn new(
-        parse_state: &Rc<Vec<Vec<Item<'a>>>>,
-        input: &Rc<Vec<char>>,
+        grammar: &Grammar,
+        parse_state: &[StateSet<'a>],
+        input: Vec<char>,
+        limit: Option<DepthLimit>,
+    ) -> Self {
+        let parse_state = transpose(parse_state);
+        let candidates = Candidates::new(grammar, &parse_state);
+        let start_symbol = parse_state.first().and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.rule.name())
+                .find(|&name| name == grammar.start_symbol())
+        });
+        let active = HashSet::new();
+        let root = Frame::new(
+            &candidates,
+            &active,
+            grammar.start_symbol(),
+            0,
+            Uncertain::Known(input.len()),
+            1,
+            limit.as_ref(),
+        );
+        let mut nodes = NodeIterator {
+            stack: Vec::new(),
+            candidates,
+            input,
+            limit,
+            active,
+            parse_state,
+            start_symbol,
+            produced: 0,
+            total: OnceCell::new(),
+        };
+        nodes.push(root);
+        nodes
+    }
+
+    fn push(&mut self, frame: Frame<'a>) {
+        if let Some(key) = frame.key() {
+            let _ = self.active.insert(key);
+        }
+        self.stack.push(frame);
+    }
+
+    /// Pop the top frame, which has finished (for now)
+    fn pop(&mut self) -> Option<Frame<'a>> {
+        let frame = self.stack.pop()?;
+        if let Some(key) = frame.key() {
+            let _ = self.active.remove(&key);
+        }
+        Some(frame)
+    }
+
+    /// The total number of trees, `None` if some derivations are cyclic or
+    /// counting them took more than `SIZE_HINT_BUDGET` steps
+    fn total(&self) -> Option<usize> {
+        *self.total.get_or_init(|| {
+            let Some(start_symbol) = self.start_symbol else {
+                return Some(0);
+            };
+            let mut counter = Counter::new(&self.parse_state, &self.input, HashMap::new())
+                .with_budget(SIZE_HINT_BUDGET);
+            let total = counter.count(start_symbol, 0, self.input.len());
+            (!counter.skipped_cycles() && !counter.gave_up()).then_some(total)
+        })
+    }
+}
+
+impl<'a> NodeIterator<'a> {
+    /// The next tree, not yet copied out of the frames that produced it
+    fn next_tree(&mut self) -> Option<Rc<Partial<'a>>> {
+        // Repeat until the root frame produces a node
+        loop {
+            // End the iterator once the root frame is exhausted. The frame may
+            // change candidate while it works, it is taken out of the active
+            // items and put back afterwards if it is still on the stack
+            let mut frame = self.pop()?;
+            match frame.step(&self.input) {
+                Step::Descend(name, start, end) => {
+                    self.push(frame);
+                    let child = Frame::new(
+                        &self.candidates,
+                        &self.active,
+                        name,
+                        start,
+                        end,
+                        self.stack.len() + 1,
+                        self.limit.as_ref(),
+                    );
+                    self.push(child);
+                }
+                Step::Resume(child) => {
+                    self.push(frame);
+                    self.push(child);
+                }
+                // The root frame stays on the stack to produce the next tree
+                Step::Produced(node, _) if self.stack.is_empty() => {
+                    self.push(frame);
+                    self.produced += 1;
+                    return Some(node);
+                }
+                // Hand the node to the waiting parent along with the frame
+                // that produced it, to be resumed if the parent backtracks
+                Step::Produced(node, len) => {
+                    if let Some(parent) = self.stack.last_mut() {
+                        parent.progress.push(Child {
+                            node: Some(node),
+                            len,
+                            frame: Some(frame),
+                            stop: false,
+                        });
+                    }
+                }
+                // The parent can't find a node for this child, it has to
+                // backtrack
+                Step::Exhausted => {
+                    if let Some(parent) = self.stack.last_mut() {
+                        parent.backtrack = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for NodeIterator<'_> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_tree().map(|tree| tree.to_node())
+    }
+
+    /// Exact while every tree is counted. Cyclic derivations can't be counted
+    /// and counting large parses is abandoned (see `SIZE_HINT_BUDGET`) so
+    /// there's no hint beyond the default for them, and trees skipped by a
+    /// depth limit mean only the upper bound is known
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.stack.is_empty() {
+            return (0, Some(0));
+        }
+        let Some(total) = self.total() else {
+            return (0, None);
+        };
+        let remaining = total.saturating_sub(self.produced);
+        // A saturated count is only a lower bound on the number of trees
+        let upper = (total != usize::MAX).then_some(remaining);
+        let lower = if self.limit.is_some() { 0 } else { remaining };
+        (lower, upper)
+    }
+}
+
+// Once the root frame is exhausted the stack stays empty
+impl FusedIterator for NodeIterator<'_> {}
+
+/// As `NodeIterator` but producing `SpannedNode`s
+struct SpannedNodeIterator<'a>(NodeIterator<'a>);
+
+impl Iterator for SpannedNodeIterator<'_> {
+    type Item = SpannedNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_tree().map(|tree| tree.to_spanned_node())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl FusedIterator for SpannedNodeIterator<'_> {}
+
+impl<'a> Frame<'a> {
+    /// A frame for a node at `depth` (the root is at depth 1) produced by the
+    /// rule `name` between `start` and `end`
+    fn new(
+        candidates: &Candidates<'a>,
+        active: &HashSet<Key>,
         name: &str,
         start: usize,
         end: Uncertain,
+        depth: usize,
+        limit: Option<&DepthLimit>,
     ) -> Self {
-        let mut candidates =
-            // Check for candidates that start at the correct position ...
-            parse_state[start].iter()
-            // ... with a matching name ...
-            .filter(|item| item.rule.name() == name)
-            // ... and isn't too long
-            .filter(|item| match end {
-                Uncertain::Known(e) => item.end == e,
-                Uncertain::Unknown(e) => item.end <= e
-            })
-            .copied().collect::<Vec<_>>();
+        // Candidates that start at the correct position with a matching name
+        // that aren't too long. A candidate already in use by an ancestor
+        // would make the node its own descendant, only possible with a
+        // cyclic grammar and never ending
+        let mut candidates = candidates.find(name, start, end);
+        candidates.retain(|item| !active.contains(&item_key(item, start)));
 
-        // Reverse to treat it as a stack of candidates. This favors items which
-        // appear earlier in the parse state. Because of how the parse state is
-        // constructed this winds up favoring rules defined higher up the
-        // grammar
+        // Any candidate would produce a node that is too deep, drop them all
+        // and record that trees were skipped
+        if let Some(limit) = limit {
+            if depth > limit.max && !candidates.is_empty() {
+                limit.exceeded.set(true);
+                candidates.clear();
+            }
+        }
+
+        // Reverse to treat it as a stack of candidates, they are tried in the
+        // order `Candidates` sorts them: by end position then with the rule
+        // that comes first in `Grammar::rule_order` first
         candidates.reverse();
 
         // First candidate or None if no candidate was found
         let current = candidates.pop();
 
-        NodeIterator {
+        Frame {
             current,
             candidates,
             progress: Vec::new(),
-            parse_state: Rc::clone(parse_state),
-            input: Rc::clone(input),
             start,
-            end,
+            backtrack: false,
         }
     }
 
-    /// Backtrack to the nearest decision point with choices remaining. May
-    /// reduce the length of `self.progress`. If `self.progress` empties
-    /// completely a new candidate is popped from `self.candidates` into
-    /// `self.current`
-    fn step(&mut self) {
-        // Repeat until we find a non-exhausted decision point or choose another
-        // candidate
+    /// The item this frame is currently using, if any
+    fn key(&self) -> Option<Key> {
+        self.current.map(|item| item_key(&item, self.start))
+    }
+
+    /// Work on this frame until it produces a node, runs out of candidates or
+    /// needs a child frame to run
+    fn step(&mut self, input: &[char]) -> Step<'a> {
         loop {
-            if let Some((_, mut iter)) = self.progress.pop() {
-                if let Some(node) = iter.next() {
-                    // New decision made, pass back to the main algorithm to see
-                    // if we have enough children for a node
-                    self.progress.push((node, iter));
-                    return;
+            if self.backtrack {
+                self.backtrack = false;
+                // Backtrack to the nearest decision point with choices
+                // remaining. Leaves have no other choices, internal nodes
+                // resume the frame that produced them and a repeat that was
+                // going to match again can stop instead
+                let mut resumed = false;
+                while let Some(child) = self.progress.pop() {
+                    if let Some(frame) = child.frame {
+                        return Step::Resume(frame);
+                    }
+                    if child.is_again() {
+                        self.progress.push(Child::choice(true));
+                        resumed = true;
+                        break;
+                    }
                 }
-                // No more candidates for this decision point, throw it away and
-                // work on the one below
-            } else {
                 // Exausted all of the decision points for the current
                 // candidate, select the next one (or None if there isn't
-                // another one) and pass back to the main algorithm to restart
-                // the search
-                self.current = self.candidates.pop();
-                return;
+                // another one) and restart the search
+                if !resumed {
+                    self.current = self.candidates.pop();
+                }
             }
-        }
-    }
-}
 
-impl Iterator for NodeIterator<'_> {
-    type Item = Node;
-
-    #[allow(clippy::option_if_let_else)]
-    fn next(&mut self) -> Option<Self::Item> {
-        // Repeat until we produce a node
-        loop {
-            // End the iterator if there is no current candidate
-            let current = self.current?;
+            let Some(current) = self.current else {
+                return Step::Exhausted;
+            };
             let body = current.rule.body();
+            // Advance the start position by the known length of all the nodes
+            // we've found so far
+            let child_start =
+                self.start + self.progress.iter().map(|child| child.len).sum::<usize>();
+            // Work out which symbol the children found so far have got up to.
+            // A child that doesn't fit (e.g. an empty repetition that could
+            // be left out) can't be used
+            let Some(cursor) = self.cursor(body) else {
+                self.backtrack = true;
+                continue;
+            };
 
-            if self.progress.len() == body.len() {
-                // Constructed a full set of children, make a copy ...
+            if cursor.index == body.len() {
+                // Whatever happens this selection of children is finished with
+                self.backtrack = true;
+                // A terminal at the end of the rule doesn't check where it
+                // ends, so the children may stop short of the candidate (e.g.
+                // `Number -> Number ["0123456789"]` with too short an inner
+                // Number)
+                if child_start != current.end {
+                    continue;
+                }
+                // Constructed a full set of children, produce a node sharing
+                // them
                 let children = self
                     .progress
                     .iter()
-                    .map(|(n, _)| n)
-                    .cloned()
-                    .collect::<Vec<_>>();
-                // ... step to the next state ...
-                self.step();
-                // ... and return the node
-                return Some(Node::Internal {
-                    name: String::from(current.rule.name()),
-                    children,
-                });
+                    .filter_map(|child| child.node.as_ref().map(Rc::clone))
+                    .collect();
+                return Step::Produced(
+                    Rc::new(Partial::Internal {
+                        name: current.rule.name(),
+                        start: self.start,
+                        end: current.end,
+                        children,
+                    }),
+                    current.end - self.start,
+                );
             }
 
-            // Symbol we need to produce a child node for
-            let current_symbol = &body[self.progress.len()];
-            // The part of the rule we don't yet have child nodes for
-            let rest = &body[self.progress.len() + 1..];
-
-            // Advance the start position by the known length of all the nodes
-            // we've found so far
-            let child_start = self.start + length(&self.progress[..]);
-            let child_end = if self.progress.len() == body.len() - 1 {
-                // If the current symbol is the last one the end marker is the
-                // same as the current one
-                self.end
-            } else {
-                // Otherwise calculate an (uncertain) lower bound on the length
-                // of the remaining symbols and subtract from the end position,
-                // always results in an uncertain value
-                self.end - lowerbound_length(rest)
+            // Symbol we need to produce a child node for, a repeat which can
+            // either match again or stop tries matching again first
+            let Some(expected) = cursor.expected(body) else {
+                self.progress.push(Child::choice(true));
+                continue;
             };
+            let again = self.progress.last().is_some_and(Child::is_again);
+            if cursor.stopped(body).is_some() && !again {
+                self.progress.push(Child::choice(false));
+                continue;
+            }
+            // The part of the rule we don't yet have child nodes for
+            let rest = &body[cursor.index + 1..];
 
-            match current_symbol {
-                Symbol::Rule(name) => {
-                    // Dispatch to a sub iterator to handle internal nodes
-                    let mut nodes = NodeIterator::new(
-                        &Rc::clone(&self.parse_state),
-                        &Rc::clone(&self.input),
-                        name,
-                        child_start,
-                        child_end,
-                    );
+            // The current candidate ends at a known position even if the
+            // bound we were given is uncertain. Using the bound here would let
+            // this candidate produce nodes of other lengths, duplicating nodes
+            // produced by the other candidates. Subtract a lower bound on the
+            // length of the remaining symbols to get the child's end, any
+            // more repetitions make it uncertain
+            let mut child_end = Uncertain::Known(current.end) - lowerbound_length(rest);
+            if let Symbol::Repeat { .. } = body[cursor.index] {
+                child_end = Uncertain::Unknown(child_end.value());
+            }
 
-                    // Fail if we can't find at least one node
-                    if let Some(node) = nodes.next() {
-                        self.progress.push((node, Box::new(nodes)));
-                    } else {
-                        self.step();
-                    }
-                }
+            match expected {
+                // Internal nodes are produced by a new frame
+                Symbol::Rule(name) => return Step::Descend(name, child_start, child_end),
                 // Terminal symbols have a have no alternate choices and fail
                 // immediately if the input doesn't match what is expected
-                Symbol::Literal(c) => {
-                    if self.input[child_start] == *c {
-                        self.progress.push((Node::Leaf(*c), Box::new(empty())));
+                terminal => {
+                    let input = input.get(child_start..).unwrap_or_default();
+                    if let Some(width) = terminal.scan(input) {
+                        self.progress.push(Child::leaf(&input[..width]));
                     } else {
-                        self.step();
-                    }
-                }
-                Symbol::OneOf(chars) => {
-                    if chars.contains(&self.input[child_start]) {
-                        self.progress
-                            .push((Node::Leaf(self.input[child_start]), Box::new(empty())));
-                    } else {
-                        self.step();
+                        self.backtrack = true;
                     }
                 }
             }
         }
     }
+
+    /// Replay the children found so far through `body`, `None` if they
+    /// don't fit
+    fn cursor(&self, body: &[Symbol]) -> Option<Cursor> {
+        self.progress
+            .iter()
+            .try_fold(Cursor::default(), |cursor, child| match child.node {
+                Some(_) => cursor.matched(body, child.len),
+                None if child.stop => cursor.stopped(body),
+                None => Some(cursor),
+            })
+    }
 }
 
-/// Helper function to calculate the length of the nodes currently in the
-/// progress stack
-fn length<'a>(nodes: &[(Node, Box<dyn Iterator<Item = Node> + 'a>)]) -> usize {
-    nodes.iter().map(|(n, _)| n.len()).sum()
+/// The key for `item`, which starts at `start`
+fn item_key(item: &Item<'_>, start: usize) -> Key {
+    (std::ptr::from_ref(item.rule), start, item.end)
 }
 
 /// Helper function to calculate a lower bound on the number of characters
-/// needed for a sequence of symbols
+/// needed for a sequence of symbols. The bound is exact if the sequence is
+/// all terminals
 fn lowerbound_length(items: &[Symbol]) -> Uncertain {
-    // Assume the minimum length of all symbols is 1 TODO: This is probably
-    // wrong given nullable rules
-    Uncertain::Unknown(items.len())
+    // Terminals consume a fixed number of characters, one for everything but
+    // a grapheme cluster which consumes all of its characters and a newline
+    // which consumes one or two. Rules (and repeats) may be nullable so can't
+    // be assumed to consume anything, overestimating here would rule out valid
+    // trees.
+    let terminals = items
+        .iter()
+        .map(|symbol| match symbol {
+            Symbol::Rule(_) | Symbol::Repeat { .. } => 0,
+            Symbol::Grapheme(cluster) => cluster.chars().count(),
+            Symbol::Literal(_)
+            | Symbol::OneOf(_)
+            | Symbol::Range(..)
+            | Symbol::AnyChar
+            | Symbol::Not(_)
+            | Symbol::Newline => 1,
+        })
+        .sum();
+    if items.iter().any(|symbol| {
+        matches!(
+            symbol,
+            Symbol::Rule(_) | Symbol::Repeat { .. } | Symbol::Newline
+        )
+    }) {
+        Uncertain::Unknown(terminals)
+    } else {
+        // Knowing exactly where the child before these ends stops it
+        // producing every shorter node just for them to be thrown away, which
+        // is what makes left recursion expensive
+        Uncertain::Known(terminals)
+    }
 }
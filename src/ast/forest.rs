@@ -0,0 +1,186 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use super::{transpose, Counter, Cursor, Item, Node};
+use crate::grammar::{Grammar, Symbol};
+use crate::state::StateSet;
+
+type Memo<'a> = HashMap<(&'a str, usize, usize), Option<usize>>;
+
+/// Every parse tree of an input, addressable by index. Where
+/// [`parse`](crate::parse) only produces trees in sequence this can go straight
+/// to the k-th tree using the number of derivations below each completion,
+/// useful for sampling from heavily ambiguous parses. Indices follow the order
+/// of [`parse`](crate::parse). Derivations which pass through a cycle (e.g.
+/// `A -> B`, `B -> A`) aren't counted, see
+/// [`derivation_counts`](crate::derivation_counts), so the orders only agree
+/// for grammars without cycles.
+#[derive(Debug)]
+pub struct Forest<'a> {
+    start_symbol: &'a str,
+    parse_state: Vec<Vec<Item<'a>>>,
+    input: Vec<char>,
+    /// Derivation counts shared between lookups
+    memo: RefCell<Memo<'a>>,
+}
+
+impl<'a> Forest<'a> {
+    pub(crate) fn from_parse_state(
+        grammar: &'a Grammar,
+        parse_state: &[StateSet<'a>],
+        input: Vec<char>,
+    ) -> Self {
+        // The same order the tree iterator tries candidates in, by end
+        // position then by declaration
+        let order = grammar.rule_order();
+        let mut parse_state = transpose(parse_state);
+        for items in &mut parse_state {
+            items
+                .sort_by_key(|item| (item.end, order.get(&std::ptr::from_ref(item.rule)).copied()));
+        }
+        Forest {
+            start_symbol: grammar.start_symbol(),
+            parse_state,
+            input,
+            memo: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The number of trees in the forest
+    #[must_use]
+    pub fn tree_count(&self) -> usize {
+        self.with_counter(|forest, counter| {
+            counter.count(forest.start_symbol, 0, forest.input.len())
+        })
+    }
+
+    /// The tree at `index` in the order [`parse`](crate::parse) produces
+    /// them, `None` if there are `index` or fewer trees
+    #[must_use]
+    pub fn tree_at(&self, mut index: usize) -> Option<Node> {
+        self.with_counter(|forest, counter| {
+            let end = forest.input.len();
+            for item in forest.items(forest.start_symbol, 0, end) {
+                let trees = counter.ways(item.rule.body(), Cursor::default(), 0, end);
+                if index < trees {
+                    return forest.node(counter, item, 0, index);
+                }
+                index -= trees;
+            }
+            None
+        })
+    }
+
+    /// Run `f` with a counter using the shared memo
+    fn with_counter<T>(&self, f: impl FnOnce(&Self, &mut Counter<'a, '_>) -> T) -> T {
+        let mut counter = Counter::new(&self.parse_state, &self.input, self.memo.take());
+        let result = f(self, &mut counter);
+        let _ = self.memo.replace(counter.memo);
+        result
+    }
+
+    /// The tree at `index` among the trees of the completed `item` starting at
+    /// `start`
+    fn node(
+        &self,
+        counter: &mut Counter<'a, '_>,
+        item: Item<'a>,
+        start: usize,
+        index: usize,
+    ) -> Option<Node> {
+        let mut children = Vec::new();
+        self.children(
+            counter,
+            item.rule.body(),
+            Cursor::default(),
+            start,
+            item.end,
+            index,
+            &mut children,
+        )?;
+        Some(Node::Internal {
+            name: String::from(item.rule.name()),
+            children,
+        })
+    }
+
+    /// Push the children at `index` among the ways the rest of `body` from
+    /// `cursor` can produce `start..end` onto `children`. Later symbols vary
+    /// fastest, as they do when backtracking through the trees in sequence,
+    /// and a repeat matching again comes before it stopping
+    #[allow(clippy::too_many_arguments)]
+    fn children(
+        &self,
+        counter: &mut Counter<'a, '_>,
+        body: &'a [Symbol],
+        cursor: Cursor,
+        start: usize,
+        end: usize,
+        mut index: usize,
+        children: &mut Vec<Node>,
+    ) -> Option<()> {
+        if cursor.index == body.len() {
+            return (start == end && index == 0).then_some(());
+        }
+
+        match cursor.expected(body) {
+            Some(Symbol::Rule(name)) => {
+                for mid in start..=end {
+                    let Some(next) = cursor.matched(body, mid - start) else {
+                        continue;
+                    };
+                    let rest_trees = counter.ways(body, next, mid, end);
+                    if rest_trees == 0 {
+                        continue;
+                    }
+                    for item in self.items(name, start, mid) {
+                        let trees = counter
+                            .ways(item.rule.body(), Cursor::default(), start, mid)
+                            .saturating_mul(rest_trees);
+                        if index < trees {
+                            children.push(self.node(counter, item, start, index / rest_trees)?);
+                            let index = index % rest_trees;
+                            return self.children(counter, body, next, mid, end, index, children);
+                        }
+                        index -= trees;
+                    }
+                }
+            }
+            Some(terminal) => {
+                let matched = terminal
+                    .scan(&self.input[start..end])
+                    .and_then(|width| Some((width, cursor.matched(body, width)?)));
+                if let Some((width, next)) = matched {
+                    let trees = counter.ways(body, next, start + width, end);
+                    if index < trees {
+                        children.push(Node::leaf(&self.input[start..start + width]));
+                        return self.children(
+                            counter,
+                            body,
+                            next,
+                            start + width,
+                            end,
+                            index,
+                            children,
+                        );
+                    }
+                    index -= trees;
+                }
+            }
+            None => (),
+        }
+
+        let stopped = cursor.stopped(body)?;
+        self.children(counter, body, stopped, start, end, index, children)
+    }
+
+    /// The completed items for the rule called `name` spanning `start..end`
+    /// in declaration order
+    fn items(&self, name: &str, start: usize, end: usize) -> Vec<Item<'a>> {
+        self.parse_state[start]
+            .iter()
+            .filter(|item| item.rule.name() == name && item.end == end)
+            .copied()
+            .collect()
+    }
+}
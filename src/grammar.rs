@@ -1,52 +1,407 @@
 //! Grammar representation
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use syntax_abuse::do_while;
 
+use crate::NonEmptyHashSet;
+
+pub use bnf::BnfError;
+pub use check::{GrammarError, GrammarWarning};
+pub use list::TrailingSeparator;
+pub use precedence::{Associativity, Precedence};
 pub use rule::Rule;
 pub use symbol::Symbol;
 
+pub(crate) use symbol::Repetition;
+
+mod bnf;
+mod check;
+mod clusters;
+mod first;
+mod language;
+mod layout;
+mod list;
+mod merge;
+mod minimize;
+mod precedence;
+mod predicate;
+mod rename;
 mod rule;
 mod symbol;
 
 /// Grammar suitable for Earley parsing
-#[derive(Debug, PartialEq)]
+///
+/// Cloning a grammar copies its precomputed analysis (e.g. the set of
+/// nullable rules) rather than recomputing it
+#[derive(Debug, Clone, PartialEq)]
 pub struct Grammar {
     rules: Vec<Rule>,
     nullables: HashSet<String>,
+    units: Units,
+    predicates: HashMap<String, predicate::Predicate>,
+    start: String,
 }
 
 impl Grammar {
     /// Construct a new grammar from a list of rules. The first rule in the list
     /// is the root rule. Alternations (`A -> B | C`) are not supported, instead
-    /// create several rules with the same name (`A -> B` and `A -> C`)
+    /// create several rules with the same name (`A -> B` and `A -> C`), the
+    /// [`grammar!`](crate::grammar!) macro does this for groups such as
+    /// `A -> X (B | C)`. When several alternatives match the same input the
+    /// one with the highest precedence (see [`Rule::with_precedence`]) is
+    /// preferred, then the one declared first, see [`parse`](crate::parse).
+    /// References to rules that don't exist are allowed, the alternatives
+    /// using them never match. Use [`Grammar::try_new`] to reject them.
     ///
     /// # Panics
-    /// If the rule list is empty
+    /// If the rule list is empty, or a rule negates a rule, repeat, grapheme
+    /// cluster or newline with [`Symbol::Not`]
     #[must_use]
     pub fn new(rules: Vec<Rule>) -> Self {
         assert!(!rules.is_empty(), "A grammar must have at least one rule");
+        assert!(
+            !rules
+                .iter()
+                .flat_map(Rule::body)
+                .any(Symbol::has_invalid_negation),
+            "Only single character terminals can be negated, not rules, repeats, grapheme \
+             clusters or newlines"
+        );
         let nullables = find_nullable_rules(&rules);
-        Grammar { rules, nullables }
+        let units = find_units(&rules, &nullables);
+        let start = String::from(rules[0].name());
+        Grammar {
+            rules,
+            nullables,
+            units,
+            predicates: HashMap::new(),
+            start,
+        }
     }
 
-    pub(crate) fn start_symbol(&self) -> &str {
-        self.rules[0].name()
+    /// Construct a grammar from its textual representation. The format mirrors
+    /// the [`grammar!`](crate::grammar!) macro with the addition of `#` line
+    /// comments:
+    /// ```
+    /// # use parsey::grammar::Grammar;
+    /// let grammar = Grammar::from_bnf(
+    ///     "# Digits
+    ///      Number -> [\"0123456789\"] Number;
+    ///      Number -> [\"0123456789\"];"
+    /// );
+    /// assert!(grammar.is_ok());
+    /// ```
+    ///
+    /// # Errors
+    /// If the source is malformed or contains no rules
+    pub fn from_bnf(source: &str) -> Result<Self, BnfError> {
+        bnf::parse(source)
     }
 
-    pub(crate) fn get_rules_by_name(&self, name: &str) -> Vec<&Rule> {
-        self.rules
+    /// Build lexical rules for scannerless parsing. Each `(name, pattern)`
+    /// pair produces rules named `name` which match one or more characters from
+    /// `pattern`. The rules can be used as if they were terminals in a larger
+    /// grammar, removing the need for a separate tokenizer:
+    /// ```
+    /// # use parsey::{grammar::{Grammar, Rule}, recognise, rule};
+    /// let mut rules = vec![
+    ///     rule!(Assignment -> Ident "=" Number),
+    /// ];
+    /// rules.extend(Grammar::tokens(&[
+    ///     ("Ident", "abcdefghijklmnopqrstuvwxyz"),
+    ///     ("Number", "0123456789"),
+    /// ]));
+    /// let grammar = Grammar::new(rules);
+    /// assert!(recognise(&grammar, "answer=42"));
+    /// ```
+    ///
+    /// # Panics
+    /// If any pattern is empty
+    #[must_use]
+    pub fn tokens(definitions: &[(&str, &str)]) -> Vec<Rule> {
+        definitions
             .iter()
-            .filter(|rule| rule.name() == name)
-            .collect::<Vec<_>>()
+            .flat_map(|(name, pattern)| {
+                let chars = Symbol::OneOf(NonEmptyHashSet::new(pattern.chars().collect()));
+                vec![
+                    Rule::new(
+                        String::from(*name),
+                        vec![chars.clone(), Symbol::Rule(String::from(*name))],
+                    ),
+                    Rule::new(String::from(*name), vec![chars]),
+                ]
+            })
+            .collect()
+    }
+
+    /// The symbols (both rules and terminals) which could come next after
+    /// `partial_input`, the foundation for grammar driven autocompletion. If
+    /// `partial_input` can't be extended to a sentence in the language the
+    /// result is empty.
+    #[must_use]
+    pub fn possible_next_symbols<S>(&self, partial_input: S) -> Vec<Symbol>
+    where
+        S: AsRef<str>,
+    {
+        crate::ParseState::build(self, partial_input).possible_next_symbols()
+    }
+
+    /// Find rules with alternatives that share a common prefix, e.g.
+    /// `A -> "x" B` and `A -> "x" C`. The parser predicts and scans the shared
+    /// prefix once per alternative, factoring it out into a separate rule
+    /// avoids the redundant work. Returns the rule name and the longest prefix
+    /// shared by each pair of alternatives (each reported once).
+    #[must_use]
+    pub fn left_factoring_opportunities(&self) -> Vec<(String, Vec<Symbol>)> {
+        let mut result: Vec<(String, Vec<Symbol>)> = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            for other in self.rules[i + 1..]
+                .iter()
+                .filter(|other| other.name() == rule.name())
+            {
+                let prefix = rule
+                    .body()
+                    .iter()
+                    .zip(other.body())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a.clone())
+                    .collect::<Vec<_>>();
+                if prefix.is_empty() {
+                    continue;
+                }
+
+                let opportunity = (String::from(rule.name()), prefix);
+                if !result.contains(&opportunity) {
+                    result.push(opportunity);
+                }
+            }
+        }
+        result
+    }
+
+    /// The number of alternatives of the start symbol, i.e. the rules sharing
+    /// its name
+    #[must_use]
+    pub fn start_symbol_alternatives_count(&self) -> usize {
+        self.get_rules_by_name(self.start_symbol()).count()
+    }
+
+    /// The positions in the rule list of alternatives of the start symbol
+    /// which are separated from the rest by other rules. Alternatives are
+    /// usually written together, one declared far down the list is easy to
+    /// mistake for a rule of its own when the start symbol is implicitly the
+    /// first rule's name. Empty if the alternatives are all adjacent.
+    #[must_use]
+    pub fn scattered_start_alternatives(&self) -> Vec<usize> {
+        let start = self.start_symbol();
+        let mut positions = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.name() == start)
+            .map(|(i, _)| i);
+        // The first run of alternatives is where the start symbol is
+        // defined, anything after a gap is scattered
+        let mut previous = positions.next();
+        positions
+            .skip_while(|&i| {
+                let adjacent = previous.is_some_and(|previous| i == previous + 1);
+                previous = Some(i);
+                adjacent
+            })
+            .collect()
+    }
+
+    /// The number of times each rule is referenced across all rule bodies,
+    /// including references from its own rules. Rules which are never
+    /// referenced (such as the start symbol usually) are included with a
+    /// count of zero, references to undefined rules are counted too.
+    #[must_use]
+    pub fn symbol_usage(&self) -> HashMap<String, usize> {
+        let mut usage = self
+            .rules
+            .iter()
+            .map(|rule| (String::from(rule.name()), 0))
+            .collect::<HashMap<_, _>>();
+        for name in self
+            .rules
+            .iter()
+            .flat_map(Rule::body)
+            .flat_map(Symbol::rule_names)
+        {
+            *usage.entry(String::from(name)).or_default() += 1;
+        }
+        usage
+    }
+
+    /// The name of the rule parsing starts from, the name of the first rule
+    /// unless set with [`Grammar::with_start_symbol`]
+    /// ```
+    /// # use parsey::grammar;
+    /// let grammar = grammar! {
+    ///     Sum -> Sum "+" Number;
+    ///     Sum -> Number;
+    ///     Number -> ["0123456789"];
+    /// };
+    /// assert_eq!(grammar.start_symbol(), "Sum");
+    /// ```
+    #[must_use]
+    pub fn start_symbol(&self) -> &str {
+        &self.start
+    }
+
+    /// Start parsing from the rule `name` instead of the first rule, so the
+    /// language doesn't depend on the order the rules were written in. The
+    /// `grammar!` macro and [`Grammar::from_bnf`] accept a `start: Name;`
+    /// directive which does the same
+    /// ```
+    /// # use parsey::{grammar, recognise};
+    /// let grammar = grammar! {
+    ///     Number -> ["0123456789"];
+    ///     Sum -> Sum "+" Number;
+    ///     Sum -> Number;
+    /// }
+    /// .with_start_symbol("Sum");
+    /// assert!(recognise(&grammar, "1+2"));
+    /// ```
+    ///
+    /// # Panics
+    /// If the grammar has no rule called `name`
+    #[must_use]
+    pub fn with_start_symbol(mut self, name: &str) -> Grammar {
+        assert!(
+            self.rules.iter().any(|rule| rule.name() == name),
+            "No rule named {} to start from",
+            name
+        );
+        self.start = String::from(name);
+        self
+    }
+
+    /// The rules named `name` in the order they were defined. Returns an
+    /// iterator rather than a collection because this is called on every
+    /// prediction
+    pub(crate) fn get_rules_by_name<'a, 'b>(
+        &'a self,
+        name: &'b str,
+    ) -> impl Iterator<Item = &'a Rule> + 'b
+    where
+        'a: 'b,
+    {
+        self.rules.iter().filter(move |rule| rule.name() == name)
+    }
+
+    /// The position of each rule in the order alternatives are tried when
+    /// building trees, keyed by the address of the rule. Rules with higher
+    /// precedence come first, then rules are in the order they were declared.
+    /// Items refer to their rule by reference so this identifies the rule an
+    /// item came from even if an identical rule appears twice
+    pub(crate) fn rule_order(&self) -> HashMap<*const Rule, usize> {
+        let mut rules = self.rules.iter().collect::<Vec<_>>();
+        // Stable, so declaration order is kept between equal precedences
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.precedence()));
+        rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, rule)| (std::ptr::from_ref(rule), index))
+            .collect()
+    }
+
+    /// True if the body of `rule` can match the empty string given the rules
+    /// of this grammar, i.e. it is empty or every symbol in it is a nullable
+    /// rule. Uses the nullable rules worked out when the grammar was built so
+    /// custom analyses don't need to compute them again. `rule` doesn't have
+    /// to belong to the grammar, references to rules the grammar doesn't have
+    /// aren't nullable (except the rule referring to itself)
+    /// ```
+    /// # use parsey::{grammar, rule};
+    /// let grammar = grammar! {
+    ///     TriviallyNullable -> ;
+    ///     OnlyUsesNullableRules -> TriviallyNullable TriviallyNullable;
+    ///     RecursivelyNullable -> OnlyUsesNullableRules RecursivelyNullable;
+    ///     Literal -> "Literal";
+    ///     OneOf -> ["abcde"];
+    ///     NotNullable -> Literal TriviallyNullable OneOf;
+    /// };
+    /// assert!(grammar.rule_body_is_nullable(&rule!(TriviallyNullable -> )));
+    /// assert!(grammar.rule_body_is_nullable(&rule!(
+    ///     RecursivelyNullable -> OnlyUsesNullableRules RecursivelyNullable
+    /// )));
+    /// assert!(!grammar.rule_body_is_nullable(&rule!(
+    ///     NotNullable -> Literal TriviallyNullable OneOf
+    /// )));
+    /// // A new rule built from nullable rules
+    /// assert!(grammar.rule_body_is_nullable(&rule!(
+    ///     Both -> TriviallyNullable RecursivelyNullable
+    /// )));
+    /// assert!(!grammar.rule_body_is_nullable(&rule!(Undefined -> Missing)));
+    /// ```
+    #[must_use]
+    pub fn rule_body_is_nullable(&self, rule: &Rule) -> bool {
+        rule.is_nullable(&self.nullables)
     }
 
     pub(crate) fn rule_is_nullable(&self, rule: &str) -> bool {
         self.nullables.contains(rule)
     }
 
+    /// True if `symbol` can match the empty string
+    pub(crate) fn symbol_is_nullable(&self, symbol: &Symbol) -> bool {
+        symbol.can_match(&|name| self.rule_is_nullable(name), false)
+    }
+
+    /// True if any rule in the grammar is nullable, when false the parser can
+    /// skip nullable handling entirely
+    pub(crate) fn has_nullable_rules(&self) -> bool {
+        !self.nullables.is_empty()
+    }
+
+    /// Decide whether an input of zero or one characters is in the language
+    /// without building any state sets. The empty input is accepted if the
+    /// start symbol is nullable, a single character if the start symbol can
+    /// produce it on its own. `None` for longer input, and for grammars with
+    /// predicates as those can reject completions the rules alone allow.
+    /// Single characters are left to the parser in grammars using
+    /// [`Symbol::Repeat`]
+    pub(crate) fn recognise_short(&self, input: &[char]) -> Option<bool> {
+        if !self.predicates.is_empty() {
+            return None;
+        }
+        match input {
+            [] => Some(self.rule_is_nullable(self.start_symbol())),
+            [_] if self.has_repeats() => None,
+            [c] => Some(self.rules_producing(*c).contains(self.start_symbol())),
+            _ => None,
+        }
+    }
+
+    fn has_repeats(&self) -> bool {
+        self.units.has_repeats
+    }
+
+    /// The names of the rules which can produce exactly `c`, i.e. they have an
+    /// alternative where one symbol produces `c` and the rest are nullable
+    fn rules_producing(&self, c: char) -> HashSet<&str> {
+        let mut producing = HashSet::new();
+        let mut pending = self
+            .units
+            .terminals
+            .iter()
+            .filter(|(terminal, _)| terminal.scan(&[c]) == Some(1))
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>();
+        while let Some(name) = pending.pop() {
+            if producing.insert(name) {
+                if let Some(parents) = self.units.parents.get(name) {
+                    pending.extend(parents.iter().map(String::as_str));
+                }
+            }
+        }
+        producing
+    }
+
     #[cfg(test)]
     #[must_use]
     pub(crate) fn index(&self, idx: usize) -> &Rule {
@@ -85,19 +440,134 @@ fn find_nullable_rules(rules: &[Rule]) -> HashSet<String> {
     nullables
 }
 
+/// The alternatives which produce whatever one of their symbols does, because
+/// every other symbol is a nullable rule. Worked out once by [`Grammar::new`] so
+/// [`Grammar::recognise_short`] only has to follow them
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Units {
+    /// True if any rule uses [`Symbol::Repeat`]
+    has_repeats: bool,
+    /// Terminals paired with the rule they can make up on their own
+    terminals: Vec<(Symbol, String)>,
+    /// For each rule, the rules it can make up on its own
+    parents: HashMap<String, Vec<String>>,
+}
+
+fn find_units(rules: &[Rule], nullables: &HashSet<String>) -> Units {
+    let mut units = Units {
+        has_repeats: rules
+            .iter()
+            .flat_map(Rule::body)
+            .any(|symbol| matches!(symbol, Symbol::Repeat { .. })),
+        ..Units::default()
+    };
+    let nullable = |symbol: &Symbol| {
+        symbol
+            .rule_name()
+            .is_some_and(|name| nullables.contains(name))
+    };
+    for rule in rules {
+        // An alternative with two symbols other than nullable rules can't make
+        // up a single character, with one only that symbol can
+        let mut required = rule.body().iter().filter(|symbol| !nullable(symbol));
+        let candidates = match (required.next(), required.next()) {
+            (None, _) => rule.body().iter().collect(),
+            (Some(symbol), None) => vec![symbol],
+            (Some(_), Some(_)) => continue,
+        };
+        for symbol in candidates {
+            let name = String::from(rule.name());
+            match symbol {
+                Symbol::Rule(child) => {
+                    let parents = units.parents.entry(child.clone()).or_default();
+                    if !parents.contains(&name) {
+                        parents.push(name);
+                    }
+                }
+                terminal => units.terminals.push((terminal.clone(), name)),
+            }
+        }
+    }
+    units
+}
+
 syntax_abuse::tests! {
+    /// Build the expected [`Units`], `parents` lists each rule with a rule it
+    /// makes up on its own
+    fn units(terminals: Vec<(Symbol, &str)>, parents: &[(&str, &str)]) -> Units {
+        let mut units = Units {
+            terminals: terminals
+                .into_iter()
+                .map(|(terminal, name)| (terminal, String::from(name)))
+                .collect(),
+            ..Units::default()
+        };
+        for (child, parent) in parents {
+            units
+                .parents
+                .entry(String::from(*child))
+                .or_default()
+                .push(String::from(*parent));
+        }
+        units
+    }
+
     #[test]
     #[should_panic]
     fn empty_rules() {
         drop(Grammar::new(vec![]));
     }
 
+    #[test]
+    #[should_panic(expected = "Only single character terminals can be negated")]
+    fn negated_rule() {
+        drop(Grammar::new(vec![Rule::new(
+            String::from("A"),
+            vec![Symbol::negate(Symbol::Rule(String::from("B")))],
+        )]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only single character terminals can be negated")]
+    fn negated_rule_in_repeat() {
+        drop(Grammar::new(vec![Rule::new(
+            String::from("A"),
+            vec![Symbol::repeat(
+                Symbol::Literal('a'),
+                Some(Symbol::negate(Symbol::Rule(String::from("B")))),
+                0,
+                None,
+            )],
+        )]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only single character terminals can be negated")]
+    fn negated_newline() {
+        drop(Grammar::new(vec![Rule::new(
+            String::from("A"),
+            vec![Symbol::negate(Symbol::Newline)],
+        )]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only single character terminals can be negated")]
+    fn negated_grapheme() {
+        drop(Grammar::new(vec![Rule::new(
+            String::from("A"),
+            vec![Symbol::negate(Symbol::Grapheme(String::from("e\u{301}")))],
+        )]));
+    }
+
     testcase! {
         non_empty_rules,
         Grammar::new(vec![Rule::new(String::from("Test"), vec![])]),
         Grammar {
             rules: vec![Rule::new(String::from("Test"), vec![])],
-            nullables: hashset![String::from("Test")]
+            nullables: hashset![String::from("Test")],
+            units: Units::default(),
+            predicates: HashMap::new(),
+            start: String::from("Test")
         }
     }
 
@@ -126,7 +596,10 @@ syntax_abuse::tests! {
                     ]
                 )
             ],
-            nullables: hashset![]
+            nullables: hashset![],
+            units: units(vec![], &[("Rule2", "Rule")]),
+            predicates: HashMap::new(),
+            start: String::from("Rule")
         }
     }
 
@@ -155,7 +628,24 @@ syntax_abuse::tests! {
                     ]
                 )
             ],
-            nullables: hashset![]
+            nullables: hashset![],
+            units: units(vec![], &[("Rule2", "Rule")]),
+            predicates: HashMap::new(),
+            start: String::from("Rule")
+        }
+    }
+
+    testcase! {
+        comments,
+        grammar! {
+            // The root rule
+            Rule -> Rule2;
+            /* A literal */
+            Rule2 -> "literal";
+        },
+        grammar! {
+            Rule -> Rule2;
+            Rule2 -> "literal";
         }
     }
 
@@ -179,7 +669,10 @@ syntax_abuse::tests! {
                     ]
                 )
             ],
-            nullables: hashset![]
+            nullables: hashset![],
+            units: Units::default(),
+            predicates: HashMap::new(),
+            start: String::from("Rule")
         }
     }
 
@@ -275,11 +768,129 @@ syntax_abuse::tests! {
                     ]
                 )
             ],
-            nullables: hashset![]
+            nullables: hashset![],
+            units: units(
+                vec![(Symbol::OneOf(nonempty_hashset![
+                        '0', '1', '2', '3', '4', '5', '6', '7', '8', '9'
+                    ]), "Number")],
+                &[("Product", "Sum"), ("Factor", "Product"), ("Number", "Factor")]
+            ),
+            predicates: HashMap::new(),
+            start: String::from("Sum")
         }
     }
 
 
+    testcase! {
+        tokens,
+        Grammar::tokens(&[("Number", "01"), ("Ident", "ab")]),
+        vec![
+            rule!(Number -> ["01"] Number),
+            rule!(Number -> ["01"]),
+            rule!(Ident -> ["ab"] Ident),
+            rule!(Ident -> ["ab"])
+        ]
+    }
+
+    tests! {
+        scannerless:
+
+        testdata! {
+            EXPR: Grammar = Grammar::new(
+                vec![
+                    rule!(Sum -> Sum "+" Term),
+                    rule!(Sum -> Term),
+                    rule!(Term -> Ident),
+                    rule!(Term -> Number),
+                    rule!(Term -> Ident "(" Sum ")")
+                ].into_iter().chain(Grammar::tokens(&[
+                    ("Ident", "abcdefghijklmnopqrstuvwxyz_"),
+                    ("Number", "0123456789")
+                ])).collect()
+            );
+        }
+
+        testcase! {
+            identifier,
+            crate::recognise(&EXPR, "foo_bar"),
+            true
+        }
+
+        testcase! {
+            number,
+            crate::recognise(&EXPR, "1234"),
+            true
+        }
+
+        testcase! {
+            expression,
+            crate::recognise(&EXPR, "abs(x+12)+y+3"),
+            true
+        }
+
+        testcase! {
+            invalid,
+            crate::recognise(&EXPR, "abs(x+12"),
+            false
+        }
+
+        testcase! {
+            mixed_token,
+            crate::recognise(&EXPR, "x1"),
+            false
+        }
+    }
+
+    tests! {
+        possible_next_symbols:
+
+        testdata! {
+            ARITH: Grammar = grammar! {
+                Sum -> Sum ["+-"] Product;
+                Sum -> Product;
+                Product -> Product ["*/"] Factor;
+                Product -> Factor;
+                Factor -> "(" Sum ")";
+                Factor -> Number;
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+            };
+        }
+
+        fn digits() -> Symbol {
+            Symbol::OneOf(nonempty_hashset!['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'])
+        }
+
+        testcase! {
+            after_operator,
+            ARITH.possible_next_symbols("1+"),
+            vec![
+                Symbol::Rule(String::from("Product")),
+                Symbol::Rule(String::from("Factor")),
+                Symbol::Literal('('),
+                Symbol::Rule(String::from("Number")),
+                digits()
+            ]
+        }
+
+        testcase! {
+            after_number,
+            ARITH.possible_next_symbols("12"),
+            vec![
+                Symbol::Rule(String::from("Number")),
+                digits(),
+                Symbol::OneOf(nonempty_hashset!['*', '/']),
+                Symbol::OneOf(nonempty_hashset!['+', '-'])
+            ]
+        }
+
+        testcase! {
+            invalid_prefix,
+            ARITH.possible_next_symbols("1+)"),
+            vec![]
+        }
+    }
+
     testdata! {
         NULLABILITY: Grammar = grammar! {
             TriviallyNullable -> ;
@@ -307,7 +918,17 @@ syntax_abuse::tests! {
                 String::from("TriviallyNullable"),
                 String::from("OnlyUsesNullableRules"),
                 String::from("RecursivelyNullable")
-            ]
+            ],
+            units: units(
+                vec![(Symbol::OneOf(nonempty_hashset!['a', 'b', 'c', 'd', 'e']), "OneOf")],
+                &[
+                    ("TriviallyNullable", "OnlyUsesNullableRules"),
+                    ("OnlyUsesNullableRules", "RecursivelyNullable"),
+                    ("RecursivelyNullable", "RecursivelyNullable")
+                ]
+            ),
+            predicates: HashMap::new(),
+            start: String::from("TriviallyNullable")
         }
     }
 
@@ -349,5 +970,399 @@ syntax_abuse::tests! {
             NULLABILITY.rule_is_nullable("NotNullable"),
             false
         }
+
+        testcase! {
+            every_body_agrees,
+            NULLABILITY.rules.iter().all(|rule| {
+                NULLABILITY.rule_body_is_nullable(rule) == NULLABILITY.rule_is_nullable(rule.name())
+            }),
+            true
+        }
+    }
+
+    tests! {
+        has_nullable_rules:
+
+        use crate::ast::Node;
+        use crate::{parse, recognise};
+
+        testdata! {
+            NO_NULLABLES: Grammar = grammar! {
+                List -> Item "," List;
+                List -> Item;
+                Item -> ["abc"];
+            };
+        }
+
+        testcase! {
+            nullable,
+            NULLABILITY.has_nullable_rules(),
+            true
+        }
+
+        testcase! {
+            not_nullable,
+            NO_NULLABLES.has_nullable_rules(),
+            false
+        }
+
+        testcase! {
+            recognise_without_nullables,
+            (recognise(&NO_NULLABLES, "a,b,c"), recognise(&NO_NULLABLES, "a,b,")),
+            (true, false)
+        }
+
+        testcase! {
+            parse_without_nullables,
+            parse(&NO_NULLABLES, "a,b").unwrap().count(),
+            1
+        }
+
+        testcase! {
+            recognise_with_nullables,
+            (recognise(&NULLABILITY, "Literala"), recognise(&NULLABILITY, "")),
+            (false, true)
+        }
+    }
+    tests! {
+        left_factoring_opportunities:
+
+        testcase! {
+            shared_prefix,
+            grammar! {
+                A -> "x" B;
+                A -> "x" C;
+                B -> "b";
+                C -> "c";
+            }
+            .left_factoring_opportunities(),
+            vec![(String::from("A"), vec![Symbol::Literal('x')])]
+        }
+
+        testcase! {
+            longest_prefix,
+            grammar! {
+                A -> "x" B "y";
+                A -> "x" B "z";
+                B -> "b";
+            }
+            .left_factoring_opportunities(),
+            vec![(
+                String::from("A"),
+                vec![Symbol::Literal('x'), Symbol::Rule(String::from("B"))]
+            )]
+        }
+
+        testcase! {
+            reported_once,
+            grammar! {
+                A -> "x" "a";
+                A -> "x" "b";
+                A -> "x" "c";
+            }
+            .left_factoring_opportunities(),
+            vec![(String::from("A"), vec![Symbol::Literal('x')])]
+        }
+
+        testcase! {
+            different_rules,
+            grammar! {
+                A -> "x" B;
+                B -> "x";
+            }
+            .left_factoring_opportunities(),
+            vec![]
+        }
+
+        testcase! {
+            no_shared_prefix,
+            grammar! {
+                A -> "x" B;
+                A -> "y" B;
+                A -> ;
+                B -> "b";
+            }
+            .left_factoring_opportunities(),
+            vec![]
+        }
+    }
+
+    tests! {
+        start_symbol_alternatives:
+
+        testdata! {
+            ARITH: Grammar = grammar! {
+                Sum -> Sum ["+-"] Product;
+                Sum -> Product;
+                Product -> Product ["*/"] Factor;
+                Product -> Factor;
+                Factor -> "(" Sum ")";
+                Factor -> Number;
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+            };
+            SCATTERED: Grammar = grammar! {
+                Sum -> Sum "+" Number;
+                Number -> ["0123456789"];
+                Sum -> Number;
+                Number -> ["0123456789"] Number;
+                Sum -> "(" Sum ")";
+                Sum -> "-" Sum;
+            };
+        }
+
+        testcase! {
+            count,
+            ARITH.start_symbol_alternatives_count(),
+            2
+        }
+
+        testcase! {
+            adjacent,
+            ARITH.scattered_start_alternatives(),
+            Vec::<usize>::new()
+        }
+
+        testcase! {
+            scattered,
+            (SCATTERED.start_symbol_alternatives_count(), SCATTERED.scattered_start_alternatives()),
+            (4, vec![2, 4, 5])
+        }
+
+        testcase! {
+            explicit_start,
+            {
+                let grammar = SCATTERED.clone().with_start_symbol("Number");
+                (grammar.start_symbol_alternatives_count(), grammar.scattered_start_alternatives())
+            },
+            (2, vec![3])
+        }
+    }
+
+    tests! {
+        symbol_usage:
+
+        testdata! {
+            ARITH: Grammar = grammar! {
+                Sum -> Sum ["+-"] Product;
+                Sum -> Product;
+                Product -> Product ["*/"] Factor;
+                Product -> Factor;
+                Factor -> "(" Sum ")";
+                Factor -> "-" Factor;
+                Factor -> Number;
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+            };
+        }
+
+        fn usage(grammar: &Grammar, name: &str) -> Option<usize> {
+            grammar.symbol_usage().get(name).copied()
+        }
+
+        testcase! {
+            arith,
+            ["Sum", "Product", "Factor", "Number"]
+                .iter()
+                .map(|name| usage(&ARITH, name))
+                .collect::<Vec<_>>(),
+            vec![Some(2), Some(3), Some(3), Some(2)]
+        }
+
+        testcase! {
+            unreferenced,
+            usage(&grammar! { Start -> "a"; Unused -> "b"; }, "Unused"),
+            Some(0)
+        }
+
+        testcase! {
+            undefined,
+            usage(&grammar! { Start -> Missing Missing; }, "Missing"),
+            Some(2)
+        }
+
+        testcase! {
+            terminals_ignored,
+            grammar! { Start -> "a" ["bc"]; }.symbol_usage().len(),
+            1
+        }
+    }
+
+    tests! {
+        get_rules_by_name:
+
+        testcase! {
+            in_definition_order,
+            NULLABILITY.get_rules_by_name("NotNullable").collect::<Vec<_>>(),
+            vec![NULLABILITY.index(5)]
+        }
+
+        testcase! {
+            several,
+            grammar! {
+                A -> "a";
+                B -> "b";
+                A -> "c";
+            }
+            .get_rules_by_name("A")
+            .cloned()
+            .collect::<Vec<_>>(),
+            vec![rule!(A -> "a"), rule!(A -> "c")]
+        }
+
+        testcase! {
+            missing,
+            NULLABILITY.get_rules_by_name("Missing").count(),
+            0
+        }
+    }
+
+    tests! {
+        clone:
+
+        testcase! {
+            equals_original,
+            NULLABILITY.clone(),
+            *NULLABILITY
+        }
+
+        testcase! {
+            preserves_nullables,
+            NULLABILITY.clone().nullables,
+            NULLABILITY.nullables
+        }
+
+        testcase! {
+            preserves_analysis,
+            ["TriviallyNullable", "RecursivelyNullable", "Literal", "NotNullable"]
+                .iter()
+                .map(|rule| NULLABILITY.clone().rule_is_nullable(rule))
+                .collect::<Vec<_>>(),
+            vec![true, true, false, false]
+        }
+    }
+
+    tests! {
+        charset:
+
+        use crate::recognise;
+
+        testdata! {
+            VERSION: Grammar = grammar! {
+                charset Digit = ["0123456789"];
+                Version -> Number "." Number;
+                Number -> [Digit] Number;
+                Number -> [Digit];
+                Patch -> "-" [Digit];
+            };
+        }
+
+        testcase! {
+            expanded,
+            VERSION.clone(),
+            grammar! {
+                Version -> Number "." Number;
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+                Patch -> "-" ["0123456789"];
+            }
+        }
+
+        testcase! {
+            recognised,
+            ["1.2", "10.20", "1.", "a.b"]
+                .iter()
+                .map(|input| recognise(&VERSION, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, false, false]
+        }
+
+        testcase! {
+            with_start_directive,
+            grammar! {
+                start: Number;
+                charset Digit = ["01"];
+                Bit -> [Digit];
+                Number -> Bit Number;
+                Number -> Bit;
+            }
+            .start_symbol(),
+            "Number"
+        }
+    }
+
+    tests! {
+        start_symbol:
+
+        use crate::ast::Node;
+        use crate::{parse, recognise};
+
+        testdata! {
+            EXPLICIT: Grammar = grammar! {
+                start: Sum;
+                Number -> ["0123456789"];
+                Sum -> Sum "+" Number;
+                Sum -> Number;
+            };
+        }
+
+        testcase! {
+            defaults_to_first_rule,
+            grammar! {
+                Number -> ["0123456789"];
+                Sum -> Number;
+            }
+            .start_symbol(),
+            "Number"
+        }
+
+        testcase! {
+            directive,
+            EXPLICIT.start_symbol(),
+            "Sum"
+        }
+
+        testcase! {
+            recognition_uses_directive,
+            ["1+2", "1", "+"]
+                .iter()
+                .map(|input| recognise(&EXPLICIT, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, false]
+        }
+
+        testcase! {
+            trees_rooted_at_directive,
+            parse(&EXPLICIT, "1").unwrap().next(),
+            Some(Node::Internal {
+                name: String::from("Sum"),
+                children: vec![Node::Internal {
+                    name: String::from("Number"),
+                    children: vec![Node::Leaf('1')]
+                }]
+            })
+        }
+
+        testcase! {
+            survives_rename,
+            {
+                let mut grammar = EXPLICIT.clone();
+                grammar.rename_rule("Sum", "Total");
+                String::from(grammar.start_symbol())
+            },
+            String::from("Total")
+        }
+
+        testcase! {
+            survives_merge,
+            EXPLICIT.clone().merge_char_alternatives().start_symbol(),
+            "Sum"
+        }
+
+        #[test]
+        #[should_panic(expected = "No rule named Product to start from")]
+        fn missing_rule() {
+            drop(EXPLICIT.clone().with_start_symbol("Product"));
+        }
     }
 }
@@ -0,0 +1,685 @@
+//! Runtime loader for the textual grammar format
+//!
+//! The format mirrors the `grammar!` macro:
+//! ```text
+//! # Comments run from # to the end of the line
+//! Sum -> Sum ["+-"] Product;
+//! Sum -> Product;
+//! Empty -> ;
+//! ```
+//! Rule names are identifiers, a quoted string is a sequence of
+//! [`Symbol::Literal`] (or [`Symbol::Grapheme`] for multi code point grapheme
+//! clusters) and a quoted string wrapped in `[]` is a
+//! [`Symbol::OneOf`]. `$` is a [`Symbol::Newline`] and `.` is a
+//! [`Symbol::AnyChar`]. As with the macro the `;` after the final rule is
+//! optional. Quoted strings support the escape sequences `\n`, `\r`, `\t`,
+//! `\\`, `\"` and `\uXXXX`.
+//!
+//! A `start: Name;` directive anywhere in the source makes `Name` the start
+//! symbol instead of the first rule, see [`Grammar::with_start_symbol`]. A
+//! `charset Name = ["..."];` directive names a character class, rules after it
+//! can refer to the class as `[Name]`.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Grammar, Rule, Symbol};
+use crate::NonEmptyHashSet;
+
+/// Error produced when loading a grammar from its textual representation
+#[derive(Debug, Clone, PartialEq)]
+pub struct BnfError {
+    /// The (one indexed) line the error occurred on
+    pub line: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for BnfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for BnfError {}
+
+/// Load a grammar from its textual representation, see [`Grammar::from_bnf`]
+/// ```
+/// # use parsey::grammar::Grammar;
+/// # fn main() -> Result<(), parsey::grammar::BnfError> {
+/// let grammar: Grammar = "Bit -> [\"01\"];".parse()?;
+/// assert_eq!(grammar.start_symbol(), "Bit");
+/// # Ok(())
+/// # }
+/// ```
+impl FromStr for Grammar {
+    type Err = BnfError;
+
+    fn from_str(source: &str) -> Result<Self> {
+        parse(source)
+    }
+}
+
+/// Load a grammar from its textual representation, see [`Grammar::from_bnf`]
+impl TryFrom<&str> for Grammar {
+    type Error = BnfError;
+
+    fn try_from(source: &str) -> Result<Self> {
+        parse(source)
+    }
+}
+
+type Result<T> = std::result::Result<T, BnfError>;
+
+/// Parse the textual grammar format
+pub(super) fn parse(source: &str) -> Result<Grammar> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 1,
+        charsets: HashMap::new(),
+    };
+
+    let mut rules = Vec::new();
+    let mut start = None;
+    while parser.skip_trivia() {
+        let name = parser.identifier()?;
+        if name == "charset" && parser.identifier_follows() {
+            parser.charset()?;
+        } else if parser.directive_follows() {
+            if name != "start" {
+                return Err(parser.error(format!("Unknown directive '{}'", name)));
+            }
+            if start.is_some() {
+                return Err(parser.error("Duplicate start directive"));
+            }
+            start = Some((parser.directive()?, parser.line));
+        } else {
+            rules.push(parser.rule(name)?);
+        }
+    }
+
+    if rules.is_empty() {
+        return Err(parser.error("A grammar must have at least one rule"));
+    }
+    let grammar = Grammar::new(rules);
+    match start {
+        Some((name, _)) if grammar.get_rules_by_name(&name).next().is_some() => {
+            Ok(grammar.with_start_symbol(&name))
+        }
+        Some((name, line)) => Err(BnfError {
+            line,
+            message: format!("No rule named {} to start from", name),
+        }),
+        None => Ok(grammar),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    /// Character sets defined so far by charset directives
+    charsets: HashMap<String, NonEmptyHashSet<char>>,
+}
+
+impl Parser {
+    /// Skip whitespace and comments, returns false if there is no input left
+    fn skip_trivia(&mut self) -> bool {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    let _ = self.advance();
+                }
+            } else if c.is_whitespace() {
+                let _ = self.advance();
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        if c == '\n' {
+            self.line += 1;
+        }
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> BnfError {
+        BnfError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        let _ = self.skip_trivia();
+        match self.advance() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(format!("Expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("Expected '{}', found end of input", expected))),
+        }
+    }
+
+    /// True if the next character is the `:` of a directive
+    fn directive_follows(&mut self) -> bool {
+        self.skip_trivia() && self.peek() == Some(':')
+    }
+
+    /// Directive syntax is `directive: Name;`, parse from the `:` and return
+    /// the name
+    fn directive(&mut self) -> Result<String> {
+        self.expect(':')?;
+        let name = self.identifier()?;
+        self.expect(';')?;
+        Ok(name)
+    }
+
+    /// True if the next character starts an identifier
+    fn identifier_follows(&mut self) -> bool {
+        self.skip_trivia() && self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    /// Charset syntax is `charset Name = ["..."];`, parse from the name and
+    /// remember the set for later rules
+    fn charset(&mut self) -> Result<()> {
+        let name = self.identifier()?;
+        self.expect('=')?;
+        let chars = self.chars()?;
+        self.expect(';')?;
+        if self.charsets.insert(name.clone(), chars).is_some() {
+            return Err(self.error(format!("Duplicate character set {}", name)));
+        }
+        Ok(())
+    }
+
+    /// Rule syntax is `Name -> body` followed by `;` or end of input, parse
+    /// from the `->` given the name
+    fn rule(&mut self, name: String) -> Result<Rule> {
+        self.expect('-')?;
+        self.expect('>')?;
+
+        let mut body = Vec::new();
+        loop {
+            if !self.skip_trivia() {
+                break;
+            }
+            match self.peek() {
+                Some(';') => {
+                    let _ = self.advance();
+                    break;
+                }
+                Some('"') => body.extend(Symbol::literals(
+                    &self.string()?.into_iter().collect::<String>(),
+                )),
+                Some('[') => body.push(self.oneof()?),
+                Some('$') => {
+                    let _ = self.advance();
+                    body.push(Symbol::Newline);
+                }
+                Some('.') => {
+                    let _ = self.advance();
+                    body.push(Symbol::AnyChar);
+                }
+                Some(_) => body.push(Symbol::Rule(self.identifier()?)),
+                None => unreachable!(),
+            }
+        }
+
+        Ok(Rule::new(name, body))
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        let _ = self.skip_trivia();
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+                let _ = self.advance();
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => self.error(format!("Expected a rule name, found '{}'", c)),
+                None => self.error("Expected a rule name, found end of input"),
+            });
+        }
+        Ok(name)
+    }
+
+    fn string(&mut self) -> Result<Vec<char>> {
+        self.expect('"')?;
+        let mut contents = Vec::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(contents),
+                Some('\\') => contents.push(self.escape()?),
+                Some(c) => contents.push(c),
+                None => return Err(self.error("Unterminated string literal")),
+            }
+        }
+    }
+
+    /// Parse the remainder of an escape sequence after the `\`
+    fn escape(&mut self) -> Result<char> {
+        match self.advance() {
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('\\') => Ok('\\'),
+            Some('"') => Ok('"'),
+            Some('u') => {
+                let mut digits = String::new();
+                for _ in 0..4 {
+                    match self.advance() {
+                        Some(c) if c.is_ascii_hexdigit() => digits.push(c),
+                        _ => return Err(self.error("Expected four hex digits after \\u")),
+                    }
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| self.error(format!("Invalid character \\u{}", digits)))
+            }
+            Some(c) => Err(self.error(format!("Unknown escape sequence \\{}", c))),
+            None => Err(self.error("Unterminated string literal")),
+        }
+    }
+
+    fn oneof(&mut self) -> Result<Symbol> {
+        Ok(Symbol::OneOf(self.chars()?))
+    }
+
+    /// A character class, either a quoted string or the name of a charset
+    /// wrapped in `[]`
+    fn chars(&mut self) -> Result<NonEmptyHashSet<char>> {
+        self.expect('[')?;
+        let _ = self.skip_trivia();
+        if self.peek() != Some('"') {
+            let name = self.identifier()?;
+            self.expect(']')?;
+            return self
+                .charsets
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| self.error(format!("Unknown character set {}", name)));
+        }
+
+        let chars = self.string()?.into_iter().collect::<HashSet<_>>();
+        self.expect(']')?;
+        if chars.is_empty() {
+            return Err(self.error("Character classes must not be empty"));
+        }
+        Ok(NonEmptyHashSet::new(chars))
+    }
+}
+
+syntax_abuse::tests! {
+    testcase! {
+        simple,
+        parse("Rule -> Rule2; Rule2 -> \"literal\";"),
+        Ok(grammar! {
+            Rule -> Rule2;
+            Rule2 -> "literal";
+        })
+    }
+
+    testcase! {
+        trailing_semi_is_optional,
+        parse("Rule -> \"literal\""),
+        Ok(grammar! {
+            Rule -> "literal"
+        })
+    }
+
+    testcase! {
+        any_char,
+        parse("Escaped -> \"\\\\\" .;"),
+        Ok(grammar! {
+            Escaped -> "\\" .;
+        })
+    }
+
+    testcase! {
+        empty_rule,
+        parse("Empty -> ;"),
+        Ok(grammar! {
+            Empty -> ;
+        })
+    }
+
+    testcase! {
+        comments_and_blank_lines,
+        parse(
+            "# Arithmetic
+             Sum -> Sum [\"+-\"] Product; # Addition
+
+             Sum -> Product;
+             # Multiplication
+             Product -> Product [\"*/\"] Factor;
+             Product -> Factor;
+
+             Factor -> \"(\" Sum \")\";
+             Factor -> Number;
+             Number -> [\"0123456789\"] Number;
+             Number -> [\"0123456789\"];
+             # Trailing comment"
+        ),
+        Ok(grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        })
+    }
+
+    testcase! {
+        hash_inside_string,
+        parse("Rule -> \"#\" [\"#\"]"),
+        Ok(grammar! {
+            Rule -> "#" ["#"]
+        })
+    }
+
+    tests! {
+        charset:
+
+        testcase! {
+            shared,
+            parse(
+                "charset Digit = [\"0123456789\"];
+                 Number -> [Digit] Number;
+                 Number -> [Digit];
+                 Version -> Number \".\" [Digit];"
+            ),
+            Ok(grammar! {
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+                Version -> Number "." ["0123456789"];
+            })
+        }
+
+        testcase! {
+            same_as_macro,
+            parse("charset Digit = [\"0123456789\"]; Number -> [Digit] Number; Number -> [Digit];"),
+            Ok(grammar! {
+                charset Digit = ["0123456789"];
+                Number -> [Digit] Number;
+                Number -> [Digit];
+            })
+        }
+
+        testcase! {
+            rule_named_charset,
+            parse("charset -> \"a\";"),
+            Ok(grammar! {
+                charset -> "a";
+            })
+        }
+
+        testcase! {
+            unknown,
+            parse("Number -> [Digit];"),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Unknown character set Digit")
+            })
+        }
+
+        testcase! {
+            used_before_definition,
+            parse("Number -> [Digit];\ncharset Digit = [\"0123456789\"];"),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Unknown character set Digit")
+            })
+        }
+
+        testcase! {
+            duplicate,
+            parse("charset Digit = [\"01\"];\ncharset Digit = [\"23\"];\nNumber -> [Digit];"),
+            Err(BnfError {
+                line: 2,
+                message: String::from("Duplicate character set Digit")
+            })
+        }
+
+        testcase! {
+            empty,
+            parse("charset Digit = [\"\"];\nNumber -> [Digit];"),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Character classes must not be empty")
+            })
+        }
+    }
+
+    tests! {
+        start_directive:
+
+        testcase! {
+            start_symbol,
+            parse("Number -> [\"0123456789\"]; start: Sum; Sum -> Number \"+\" Number;")
+                .map(|grammar| String::from(grammar.start_symbol())),
+            Ok(String::from("Sum"))
+        }
+
+        testcase! {
+            same_as_macro,
+            parse("start: Sum;\nNumber -> [\"0123456789\"];\nSum -> Number \"+\" Number;"),
+            Ok(grammar! {
+                start: Sum;
+                Number -> ["0123456789"];
+                Sum -> Number "+" Number;
+            })
+        }
+
+        testcase! {
+            missing_rule,
+            parse("Sum -> \"+\";\nstart: Product;"),
+            Err(BnfError {
+                line: 2,
+                message: String::from("No rule named Product to start from")
+            })
+        }
+
+        testcase! {
+            duplicate,
+            parse("start: A;\nstart: A;\nA -> \"a\";"),
+            Err(BnfError {
+                line: 2,
+                message: String::from("Duplicate start directive")
+            })
+        }
+
+        testcase! {
+            unknown_directive,
+            parse("begin: A;\nA -> \"a\";"),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Unknown directive 'begin'")
+            })
+        }
+    }
+
+    tests! {
+        escapes:
+
+        testcase! {
+            tab_and_newline,
+            parse(r#"Rule -> "a\tb\n""#),
+            Ok(grammar! {
+                Rule -> "a\tb\n"
+            })
+        }
+
+        testcase! {
+            quote_and_backslash,
+            parse(r#"Rule -> "\"\\""#),
+            Ok(grammar! {
+                Rule -> "\"\\"
+            })
+        }
+
+        testcase! {
+            unicode,
+            parse(r#"Rule -> "\u00e9\u0041""#),
+            Ok(grammar! {
+                Rule -> "\u{e9}A"
+            })
+        }
+
+        testcase! {
+            combining_character,
+            parse(r#"Rule -> "e\u0301""#),
+            Ok(Grammar::new(vec![Rule::new(
+                String::from("Rule"),
+                vec![Symbol::Grapheme(String::from("e\u{301}"))]
+            )]))
+        }
+
+        testcase! {
+            oneof,
+            parse(r#"Whitespace -> [" \t\r\n"]"#),
+            Ok(grammar! {
+                Whitespace -> [" \t\r\n"]
+            })
+        }
+
+        testcase! {
+            recognise_control_characters,
+            crate::recognise(
+                &parse(r#"Line -> "a\tb\n""#).unwrap(),
+                "a\tb\n"
+            ),
+            true
+        }
+
+        testcase! {
+            unknown,
+            parse(r#"Rule -> "\q""#),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Unknown escape sequence \\q")
+            })
+        }
+
+        testcase! {
+            short_unicode,
+            parse(r#"Rule -> "\u12""#),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Expected four hex digits after \\u")
+            })
+        }
+
+        testcase! {
+            invalid_unicode,
+            parse(r#"Rule -> "\uD800""#),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Invalid character \\uD800")
+            })
+        }
+    }
+
+    tests! {
+        errors:
+
+        testcase! {
+            no_rules,
+            parse("# Nothing here\n\n"),
+            Err(BnfError {
+                line: 3,
+                message: String::from("A grammar must have at least one rule")
+            })
+        }
+
+        testcase! {
+            missing_arrow,
+            parse("Rule \"literal\""),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Expected '-', found '\"'")
+            })
+        }
+
+        testcase! {
+            unterminated_string,
+            parse("Rule -> \"literal"),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Unterminated string literal")
+            })
+        }
+
+        testcase! {
+            empty_oneof,
+            parse("Rule -> [\"\"]"),
+            Err(BnfError {
+                line: 1,
+                message: String::from("Character classes must not be empty")
+            })
+        }
+
+        testcase! {
+            line_numbers,
+            parse("Rule -> Rule2;\n# Comment\nRule2 -> ]"),
+            Err(BnfError {
+                line: 3,
+                message: String::from("Expected a rule name, found ']'")
+            })
+        }
+    }
+
+    tests! {
+        from_str:
+
+        testcase! {
+            parse_method,
+            "Rule -> Rule2; Rule2 -> \"literal\";".parse::<Grammar>(),
+            Ok(grammar! {
+                Rule -> Rule2;
+                Rule2 -> "literal";
+            })
+        }
+
+        testcase! {
+            try_from,
+            Grammar::try_from("start: B; A -> \"a\"; B -> A A;"),
+            Ok(grammar! {
+                start: B;
+                A -> "a";
+                B -> A A;
+            })
+        }
+
+        testcase! {
+            error,
+            "Rule -> \"a\"; Rule =".parse::<Grammar>().map_err(|error| error.to_string()),
+            Err(String::from("line 1: Expected '-', found '='"))
+        }
+
+        testcase! {
+            boxed_error,
+            Grammar::try_from("").map_err(Box::<dyn std::error::Error>::from).is_err(),
+            true
+        }
+    }
+}
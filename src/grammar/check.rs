@@ -0,0 +1,488 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use syntax_abuse::do_while;
+
+use super::{Grammar, Rule, Symbol};
+
+/// A non-fatal problem with a grammar found by [`Grammar::new_checked`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrammarWarning {
+    /// `rule` refers to `reference` but no rule has that name, so the
+    /// alternatives using it can never match
+    UndefinedReference {
+        /// The rule containing the reference
+        rule: String,
+        /// The name that isn't defined
+        reference: String,
+    },
+    /// `rule` can't be reached from the start symbol so never takes part in
+    /// a parse
+    Unreachable {
+        /// The unreachable rule
+        rule: String,
+    },
+    /// Every alternative of `rule` needs infinite input (or an undefined
+    /// rule) so it can never complete
+    NonProductive {
+        /// The non-productive rule
+        rule: String,
+    },
+    /// `rule` can derive itself as its leftmost symbol, either directly or
+    /// through other rules and nullable prefixes. The Earley algorithm handles
+    /// this without trouble, but the grammar can't be used with a top down
+    /// parser as it is.
+    LeftRecursive {
+        /// The left recursive rule
+        rule: String,
+    },
+}
+
+/// Error produced by [`Grammar::try_new`] when rules refer to names that no
+/// rule has
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrammarError {
+    /// Each undefined name along with the rule referring to it as
+    /// `(rule, reference)`, in the order the rules were declared
+    pub undefined: Vec<(String, String)>,
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Undefined rules: {}",
+            self.undefined
+                .iter()
+                .map(|(rule, reference)| format!("{} (used by {})", reference, rule))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl Grammar {
+    /// As [`Grammar::new`] but a reference to a rule that doesn't exist is an
+    /// error, rather than an alternative that can never match (see
+    /// [`GrammarWarning::UndefinedReference`])
+    /// ```
+    /// # use parsey::grammar::Grammar;
+    /// # use parsey::rule;
+    /// let error = Grammar::try_new(vec![rule!(A -> B "c")]).unwrap_err();
+    /// assert_eq!(error.to_string(), "Undefined rules: B (used by A)");
+    /// ```
+    ///
+    /// # Errors
+    /// If any rule refers to a name that no rule has
+    ///
+    /// # Panics
+    /// See [`Grammar::new`]
+    pub fn try_new(rules: Vec<Rule>) -> Result<Grammar, GrammarError> {
+        let grammar = Grammar::new(rules);
+        let undefined = grammar.undefined_references();
+        if undefined.is_empty() {
+            Ok(grammar)
+        } else {
+            Err(GrammarError { undefined })
+        }
+    }
+
+    /// As [`Grammar::new`] but also check the grammar for problems that don't
+    /// stop it being used, such as rules that can never match. Warnings are
+    /// grouped by kind, within each kind they follow the order the rules were
+    /// declared in and each rule is reported once.
+    ///
+    /// # Panics
+    /// See [`Grammar::new`]
+    #[must_use]
+    pub fn new_checked(rules: Vec<Rule>) -> (Grammar, Vec<GrammarWarning>) {
+        let grammar = Grammar::new(rules);
+        let mut warnings = grammar
+            .undefined_references()
+            .into_iter()
+            .map(|(rule, reference)| GrammarWarning::UndefinedReference { rule, reference })
+            .collect::<Vec<_>>();
+        warnings.extend(
+            grammar
+                .unreachable()
+                .into_iter()
+                .map(|rule| GrammarWarning::Unreachable { rule }),
+        );
+        warnings.extend(
+            grammar
+                .non_productive()
+                .into_iter()
+                .map(|rule| GrammarWarning::NonProductive { rule }),
+        );
+        warnings.extend(
+            grammar
+                .left_recursive()
+                .into_iter()
+                .map(|rule| GrammarWarning::LeftRecursive { rule }),
+        );
+        (grammar, warnings)
+    }
+
+    /// The rules (every alternative) whose name can't be reached by following
+    /// rule references from the start symbol, in declaration order. Usually a
+    /// typo or a leftover, [`Grammar::new_checked`] reports the same names as
+    /// warnings but this can be called on any grammar
+    /// ```
+    /// # use parsey::grammar;
+    /// let grammar = grammar! {
+    ///     Start -> "a" Start;
+    ///     Start -> ;
+    ///     Strat -> "b";
+    /// };
+    /// let unreachable = grammar
+    ///     .unreachable_rules()
+    ///     .iter()
+    ///     .map(ToString::to_string)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(unreachable, vec!["Strat -> 'b'"]);
+    /// ```
+    #[must_use]
+    pub fn unreachable_rules(&self) -> Vec<&Rule> {
+        let reachable = self.reachable();
+        self.rules
+            .iter()
+            .filter(|rule| !reachable.contains(rule.name()))
+            .collect()
+    }
+
+    /// Check every rule can be reached from the start symbol, see
+    /// [`Grammar::unreachable_rules`]
+    ///
+    /// # Errors
+    /// The names of the unreachable rules, in declaration order without
+    /// duplicates
+    pub fn validate_reachability(&self) -> Result<(), Vec<String>> {
+        let unreachable = self.unreachable();
+        if unreachable.is_empty() {
+            Ok(())
+        } else {
+            Err(unreachable)
+        }
+    }
+
+    /// Each name referred to by a rule which no rule has, as
+    /// `(rule, reference)`
+    fn undefined_references(&self) -> Vec<(String, String)> {
+        let defined = self.rule_names();
+        let mut undefined = Vec::new();
+        for rule in &self.rules {
+            for reference in rule.body().iter().flat_map(Symbol::rule_names) {
+                let pair = (String::from(rule.name()), String::from(reference));
+                if !defined.contains(reference) && !undefined.contains(&pair) {
+                    undefined.push(pair);
+                }
+            }
+        }
+        undefined
+    }
+
+    /// The rules that can't be reached from the start symbol
+    fn unreachable(&self) -> Vec<String> {
+        let reachable = self.reachable();
+        self.names_where(|name| !reachable.contains(name))
+    }
+
+    /// The names reachable from the start symbol, including the start symbol
+    /// itself and any undefined references
+    fn reachable(&self) -> HashSet<&str> {
+        let mut reachable = HashSet::new();
+        let mut pending = vec![self.start_symbol()];
+        while let Some(name) = pending.pop() {
+            if !reachable.insert(name) {
+                continue;
+            }
+            for rule in self.get_rules_by_name(name) {
+                pending.extend(rule.body().iter().flat_map(Symbol::rule_names));
+            }
+        }
+        reachable
+    }
+
+    /// The rules which can't produce any finite input
+    pub(super) fn non_productive(&self) -> Vec<String> {
+        let mut productive = HashSet::new();
+        let mut count;
+        do_while! {
+            do {
+                count = productive.len();
+                for rule in &self.rules {
+                    let is_productive = |name: &str| productive.contains(name);
+                    if rule
+                        .body()
+                        .iter()
+                        .all(|symbol| symbol.can_match(&is_productive, true))
+                    {
+                        let _ = productive.insert(rule.name());
+                    }
+                }
+            } while count < productive.len()
+        };
+        self.names_where(|name| !productive.contains(name))
+    }
+
+    /// The rules which can derive themselves as their leftmost symbol
+    fn left_recursive(&self) -> Vec<String> {
+        self.names_where(|name| {
+            // Follow left corners (the first symbol of each alternative, or
+            // any symbol preceded only by nullable rules) looking for `name`
+            let mut seen = HashSet::new();
+            let mut pending = self.left_corners(name);
+            while let Some(corner) = pending.pop() {
+                if corner == name {
+                    return true;
+                }
+                if seen.insert(corner) {
+                    pending.extend(self.left_corners(corner));
+                }
+            }
+            false
+        })
+    }
+
+    /// The rules which can start an alternative of the rule `name`
+    fn left_corners(&self, name: &str) -> Vec<&str> {
+        self.leading_symbols(name)
+            .into_iter()
+            .filter_map(Symbol::rule_name)
+            .collect()
+    }
+
+    fn rule_names(&self) -> HashSet<&str> {
+        self.rules.iter().map(Rule::name).collect()
+    }
+
+    /// The name of each rule for which `predicate` holds, in declaration
+    /// order without duplicates
+    fn names_where<F>(&self, predicate: F) -> Vec<String>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut names: Vec<String> = Vec::new();
+        for rule in &self.rules {
+            if !names.iter().any(|name| name == rule.name()) && predicate(rule.name()) {
+                names.push(String::from(rule.name()));
+            }
+        }
+        names
+    }
+}
+
+syntax_abuse::tests! {
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+    }
+
+    fn warnings(grammar: &Grammar) -> Vec<GrammarWarning> {
+        Grammar::new_checked(grammar.rules.clone()).1
+    }
+
+    fn rule(name: &str) -> String {
+        String::from(name)
+    }
+
+    testcase! {
+        messy,
+        warnings(&grammar! {
+            Root -> Statement Root;
+            Root -> Statement;
+            Statement -> Expr ";";
+            Statement -> Block;
+            Expr -> Term;
+            Term -> Expr "+" Missing;
+            Block -> Optional Block "}";
+            Optional -> ;
+            Orphan -> "x";
+            Orphan -> Gone;
+        }),
+        vec![
+            GrammarWarning::UndefinedReference { rule: rule("Term"), reference: rule("Missing") },
+            GrammarWarning::UndefinedReference { rule: rule("Orphan"), reference: rule("Gone") },
+            GrammarWarning::Unreachable { rule: rule("Orphan") },
+            GrammarWarning::NonProductive { rule: rule("Root") },
+            GrammarWarning::NonProductive { rule: rule("Statement") },
+            GrammarWarning::NonProductive { rule: rule("Expr") },
+            GrammarWarning::NonProductive { rule: rule("Term") },
+            GrammarWarning::NonProductive { rule: rule("Block") },
+            GrammarWarning::LeftRecursive { rule: rule("Expr") },
+            GrammarWarning::LeftRecursive { rule: rule("Term") },
+            GrammarWarning::LeftRecursive { rule: rule("Block") },
+        ]
+    }
+
+    testcase! {
+        arith,
+        warnings(&ARITH),
+        vec![
+            GrammarWarning::LeftRecursive { rule: rule("Sum") },
+            GrammarWarning::LeftRecursive { rule: rule("Product") },
+        ]
+    }
+
+    testcase! {
+        clean,
+        warnings(&grammar! {
+            List -> Item "," List;
+            List -> Item;
+            Item -> ["abc"];
+        }),
+        vec![]
+    }
+
+    testcase! {
+        repeat,
+        warnings(&Grammar::new(vec![
+            Rule::new(
+                rule("List"),
+                vec![Symbol::repeat(
+                    Symbol::Rule(rule("Item")),
+                    Some(Symbol::Rule(rule("Sep"))),
+                    2,
+                    None,
+                )],
+            ),
+            Rule::new(rule("Item"), vec![Symbol::Rule(rule("Item")), Symbol::Literal('a')]),
+            Rule::new(rule("Item"), vec![Symbol::Literal('a')]),
+        ])),
+        vec![
+            GrammarWarning::UndefinedReference { rule: rule("List"), reference: rule("Sep") },
+            GrammarWarning::NonProductive { rule: rule("List") },
+            GrammarWarning::LeftRecursive { rule: rule("Item") },
+        ]
+    }
+
+    testcase! {
+        grammar_unchanged,
+        Grammar::new_checked(ARITH.rules.clone()).0,
+        ARITH.clone()
+    }
+
+    testcase! {
+        undefined_reported_once,
+        warnings(&grammar! {
+            A -> B B;
+            A -> "a" B;
+        }),
+        vec![
+            GrammarWarning::UndefinedReference { rule: rule("A"), reference: rule("B") },
+            GrammarWarning::NonProductive { rule: rule("A") },
+        ]
+    }
+
+    tests! {
+        try_new:
+
+        testcase! {
+            defined,
+            Grammar::try_new(ARITH.rules.clone()),
+            Ok(ARITH.clone())
+        }
+
+        testcase! {
+            undefined,
+            Grammar::try_new(
+                grammar! {
+                    A -> B C;
+                    A -> "a" B;
+                    C -> D;
+                }
+                .rules
+            ),
+            Err(GrammarError {
+                undefined: vec![
+                    (rule("A"), rule("B")),
+                    (rule("C"), rule("D")),
+                ]
+            })
+        }
+
+        testcase! {
+            inside_repeat,
+            Grammar::try_new(vec![Rule::new(
+                rule("List"),
+                vec![Symbol::repeat(Symbol::Rule(rule("Item")), None, 0, None)],
+            )])
+            .map_err(|error| error.to_string()),
+            Err(String::from("Undefined rules: Item (used by List)"))
+        }
+    }
+    tests! {
+        reachability:
+
+        testdata! {
+            ORPHANS: Grammar = grammar! {
+                Start -> A;
+                A -> "a";
+                Orphan -> "x";
+                B -> "b";
+                Orphan -> B;
+            };
+        }
+
+        /// The name of each unreachable alternative
+        fn unreachable(grammar: &Grammar) -> Vec<&str> {
+            grammar.unreachable_rules().into_iter().map(Rule::name).collect()
+        }
+
+        testcase! {
+            arith,
+            unreachable(&ARITH),
+            Vec::<&str>::new()
+        }
+
+        testcase! {
+            every_alternative,
+            unreachable(&ORPHANS),
+            vec!["Orphan", "B", "Orphan"]
+        }
+
+        testcase! {
+            from_start_symbol,
+            unreachable(&ORPHANS.clone().with_start_symbol("Orphan")),
+            vec!["Start", "A"]
+        }
+
+        testcase! {
+            through_repeat,
+            unreachable(&Grammar::new(vec![
+                Rule::new(
+                    rule("List"),
+                    vec![Symbol::repeat(
+                        Symbol::Rule(rule("Item")),
+                        Some(Symbol::Rule(rule("Sep"))),
+                        0,
+                        None,
+                    )],
+                ),
+                Rule::new(rule("Item"), vec![Symbol::Literal('a')]),
+                Rule::new(rule("Sep"), vec![Symbol::Literal(',')]),
+            ])),
+            Vec::<&str>::new()
+        }
+
+        testcase! {
+            valid,
+            ARITH.validate_reachability(),
+            Ok(())
+        }
+
+        testcase! {
+            invalid,
+            ORPHANS.validate_reachability(),
+            Err(vec![rule("Orphan"), rule("B")])
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use super::{Grammar, Symbol};
+
+/// State for Tarjan's strongly connected components algorithm over the rule
+/// names, each identified by its position in declaration order
+struct Tarjan<'a> {
+    /// The rules each rule refers to
+    edges: &'a [Vec<usize>],
+    /// The order each rule was visited in, `None` until it has been visited
+    index: Vec<Option<usize>>,
+    /// The lowest index reachable from each rule through the rules on `stack`
+    low: Vec<usize>,
+    /// Visited rules which haven't been assigned a component yet
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    next: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, rule: usize) {
+        self.index[rule] = Some(self.next);
+        self.low[rule] = self.next;
+        self.next += 1;
+        self.stack.push(rule);
+        self.on_stack[rule] = true;
+
+        for &other in &self.edges[rule] {
+            match self.index[other] {
+                None => {
+                    self.visit(other);
+                    self.low[rule] = self.low[rule].min(self.low[other]);
+                }
+                Some(index) if self.on_stack[other] => {
+                    self.low[rule] = self.low[rule].min(index);
+                }
+                Some(_) => (),
+            }
+        }
+
+        // `rule` is the first rule visited in its component, everything above
+        // it on the stack belongs to the same component
+        if Some(self.low[rule]) == self.index[rule] {
+            let mut component = Vec::new();
+            while let Some(other) = self.stack.pop() {
+                self.on_stack[other] = false;
+                component.push(other);
+                if other == rule {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+impl Grammar {
+    /// Groups of mutually recursive rules, the strongly connected components
+    /// of the graph with an edge from each rule to every rule in its bodies.
+    /// Only recursive groups are included, a rule on its own is only a group
+    /// if it refers to itself. Groups are in the order their first rule was
+    /// declared, as are the rules within each group.
+    /// ```
+    /// # use parsey::grammar;
+    /// let grammar = grammar! {
+    ///     Sum -> Sum "+" Product;
+    ///     Sum -> Product;
+    ///     Product -> Factor "*" Product;
+    ///     Product -> Factor;
+    ///     Factor -> "(" Sum ")";
+    ///     Factor -> Digit;
+    ///     Digit -> ["0123456789"];
+    /// };
+    /// assert_eq!(
+    ///     grammar.recursive_clusters(),
+    ///     vec![vec!["Sum", "Product", "Factor"]]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn recursive_clusters(&self) -> Vec<Vec<String>> {
+        let mut names: Vec<&str> = Vec::new();
+        for rule in &self.rules {
+            if !names.contains(&rule.name()) {
+                names.push(rule.name());
+            }
+        }
+        let positions = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| (name, i))
+            .collect::<HashMap<_, _>>();
+
+        // References to undefined rules can't be part of a cycle
+        let mut edges = vec![Vec::new(); names.len()];
+        for rule in &self.rules {
+            edges[positions[rule.name()]].extend(
+                rule.body()
+                    .iter()
+                    .flat_map(Symbol::rule_names)
+                    .filter_map(|name| positions.get(name).copied()),
+            );
+        }
+
+        let mut tarjan = Tarjan {
+            edges: &edges,
+            index: vec![None; names.len()],
+            low: vec![0; names.len()],
+            stack: Vec::new(),
+            on_stack: vec![false; names.len()],
+            next: 0,
+            components: Vec::new(),
+        };
+        for rule in 0..names.len() {
+            if tarjan.index[rule].is_none() {
+                tarjan.visit(rule);
+            }
+        }
+
+        let mut clusters = tarjan
+            .components
+            .into_iter()
+            .filter(|component| component.len() > 1 || edges[component[0]].contains(&component[0]))
+            .map(|mut component| {
+                component.sort_unstable();
+                component
+            })
+            .collect::<Vec<_>>();
+        clusters.sort_unstable();
+        clusters
+            .into_iter()
+            .map(|component| {
+                component
+                    .into_iter()
+                    .map(|i| String::from(names[i]))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+syntax_abuse::tests! {
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+    }
+
+    /// Each cluster's rules separated by spaces, for easier comparison
+    fn clusters(grammar: &Grammar) -> Vec<String> {
+        grammar
+            .recursive_clusters()
+            .iter()
+            .map(|cluster| cluster.join(" "))
+            .collect()
+    }
+
+    testcase! {
+        arith,
+        clusters(&ARITH),
+        vec!["Sum Product Factor", "Number"]
+    }
+
+    testcase! {
+        without_brackets,
+        clusters(&grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> ["0123456789"];
+        }),
+        vec!["Sum", "Product"]
+    }
+
+    testcase! {
+        not_recursive,
+        clusters(&grammar! {
+            A -> B C;
+            B -> C;
+            C -> "c";
+        }),
+        Vec::<String>::new()
+    }
+
+    testcase! {
+        mutual,
+        clusters(&grammar! {
+            Start -> Even;
+            Odd -> "a" Even;
+            Even -> "a" Odd;
+            Even -> ;
+        }),
+        vec!["Odd Even"]
+    }
+
+    testcase! {
+        separate_clusters,
+        clusters(&grammar! {
+            Start -> A C;
+            A -> "a" B;
+            B -> A;
+            B -> ;
+            C -> "c" D;
+            D -> C;
+            D -> ;
+        }),
+        vec!["A B", "C D"]
+    }
+
+    testcase! {
+        undefined_reference,
+        clusters(&grammar! {
+            A -> A Missing;
+            A -> "a";
+        }),
+        vec!["A"]
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+
+use super::{Grammar, Symbol};
+
+impl Grammar {
+    /// The characters that can begin a match of the rule `name`, its FIRST
+    /// set. Useful for dispatching on the first character of the input or
+    /// rejecting it before running a full parse. Empty if there is no rule
+    /// called `name` or it only matches the empty string. Predicates (see
+    /// [`Grammar::with_predicate`]) aren't taken into account.
+    /// ```
+    /// # use parsey::grammar;
+    /// let grammar = grammar! {
+    ///     Value -> Sign ["0123456789"];
+    ///     Sign -> "-";
+    ///     Sign -> ;
+    /// };
+    /// let mut first = grammar.first_chars("Value").into_iter().collect::<Vec<_>>();
+    /// first.sort_unstable();
+    /// assert_eq!(first, "-0123456789".chars().collect::<Vec<_>>());
+    /// ```
+    #[must_use]
+    pub fn first_chars(&self, name: &str) -> HashSet<char> {
+        let mut chars = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![name];
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name) {
+                continue;
+            }
+            for symbol in self.leading_symbols(name) {
+                match symbol {
+                    Symbol::Rule(name) => pending.push(name),
+                    Symbol::Literal(c) => {
+                        let _ = chars.insert(*c);
+                    }
+                    Symbol::OneOf(set) => chars.extend(set.iter()),
+                    Symbol::Range(start, end) => chars.extend(*start..=*end),
+                    Symbol::AnyChar => chars.extend(char::MIN..=char::MAX),
+                    Symbol::Not(_) => {
+                        chars.extend((char::MIN..=char::MAX).filter(|c| symbol.matches(*c)));
+                    }
+                    Symbol::Grapheme(cluster) => chars.extend(cluster.chars().next()),
+                    Symbol::Newline => chars.extend(['\n', '\r']),
+                    // Never returned, a repeat's leading symbols are those
+                    // inside it
+                    Symbol::Repeat { .. } => (),
+                }
+            }
+        }
+        chars
+    }
+
+    /// The symbols (rules or terminals) which can start an alternative of the
+    /// rule `name`, the first symbol of each alternative or any symbol
+    /// preceded only by nullable ones
+    pub(super) fn leading_symbols(&self, name: &str) -> Vec<&Symbol> {
+        let mut leading = Vec::new();
+        for rule in self.get_rules_by_name(name) {
+            for symbol in rule.body() {
+                // The separator of a repeat only comes first if the repeated
+                // symbol can be empty, the repeated symbol has to come first
+                // even when it needn't be there at all
+                if let Symbol::Repeat { inner, sep, .. } = symbol {
+                    leading.push(&**inner);
+                    if self.symbol_is_nullable(inner) {
+                        leading.extend(sep.as_deref());
+                    }
+                } else {
+                    leading.push(symbol);
+                }
+                if !self.symbol_is_nullable(symbol) {
+                    break;
+                }
+            }
+        }
+        leading
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::grammar::Rule;
+
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+    }
+
+    /// The first characters of `name` in order, for easier comparison
+    fn first(grammar: &Grammar, name: &str) -> String {
+        let mut chars = grammar.first_chars(name).into_iter().collect::<Vec<_>>();
+        chars.sort_unstable();
+        chars.into_iter().collect()
+    }
+
+    testcase! {
+        factor,
+        first(&ARITH, "Factor"),
+        "(0123456789"
+    }
+
+    testcase! {
+        left_recursive,
+        first(&ARITH, "Sum"),
+        "(0123456789"
+    }
+
+    testcase! {
+        missing_rule,
+        first(&ARITH, "Term"),
+        ""
+    }
+
+    testcase! {
+        nullable_prefix,
+        first(
+            &grammar! {
+                Start -> Maybe Maybe "c";
+                Maybe -> "a";
+                Maybe -> "b";
+                Maybe -> ;
+            },
+            "Start"
+        ),
+        "abc"
+    }
+
+    testcase! {
+        only_empty,
+        first(&grammar! { Empty -> ; }, "Empty"),
+        ""
+    }
+
+    testcase! {
+        multi_character_terminals,
+        first(
+            &grammar! {
+                Start -> "e\u{301}";
+                Start -> $;
+            },
+            "Start"
+        ),
+        "\n\re"
+    }
+
+    testcase! {
+        repeat,
+        first(
+            &Grammar::new(vec![
+                Rule::new(
+                    String::from("List"),
+                    vec![
+                        Symbol::repeat(
+                            Symbol::Rule(String::from("Item")),
+                            Some(Symbol::Literal(',')),
+                            0,
+                            None,
+                        ),
+                        Symbol::Literal(';'),
+                    ],
+                ),
+                Rule::new(String::from("Item"), vec![Symbol::Literal('a')]),
+                Rule::new(String::from("Item"), Vec::new()),
+            ]),
+            "List"
+        ),
+        ",;a"
+    }
+}
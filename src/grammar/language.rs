@@ -0,0 +1,614 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use syntax_abuse::do_while;
+
+use super::{Grammar, Rule, Symbol};
+
+/// The first strings in order each rule can produce keyed by rule name and
+/// length, filled in one length at a time
+type Table<'a> = HashMap<(&'a str, usize), BTreeSet<String>>;
+
+impl Grammar {
+    /// True if the language described by the grammar contains finitely many
+    /// strings. The language is infinite exactly when a rule reachable from the
+    /// start symbol can derive itself alongside symbols which produce at least
+    /// one character, so cycles that add nothing (`A -> B; B -> A;`, or
+    /// `A -> A Empty;` with `Empty` only producing the empty string) don't
+    /// count. Rules which can never complete are ignored. Semantic predicates
+    /// (see [`Grammar::with_predicate`]) aren't taken into account.
+    /// ```
+    /// # use parsey::grammar;
+    /// assert!(grammar! { Bit -> ["01"]; }.is_finite_language());
+    /// assert!(!grammar! { Bits -> ["01"] Bits; Bits -> ["01"]; }.is_finite_language());
+    /// ```
+    #[must_use]
+    pub fn is_finite_language(&self) -> bool {
+        if self.has_repeats() {
+            return self.without_repeats().is_finite_language();
+        }
+
+        let usable = self.usable_rules();
+        let non_empty = non_empty_rules(&usable);
+
+        // Edges from each rule to the rules in its bodies, and the edges where
+        // the rest of the body can produce a character
+        let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut growing = Vec::new();
+        for rule in &usable {
+            let body = rule.body();
+            for (i, name) in body
+                .iter()
+                .enumerate()
+                .filter_map(|(i, symbol)| Some((i, symbol.rule_name()?)))
+            {
+                edges.entry(rule.name()).or_default().push(name);
+                let grows = body.iter().enumerate().any(|(j, other)| {
+                    j != i
+                        && other
+                            .rule_name()
+                            .is_none_or(|other| non_empty.contains(other))
+                });
+                if grows {
+                    growing.push((rule.name(), name));
+                }
+            }
+        }
+
+        // A growing edge on a cycle can be pumped as often as we like
+        !growing
+            .into_iter()
+            .any(|(from, to)| reachable(&edges, to).contains(from))
+    }
+
+    /// Up to `max` strings in the language described by the grammar, shortest
+    /// first and in lexicographic order within each length. Finite languages
+    /// (see [`Grammar::is_finite_language`]) are enumerated completely if
+    /// `max` allows. Useful for exhaustively testing small grammars. Only the
+    /// first `max` strings of each length are worked out for each rule, so
+    /// large character classes (e.g. `.` or a negation) don't make this any
+    /// slower than small ones. Semantic predicates aren't applied.
+    /// ```
+    /// # use parsey::grammar;
+    /// let grammar = grammar! {
+    ///     Answer -> "yes" Excited;
+    ///     Answer -> "no" Excited;
+    ///     Excited -> "!";
+    ///     Excited -> ;
+    /// };
+    /// assert_eq!(grammar.enumerate_language(10), vec!["no", "no!", "yes", "yes!"]);
+    /// ```
+    #[must_use]
+    pub fn enumerate_language(&self, max: usize) -> Vec<String> {
+        if self.has_repeats() {
+            return self.without_repeats().enumerate_language(max);
+        }
+
+        // An infinite language always has another string to find, a finite one
+        // has nothing longer than its longest string
+        let longest = if self.is_finite_language() {
+            Some(self.longest_string())
+        } else {
+            None
+        };
+
+        let usable = self.usable_rules();
+        let mut table = Table::new();
+        let mut language = Vec::new();
+        let mut len = 0;
+        while language.len() < max && longest.is_none_or(|longest| len <= longest) {
+            fill(&mut table, &usable, len, max);
+            if let Some(strings) = table.get(&(self.start_symbol(), len)) {
+                language.extend(strings.iter().take(max - language.len()).cloned());
+            }
+            len += 1;
+        }
+        language
+    }
+
+    /// The same language with each [`Symbol::Repeat`] replaced by helper rules
+    /// (with reserved names), so the analyses here only have to deal with
+    /// sequences of symbols
+    fn without_repeats(&self) -> Grammar {
+        let mut helpers = Vec::new();
+        let mut repeats = 0;
+        let mut rules = Vec::new();
+        for rule in &self.rules {
+            let body = rule
+                .body()
+                .iter()
+                .map(|symbol| {
+                    let Symbol::Repeat {
+                        inner,
+                        sep,
+                        min,
+                        max,
+                    } = symbol
+                    else {
+                        return symbol.clone();
+                    };
+                    let name = format!("@repeat{}", repeats);
+                    repeats += 1;
+                    helpers.extend(repeat_rules(&name, inner, sep.as_deref(), *min, *max));
+                    Symbol::Rule(name)
+                })
+                .collect();
+            rules.push(Rule::new(String::from(rule.name()), body));
+        }
+        rules.extend(helpers);
+
+        let mut grammar = Grammar::new(rules);
+        grammar.start.clone_from(&self.start);
+        grammar
+    }
+
+    /// The rules reachable from the start symbol whose bodies only contain
+    /// symbols that can complete
+    fn usable_rules(&self) -> Vec<&Rule> {
+        let non_productive = self.non_productive();
+        let productive = |symbol: &Symbol| {
+            symbol.rule_name().is_none_or(|name| {
+                self.rules.iter().any(|rule| rule.name() == name)
+                    && !non_productive.iter().any(|other| other == name)
+            })
+        };
+        let candidates = self
+            .rules
+            .iter()
+            .filter(|rule| rule.body().iter().all(productive))
+            .collect::<Vec<_>>();
+
+        let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+        for rule in &candidates {
+            edges
+                .entry(rule.name())
+                .or_default()
+                .extend(rule.body().iter().filter_map(Symbol::rule_name));
+        }
+        let reachable = reachable(&edges, self.start_symbol());
+        candidates
+            .into_iter()
+            .filter(|rule| reachable.contains(rule.name()))
+            .collect()
+    }
+
+    /// The length in characters of the longest string in a finite language
+    fn longest_string(&self) -> usize {
+        let usable = self.usable_rules();
+        let mut longest: HashMap<&str, usize> = HashMap::new();
+        let mut changed;
+        do_while! {
+            do {
+                changed = false;
+                for rule in &usable {
+                    let len = rule
+                        .body()
+                        .iter()
+                        .map(|symbol| match symbol {
+                            Symbol::Rule(name) => longest.get(name.as_str()).copied().unwrap_or(0),
+                            Symbol::Grapheme(cluster) => cluster.chars().count(),
+                            Symbol::Newline => 2,
+                            terminal => usize::from(!terminal_strings(terminal, 1, 1).is_empty()),
+                        })
+                        .sum();
+                    let entry = longest.entry(rule.name()).or_insert(0);
+                    if len > *entry {
+                        *entry = len;
+                        changed = true;
+                    }
+                }
+            } while changed
+        };
+        longest.get(self.start_symbol()).copied().unwrap_or(0)
+    }
+}
+
+/// Rules called `name` matching `inner` `min` to `max` times separated by
+/// `sep`, see [`Grammar::without_repeats`]
+fn repeat_rules(
+    name: &str,
+    inner: &Symbol,
+    sep: Option<&Symbol>,
+    min: usize,
+    max: Option<usize>,
+) -> Vec<Rule> {
+    // `count` repetitions in a row
+    let repetitions = |count: usize| {
+        let mut body = Vec::new();
+        for i in 0..count {
+            if i > 0 {
+                body.extend(sep.cloned());
+            }
+            body.push(inner.clone());
+        }
+        body
+    };
+
+    if let Some(max) = max {
+        return (min..=max)
+            .map(|count| Rule::reserved(String::from(name), repetitions(count)))
+            .collect();
+    }
+
+    // Enough repetitions to reach the minimum then a tail of any length
+    let tail = format!("{}tail", name);
+    let mut body = repetitions(min.max(1));
+    body.push(Symbol::Rule(tail.clone()));
+    let mut rules = vec![Rule::reserved(String::from(name), body)];
+    if min == 0 {
+        rules.push(Rule::reserved(String::from(name), Vec::new()));
+    }
+    let mut body = sep.into_iter().cloned().collect::<Vec<_>>();
+    body.push(inner.clone());
+    body.push(Symbol::Rule(tail.clone()));
+    rules.push(Rule::reserved(tail.clone(), body));
+    rules.push(Rule::reserved(tail, Vec::new()));
+    rules
+}
+
+/// The names of the rules which can produce at least one character
+fn non_empty_rules<'a>(rules: &[&'a Rule]) -> HashSet<&'a str> {
+    let mut non_empty = HashSet::new();
+    let mut count;
+    do_while! {
+        do {
+            count = non_empty.len();
+            for rule in rules {
+                if rule
+                    .body()
+                    .iter()
+                    .any(|symbol| symbol.rule_name().is_none_or(|name| non_empty.contains(name)))
+                {
+                    let _ = non_empty.insert(rule.name());
+                }
+            }
+        } while count < non_empty.len()
+    };
+    non_empty
+}
+
+/// The names reachable from `start` by following `edges`, including `start`
+fn reachable<'a>(edges: &HashMap<&'a str, Vec<&'a str>>, start: &'a str) -> HashSet<&'a str> {
+    let mut reachable = HashSet::new();
+    let mut pending = vec![start];
+    while let Some(name) = pending.pop() {
+        if reachable.insert(name) {
+            pending.extend(edges.get(name).into_iter().flatten());
+        }
+    }
+    reachable
+}
+
+/// Add the first `max` strings of length `len` produced by each rule to the
+/// table, the entries for shorter strings must already be complete. Rules can
+/// depend on each other at the same length through nullable symbols
+/// (`A -> Empty B`) so keep going until nothing changes.
+///
+/// Keeping only the first `max` strings of every entry doesn't lose any of the
+/// first `max` strings of the start symbol. Those of a rule come from the first
+/// `max` of each of its alternatives, and those of a sequence from the first
+/// `max` of each symbol for each way of splitting up the length.
+fn fill<'a>(table: &mut Table<'a>, rules: &[&'a Rule], len: usize, max: usize) {
+    let mut changed;
+    do_while! {
+        do {
+            changed = false;
+            for rule in rules {
+                let strings = sequences(table, rule.body(), len, max);
+                let entry = table.entry((rule.name(), len)).or_default();
+                for string in strings {
+                    // A full entry only takes strings that come before its
+                    // last, so nothing it has dropped can come back
+                    if entry.len() >= max && entry.last().is_none_or(|last| *last <= string) {
+                        continue;
+                    }
+                    changed |= entry.insert(string);
+                    if entry.len() > max {
+                        let _ = entry.pop_last();
+                    }
+                }
+            }
+        } while changed
+    };
+}
+
+/// The first `max` strings of length `len` the sequence of symbols `body` can
+/// produce according to the table so far, in order
+fn sequences(table: &Table<'_>, body: &[Symbol], len: usize, max: usize) -> Vec<String> {
+    let Some((first, rest)) = body.split_first() else {
+        return if len == 0 {
+            vec![String::new()]
+        } else {
+            Vec::new()
+        };
+    };
+
+    let mut result = BTreeSet::new();
+    for first_len in 0..=len {
+        let firsts = match first {
+            Symbol::Rule(name) => table
+                .get(&(name.as_str(), first_len))
+                .map(|strings| strings.iter().cloned().collect())
+                .unwrap_or_default(),
+            terminal => terminal_strings(terminal, first_len, max),
+        };
+        if firsts.is_empty() {
+            continue;
+        }
+        let rests = sequences(table, rest, len - first_len, max);
+        // The firsts are all the same length so their concatenations with the
+        // rests come out in order
+        result.extend(
+            firsts
+                .iter()
+                .flat_map(|first| rests.iter().map(move |rest| first.clone() + rest))
+                .take(max),
+        );
+    }
+    result.into_iter().take(max).collect()
+}
+
+/// The first `max` strings of length `len` matched by a terminal, in order
+fn terminal_strings(terminal: &Symbol, len: usize, max: usize) -> Vec<String> {
+    match terminal {
+        Symbol::Grapheme(cluster) if cluster.chars().count() == len => vec![cluster.clone()],
+        Symbol::Newline if len == 1 => vec![String::from("\n")],
+        Symbol::Newline if len == 2 => vec![String::from("\r\n")],
+        Symbol::Literal(_)
+        | Symbol::OneOf(_)
+        | Symbol::Range(..)
+        | Symbol::AnyChar
+        | Symbol::Not(_)
+            if len == 1 =>
+        {
+            code_points(terminal)
+                .into_iter()
+                .flat_map(|(start, end)| (start..=end).filter_map(char::from_u32))
+                .take(max)
+                .map(String::from)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The code points matched by a single character terminal as sorted,
+/// non-overlapping inclusive ranges, so big classes and their negations can be
+/// walked in order without listing every character they match
+fn code_points(terminal: &Symbol) -> Vec<(u32, u32)> {
+    match terminal {
+        Symbol::Literal(c) => vec![(u32::from(*c), u32::from(*c))],
+        Symbol::OneOf(chars) => {
+            let mut points = chars
+                .iter()
+                .map(|c| (u32::from(*c), u32::from(*c)))
+                .collect::<Vec<_>>();
+            points.sort_unstable();
+            points
+        }
+        Symbol::Range(start, end) if start <= end => vec![(u32::from(*start), u32::from(*end))],
+        Symbol::AnyChar => vec![(0, u32::from(char::MAX))],
+        Symbol::Not(inner) => {
+            // The gaps between the ranges the inner terminal matches
+            let mut points = Vec::new();
+            let mut next = 0;
+            for (start, end) in code_points(inner) {
+                if next < start {
+                    points.push((next, start - 1));
+                }
+                next = end + 1;
+            }
+            if next <= u32::from(char::MAX) {
+                points.push((next, u32::from(char::MAX)));
+            }
+            points
+        }
+        // A range running backwards matches nothing
+        Symbol::Range(..)
+        | Symbol::Grapheme(_)
+        | Symbol::Newline
+        | Symbol::Rule(_)
+        | Symbol::Repeat { .. } => Vec::new(),
+    }
+}
+
+syntax_abuse::tests! {
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+        GREETING: Grammar = grammar! {
+            Greeting -> Word Punctuation;
+            Word -> "hi";
+            Word -> "yo";
+            Punctuation -> ["!?"];
+            Punctuation -> ;
+        };
+    }
+
+    testcase! {
+        finite,
+        GREETING.is_finite_language(),
+        true
+    }
+
+    testcase! {
+        complete_language,
+        GREETING.enumerate_language(100),
+        vec!["hi", "yo", "hi!", "hi?", "yo!", "yo?"]
+    }
+
+    testcase! {
+        truncated,
+        GREETING.enumerate_language(3),
+        vec!["hi", "yo", "hi!"]
+    }
+
+    testcase! {
+        nothing_requested,
+        GREETING.enumerate_language(0),
+        Vec::<String>::new()
+    }
+
+    testcase! {
+        arith_infinite,
+        ARITH.is_finite_language(),
+        false
+    }
+
+    testcase! {
+        bounded_repeat,
+        {
+            let grammar = Grammar::new(vec![Rule::new(
+                String::from("List"),
+                vec![Symbol::repeat(Symbol::Literal('a'), Some(Symbol::Literal(',')), 1, Some(2))],
+            )]);
+            (grammar.is_finite_language(), grammar.enumerate_language(10))
+        },
+        (true, vec![String::from("a"), String::from("a,a")])
+    }
+
+    testcase! {
+        unbounded_repeat,
+        Grammar::new(vec![Rule::new(
+            String::from("List"),
+            vec![Symbol::repeat(Symbol::Literal('a'), None, 0, None)],
+        )])
+        .enumerate_language(4),
+        vec!["", "a", "aa", "aaa"]
+    }
+
+    testcase! {
+        arith_shortest_first,
+        ARITH.enumerate_language(12),
+        vec!["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "00", "01"]
+    }
+
+    testcase! {
+        enumerated_strings_recognised,
+        ARITH
+            .enumerate_language(200)
+            .iter()
+            .all(|input| crate::recognise(&ARITH, input)),
+        true
+    }
+
+    testcase! {
+        unit_cycle_finite,
+        grammar! {
+            A -> B;
+            B -> A;
+            B -> "x";
+        }
+        .enumerate_language(10),
+        vec!["x"]
+    }
+
+    testcase! {
+        empty_growth_finite,
+        grammar! {
+            A -> A Empty;
+            A -> "a";
+            Empty -> ;
+        }
+        .is_finite_language(),
+        true
+    }
+
+    testcase! {
+        nullable_growth_infinite,
+        grammar! {
+            A -> Maybe A;
+            A -> "a";
+            Maybe -> "b";
+            Maybe -> ;
+        }
+        .enumerate_language(4),
+        vec!["a", "ba", "bba", "bbba"]
+    }
+
+    testcase! {
+        unusable_cycles_ignored,
+        grammar! {
+            Start -> "s";
+            Start -> Never;
+            Never -> "n" Never;
+            Unreachable -> "u" Unreachable;
+            Unreachable -> "u";
+        }
+        .enumerate_language(10),
+        vec!["s"]
+    }
+
+    testcase! {
+        empty_language,
+        (
+            grammar! { A -> "a" A; }.is_finite_language(),
+            grammar! { A -> "a" A; }.enumerate_language(10)
+        ),
+        (true, Vec::<String>::new())
+    }
+
+    testcase! {
+        empty_string,
+        grammar! {
+            Maybe -> "a";
+            Maybe -> ;
+        }
+        .enumerate_language(10),
+        vec!["", "a"]
+    }
+
+    testcase! {
+        newlines_and_graphemes,
+        grammar! {
+            Line -> "e\u{301}" $;
+        }
+        .enumerate_language(10),
+        vec!["e\u{301}\n", "e\u{301}\r\n"]
+    }
+
+    testcase! {
+        any_char_bounded,
+        grammar! { S -> "a" . ; }.enumerate_language(2),
+        vec!["a\0", "a\u{1}"]
+    }
+
+    testcase! {
+        any_chars_bounded,
+        grammar! { S -> . . ; }.enumerate_language(3),
+        vec!["\0\0", "\0\u{1}", "\0\u{2}"]
+    }
+
+    testcase! {
+        negation_bounded,
+        grammar! { S -> [^ '\0'-'b'] [^ 'a'-'z']; }.enumerate_language(2),
+        vec!["c\0", "c\u{1}"]
+    }
+
+    testcase! {
+        negated_any_char,
+        Grammar::new(vec![Rule::new(
+            String::from("S"),
+            vec![Symbol::Not(Box::new(Symbol::AnyChar))],
+        )])
+        .enumerate_language(2),
+        Vec::<String>::new()
+    }
+
+    testcase! {
+        range_skips_surrogates,
+        Grammar::new(vec![Rule::new(
+            String::from("S"),
+            vec![Symbol::Range('\u{d7ff}', '\u{e001}')],
+        )])
+        .enumerate_language(3),
+        vec!["\u{d7ff}", "\u{e000}", "\u{e001}"]
+    }
+}
@@ -0,0 +1,143 @@
+use super::{Grammar, Rule, Symbol};
+
+impl Grammar {
+    /// Insert the rule `layout` between every pair of adjacent symbols in
+    /// every rule body, so a scannerless grammar tolerates whitespace between
+    /// tokens without threading it through each rule by hand. `layout` should
+    /// be a nullable rule already in the grammar matching optional whitespace
+    /// (or comments). Its own rules are left alone, as are the rules named in
+    /// `lexical` which describe single tokens (e.g. the digits of a number
+    /// shouldn't be separated by spaces). Layout isn't added before or after
+    /// the whole input.
+    /// ```
+    /// # use parsey::{grammar, recognise};
+    /// let grammar = grammar! {
+    ///     Sum -> Sum ["+-"] Number;
+    ///     Sum -> Number;
+    ///     Number -> ["0123456789"] Number;
+    ///     Number -> ["0123456789"];
+    ///     Ws -> " " Ws;
+    ///     Ws -> ;
+    /// };
+    /// assert!(!recognise(&grammar, "12 + 3"));
+    /// let grammar = grammar.with_layout("Ws", &["Number"]);
+    /// assert!(recognise(&grammar, "12 + 3"));
+    /// assert!(!recognise(&grammar, "1 2 + 3"));
+    /// ```
+    ///
+    /// # Panics
+    /// If the grammar has no rule called `layout`
+    #[must_use]
+    pub fn with_layout(mut self, layout: &str, lexical: &[&str]) -> Grammar {
+        assert!(
+            self.rules.iter().any(|rule| rule.name() == layout),
+            "No rule named {} to use as layout",
+            layout
+        );
+
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                if rule.name() == layout || lexical.contains(&rule.name()) {
+                    return rule.clone();
+                }
+                let mut body = Vec::with_capacity(rule.body().len() * 2);
+                for (i, symbol) in rule.body().iter().enumerate() {
+                    if i > 0 {
+                        body.push(Symbol::Rule(String::from(layout)));
+                    }
+                    body.push(symbol.clone());
+                }
+                Rule::new(String::from(rule.name()), body).with_precedence(rule.precedence())
+            })
+            .collect();
+
+        let predicates = std::mem::take(&mut self.predicates);
+        let start = std::mem::take(&mut self.start);
+
+        // Rebuild so the nullable analysis sees the new bodies
+        let mut grammar = Grammar::new(rules);
+        grammar.predicates = predicates;
+        grammar.start = start;
+        grammar
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::{parse, recognise};
+
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Number;
+            Product -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+            Ws -> [" \t"] Ws;
+            Ws -> ;
+        };
+        LAYOUT: Grammar = ARITH.clone().with_layout("Ws", &["Number"]);
+    }
+
+    testcase! {
+        rules,
+        LAYOUT.clone(),
+        grammar! {
+            Sum -> Sum Ws ["+-"] Ws Product;
+            Sum -> Product;
+            Product -> Product Ws ["*/"] Ws Number;
+            Product -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+            Ws -> [" \t"] Ws;
+            Ws -> ;
+        }
+    }
+
+    testcase! {
+        without_layout,
+        ["1+2", "1 + 2", "1 +2"]
+            .iter()
+            .map(|input| recognise(&ARITH, input))
+            .collect::<Vec<_>>(),
+        vec![true, false, false]
+    }
+
+    testcase! {
+        with_layout,
+        ["1+2", "1 + 2", "1 +2", "12 *\t3 -  4"]
+            .iter()
+            .map(|input| recognise(&LAYOUT, input))
+            .collect::<Vec<_>>(),
+        vec![true, true, true, true]
+    }
+
+    testcase! {
+        lexical_rules_unchanged,
+        ["1 2 + 3", " 1 + 2", "1 + 2 "]
+            .iter()
+            .map(|input| recognise(&LAYOUT, input))
+            .collect::<Vec<_>>(),
+        vec![false, false, false]
+    }
+
+    testcase! {
+        unambiguous,
+        parse(&LAYOUT, "1 + 2 * 3").unwrap().count(),
+        1
+    }
+
+    testcase! {
+        start_symbol_kept,
+        ARITH.clone().with_start_symbol("Product").with_layout("Ws", &[]).start_symbol(),
+        "Product"
+    }
+
+    #[test]
+    #[should_panic(expected = "No rule named Space to use as layout")]
+    fn missing_layout_rule() {
+        drop(ARITH.clone().with_layout("Space", &[]));
+    }
+}
@@ -0,0 +1,156 @@
+use super::{Grammar, Rule, Symbol};
+
+/// Whether a list generated by [`Grammar::separated_by`] may end with a
+/// separator
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TrailingSeparator {
+    /// `a, b, c,` is accepted as well as `a, b, c`
+    Allowed,
+    /// Only `a, b, c` is accepted
+    Forbidden,
+}
+
+impl Grammar {
+    /// Build rules named `name` matching one or more `element`s separated by
+    /// `separator`, optionally followed by a trailing `separator`. The rules
+    /// can be added to a larger grammar like any other:
+    /// ```
+    /// # use parsey::grammar::{Grammar, Symbol, TrailingSeparator};
+    /// # use parsey::{recognise, rule};
+    /// let mut rules = vec![rule!(Array -> "[" Items "]")];
+    /// rules.extend(Grammar::separated_by(
+    ///     "Items",
+    ///     Symbol::Rule(String::from("Digit")),
+    ///     Symbol::Literal(','),
+    ///     TrailingSeparator::Allowed,
+    /// ));
+    /// rules.push(rule!(Digit -> ["0123456789"]));
+    /// let grammar = Grammar::new(rules);
+    /// assert!(recognise(&grammar, "[1,2,3]"));
+    /// assert!(recognise(&grammar, "[1,2,3,]"));
+    /// ```
+    ///
+    /// # Panics
+    /// See [`Rule::new`]
+    #[must_use]
+    pub fn separated_by(
+        name: &str,
+        element: Symbol,
+        separator: Symbol,
+        trailing: TrailingSeparator,
+    ) -> Vec<Rule> {
+        let list = Symbol::Rule(String::from(name));
+        let mut rules = vec![
+            Rule::new(
+                String::from(name),
+                vec![element.clone(), separator.clone(), list],
+            ),
+            Rule::new(String::from(name), vec![element.clone()]),
+        ];
+        if trailing == TrailingSeparator::Allowed {
+            rules.push(Rule::new(String::from(name), vec![element, separator]));
+        }
+        rules
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::recognise;
+
+    fn list(trailing: TrailingSeparator) -> Grammar {
+        Grammar::new(Grammar::separated_by(
+            "List",
+            Symbol::Literal('a'),
+            Symbol::Literal(','),
+            trailing,
+        ))
+    }
+
+    testdata! {
+        ALLOWED: Grammar = list(TrailingSeparator::Allowed);
+        FORBIDDEN: Grammar = list(TrailingSeparator::Forbidden);
+    }
+
+    testcase! {
+        rules,
+        Grammar::separated_by(
+            "List",
+            Symbol::Rule(String::from("Item")),
+            Symbol::Literal(','),
+            TrailingSeparator::Allowed
+        ),
+        vec![
+            rule!(List -> Item "," List),
+            rule!(List -> Item),
+            rule!(List -> Item ",")
+        ]
+    }
+
+    tests! {
+        trailing_allowed:
+
+        testcase! {
+            single,
+            recognise(&ALLOWED, "a"),
+            true
+        }
+
+        testcase! {
+            several,
+            recognise(&ALLOWED, "a,a,a"),
+            true
+        }
+
+        testcase! {
+            trailing,
+            recognise(&ALLOWED, "a,a,a,"),
+            true
+        }
+
+        testcase! {
+            only_separator,
+            recognise(&ALLOWED, ","),
+            false
+        }
+
+        testcase! {
+            double_trailing,
+            recognise(&ALLOWED, "a,a,,"),
+            false
+        }
+
+        testcase! {
+            empty,
+            recognise(&ALLOWED, ""),
+            false
+        }
+    }
+
+    tests! {
+        trailing_forbidden:
+
+        testcase! {
+            single,
+            recognise(&FORBIDDEN, "a"),
+            true
+        }
+
+        testcase! {
+            several,
+            recognise(&FORBIDDEN, "a,a,a"),
+            true
+        }
+
+        testcase! {
+            trailing,
+            recognise(&FORBIDDEN, "a,a,a,"),
+            false
+        }
+
+        testcase! {
+            leading,
+            recognise(&FORBIDDEN, ",a"),
+            false
+        }
+    }
+}
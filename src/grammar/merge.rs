@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use super::{Grammar, Rule, Symbol};
+
+impl Grammar {
+    /// Combine alternatives of the same rule which each match a single
+    /// character class, e.g. `A -> ["abc"]` and `A -> ["def"]` become
+    /// `A -> ["abcdef"]`. The language is unchanged but fewer rules are
+    /// predicted when the rule is. The merged rule takes the place of the
+    /// first alternative it replaces, other rules keep their order.
+    #[must_use]
+    pub fn merge_char_alternatives(mut self) -> Grammar {
+        let mut merged: HashMap<String, usize> = HashMap::new();
+        let mut rules: Vec<Rule> = Vec::new();
+        for rule in self.rules.drain(..) {
+            if let [Symbol::OneOf(chars)] = rule.body() {
+                if let Some(&index) = merged.get(rule.name()) {
+                    if let [Symbol::OneOf(existing)] = rules[index].body() {
+                        let union = Symbol::OneOf(existing.union(chars));
+                        rules[index] = Rule::new(String::from(rule.name()), vec![union])
+                            .with_precedence(rules[index].precedence());
+                    }
+                    continue;
+                }
+                let _ = merged.insert(String::from(rule.name()), rules.len());
+            }
+            rules.push(rule);
+        }
+
+        let mut grammar = Grammar::new(rules);
+        grammar.predicates = std::mem::take(&mut self.predicates);
+        grammar.start = std::mem::take(&mut self.start);
+        grammar
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::recognise;
+
+    testdata! {
+        LETTERS: Grammar = grammar! {
+            Word -> Letter Word;
+            Word -> Letter;
+            Letter -> ["abc"];
+            Letter -> "x";
+            Letter -> ["def"];
+            Letter -> ["cg"];
+        };
+    }
+
+    testcase! {
+        merged,
+        LETTERS.clone().merge_char_alternatives(),
+        grammar! {
+            Word -> Letter Word;
+            Word -> Letter;
+            Letter -> ["abcdefg"];
+            Letter -> "x";
+        }
+    }
+
+    testcase! {
+        language_unchanged,
+        ["abc", "xdg", "", "h", "aha", "gfedcbax"]
+            .iter()
+            .filter(|input| {
+                recognise(&LETTERS, input)
+                    != recognise(&LETTERS.clone().merge_char_alternatives(), input)
+            })
+            .collect::<Vec<_>>(),
+        Vec::<&&str>::new()
+    }
+
+    testcase! {
+        other_rules_untouched,
+        grammar! {
+            A -> ["ab"] B;
+            A -> ["cd"] B;
+            B -> ["ab"];
+        }
+        .merge_char_alternatives(),
+        grammar! {
+            A -> ["ab"] B;
+            A -> ["cd"] B;
+            B -> ["ab"];
+        }
+    }
+
+    testcase! {
+        different_rules_not_merged,
+        grammar! {
+            A -> B;
+            A -> C;
+            B -> ["ab"];
+            C -> ["cd"];
+        }
+        .merge_char_alternatives(),
+        grammar! {
+            A -> B;
+            A -> C;
+            B -> ["ab"];
+            C -> ["cd"];
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use super::{Grammar, Rule, Symbol};
+
+/// The alternatives of a rule with every rule they refer to replaced by its
+/// class, as `(precedence, body)`
+type Signature = Vec<(i32, Vec<Symbol>)>;
+
+impl Grammar {
+    /// Merge rules which are the same up to renaming, i.e. their alternatives
+    /// are identical once the rules they refer to are merged. Like minimizing
+    /// a DFA the rules start out in one class which is split until every rule
+    /// in a class has the same alternatives, then each class is replaced by
+    /// one of its rules (the start symbol if it is in the class, otherwise the
+    /// first declared). The language is unchanged but machine generated
+    /// grammars can shrink a lot. Alternatives which become identical are only
+    /// kept once, so ambiguity between the merged rules goes away. Rules with
+    /// a predicate (see [`Grammar::with_predicate`]) are never merged.
+    /// ```
+    /// # use parsey::grammar;
+    /// let grammar = grammar! {
+    ///     Pair -> Left "," Right;
+    ///     Left -> ["0123456789"] Left;
+    ///     Left -> ["0123456789"];
+    ///     Right -> ["0123456789"] Right;
+    ///     Right -> ["0123456789"];
+    /// };
+    /// assert_eq!(
+    ///     grammar.minimize(),
+    ///     grammar! {
+    ///         Pair -> Left "," Left;
+    ///         Left -> ["0123456789"] Left;
+    ///         Left -> ["0123456789"];
+    ///     }
+    /// );
+    /// ```
+    #[must_use]
+    pub fn minimize(mut self) -> Grammar {
+        let mut names: Vec<&str> = Vec::new();
+        for rule in &self.rules {
+            if !names.contains(&rule.name()) {
+                names.push(rule.name());
+            }
+        }
+
+        // Rules with a predicate start out in classes of their own, the rest
+        // together
+        let mut classes = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| {
+                let class = if self.predicates.contains_key(name) {
+                    names.len() + i
+                } else {
+                    0
+                };
+                (name, class)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut count = 0;
+        loop {
+            let signatures = names
+                .iter()
+                .map(|&name| self.signature(name, &classes))
+                .collect::<Vec<_>>();
+
+            // Split each class by signature, rules stay together only if they
+            // were already together and have the same alternatives
+            let mut groups: Vec<(usize, &Signature)> = Vec::new();
+            let mut split = HashMap::new();
+            for (&name, signature) in names.iter().zip(&signatures) {
+                let class = classes[name];
+                let group = groups
+                    .iter()
+                    .position(|&(other, alternatives)| {
+                        other == class && same_alternatives(alternatives, signature)
+                    })
+                    .unwrap_or_else(|| {
+                        groups.push((class, signature));
+                        groups.len() - 1
+                    });
+                let _ = split.insert(name, group);
+            }
+
+            // Classes are only ever split, once that stops they are final
+            let done = groups.len() == count;
+            count = groups.len();
+            classes = split;
+            if done {
+                break;
+            }
+        }
+
+        // Each class is replaced by its representative
+        let mut representatives: HashMap<usize, &str> = HashMap::new();
+        let _ = representatives.insert(classes[self.start_symbol()], self.start_symbol());
+        for &name in &names {
+            let _ = representatives.entry(classes[name]).or_insert(name);
+        }
+        let rename = |name: &str| {
+            classes
+                .get(name)
+                .map_or(name, |class| representatives[class])
+                .to_string()
+        };
+
+        let mut rules: Vec<Rule> = Vec::new();
+        for rule in &self.rules {
+            if representatives[&classes[rule.name()]] != rule.name() {
+                continue;
+            }
+            let rule = Rule::new(
+                String::from(rule.name()),
+                rule.body()
+                    .iter()
+                    .map(|symbol| symbol.map_rule_names(&rename))
+                    .collect(),
+            )
+            .with_precedence(rule.precedence());
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+
+        let mut grammar = Grammar::new(rules);
+        grammar.predicates = std::mem::take(&mut self.predicates);
+        grammar.start = std::mem::take(&mut self.start);
+        grammar
+    }
+
+    /// The alternatives of the rule `name` with the rules they refer to
+    /// replaced by their class in `classes`. Rules that don't exist keep
+    /// their names, which can't clash with the reserved names used for
+    /// classes.
+    fn signature(&self, name: &str, classes: &HashMap<&str, usize>) -> Signature {
+        let class = |name: &str| {
+            classes
+                .get(name)
+                .map_or_else(|| String::from(name), |class| format!("@{}", class))
+        };
+        self.get_rules_by_name(name)
+            .map(|rule| {
+                (
+                    rule.precedence(),
+                    rule.body()
+                        .iter()
+                        .map(|symbol| symbol.map_rule_names(&class))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// True if every alternative in `left` is also in `right` and vice versa,
+/// regardless of order or repetition
+fn same_alternatives(left: &Signature, right: &Signature) -> bool {
+    left.iter().all(|alternative| right.contains(alternative))
+        && right.iter().all(|alternative| left.contains(alternative))
+}
+
+syntax_abuse::tests! {
+    use crate::recognise;
+
+    testdata! {
+        DUPLICATED: Grammar = grammar! {
+            Sum -> Sum "+" Product;
+            Sum -> Product;
+            Product -> Product "*" Number;
+            Product -> Number;
+            Number -> Digit Number;
+            Number -> Digit;
+            Digit -> ["0123456789"];
+            Numeral -> Numeral Figure;
+            Numeral -> Figure;
+            Figure -> ["0123456789"];
+        };
+        TWINS: Grammar = grammar! {
+            Pair -> Even Odd;
+            Even -> "a" Odd;
+            Even -> ;
+            Odd -> "a" Even;
+            Other -> "a" Another;
+            Other -> ;
+            Another -> "a" Other;
+            Start -> Other Another;
+        };
+    }
+
+    /// Inputs on which `grammar` and its minimized version disagree
+    fn disagreements(grammar: &Grammar, corpus: &[&str]) -> Vec<String> {
+        let minimized = grammar.clone().minimize();
+        corpus
+            .iter()
+            .filter(|input| recognise(grammar, input) != recognise(&minimized, input))
+            .map(|input| String::from(*input))
+            .collect()
+    }
+
+    testcase! {
+        identical_rules,
+        grammar! {
+            S -> A "+" B;
+            A -> ["ab"];
+            A -> "c";
+            B -> "c";
+            B -> ["ab"];
+        }
+        .minimize(),
+        grammar! {
+            S -> A "+" A;
+            A -> ["ab"];
+            A -> "c";
+        }
+    }
+
+    testcase! {
+        mutually_recursive,
+        TWINS.clone().minimize(),
+        grammar! {
+            Pair -> Even Odd;
+            Even -> "a" Odd;
+            Even -> ;
+            Odd -> "a" Even;
+        }
+    }
+
+    testcase! {
+        different_precedence,
+        grammar! {
+            S -> A B;
+            [1] A -> "a";
+            B -> "a";
+        }
+        .minimize(),
+        grammar! {
+            S -> A B;
+            [1] A -> "a";
+            B -> "a";
+        }
+    }
+
+    testcase! {
+        only_identical_rules_merged,
+        DUPLICATED.clone().minimize(),
+        grammar! {
+            Sum -> Sum "+" Product;
+            Sum -> Product;
+            Product -> Product "*" Number;
+            Product -> Number;
+            Number -> Digit Number;
+            Number -> Digit;
+            Digit -> ["0123456789"];
+            Numeral -> Numeral Digit;
+            Numeral -> Digit;
+        }
+    }
+
+    testcase! {
+        start_symbol_kept,
+        grammar! {
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+            Start -> ["0123456789"] Start;
+            Start -> ["0123456789"];
+        }
+        .with_start_symbol("Start")
+        .minimize(),
+        grammar! {
+            start: Start;
+            Start -> ["0123456789"] Start;
+            Start -> ["0123456789"];
+        }
+    }
+
+    testcase! {
+        duplicate_alternatives_dropped,
+        grammar! {
+            S -> A;
+            S -> B;
+            A -> "x";
+            B -> "x";
+        }
+        .minimize(),
+        grammar! {
+            S -> A;
+            A -> "x";
+        }
+    }
+
+    testcase! {
+        undefined_rules_kept_apart,
+        grammar! {
+            S -> A B;
+            A -> Missing;
+            B -> Absent;
+        }
+        .minimize(),
+        grammar! {
+            S -> A B;
+            A -> Missing;
+            B -> Absent;
+        }
+    }
+
+    testcase! {
+        predicates_not_merged,
+        grammar! {
+            S -> A B;
+            A -> ["ab"];
+            B -> ["ab"];
+        }
+        .with_predicate("A", |matched| matched == ['a'])
+        .minimize()
+        .to_string(),
+        grammar! {
+            S -> A B;
+            A -> ["ab"];
+            B -> ["ab"];
+        }
+        .to_string()
+    }
+
+    testcase! {
+        same_language,
+        [&*TWINS, &*DUPLICATED]
+            .iter()
+            .flat_map(|grammar| {
+                disagreements(grammar, &["", "a", "aa", "aaa", "1", "1+2", "12*3+4", "1+", "+"])
+            })
+            .collect::<Vec<_>>(),
+        Vec::<String>::new()
+    }
+}
@@ -0,0 +1,180 @@
+use super::{Grammar, Rule, Symbol};
+
+/// Associativity of the operators in a [`Precedence`] level
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Associativity {
+    /// `a - b - c` is parsed as `(a - b) - c`
+    Left,
+    /// `a ^ b ^ c` is parsed as `a ^ (b ^ (c))`
+    Right,
+}
+
+/// One level of an operator precedence table, see
+/// [`Grammar::from_precedence`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Precedence<'a> {
+    /// The name of the rule generated for this level
+    pub name: &'a str,
+    /// How operators on this level group
+    pub associativity: Associativity,
+    /// The binary operators on this level
+    pub operators: &'a [&'a str],
+}
+
+impl Grammar {
+    /// Construct a grammar for binary operator expressions from a precedence
+    /// table, generating the layered rules that would otherwise be written by
+    /// hand. `levels` are ordered from lowest to highest precedence and the
+    /// first level is the root rule. The operands of the highest precedence
+    /// level are produced by the rule named `atom`, which along with any other
+    /// supporting rules should be defined in `rules`.
+    /// ```
+    /// # use parsey::grammar::{Associativity, Grammar, Precedence};
+    /// # use parsey::{recognise, rule};
+    /// // Equivalent to
+    /// // Sum -> Sum "+" Product;
+    /// // Sum -> Product;
+    /// // Product -> Product "*" Number;
+    /// // Product -> Number;
+    /// // Number -> ["0123456789"];
+    /// let grammar = Grammar::from_precedence(
+    ///     &[
+    ///         Precedence { name: "Sum", associativity: Associativity::Left, operators: &["+"] },
+    ///         Precedence { name: "Product", associativity: Associativity::Left, operators: &["*"] },
+    ///     ],
+    ///     "Number",
+    ///     vec![rule!(Number -> ["0123456789"])],
+    /// );
+    /// assert!(recognise(&grammar, "1+2*3"));
+    /// ```
+    ///
+    /// # Panics
+    /// If both `levels` and `rules` are empty, see also [`Rule::new`]
+    #[must_use]
+    pub fn from_precedence(levels: &[Precedence<'_>], atom: &str, rules: Vec<Rule>) -> Self {
+        let mut generated = Vec::new();
+
+        for (i, level) in levels.iter().enumerate() {
+            let this = Symbol::Rule(String::from(level.name));
+            // Operands are produced by the next level up, or the atom rule for
+            // the highest precedence level
+            let next = Symbol::Rule(String::from(
+                levels.get(i + 1).map_or(atom, |next| next.name),
+            ));
+
+            for operator in level.operators {
+                let (lhs, rhs) = match level.associativity {
+                    Associativity::Left => (this.clone(), next.clone()),
+                    Associativity::Right => (next.clone(), this.clone()),
+                };
+                let mut body = vec![lhs];
+                body.extend(Symbol::literals(operator));
+                body.push(rhs);
+                generated.push(Rule::new(String::from(level.name), body));
+            }
+            generated.push(Rule::new(String::from(level.name), vec![next]));
+        }
+
+        generated.extend(rules);
+        Grammar::new(generated)
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::{parse, ast::Node};
+
+    const LEFT: Associativity = Associativity::Left;
+    const RIGHT: Associativity = Associativity::Right;
+
+    testdata! {
+        ARITH: Grammar = Grammar::from_precedence(
+            &[
+                Precedence { name: "Sum", associativity: LEFT, operators: &["+", "-"] },
+                Precedence { name: "Product", associativity: LEFT, operators: &["*", "/"] },
+                Precedence { name: "Power", associativity: RIGHT, operators: &["**"] }
+            ],
+            "Number",
+            vec![rule!(Number -> ["0123456789"])]
+        );
+    }
+
+    fn node(name: &str, children: Vec<Node>) -> Node {
+        Node::Internal { name: String::from(name), children }
+    }
+
+    fn number(c: char) -> Node {
+        node("Number", vec![Node::Leaf(c)])
+    }
+
+    fn first_tree(input: &str) -> Option<Node> {
+        parse(&ARITH, input).ok()?.next()
+    }
+
+    testcase! {
+        generated_rules,
+        *ARITH,
+        grammar! {
+            Sum -> Sum "+" Product;
+            Sum -> Sum "-" Product;
+            Sum -> Product;
+            Product -> Product "*" Power;
+            Product -> Product "/" Power;
+            Product -> Power;
+            Power -> Number "**" Power;
+            Power -> Number;
+            Number -> ["0123456789"];
+        }
+    }
+
+    testcase! {
+        precedence,
+        first_tree("1+2*3"),
+        Some(node("Sum", vec![
+            node("Sum", vec![node("Product", vec![node("Power", vec![number('1')])])]),
+            Node::Leaf('+'),
+            node("Product", vec![
+                node("Product", vec![node("Power", vec![number('2')])]),
+                Node::Leaf('*'),
+                node("Power", vec![number('3')])
+            ])
+        ]))
+    }
+
+    testcase! {
+        left_associative,
+        first_tree("1-2-3"),
+        Some(node("Sum", vec![
+            node("Sum", vec![
+                node("Sum", vec![node("Product", vec![node("Power", vec![number('1')])])]),
+                Node::Leaf('-'),
+                node("Product", vec![node("Power", vec![number('2')])])
+            ]),
+            Node::Leaf('-'),
+            node("Product", vec![node("Power", vec![number('3')])])
+        ]))
+    }
+
+    testcase! {
+        right_associative,
+        first_tree("1**2**3"),
+        Some(node("Sum", vec![node("Product", vec![
+            node("Power", vec![
+                number('1'),
+                Node::Leaf('*'),
+                Node::Leaf('*'),
+                node("Power", vec![
+                    number('2'),
+                    Node::Leaf('*'),
+                    Node::Leaf('*'),
+                    node("Power", vec![number('3')])
+                ])
+            ])
+        ])]))
+    }
+
+    testcase! {
+        unambiguous,
+        parse(&ARITH, "1+2*3-4/5**6").map(Iterator::count),
+        Ok(1)
+    }
+}
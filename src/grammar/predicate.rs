@@ -0,0 +1,168 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::Grammar;
+
+type PredicateFn = dyn Fn(&[char]) -> bool + Send + Sync;
+
+/// A semantic predicate guarding a rule, see [`Grammar::with_predicate`]
+#[derive(Clone)]
+pub(crate) struct Predicate(Arc<PredicateFn>);
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Predicate")
+    }
+}
+
+/// Closures can't be compared, predicates are only equal if they are the same
+/// closure
+impl PartialEq for Predicate {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Grammar {
+    /// Guard the rule `name` with a semantic predicate. Whenever the rule
+    /// completes the predicate is given the characters it matched and the
+    /// completion only counts if it returns true, e.g. rejecting reserved
+    /// words from an identifier rule. This makes it possible to express
+    /// context sensitive constraints the rules alone can't. The predicate
+    /// applies to every alternative of the rule and replaces any predicate
+    /// the rule already had.
+    #[must_use]
+    pub fn with_predicate<F>(mut self, name: &str, predicate: F) -> Grammar
+    where
+        F: Fn(&[char]) -> bool + Send + Sync + 'static,
+    {
+        let _ = self
+            .predicates
+            .insert(String::from(name), Predicate(Arc::new(predicate)));
+        self
+    }
+
+    /// True if the rule `name` is allowed to complete having matched `matched`
+    pub(crate) fn allows_completion(&self, name: &str, matched: &[char]) -> bool {
+        // Most grammars have no predicates, skip hashing the name in that case
+        self.predicates.is_empty()
+            || self
+                .predicates
+                .get(name)
+                .is_none_or(|predicate| (predicate.0)(matched))
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::{derivation_counts, parse, recognise};
+
+    testdata! {
+        LET: Grammar = grammar! {
+            Statement -> Keyword " " Name;
+            Keyword -> "let";
+            Name -> Letter Name;
+            Name -> Letter;
+            Letter -> ["abcdefghijklmnopqrstuvwxyz"];
+        }
+        .with_predicate("Name", |matched| !is_keyword(matched));
+        WORD: Grammar = grammar! {
+            Word -> Keyword;
+            Word -> Name;
+            Keyword -> "if";
+            Name -> Letter Name;
+            Name -> Letter;
+            Letter -> ["abcdefghijklmnopqrstuvwxyz"];
+        }
+        .with_predicate("Name", |matched| !is_keyword(matched));
+    }
+
+    fn is_keyword(matched: &[char]) -> bool {
+        ["let", "if"].contains(&matched.iter().collect::<String>().as_str())
+    }
+
+    testcase! {
+        rejects_keyword,
+        ["let x", "let let", "let lets", "let le"]
+            .iter()
+            .map(|input| recognise(&LET, input))
+            .collect::<Vec<_>>(),
+        vec![true, false, true, true]
+    }
+
+    testcase! {
+        without_predicate,
+        recognise(
+            &grammar! {
+                Statement -> Keyword " " Name;
+                Keyword -> "let";
+                Name -> Letter Name;
+                Name -> Letter;
+                Letter -> ["abcdefghijklmnopqrstuvwxyz"];
+            },
+            "let let"
+        ),
+        true
+    }
+
+    testcase! {
+        rejected_completion_not_in_trees,
+        parse(&WORD, "if")
+            .unwrap()
+            .map(|tree| format!("{:?}", tree))
+            .collect::<Vec<_>>(),
+        vec![String::from(" Word { Keyword { i f } }")]
+    }
+
+    testcase! {
+        rejected_completion_not_counted,
+        derivation_counts(&WORD, "if")
+            .unwrap()
+            .iter()
+            .any(|count| count.name == "Name" && count.start == 0 && count.end == 2),
+        false
+    }
+
+    testcase! {
+        identifier_still_allowed,
+        parse(&WORD, "it").map(Iterator::count),
+        Ok(1)
+    }
+
+    testcase! {
+        nullable,
+        ["", "a"]
+            .iter()
+            .map(|input| {
+                recognise(
+                    &grammar! {
+                        S -> Optional "a";
+                        S -> "a";
+                        Optional -> ;
+                    }
+                    .with_predicate("Optional", |_| false),
+                    input
+                )
+            })
+            .collect::<Vec<_>>(),
+        vec![false, true]
+    }
+
+    testcase! {
+        follows_rename,
+        {
+            let mut grammar = LET.clone().with_prefix("let_");
+            grammar.rename_rule("let_Name", "Ident");
+            (recognise(&grammar, "let let"), recognise(&grammar, "let x"))
+        },
+        (false, true)
+    }
+
+    testcase! {
+        replaces_existing,
+        recognise(
+            &LET.clone().with_predicate("Name", |_| true),
+            "let let"
+        ),
+        true
+    }
+}
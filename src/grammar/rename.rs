@@ -0,0 +1,247 @@
+use super::{Grammar, Rule};
+
+impl Grammar {
+    /// Rename the rule `from` to `to` everywhere it appears, both the rules
+    /// producing it and every reference to it in a rule body. Rule order is
+    /// unchanged and renaming the start symbol changes the start symbol. If a
+    /// rule named `to` already exists the two sets of rules become alternatives
+    /// of the same rule. Renaming a rule that doesn't exist does nothing.
+    ///
+    /// # Panics
+    /// If `to` begins with `@`, see [`Rule::new`]
+    pub fn rename_rule(&mut self, from: &str, to: &str) {
+        assert!(
+            !to.starts_with('@'),
+            "Rule names beginning with @ are reserved"
+        );
+        self.rename_rules(|name| (name == from).then(|| String::from(to)));
+    }
+
+    /// Prefix the name of every rule and every reference to a rule with
+    /// `prefix`, e.g. `Expr` becomes `json_Expr`. Prefixing two grammars
+    /// differently means their rules can be combined into one grammar without
+    /// rules that happened to share a name becoming alternatives of each
+    /// other. Terminals are unchanged.
+    ///
+    /// # Panics
+    /// If `prefix` begins with `@`, see [`Rule::new`]
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: &str) -> Grammar {
+        assert!(
+            !prefix.starts_with('@'),
+            "Rule names beginning with @ are reserved"
+        );
+        // All of the names are renamed at once, renaming one at a time would
+        // merge `A` into an existing `{prefix}A` before that was renamed
+        self.rename_rules(|name| Some(format!("{}{}", prefix, name)));
+        self
+    }
+
+    /// Rename every rule and reference to a rule for which `rename` returns a
+    /// new name
+    fn rename_rules<F>(&mut self, rename: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let rename = |name: &str| rename(name).unwrap_or_else(|| String::from(name));
+
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                Rule::new(
+                    rename(rule.name()),
+                    rule.body()
+                        .iter()
+                        .map(|symbol| symbol.map_rule_names(&rename))
+                        .collect(),
+                )
+                .with_precedence(rule.precedence())
+            })
+            .collect();
+
+        let predicates = self
+            .predicates
+            .drain()
+            .map(|(name, predicate)| (rename(&name), predicate))
+            .collect();
+
+        let start = rename(&self.start);
+
+        // Rebuild so the nullable analysis uses the new names
+        *self = Grammar::new(rules);
+        self.predicates = predicates;
+        self.start = start;
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::grammar::Symbol;
+    use crate::recognise;
+
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Number;
+            Sum -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+        OPTIONAL: Grammar = grammar! {
+            List -> Item Rest;
+            Rest -> ;
+            Rest -> "," Item Rest;
+            Item -> "a";
+        };
+    }
+
+    fn renamed(grammar: &Grammar, from: &str, to: &str) -> Grammar {
+        let mut grammar = grammar.clone();
+        grammar.rename_rule(from, to);
+        grammar
+    }
+
+    testcase! {
+        references_updated,
+        renamed(&ARITH, "Number", "Digits"),
+        grammar! {
+            Sum -> Sum ["+-"] Digits;
+            Sum -> Digits;
+            Digits -> ["0123456789"] Digits;
+            Digits -> ["0123456789"];
+        }
+    }
+
+    testcase! {
+        start_symbol,
+        renamed(&ARITH, "Sum", "Expression").start_symbol(),
+        "Expression"
+    }
+
+    testcase! {
+        recognition_unchanged,
+        ["1+23", "1+", "", "12-3+4"]
+            .iter()
+            .map(|input| recognise(&renamed(&ARITH, "Number", "Digits"), input))
+            .collect::<Vec<_>>(),
+        vec![true, false, false, true]
+    }
+
+    testcase! {
+        nullables_recomputed,
+        {
+            let grammar = renamed(&OPTIONAL, "Rest", "Tail");
+            (grammar.rule_is_nullable("Tail"), grammar.rule_is_nullable("Rest"))
+        },
+        (true, false)
+    }
+
+    testcase! {
+        missing_rule,
+        renamed(&ARITH, "Product", "Term"),
+        ARITH.clone()
+    }
+
+    testcase! {
+        merge_with_existing,
+        recognise(&renamed(&OPTIONAL, "Item", "List"), "a,a,a"),
+        true
+    }
+
+    testcase! {
+        inside_repeat,
+        {
+            let item = Symbol::Rule(String::from("Item"));
+            let sep = Symbol::Rule(String::from("Comma"));
+            let grammar = Grammar::new(vec![
+                Rule::new(String::from("List"), vec![Symbol::repeat(item, Some(sep), 1, None)]),
+                Rule::new(String::from("Item"), vec![Symbol::Literal('a')]),
+                Rule::new(String::from("Comma"), vec![Symbol::Literal(',')]),
+            ]);
+            let grammar = renamed(&renamed(&grammar, "Item", "Element"), "Comma", "Sep");
+            (grammar.rules[0].to_string(), recognise(&grammar, "a,a,a"))
+        },
+        (String::from("List -> repeat(Element, Sep, 1..)"), true)
+    }
+
+    #[test]
+    #[should_panic(expected = "Rule names beginning with @ are reserved")]
+    fn reserved_name() {
+        drop(renamed(&ARITH, "Number", "@Number"));
+    }
+
+    tests! {
+        with_prefix:
+
+        testdata! {
+            WORDS: Grammar = grammar! {
+                Words -> Item " " Words;
+                Words -> Item;
+                Item -> ["abc"];
+            };
+            NUMBERS: Grammar = grammar! {
+                Numbers -> Item "," Numbers;
+                Numbers -> Item;
+                Item -> ["123"];
+            };
+        }
+
+        /// Both grammars use `Item` for different things, combine them as
+        /// alternatives under a new root rule
+        fn combined(words: Grammar, numbers: Grammar) -> Grammar {
+            let mut rules = vec![
+                Rule::new(String::from("Root"), vec![Symbol::Rule(String::from(words.start_symbol()))]),
+                Rule::new(String::from("Root"), vec![Symbol::Rule(String::from(numbers.start_symbol()))]),
+            ];
+            rules.extend(words.rules);
+            rules.extend(numbers.rules);
+            Grammar::new(rules)
+        }
+
+        testcase! {
+            prefixed,
+            WORDS.clone().with_prefix("words_"),
+            grammar! {
+                words_Words -> words_Item " " words_Words;
+                words_Words -> words_Item;
+                words_Item -> ["abc"];
+            }
+        }
+
+        testcase! {
+            merged,
+            ["a b c", "1,2,3", "a,b", "1 2", "a 2"]
+                .iter()
+                .map(|input| recognise(
+                    &combined(WORDS.clone().with_prefix("words_"), NUMBERS.clone().with_prefix("numbers_")),
+                    input
+                ))
+                .collect::<Vec<_>>(),
+            vec![true, true, false, false, false]
+        }
+
+        testcase! {
+            merged_without_prefix,
+            recognise(&combined(WORDS.clone(), NUMBERS.clone()), "a 2"),
+            true
+        }
+
+        testcase! {
+            prefix_of_existing_name,
+            grammar! {
+                A -> xA "a";
+                xA -> "b";
+            }
+            .with_prefix("x"),
+            grammar! {
+                xA -> xxA "a";
+                xxA -> "b";
+            }
+        }
+
+        #[test]
+        #[should_panic(expected = "Rule names beginning with @ are reserved")]
+        fn reserved_prefix() {
+            drop(WORDS.clone().with_prefix("@"));
+        }
+    }
+}
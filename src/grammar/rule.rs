@@ -10,6 +10,7 @@ use syntax_abuse as syntax;
 pub struct Rule {
     name: String,
     body: Vec<Symbol>,
+    precedence: i32,
 }
 
 impl Rule {
@@ -24,7 +25,40 @@ impl Rule {
             !name.starts_with('@'),
             "Rule names beginning with @ are reserved"
         );
-        Rule { name, body }
+        Rule {
+            name,
+            body,
+            precedence: 0,
+        }
+    }
+
+    /// As [`Rule::new`] but allowing reserved names, for rules generated
+    /// internally which mustn't clash with the user's
+    pub(crate) fn reserved(name: String, body: Vec<Symbol>) -> Self {
+        Rule {
+            name,
+            body,
+            precedence: 0,
+        }
+    }
+
+    /// Set the precedence of the rule, 0 unless set. When the input can be
+    /// parsed several ways the alternatives of a rule with higher precedence
+    /// are tried first, so trees using them come out of
+    /// [`parse`](crate::parse) first. Alternatives with equal precedence are
+    /// tried in the order they were declared. The
+    /// [`grammar!`](crate::grammar!) macro sets it with `[<Precedence>]` before
+    /// the rule name.
+    #[must_use]
+    pub fn with_precedence(mut self, precedence: i32) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// The precedence of the rule, see [`Rule::with_precedence`]
+    #[must_use]
+    pub fn precedence(&self) -> i32 {
+        self.precedence
     }
 
     syntax::get! { pub(crate) name : str }
@@ -35,20 +69,12 @@ impl Rule {
     }
 
     pub(crate) fn is_nullable(&self, nullable_symbols: &HashSet<String>) -> bool {
-        if self.body.is_empty() {
-            return true;
-        }
-
-        if self.body.iter().any(Symbol::is_terminal) {
-            return false;
-        }
-
-        self.body.iter().all(|s| {
-            let name = s.rule_name().unwrap();
-            // If the rule would otherwise be nullable, recursively calling
-            // itself shouldn't prevent it from being marked
-            name == self.name || nullable_symbols.contains(name)
-        })
+        // If the rule would otherwise be nullable, recursively calling itself
+        // shouldn't prevent it from being marked
+        let nullable = |name: &str| name == self.name || nullable_symbols.contains(name);
+        self.body
+            .iter()
+            .all(|symbol| symbol.can_match(&nullable, false))
     }
 }
 
@@ -77,7 +103,7 @@ syntax::tests! {
     testcase! {
         valid_rule,
         Rule::new(String::from("Rule"), vec![]),
-        Rule { name: String::from("Rule"), body: vec![] }
+        Rule { name: String::from("Rule"), body: vec![], precedence: 0 }
     }
 
     testcase! {
@@ -95,14 +121,15 @@ syntax::tests! {
                 Symbol::Literal('l'),
                 Symbol::OneOf(nonempty_hashset!['o', 'n', 'e', 'o', 'f']),
                 Symbol::Rule(String::from("Rule"))
-            ]
+            ],
+            precedence: 0
         }
     }
 
     testcase! {
         empty_literal,
         rule!(Rule -> ""),
-        Rule { name: String::from("Rule"), body: vec![] }
+        Rule { name: String::from("Rule"), body: vec![], precedence: 0 }
     }
 
     testcase! {
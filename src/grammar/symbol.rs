@@ -1,5 +1,7 @@
 use std::fmt;
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::NonEmptyHashSet;
 
 /// Valid symbols for a [Rule](super::Rule) body
@@ -13,17 +15,378 @@ pub enum Symbol {
     /// Succeeds if the next character in the input matches any of the contained
     /// characters
     OneOf(NonEmptyHashSet<char>),
+    /// Succeeds if the next character in the input is between the first and
+    /// second contained characters (inclusive) by code point, e.g. `a` to `z`.
+    /// Matches nothing if the first is after the second
+    Range(char, char),
+    /// Succeeds if there is any character left in the input, consuming one
+    /// character
+    AnyChar,
+    /// Succeeds if the next character in the input doesn't match the contained
+    /// terminal (see [`Symbol::matches`]), consuming one character. Only
+    /// terminals matching a single character can be negated,
+    /// [`Grammar::new`](super::Grammar::new) rejects a negated rule, repeat,
+    /// [`Symbol::Grapheme`] or [`Symbol::Newline`]. Build it with
+    /// [`Symbol::negate`], which cancels out double negation.
+    Not(Box<Symbol>),
+    /// Succeeds if the next characters in the input are the code points of the
+    /// contained grapheme cluster (e.g. a letter followed by a combining
+    /// accent), consuming all of them
+    Grapheme(String),
+    /// Succeeds if the next characters in the input are a line terminator,
+    /// either `\n` or `\r\n`, consuming both characters of `\r\n`
+    Newline,
+    /// Succeeds if `inner` matches between `min` and `max` times in a row (any
+    /// number of times from `min` if `max` is `None`) with `sep` matching
+    /// between each of them. The parser handles this directly instead of
+    /// through helper rules, the matches are children of the node for the
+    /// rule the repeat is in. Build it with [`Symbol::repeat`], which checks
+    /// the bounds.
+    Repeat {
+        /// The symbol to repeat
+        inner: Box<Symbol>,
+        /// The separator between repetitions, if any. There's no separator
+        /// before the first repetition or after the last
+        sep: Option<Box<Symbol>>,
+        /// The fewest repetitions allowed
+        min: usize,
+        /// The most repetitions allowed, `None` for no limit
+        max: Option<usize>,
+    },
+}
+
+/// How far matching has got through a [`Symbol::Repeat`]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Repetition {
+    /// The number of times the repeated symbol has matched. Without an upper
+    /// limit this stops counting once it reaches the lower limit (or 1) as
+    /// every count from there on behaves the same, otherwise every repetition
+    /// would need its own parser items
+    count: usize,
+    /// Set once a separator has matched, the repeated symbol has to come next
+    after_sep: bool,
 }
 
 impl Symbol {
-    pub(crate) fn is_terminal(&self) -> bool {
-        self.rule_name().is_none()
+    /// A [`Symbol::Repeat`] matching `inner` between `min` and `max` times,
+    /// separated by `sep`
+    /// ```
+    /// # use parsey::grammar::{Grammar, Rule, Symbol};
+    /// # use parsey::recognise;
+    /// let list = Symbol::repeat(Symbol::Literal('a'), Some(Symbol::Literal(',')), 1, Some(3));
+    /// let grammar = Grammar::new(vec![Rule::new(String::from("List"), vec![list])]);
+    /// assert!(recognise(&grammar, "a,a,a"));
+    /// assert!(!recognise(&grammar, "a,a,a,a"));
+    /// assert!(!recognise(&grammar, "a,"));
+    /// ```
+    ///
+    /// # Panics
+    /// If `max` is less than `min`, or `inner` or `sep` is itself a
+    /// [`Symbol::Repeat`]
+    #[must_use]
+    pub fn repeat(inner: Symbol, sep: Option<Symbol>, min: usize, max: Option<usize>) -> Symbol {
+        assert!(
+            max.is_none_or(|max| max >= min),
+            "A repeat can't have a maximum ({:?}) below its minimum ({})",
+            max,
+            min
+        );
+        assert!(
+            !matches!(inner, Symbol::Repeat { .. }) && !matches!(sep, Some(Symbol::Repeat { .. })),
+            "A repeat can't directly contain another repeat, use a rule"
+        );
+        Symbol::Repeat {
+            inner: Box::new(inner),
+            sep: sep.map(Box::new),
+            min,
+            max,
+        }
+    }
+
+    /// A [`Symbol::Not`] matching any character `inner` doesn't, or the
+    /// symbol inside `inner` if it is already negated
+    /// ```
+    /// # use parsey::grammar::Symbol;
+    /// let not_quote = Symbol::negate(Symbol::Literal('"'));
+    /// assert!(not_quote.matches('a'));
+    /// assert!(!not_quote.matches('"'));
+    /// assert_eq!(Symbol::negate(not_quote), Symbol::Literal('"'));
+    /// ```
+    #[must_use]
+    pub fn negate(inner: Symbol) -> Symbol {
+        match inner {
+            Symbol::Not(inner) => *inner,
+            inner => Symbol::Not(Box::new(inner)),
+        }
+    }
+
+    /// The symbols matching the string `text`. Each grapheme cluster becomes a
+    /// [`Symbol::Literal`] if it is a single code point, otherwise a
+    /// [`Symbol::Grapheme`]
+    #[must_use]
+    pub fn literals(text: &str) -> Vec<Symbol> {
+        text.graphemes(true)
+            .map(|grapheme| {
+                let mut chars = grapheme.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Symbol::Literal(c),
+                    _ => Symbol::Grapheme(String::from(grapheme)),
+                }
+            })
+            .collect()
+    }
+
+    /// True if `c` satisfies this terminal. Always false for
+    /// [`Symbol::Rule`], and for [`Symbol::Grapheme`] as a grapheme cluster
+    /// spans several characters. [`Symbol::Newline`] matches `\n`, the `\r`
+    /// of `\r\n` doesn't match on its own
+    #[must_use]
+    pub fn matches(&self, c: char) -> bool {
+        match self {
+            Symbol::Literal(expected) => *expected == c,
+            Symbol::OneOf(chars) => chars.contains(&c),
+            Symbol::Range(start, end) => (*start..=*end).contains(&c),
+            Symbol::AnyChar => true,
+            Symbol::Not(inner) => !inner.matches(c),
+            Symbol::Newline => c == '\n',
+            Symbol::Rule(_) | Symbol::Grapheme(_) | Symbol::Repeat { .. } => false,
+        }
+    }
+
+    /// The number of characters at the start of `input` matched by this
+    /// terminal, `None` if they don't match or this is a [`Symbol::Rule`] or
+    /// [`Symbol::Repeat`]
+    pub(crate) fn scan(&self, input: &[char]) -> Option<usize> {
+        match self {
+            Symbol::Rule(_) | Symbol::Repeat { .. } => None,
+            Symbol::Literal(_)
+            | Symbol::OneOf(_)
+            | Symbol::Range(..)
+            | Symbol::AnyChar
+            | Symbol::Not(_) => input.first().filter(|c| self.matches(**c)).map(|_| 1),
+            Symbol::Grapheme(cluster) => {
+                let width = cluster.chars().count();
+                input
+                    .get(..width)
+                    .filter(|next| next.iter().copied().eq(cluster.chars()))
+                    .map(|_| width)
+            }
+            Symbol::Newline => match input {
+                ['\r', '\n', ..] => Some(2),
+                ['\n', ..] => Some(1),
+                _ => None,
+            },
+        }
+    }
+
+    /// True if the number of characters this matches depends on the input,
+    /// e.g. [`Symbol::Newline`] matches one or two. Rules are not included
+    pub(crate) fn has_variable_width(&self) -> bool {
+        match self {
+            Symbol::Newline => true,
+            Symbol::Repeat { inner, sep, .. } => {
+                inner.has_variable_width() || sep.as_deref().is_some_and(Symbol::has_variable_width)
+            }
+            Symbol::Rule(_)
+            | Symbol::Literal(_)
+            | Symbol::OneOf(_)
+            | Symbol::Range(..)
+            | Symbol::AnyChar
+            | Symbol::Not(_)
+            | Symbol::Grapheme(_) => false,
+        }
     }
 
+    /// True if `input` is the start of the characters matched by this
+    /// terminal but too short to match it, e.g. the `\r` of `\r\n`
+    pub(crate) fn is_partial_match(&self, input: &[char]) -> bool {
+        match self {
+            Symbol::Rule(_)
+            | Symbol::Literal(_)
+            | Symbol::OneOf(_)
+            | Symbol::Range(..)
+            | Symbol::AnyChar
+            | Symbol::Not(_)
+            | Symbol::Repeat { .. } => false,
+            Symbol::Grapheme(cluster) => {
+                input.len() < cluster.chars().count()
+                    && cluster
+                        .chars()
+                        .zip(input)
+                        .all(|(expected, c)| expected == *c)
+            }
+            Symbol::Newline => input == ['\r'],
+        }
+    }
+
+    /// The name of the rule if this is a [`Symbol::Rule`], `None` for
+    /// everything else including a [`Symbol::Repeat`] of a rule, see
+    /// [`Symbol::rule_names`]
     pub(crate) fn rule_name(&self) -> Option<&str> {
         match self {
             Symbol::Rule(name) => Some(name),
-            Symbol::Literal(_) | Symbol::OneOf(_) => None,
+            Symbol::Literal(_)
+            | Symbol::OneOf(_)
+            | Symbol::Range(..)
+            | Symbol::AnyChar
+            | Symbol::Not(_)
+            | Symbol::Grapheme(_)
+            | Symbol::Newline
+            | Symbol::Repeat { .. } => None,
+        }
+    }
+
+    /// Every rule this symbol refers to, including those inside a
+    /// [`Symbol::Repeat`]
+    pub(crate) fn rule_names(&self) -> impl Iterator<Item = &str> {
+        let (first, second) = match self {
+            Symbol::Repeat { inner, sep, .. } => (Some(&**inner), sep.as_deref()),
+            symbol => (Some(symbol), None),
+        };
+        first
+            .into_iter()
+            .chain(second)
+            .filter_map(Symbol::rule_name)
+    }
+
+    /// True if this negates something other than a terminal matching a single
+    /// character, directly or inside a [`Symbol::Repeat`]. A negated rule or
+    /// repeat has no meaning, and a negated grapheme cluster or newline would
+    /// match the first character of the text it is meant to exclude (e.g. the
+    /// `\r` of `\r\n`)
+    pub(crate) fn has_invalid_negation(&self) -> bool {
+        match self {
+            Symbol::Not(inner) => match &**inner {
+                Symbol::Rule(_) | Symbol::Repeat { .. } | Symbol::Grapheme(_) | Symbol::Newline => {
+                    true
+                }
+                inner => inner.has_invalid_negation(),
+            },
+            Symbol::Repeat { inner, sep, .. } => {
+                inner.has_invalid_negation()
+                    || sep.as_ref().is_some_and(|sep| sep.has_invalid_negation())
+            }
+            _ => false,
+        }
+    }
+
+    /// A copy of the symbol with every rule it refers to renamed by `rename`
+    pub(crate) fn map_rule_names(&self, rename: &impl Fn(&str) -> String) -> Symbol {
+        match self {
+            Symbol::Rule(name) => Symbol::Rule(rename(name)),
+            Symbol::Repeat {
+                inner,
+                sep,
+                min,
+                max,
+            } => Symbol::Repeat {
+                inner: Box::new(inner.map_rule_names(rename)),
+                sep: sep.as_ref().map(|sep| Box::new(sep.map_rule_names(rename))),
+                min: *min,
+                max: *max,
+            },
+            symbol => symbol.clone(),
+        }
+    }
+
+    /// True if the symbol can match anything at all given the rules for which
+    /// `rule` is true, with `terminal` deciding for terminals. Used both for
+    /// nullability (a terminal never matches the empty string) and
+    /// productivity (a terminal always matches something)
+    pub(crate) fn can_match(&self, rule: &impl Fn(&str) -> bool, terminal: bool) -> bool {
+        match self {
+            Symbol::Rule(name) => rule(name),
+            Symbol::Repeat {
+                inner, sep, min, ..
+            } => {
+                // Only the repetitions up to the minimum have to match
+                *min == 0
+                    || (inner.can_match(rule, terminal)
+                        && (*min == 1
+                            || sep.as_ref().is_none_or(|sep| sep.can_match(rule, terminal))))
+            }
+            Symbol::Literal(_)
+            | Symbol::OneOf(_)
+            | Symbol::Range(..)
+            | Symbol::AnyChar
+            | Symbol::Not(_)
+            | Symbol::Grapheme(_)
+            | Symbol::Newline => terminal,
+        }
+    }
+
+    /// The symbol to match next given how far through it matching has got,
+    /// `None` if a [`Symbol::Repeat`] has reached its maximum. Anything other
+    /// than a repeat is matched as it is
+    pub(crate) fn expected(&self, repetition: Repetition) -> Option<&Symbol> {
+        let Symbol::Repeat {
+            inner, sep, max, ..
+        } = self
+        else {
+            return Some(self);
+        };
+        if repetition.after_sep || repetition.count == 0 {
+            (*max != Some(0)).then_some(&**inner)
+        } else if max.is_some_and(|max| repetition.count >= max) {
+            None
+        } else {
+            Some(sep.as_deref().unwrap_or(inner))
+        }
+    }
+
+    /// How far through this symbol matching has got after the symbol from
+    /// [`Symbol::expected`] matches, `None` once it is finished with. Only a
+    /// [`Symbol::Repeat`] goes on
+    pub(crate) fn matched(&self, repetition: Repetition) -> Option<Repetition> {
+        let Symbol::Repeat { sep, min, max, .. } = self else {
+            return None;
+        };
+        if sep.is_some() && repetition.count > 0 && !repetition.after_sep {
+            return Some(Repetition {
+                count: repetition.count,
+                after_sep: true,
+            });
+        }
+        let limit = max.unwrap_or_else(|| (*min).max(1));
+        Some(Repetition {
+            count: (repetition.count + 1).min(limit),
+            after_sep: false,
+        })
+    }
+
+    /// True if matching can move past this symbol, i.e. it is a
+    /// [`Symbol::Repeat`] which has matched enough times and isn't waiting
+    /// for the symbol after a separator
+    pub(crate) fn can_stop(&self, repetition: Repetition) -> bool {
+        match self {
+            Symbol::Repeat { min, .. } => !repetition.after_sep && repetition.count >= *min,
+            _ => false,
+        }
+    }
+
+    /// True if the next repetition of a [`Symbol::Repeat`] isn't needed to
+    /// reach the minimum, and so has to match some input. Otherwise a
+    /// repeated symbol matching the empty string would give endlessly many
+    /// trees. A separator counts as part of the repetition after it, the
+    /// first repetition of a separated repeat is exempt as the separator
+    /// after it can only be there if it is.
+    pub(crate) fn is_optional_repetition(&self, repetition: Repetition) -> bool {
+        match self {
+            Symbol::Repeat { sep, .. } => {
+                self.can_stop(repetition) && (sep.is_none() || repetition.count > 0)
+            }
+            _ => false,
+        }
+    }
+
+    /// True if the symbol [`Symbol::expected`] gives is the repeated symbol
+    /// of a [`Symbol::Repeat`], rather than its separator
+    pub(crate) fn expects_inner(&self, repetition: Repetition) -> bool {
+        match self {
+            Symbol::Repeat { sep, .. } => {
+                sep.is_none() || repetition.after_sep || repetition.count == 0
+            }
+            _ => false,
         }
     }
 }
@@ -33,17 +396,218 @@ impl fmt::Display for Symbol {
         match self {
             Symbol::Rule(name) => write!(f, "{}", name),
             Symbol::Literal(text) => write!(f, "'{}'", text),
+            Symbol::Grapheme(text) => write!(f, "'{}'", text),
+            Symbol::Newline => write!(f, "$"),
+            Symbol::Repeat {
+                inner,
+                sep,
+                min,
+                max,
+            } => {
+                write!(f, "repeat({}", inner)?;
+                if let Some(sep) = sep {
+                    write!(f, ", {}", sep)?;
+                }
+                match max {
+                    Some(max) => write!(f, ", {}..={})", min, max),
+                    None => write!(f, ", {}..)", min),
+                }
+            }
             Symbol::OneOf(chars) => {
                 let mut chars = chars.iter().collect::<Vec<_>>();
                 chars.sort_unstable();
                 write!(f, "[{}]", chars.into_iter().collect::<String>())
             }
+            Symbol::Range(start, end) => write!(f, "[{}-{}]", start, end),
+            Symbol::AnyChar => write!(f, "."),
+            // Written like a negated character class, [^a-z] rather than [^[a-z]]
+            Symbol::Not(inner) => {
+                let inner = inner.to_string();
+                match inner.strip_prefix('[') {
+                    Some(class) => write!(f, "[^{}", class),
+                    None => write!(f, "[^{}]", inner),
+                }
+            }
         }
     }
 }
 
 syntax_abuse::tests! {
 
+    tests! {
+        matches:
+
+        testcase! {
+            literal,
+            (Symbol::Literal('a').matches('a'), Symbol::Literal('a').matches('b')),
+            (true, false)
+        }
+
+        testcase! {
+            oneof,
+            (
+                symbol!(["abc"])[0].matches('b'),
+                symbol!(["abc"])[0].matches('d')
+            ),
+            (true, false)
+        }
+
+        testcase! {
+            grapheme,
+            (
+                Symbol::Grapheme(String::from("e\u{301}")).matches('e'),
+                Symbol::Grapheme(String::from("e\u{301}")).matches('\u{301}')
+            ),
+            (false, false)
+        }
+
+        testcase! {
+            rule,
+            Symbol::Rule(String::from("a")).matches('a'),
+            false
+        }
+
+        testcase! {
+            newline,
+            (Symbol::Newline.matches('\n'), Symbol::Newline.matches('\r')),
+            (true, false)
+        }
+
+        testcase! {
+            range,
+            ['a', 'm', 'z', '`', '{', 'A']
+                .iter()
+                .map(|c| Symbol::Range('a', 'z').matches(*c))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, false, false, false]
+        }
+
+        testcase! {
+            any_char,
+            ['a', '\n', '\u{10ffff}'].iter().all(|c| Symbol::AnyChar.matches(*c)),
+            true
+        }
+
+        testcase! {
+            not,
+            (
+                symbol!([^ "abc"])[0].matches('d'),
+                symbol!([^ "abc"])[0].matches('a'),
+                symbol!([^ 'a'-'z'])[0].matches('A'),
+                symbol!([^ 'a'-'z'])[0].matches('q')
+            ),
+            (true, false, true, false)
+        }
+
+        testcase! {
+            not_any_char,
+            ['a', '\n', '\u{10ffff}'].iter().any(|c| Symbol::negate(Symbol::AnyChar).matches(*c)),
+            false
+        }
+
+        testcase! {
+            double_not,
+            (
+                Symbol::Not(Box::new(Symbol::negate(Symbol::Literal('a')))).matches('a'),
+                Symbol::Not(Box::new(Symbol::negate(Symbol::Literal('a')))).matches('b')
+            ),
+            (true, false)
+        }
+
+        testcase! {
+            backwards_range,
+            (Symbol::Range('z', 'a').matches('a'), Symbol::Range('z', 'a').matches('m')),
+            (false, false)
+        }
+    }
+
+    tests! {
+        scan:
+
+        testcase! {
+            literal,
+            (Symbol::Literal('a').scan(&['a', 'b']), Symbol::Literal('a').scan(&['b'])),
+            (Some(1), None)
+        }
+
+        testcase! {
+            oneof,
+            (symbol!(["abc"])[0].scan(&['c']), symbol!(["abc"])[0].scan(&['d'])),
+            (Some(1), None)
+        }
+
+        testcase! {
+            grapheme,
+            (
+                Symbol::Grapheme(String::from("e\u{301}")).scan(&['e', '\u{301}', 'x']),
+                Symbol::Grapheme(String::from("e\u{301}")).scan(&['e', 'x'])
+            ),
+            (Some(2), None)
+        }
+
+        testcase! {
+            grapheme_truncated,
+            Symbol::Grapheme(String::from("e\u{301}")).scan(&['e']),
+            None
+        }
+
+        testcase! {
+            end_of_input,
+            Symbol::Literal('a').scan(&[]),
+            None
+        }
+
+        testcase! {
+            rule,
+            Symbol::Rule(String::from("a")).scan(&['a']),
+            None
+        }
+
+        testcase! {
+            newline,
+            (Symbol::Newline.scan(&['\n', 'a']), Symbol::Newline.scan(&['\r', '\n', 'a'])),
+            (Some(1), Some(2))
+        }
+
+        testcase! {
+            range,
+            (Symbol::Range('0', '9').scan(&['5', 'a']), Symbol::Range('0', '9').scan(&['a'])),
+            (Some(1), None)
+        }
+
+        testcase! {
+            any_char,
+            (
+                Symbol::AnyChar.scan(&['x', 'y']),
+                Symbol::AnyChar.scan(&['\r', '\n']),
+                Symbol::AnyChar.scan(&[])
+            ),
+            (Some(1), Some(1), None)
+        }
+
+        testcase! {
+            not,
+            (
+                Symbol::negate(Symbol::Literal('"')).scan(&['a', '"']),
+                Symbol::negate(Symbol::Literal('"')).scan(&['"']),
+                Symbol::negate(Symbol::Literal('"')).scan(&[])
+            ),
+            (Some(1), None, None)
+        }
+
+        testcase! {
+            lone_carriage_return,
+            (Symbol::Newline.scan(&['\r']), Symbol::Newline.scan(&['\r', 'a'])),
+            (None, None)
+        }
+    }
+
+    testcase! {
+        newline,
+        symbol!($),
+        vec![Symbol::Newline]
+    }
+
     testcase! {
         rule,
         &symbol!(Rule)[0],
@@ -56,6 +620,87 @@ syntax_abuse::tests! {
         &Symbol::OneOf(nonempty_hashset!['1', '2', '3', '4', '5'])
     }
 
+    testcase! {
+        range,
+        symbol!(['a'-'z']),
+        vec![Symbol::Range('a', 'z')]
+    }
+
+    testcase! {
+        range_display,
+        symbol!(['0'-'9'])[0].to_string(),
+        "[0-9]"
+    }
+
+    testcase! {
+        any_char,
+        symbol!(.),
+        vec![Symbol::AnyChar]
+    }
+
+    testcase! {
+        any_char_display,
+        Symbol::AnyChar.to_string(),
+        "."
+    }
+
+    tests! {
+        not:
+
+        testcase! {
+            macro_range,
+            symbol!([^ 'a'-'z']),
+            vec![Symbol::Not(Box::new(Symbol::Range('a', 'z')))]
+        }
+
+        testcase! {
+            macro_oneof,
+            symbol!([^ "12"]),
+            vec![Symbol::Not(Box::new(Symbol::OneOf(nonempty_hashset!['1', '2'])))]
+        }
+
+        testcase! {
+            double_negation_cancels,
+            Symbol::negate(Symbol::negate(Symbol::Range('0', '9'))),
+            Symbol::Range('0', '9')
+        }
+
+        testcase! {
+            display,
+            (
+                symbol!([^ 'a'-'z'])[0].to_string(),
+                Symbol::negate(Symbol::Literal('"')).to_string(),
+                Symbol::negate(Symbol::AnyChar).to_string()
+            ),
+            (String::from("[^a-z]"), String::from("[^'\"']"), String::from("[^.]"))
+        }
+
+        testcase! {
+            has_invalid_negation,
+            [
+                Symbol::negate(Symbol::Literal('a')),
+                Symbol::negate(Symbol::Rule(String::from("A"))),
+                Symbol::negate(Symbol::repeat(Symbol::Literal('a'), None, 0, None)),
+                Symbol::Not(Box::new(Symbol::negate(Symbol::Rule(String::from("A"))))),
+                Symbol::repeat(Symbol::negate(Symbol::Rule(String::from("A"))), None, 0, None),
+                Symbol::Rule(String::from("A")),
+                Symbol::negate(Symbol::Newline),
+                Symbol::negate(Symbol::Grapheme(String::from("e\u{301}"))),
+                Symbol::repeat(
+                    Symbol::Literal('a'),
+                    Some(Symbol::negate(Symbol::Newline)),
+                    0,
+                    None
+                ),
+                Symbol::Newline
+            ]
+            .iter()
+            .map(Symbol::has_invalid_negation)
+            .collect::<Vec<_>>(),
+            vec![false, true, true, true, true, false, true, true, true, false]
+        }
+    }
+
     testcase! {
         single_literal,
         symbol!("1"),
@@ -73,4 +718,74 @@ syntax_abuse::tests! {
             Symbol::Literal('5')
         ]
     }
+
+    testcase! {
+        combining_literal,
+        symbol!("e\u{301}"),
+        vec![Symbol::Grapheme(String::from("e\u{301}"))]
+    }
+
+    testcase! {
+        precomposed_literal,
+        symbol!("\u{e9}"),
+        vec![Symbol::Literal('\u{e9}')]
+    }
+
+    testcase! {
+        mixed_literal,
+        symbol!("ce\u{301}"),
+        vec![
+            Symbol::Literal('c'),
+            Symbol::Grapheme(String::from("e\u{301}"))
+        ]
+    }
+
+    tests! {
+        repeat:
+
+        testcase! {
+            display,
+            (
+                Symbol::repeat(Symbol::Rule(String::from("Item")), None, 0, None).to_string(),
+                Symbol::repeat(Symbol::Literal('a'), Some(Symbol::Literal(',')), 1, Some(3))
+                    .to_string()
+            ),
+            (String::from("repeat(Item, 0..)"), String::from("repeat('a', ',', 1..=3)"))
+        }
+
+        testcase! {
+            rule_names,
+            Symbol::repeat(
+                Symbol::Rule(String::from("Item")),
+                Some(Symbol::Rule(String::from("Sep"))),
+                0,
+                None
+            )
+            .rule_names()
+            .collect::<Vec<_>>(),
+            vec!["Item", "Sep"]
+        }
+
+        testcase! {
+            not_terminal,
+            (
+                Symbol::repeat(Symbol::Literal('a'), None, 1, None).matches('a'),
+                Symbol::repeat(Symbol::Literal('a'), None, 1, None).scan(&['a'])
+            ),
+            (false, None)
+        }
+
+        #[test]
+        #[should_panic(expected = "A repeat can't have a maximum (Some(1)) below its minimum (2)")]
+        fn max_below_min() {
+            drop(Symbol::repeat(Symbol::Literal('a'), None, 2, Some(1)));
+        }
+
+        #[test]
+        #[should_panic(expected = "A repeat can't directly contain another repeat, use a rule")]
+        fn nested() {
+            let inner = Symbol::repeat(Symbol::Literal('a'), None, 0, None);
+            drop(Symbol::repeat(inner, None, 0, None));
+        }
+    }
 }
@@ -15,19 +15,31 @@
 //#![deny(dead_code)]
 #![warn(clippy::pedantic)]
 
-pub use utils::NonEmptyHashSet;
+#[cfg(feature = "arena")]
+pub use bumpalo::Bump;
+pub use parse_state::ParseState;
+pub use parser::Parser;
+pub use utils::{NonEmptyHashSet, Uncertain};
 
-use ast::Node;
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+use std::ops::Range;
+
+use ast::{DerivationCount, Forest, Node, SpannedNode};
 use grammar::Grammar;
-use state::{Item, StateSet};
+use state::{Item, Items, StateSet};
+use tokenizer::CharacterPosition;
 
 #[macro_use]
 mod macros;
 
 pub mod ast;
 pub mod grammar;
+pub mod prelude;
 pub mod tokenizer;
 
+mod parse_state;
+mod parser;
 mod state;
 mod utils;
 
@@ -38,31 +50,325 @@ where
     input.as_ref().chars().collect()
 }
 
-fn build_parse_state<'a, 'b>(
+/// Error produced by [`recognise_with_limit`] when a single state set grows
+/// beyond the permitted number of items
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StateSetLimitExceeded {
+    /// The input position of the state set that grew too large
+    pub position: usize,
+    /// The limit that was exceeded
+    pub limit: usize,
+}
+
+impl StateSetLimitExceeded {
+    /// The line and column in `input` of the state set that grew too large
+    #[must_use]
+    pub fn location<S>(&self, input: S) -> CharacterPosition
+    where
+        S: AsRef<str>,
+    {
+        CharacterPosition::from_offset(&expand_input(input), self.position)
+    }
+}
+
+/// Produced by [`parse_with_depth_limit`] after the last tree if any trees
+/// were skipped for being deeper than the limit
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthLimitExceeded {
+    /// The limit that was exceeded
+    pub limit: usize,
+}
+
+/// A step of the Earley algorithm, passed to the callback given to
+/// [`parse_traced`] or [`ParseState::build_traced`]. Each holds the position in
+/// the input (the state set) where the step happened and the item that took
+/// it, displayed as `Rule -> body (start)` with `●` marking how far through the
+/// body the item has got.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// `item` is waiting for `rule`, so the alternatives of `rule` were added
+    /// starting from `position`
+    Predict {
+        /// The position of the state set
+        position: usize,
+        /// The item waiting for `rule`
+        item: String,
+        /// The rule predicted
+        rule: String,
+    },
+    /// The terminal `item` is waiting for matched `width` characters of the
+    /// input from `position`. Terminals which don't match aren't reported
+    Scan {
+        /// The position of the state set
+        position: usize,
+        /// The item waiting for the terminal
+        item: String,
+        /// The number of characters the terminal matched
+        width: usize,
+    },
+    /// `item` matched all of its rule, so the items waiting for the rule in
+    /// the state set where `item` started were moved past it
+    Complete {
+        /// The position of the state set
+        position: usize,
+        /// The completed item
+        item: String,
+    },
+}
+
+/// The trees produced by [`parse_prefix`] along with how much of the input
+/// they cover. Unlike [`parse`] the trees needn't span the whole input, check
+/// [`PrefixParse::is_complete`] before treating them as a parse of all of it.
+#[derive(Debug)]
+pub struct PrefixParse<I> {
+    /// The possible parse trees of `input[0..covered]`
+    pub trees: I,
+    /// The number of characters of the input covered by the trees
+    pub covered: usize,
+    /// The number of characters in the whole input
+    pub input_len: usize,
+}
+
+impl<I> PrefixParse<I> {
+    /// True if the trees cover the whole input, i.e. `[0, input_len)`
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.covered == self.input_len
+    }
+}
+
+/// Error produced when the input isn't in the language described by the
+/// grammar, describing where the parse stopped. If the input ends part way
+/// through a sentence (including an empty input the grammar doesn't accept)
+/// the parse stops at the end of the input with nothing remaining
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The number of characters consumed before the parse stopped
+    pub position: usize,
+    /// The input from `position` onwards
+    pub remaining: String,
+    /// The symbols (rule names and terminals, as displayed by
+    /// [`Symbol`](grammar::Symbol)) the parser was trying to match at
+    /// `position`, in the order the parser tried them
+    pub expected: Vec<String>,
+}
+
+impl ParseError {
+    /// The line and column in `input` where the parse stopped
+    #[must_use]
+    pub fn location<S>(&self, input: S) -> CharacterPosition
+    where
+        S: AsRef<str>,
+    {
+        CharacterPosition::from_offset(&expand_input(input), self.position)
+    }
+}
+
+/// Why [`parse_unambiguous`] didn't produce a tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseOutcome {
+    /// The input isn't in the language
+    NoParse(ParseError),
+    /// The input has more than one parse tree
+    Ambiguous {
+        /// The number of trees, `None` if there are too many to count or some
+        /// derivations are cyclic
+        count: Option<usize>,
+        /// The first tree in the order [`parse`] produces them
+        first: Node,
+        /// The second tree in the order [`parse`] produces them
+        second: Node,
+    },
+}
+
+/// The line and column where parsing stopped given the unparsed input (see
+/// [`ParseError::remaining`]). Line breaks are counted the same way as in token
+/// [`Span`](tokenizer::Span)s (`\r\n` is a single line break)
+#[must_use]
+pub fn error_location<S>(input: S, unparsed: &str) -> CharacterPosition
+where
+    S: AsRef<str>,
+{
+    let input = expand_input(input);
+    let offset = input.len().saturating_sub(unparsed.chars().count());
+    CharacterPosition::from_offset(&input, offset)
+}
+
+/// Reasons `build_parse_state` can fail
+#[derive(Debug, PartialEq)]
+enum Error {
+    /// Ran out of state before running out of input
+    Unparsed(ParseError),
+    /// A state set grew beyond the item limit
+    LimitExceeded(StateSetLimitExceeded),
+}
+
+fn build_parse_state<'a>(
+    start_symbol: &'a str,
+    grammar: &'a Grammar,
+    input: &[char],
+    limit: Option<usize>,
+) -> Result<Vec<StateSet<'a>>, Error> {
+    let parse_state = build_partial_parse_state(start_symbol, grammar, input, limit, None)
+        .map_err(Error::LimitExceeded)?;
+
+    if let Some(error) = parse_error(input, &parse_state) {
+        return Err(Error::Unparsed(error));
+    }
+
+    Ok(parse_state)
+}
+
+/// The input left over if the parse state ran out before the input did.
+///
+/// The last state set is always the furthest position any item reached, so
+/// this is where the most successful alternative failed rather than wherever
+/// some other alternative happened to give up. Every alternative advances in
+/// lockstep and a state set is only created once a scan reaches it. Nullable
+/// rules complete within the state set that predicted them so they never leave
+/// a later position behind, and the empty state sets skipped over by a multi
+/// character terminal are followed by the one its scan landed in.
+fn unparsed<I>(input: &[char], parse_state: &[StateSet<'_, I>]) -> Option<String> {
+    if parse_state.len() <= input.len() {
+        // Ran out of state before running out of input, we didn't manage to
+        // parse the whole string (use parse_state.len() - 1 because the error
+        // actually occurred while processing the last state set, safe because
+        // parse_state.len() is always >= 1)
+        Some(
+            input[parse_state.len() - 1..input.len()]
+                .iter()
+                .copied()
+                .collect::<String>(),
+        )
+    } else {
+        None
+    }
+}
+
+/// Where and why the parse stopped if the parse state ran out before the input
+/// did, see [`unparsed`]
+fn parse_error<'a, I: Items<'a>>(
+    input: &[char],
+    parse_state: &[StateSet<'a, I>],
+) -> Option<ParseError> {
+    let remaining = unparsed(input, parse_state)?;
+    // The items in the last state set were waiting for a symbol that didn't
+    // match the next character
+    let position = parse_state.len() - 1;
+    let mut expected = Vec::new();
+    for symbol in parse_state[position]
+        .items()
+        .iter()
+        .filter_map(Item::next_symbol)
+    {
+        let symbol = symbol.to_string();
+        if !expected.contains(&symbol) {
+            expected.push(symbol);
+        }
+    }
+    Some(ParseError {
+        position,
+        remaining,
+        expected,
+    })
+}
+
+/// Build as much of the parse state as possible. If the parse fails part way
+/// through the input the state sets built so far are returned, in that case
+/// there will be fewer than `input.len() + 1` of them. Each step of the
+/// algorithm is passed to `trace` if there is one.
+fn build_partial_parse_state<'a, 'b>(
     start_symbol: &'a str,
     grammar: &'a Grammar,
     input: &'b [char],
-) -> Result<Vec<StateSet<'a>>, String> {
+    limit: Option<usize>,
+    trace: Option<&mut dyn FnMut(TraceEvent)>,
+) -> Result<Vec<StateSet<'a>>, StateSetLimitExceeded> {
+    build_state_sets(start_symbol, grammar, input, limit, Vec::new, trace)
+}
+
+/// As [`build_partial_parse_state`] but with the items of each state set
+/// stored in an `I` created by `new_items`
+fn build_state_sets<'a, I: Items<'a>>(
+    start_symbol: &'a str,
+    grammar: &'a Grammar,
+    input: &[char],
+    limit: Option<usize>,
+    new_items: impl FnMut() -> I,
+    trace: Option<&mut dyn FnMut(TraceEvent)>,
+) -> Result<Vec<StateSet<'a, I>>, StateSetLimitExceeded> {
+    let mut parse_state = Vec::new();
+    fill_parse_state(
+        start_symbol,
+        grammar,
+        input,
+        limit,
+        &mut parse_state,
+        &mut VecDeque::new(),
+        new_items,
+        trace,
+    )?;
+    Ok(parse_state)
+}
+
+/// Buffers used while building a parse state which can be kept from one parse
+/// to the next, see [`Parser`]
+#[derive(Debug, Default)]
+struct Scratch<'a> {
+    /// Empty item vectors left over from earlier parses
+    spare: Vec<Vec<Item<'a>>>,
+    /// Items produced by scans waiting for the state set they belong to to be
+    /// created, see [`fill_parse_state`]
+    to_add: VecDeque<Vec<Item<'a>>>,
+}
+
+impl<'a> Scratch<'a> {
+    /// Empty `parse_state`, keeping the item vectors of its state sets (and any
+    /// left waiting in `to_add`) for the next parse
+    fn recycle(&mut self, parse_state: &mut Vec<StateSet<'a>>) {
+        let items = parse_state
+            .drain(..)
+            .map(StateSet::into_items)
+            .chain(self.to_add.drain(..));
+        self.spare.extend(items.map(|mut items| {
+            items.clear();
+            items
+        }));
+    }
+}
+
+/// As [`build_state_sets`] but building the state sets in `parse_state`
+/// (which must be empty). `to_add` holds the items produced by scans waiting
+/// for the state set they belong to to be created, the front is always the
+/// next state set, terminals matching several characters produce items
+/// further ahead.
+#[allow(clippy::too_many_arguments)]
+fn fill_parse_state<'a, I: Items<'a>>(
+    start_symbol: &'a str,
+    grammar: &'a Grammar,
+    input: &[char],
+    limit: Option<usize>,
+    parse_state: &mut Vec<StateSet<'a, I>>,
+    to_add: &mut VecDeque<I>,
+    mut new_items: impl FnMut() -> I,
+    mut trace: Option<&mut dyn FnMut(TraceEvent)>,
+) -> Result<(), StateSetLimitExceeded> {
     // Initial state set is seeded with all of the rules that can produce the
     // start symbol
-    let mut parse_state = vec![StateSet::new(Item::from_rules(
-        grammar.get_rules_by_name(start_symbol),
-        0,
-    ))];
+    let mut items = new_items();
+    for item in Item::from_rules(grammar.get_rules_by_name(start_symbol), 0) {
+        items.push(item);
+    }
+    parse_state.push(StateSet::new(items));
 
     // Include input.len() because completions still need to occur after the
     // last character is consumed, predictions can also safely occur and are
     // useless. Any attempt to scan will fail that thread of the parse.
     for current_position in 0..=input.len() {
         if current_position >= parse_state.len() {
-            // Ran out of state before running out of input, we didn't manage to
-            // parse the whole string (use current_position - 1 because the
-            // error actually occurred in the previous iteration of the loop,
-            // safe because parse_state.len() is always >= 1)
-            return Err(input[current_position - 1..input.len()]
-                .iter()
-                .copied()
-                .collect::<String>());
+            // Ran out of state before running out of input, the previous
+            // iteration didn't manage to scan anything
+            break;
         }
 
         // The algorithm requires simultaneous write access to the last state
@@ -70,92 +376,574 @@ fn build_parse_state<'a, 'b>(
         // panic because parse_state has at least one state set by construction.
         let (current_state, prev_state) = parse_state.split_last_mut().unwrap();
 
-        let mut to_add = Vec::new();
-
         while let Some(item) = current_state.next() {
             // Predictions and completions can add new items directly to the
-            // current state set. Scans (if successful) need to add items to the
-            // next state set which doesn't exist yet. We batch those up and
-            // create the next state set after fully processing the current
-            // one. This saves additional complexity to work out whether the new
-            // state set already exists (because of a previous successful
-            // scan). Note: StateSet::new assumes that all of the items in its
-            // to_add set are unique, this holds for items generated from scans
-            // because each (already unique) item in the current state set can
-            // only produce 0 or 1 item in the next, which is itself with the
-            // progress marker incremented by 1 (and the symbol to the left of
-            // the progress marker will always be a terminal). Predictions can
-            // only generate items with progress at 0 and completions generate
-            // items where the symbol to the left of the progress marker is a
-            // non-terminal.
-            if let Some(item) =
-                item.parse(grammar, current_state, prev_state, input, current_position)
-            {
-                to_add.push(item);
+            // current state set. Scans (if successful) need to add items to a
+            // later state set which doesn't exist yet. We batch those up and
+            // create each state set once we reach it. This saves additional
+            // complexity to work out whether the new state set already exists
+            // (because of a previous successful scan). Note: StateSet::new
+            // assumes that all of the items in its to_add set are unique, this
+            // holds for items generated from scans because each (already
+            // unique) item in a state set can only produce 0 or 1 item in a
+            // later one, which is itself with the progress marker incremented
+            // by 1 (and the symbol to the left of the progress marker will
+            // always be a terminal). An item appearing in several state sets
+            // scans the same terminal from each, which lands it in a different
+            // state set each time if the terminal always matches the same
+            // number of characters. Predictions can only generate items
+            // with progress at 0 and completions generate items where the
+            // symbol to the left of the progress marker is a non-terminal.
+            // The exceptions are checked: an item part way through a
+            // Symbol::Repeat, where different numbers of repetitions so far
+            // can lead to the same item, and a terminal matching a variable
+            // number of characters (Symbol::Newline can scan `\r\n` from one
+            // state set and `\n` from the next into the same one).
+            if let Some((width, item)) = item.parse(
+                grammar,
+                current_state,
+                prev_state,
+                input,
+                current_position,
+                // Reborrowed for each item, `as_deref_mut` can't shorten the
+                // lifetime of the trait object
+                trace.as_mut().map(|trace| &mut **trace as _),
+            ) {
+                if to_add.len() < width {
+                    to_add.resize_with(width, &mut new_items);
+                }
+                if !item.may_be_scanned_twice() || !to_add[width - 1].contains(&item) {
+                    to_add[width - 1].push(item);
+                }
             };
+
+            // Bail out if this state set has grown too large, pathological
+            // grammars can otherwise produce a huge number of items at a
+            // single position
+            if let Some(limit) = limit {
+                if current_state.items().len() > limit {
+                    return Err(StateSetLimitExceeded {
+                        position: current_position,
+                        limit,
+                    });
+                }
+            }
         }
 
         // Create the state set for the next iteration. If nothing is available
         // we're either on the last state set (current_position == input.len())
         // and the loop is about to terminate or the parse has failed and we'll
         // land in the if statement at the top of the loop next time around.
-        if !to_add.is_empty() {
-            parse_state.push(StateSet::new(to_add));
+        // The next state set may be empty if a multi-character terminal is
+        // still waiting for its state set further ahead.
+        if let Some(items) = to_add.pop_front() {
+            parse_state.push(StateSet::new(items));
         }
     }
 
-    Ok(parse_state)
+    Ok(())
 }
 
 /// Return `true` if the input string is in the language described by `grammar`,
 /// `false` otherwise.
-#[allow(clippy::missing_panics_doc)]
+///
+/// Like all of the entry points this takes `&Grammar` so a shared grammar
+/// (`Rc<Grammar>`, `Arc<Grammar>`) can be passed directly as `&shared`, deref
+/// coercion takes care of the rest.
 pub fn recognise<S>(grammar: &Grammar, input: S) -> bool
 where
     S: AsRef<str>,
 {
     let input = expand_input(input);
+    // Inputs this short are common when a grammar is used to classify
+    // characters, answer them from the grammar without running the parser
+    if let Some(accepted) = grammar.recognise_short(&input) {
+        return accepted;
+    }
+    ParseState::from_chars(grammar, input).accepts()
+}
+
+/// As [`recognise`] but with the items of the parse allocated in `arena`.
+/// Long inputs build a lot of state sets, each with its own vector of items,
+/// allocating them from an arena is cheaper and keeps them together in memory.
+/// Resetting the arena between calls reuses its memory for the next parse.
+/// Requires the `arena` feature.
+/// ```
+/// # use parsey::{grammar, recognise_in, Bump};
+/// let grammar = grammar! {
+///     Sum -> Sum "+" Number;
+///     Sum -> Number;
+///     Number -> ["0123456789"];
+/// };
+/// let mut arena = Bump::new();
+/// assert!(recognise_in(&arena, &grammar, "1+2+3"));
+/// arena.reset();
+/// assert!(!recognise_in(&arena, &grammar, "1+"));
+/// ```
+#[cfg(feature = "arena")]
+pub fn recognise_in<S>(arena: &Bump, grammar: &Grammar, input: S) -> bool
+where
+    S: AsRef<str>,
+{
+    let input = expand_input(input);
+    if let Some(accepted) = grammar.recognise_short(&input) {
+        return accepted;
+    }
     let start_symbol = grammar.start_symbol();
+    // Can't fail without a limit
+    let parse_state = build_state_sets(
+        start_symbol,
+        grammar,
+        &input,
+        None,
+        || bumpalo::collections::Vec::new_in(arena),
+        None,
+    );
+    parse_state.is_ok_and(|parse_state| {
+        unparsed(&input, &parse_state).is_none() && accepts(start_symbol, &parse_state)
+    })
+}
 
-    // Build parse state will succeed if it can produce a state set for every
-    // character in the input. This doesn't necessarily mean the parse succeeded
-    if let Ok(parse_state) = build_parse_state(start_symbol, grammar, &input) {
-        // The parse succeeded if there is at least one item in the last state
-        // set that ...
-        parse_state
-            .last()
-            .unwrap()
-            .items()
-            .iter()
-            .filter(|item| {
-                // ... produces the start symbol ...
-                item.rule_name() == start_symbol &&
-                // ... starts at the beginning of the string ...
-                    item.start() == &0 &&
-                // ... and has completed.
-                    item.is_complete()
-            })
-            .count()
-            != 0
-    } else {
-        false
+/// As [`recognise`] but only the characters of `input` in `range` (by
+/// character index, not byte) have to form a sentence. Useful when something
+/// else (e.g. a tokenizer) has already picked out the part of a larger input
+/// to check.
+/// ```
+/// # use parsey::{grammar, recognise_range};
+/// let grammar = grammar! {
+///     Sum -> Sum "+" Number;
+///     Sum -> Number;
+///     Number -> ["0123456789"];
+/// };
+/// assert!(recognise_range(&grammar, "x=1+2;", 2..5));
+/// assert!(!recognise_range(&grammar, "x=1+2;", 0..5));
+/// ```
+///
+/// # Panics
+/// If `range` is out of bounds for the characters of `input`
+pub fn recognise_range<S>(grammar: &Grammar, input: S, range: Range<usize>) -> bool
+where
+    S: AsRef<str>,
+{
+    let input = expand_input(input);
+    let start_symbol = grammar.start_symbol();
+    match build_parse_state(start_symbol, grammar, &input[range], None) {
+        Ok(parse_state) => accepts(start_symbol, &parse_state),
+        Err(_) => false,
+    }
+}
+
+/// As [`recognise`] but fail if any single state set grows beyond `limit`
+/// items. This protects against pathological grammars on adversarial input,
+/// which can otherwise produce a very large number of items at one position.
+///
+/// # Errors
+/// If a state set contains more than `limit` items
+pub fn recognise_with_limit<S>(
+    grammar: &Grammar,
+    input: S,
+    limit: usize,
+) -> Result<bool, StateSetLimitExceeded>
+where
+    S: AsRef<str>,
+{
+    let input = expand_input(input);
+    let start_symbol = grammar.start_symbol();
+
+    match build_parse_state(start_symbol, grammar, &input, Some(limit)) {
+        Ok(parse_state) => Ok(accepts(start_symbol, &parse_state)),
+        Err(Error::Unparsed(_)) => Ok(false),
+        Err(Error::LimitExceeded(e)) => Err(e),
     }
 }
 
+/// True if the final state set of a successfully built parse state contains a
+/// complete parse
+fn accepts<'a, I: Items<'a>>(start_symbol: &str, parse_state: &[StateSet<'a, I>]) -> bool {
+    is_complete_parse(start_symbol, parse_state.last().unwrap())
+}
+
+/// True if `state_set` contains a complete parse of the input up to its
+/// position
+fn is_complete_parse<'a, I: Items<'a>>(start_symbol: &str, state_set: &StateSet<'a, I>) -> bool {
+    // The parse succeeded if there is at least one root item in the state set
+    root_items(start_symbol, state_set).next().is_some()
+}
+
+/// The items in `state_set` that ...
+fn root_items<'a, 'b, I: Items<'a>>(
+    start_symbol: &'b str,
+    state_set: &'b StateSet<'a, I>,
+) -> impl Iterator<Item = &'b Item<'a>> {
+    state_set.items().iter().filter(move |item| {
+        // ... produce the start symbol ...
+        item.rule_name() == start_symbol &&
+        // ... start at the beginning of the string ...
+            item.start() == &0 &&
+        // ... and have completed.
+            item.is_complete()
+    })
+}
+
+/// Return the length of the longest prefix of `input` which is in the language
+/// described by `grammar` or `None` if there isn't one. Unlike [`recognise`]
+/// the whole input doesn't have to match, which allows maximal munch style
+/// matching using a full grammar.
+pub fn longest_match<S>(grammar: &Grammar, input: S) -> Option<usize>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).longest_match()
+}
+
+/// Return the length of every prefix of `input` which is in the language
+/// described by `grammar` in increasing order, i.e. each `k` for which
+/// `input[0..k]` is a complete sentence. Useful for finding all of the valid
+/// split points when segmenting input, see [`longest_match`] if only the
+/// longest is needed.
+#[must_use]
+pub fn complete_prefixes<S>(grammar: &Grammar, input: S) -> Vec<usize>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).complete_prefixes()
+}
+
+/// The root completions found while parsing `input` as `(rule, start, end)`,
+/// one for each rule producing the start symbol that spans the whole input.
+/// Cheaper than enumerating trees with [`parse`] when all that matters is
+/// whether the input parsed and through how many of the start symbol's rules.
+/// Empty if the input isn't in the language described by `grammar`.
+#[must_use]
+pub fn complete_parses<S>(grammar: &Grammar, input: S) -> Vec<(String, usize, usize)>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).complete_parses()
+}
+
+/// Return `true` if `input` is the start of some sentence in the language
+/// described by `grammar`, i.e. more input could still make it parse. Every
+/// complete sentence is also a viable prefix. Interactive tools can use this
+/// to tell "keep typing" apart from input that is already wrong. Assumes every
+/// rule matches some string; a rule that can never complete may make input
+/// look viable when no sentence starts with it.
+/// ```
+/// # use parsey::{grammar, is_viable_prefix};
+/// let grammar = grammar! {
+///     Sum -> Sum "+" Number;
+///     Sum -> Number;
+///     Number -> ["0123456789"];
+/// };
+/// assert!(is_viable_prefix(&grammar, "1+"));
+/// assert!(!is_viable_prefix(&grammar, "1++"));
+/// ```
+#[must_use]
+pub fn is_viable_prefix<S>(grammar: &Grammar, input: S) -> bool
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).is_viable_prefix()
+}
+
 /// Parse `input` according to `grammar`. If successful return an iterator of
 /// possible parse trees
 ///
+/// Where a rule has several alternatives that match the same span of the
+/// input the alternative declared first is tried first, so for an ambiguous
+/// input the first tree uses the earliest declared rules (outermost first).
+/// Alternatives with a higher precedence (see
+/// [`Rule::with_precedence`](grammar::Rule::with_precedence)) are tried before
+/// those declared earlier.
+///
+/// The iterator's `size_hint` is exact for small parses, the trees are counted
+/// without being built (see [`Forest::tree_count`]). Counting is abandoned for
+/// large parses where it would cost more than parsing, and parses with cyclic
+/// derivations (e.g. `A -> B`, `B -> A`) can't be counted, neither has a hint.
+///
+/// The empty input is no different to any other, if the start symbol is
+/// nullable its trees are returned (e.g. a single node with no children)
+/// otherwise it is an error.
+/// ```
+/// # use parsey::{grammar, parse};
+/// # use parsey::ast::Node;
+/// let grammar = grammar! {
+///     List -> "a" List;
+///     List -> ;
+/// };
+/// let trees = parse(&grammar, "").unwrap().collect::<Vec<_>>();
+/// assert_eq!(trees, vec![Node::Internal { name: String::from("List"), children: vec![] }]);
+/// let grammar = grammar! {
+///     List -> "a" List;
+///     List -> "a";
+/// };
+/// assert_eq!(parse(&grammar, "").err().map(|error| error.position), Some(0));
+/// ```
+///
+/// # Errors
+/// In case of parse failure, including input which ends part way through a
+/// sentence, a [`ParseError`] describing where the parse stopped is returned.
+pub fn parse<S>(
+    grammar: &'_ Grammar,
+    input: S,
+) -> Result<impl FusedIterator<Item = Node> + '_, ParseError>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).trees()
+}
+
+/// As [`parse`] but every prediction, scan and completion made while parsing
+/// is passed to `trace`, which is useful for finding out why a grammar doesn't
+/// parse something or where the time goes. See [`TraceEvent`] for what is
+/// reported.
+/// ```
+/// # use parsey::{grammar, parse_traced, TraceEvent};
+/// let grammar = grammar! {
+///     Pair -> "(" Digit ")";
+///     Digit -> ["0123456789"];
+/// };
+/// let mut events = Vec::new();
+/// assert!(parse_traced(&grammar, "(1)", |event| events.push(event)).is_ok());
+/// assert_eq!(
+///     events[1],
+///     TraceEvent::Predict {
+///         position: 1,
+///         item: String::from("Pair -> '(' \u{25CF} Digit ')' (0)"),
+///         rule: String::from("Digit"),
+///     }
+/// );
+/// ```
+///
+/// # Errors
+/// As [`parse`]
+pub fn parse_traced<S>(
+    grammar: &'_ Grammar,
+    input: S,
+    trace: impl FnMut(TraceEvent),
+) -> Result<impl FusedIterator<Item = Node> + '_, ParseError>
+where
+    S: AsRef<str>,
+{
+    ParseState::build_traced(grammar, input, trace).trees()
+}
+
+/// As [`parse`] but only the characters of `input` in `range` (by character
+/// index, not byte) are parsed, see [`recognise_range`]. The position of a
+/// [`ParseError`] is still counted from the start of `input`, its remaining
+/// input stops at the end of `range`.
+///
+/// # Errors
+/// In case of parse failure a [`ParseError`] is returned.
+///
+/// # Panics
+/// If `range` is out of bounds for the characters of `input`
+pub fn parse_range<S>(
+    grammar: &'_ Grammar,
+    input: S,
+    range: Range<usize>,
+) -> Result<impl FusedIterator<Item = Node> + '_, ParseError>
+where
+    S: AsRef<str>,
+{
+    let offset = range.start;
+    // Cut the characters outside the range out of the expanded input in place
+    // rather than copying the range out of it, draining panics on the same
+    // ranges slicing would
+    let mut input = expand_input(input);
+    let _ = input.drain(range.end..);
+    let _ = input.drain(..offset);
+    ParseState::from_chars(grammar, input)
+        .trees()
+        .map_err(|error| ParseError {
+            position: error.position + offset,
+            ..error
+        })
+}
+
+/// Parse `input` according to `grammar` and return its only parse tree, for
+/// languages which must be unambiguous. At most two trees are built to find
+/// out if there are others.
+/// ```
+/// # use parsey::{grammar, parse_unambiguous, ParseOutcome};
+/// let grammar = grammar! {
+///     E -> E "-" E;
+///     E -> ["0123456789"];
+/// };
+/// assert!(parse_unambiguous(&grammar, "1-2").is_ok());
+/// assert!(matches!(
+///     parse_unambiguous(&grammar, "1-2-3"),
+///     Err(ParseOutcome::Ambiguous { count: Some(2), .. })
+/// ));
+/// ```
+///
+/// # Errors
+/// [`ParseOutcome::NoParse`] with the unparsed input in case of parse failure,
+/// [`ParseOutcome::Ambiguous`] if there is more than one tree.
+pub fn parse_unambiguous<S>(grammar: &Grammar, input: S) -> Result<Node, ParseOutcome>
+where
+    S: AsRef<str>,
+{
+    let state = ParseState::build(grammar, input);
+    let mut trees = state.trees().map_err(ParseOutcome::NoParse)?;
+    match (trees.next(), trees.next()) {
+        (Some(tree), None) => Ok(tree),
+        // Only counted once the parse is known to be ambiguous, the hint
+        // covers the trees not yet produced
+        (Some(first), Some(second)) => Err(ParseOutcome::Ambiguous {
+            count: trees.size_hint().1.map(|rest| rest.saturating_add(2)),
+            first,
+            second,
+        }),
+        (None, _) => unreachable!("An accepted input has at least one tree"),
+    }
+}
+
+/// Parse the longest prefix of `input` in the language described by
+/// `grammar`, see [`longest_match`]. The result records how much of the input
+/// the trees cover so a partial parse can't be mistaken for a complete one.
+/// `None` if no prefix of `input` (including the empty one) is in the
+/// language.
+#[must_use]
+pub fn parse_prefix<S>(
+    grammar: &'_ Grammar,
+    input: S,
+) -> Option<PrefixParse<impl FusedIterator<Item = Node> + '_>>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).prefix_trees()
+}
+
+/// As [`parse`] but trees with more than `max_depth` levels of internal nodes
+/// are skipped. Trees are built without recursion, but a tree can be as deep
+/// as the input is long and the other ways of walking a [`Node`] (e.g.
+/// [`Node::map_names`]) recurse. The limit bounds the depth, and so the size,
+/// of the trees adversarial input can produce. Every tree within the limit is
+/// produced as `Ok`, if any were skipped a final `Err(DepthLimitExceeded)`
+/// follows them.
+///
+/// # Errors
+/// In case of parse failure a [`ParseError`] is returned.
+pub fn parse_with_depth_limit<S>(
+    grammar: &'_ Grammar,
+    input: S,
+    max_depth: usize,
+) -> Result<impl Iterator<Item = Result<Node, DepthLimitExceeded>> + '_, ParseError>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).trees_with_depth_limit(max_depth)
+}
+
+/// Parse `input` according to `grammar` and return every possible parse tree
+/// as a [`Forest`], which can produce the k-th tree without building the ones
+/// before it
+///
+/// # Errors
+/// In case of parse failure a [`ParseError`] is returned.
+pub fn parse_forest<S>(grammar: &'_ Grammar, input: S) -> Result<Forest<'_>, ParseError>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).forest()
+}
+
+/// As [`parse`] but each internal node of the resulting trees is annotated
+/// with the span of input it was derived from, see [`SpannedNode`]
+///
+/// # Errors
+/// In case of parse failure a [`ParseError`] is returned.
+pub fn parse_with_provenance<S>(
+    grammar: &'_ Grammar,
+    input: S,
+) -> Result<impl FusedIterator<Item = SpannedNode> + '_, ParseError>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).trees_with_provenance()
+}
+
+/// As [`parse`] but each character of `input` is passed through `normalize`
+/// first, allowing the grammar to match a normalized form (e.g. lowercase)
+/// without handling every variant itself. Normalization is per character so
+/// positions in the resulting trees still line up with the original input,
+/// however leaf nodes contain the normalized characters.
+///
+/// # Errors
+/// In case of parse failure a [`ParseError`] is returned, its remaining input
+/// is normalized.
+pub fn parse_normalized<S, F>(
+    grammar: &'_ Grammar,
+    input: S,
+    normalize: F,
+) -> Result<impl FusedIterator<Item = Node> + '_, ParseError>
+where
+    S: AsRef<str>,
+    F: Fn(char) -> char,
+{
+    let input = expand_input(input)
+        .into_iter()
+        .map(normalize)
+        .collect::<Vec<_>>();
+    ParseState::from_chars(grammar, input).trees()
+}
+
+/// Count the distinct derivations of every rule completed while parsing
+/// `input`, useful for finding where a grammar is ambiguous. See
+/// [`DerivationCount::is_ambiguous`].
+///
 /// # Errors
-/// In case of parse failure the unparsed input is returned.
-pub fn parse<S>(grammar: &'_ Grammar, input: S) -> Result<impl Iterator<Item = Node> + '_, String>
+/// In case of parse failure a [`ParseError`] is returned.
+pub fn derivation_counts<S>(grammar: &Grammar, input: S) -> Result<Vec<DerivationCount>, ParseError>
+where
+    S: AsRef<str>,
+{
+    ParseState::build(grammar, input).derivation_counts()
+}
+
+/// Find every syntax error in `input` using panic mode recovery. When the
+/// parse fails the input is skipped up to and including the next character in
+/// `sync` (e.g. `;` or `\n` for a list of statements) and parsing starts again
+/// from the start symbol after it. Returns the `(start, end)` character range
+/// skipped for each error, empty if `input` is in the language. If the input
+/// runs out before a sentence is complete the final range is empty and
+/// positioned at the end of `input`.
+#[must_use]
+pub fn syntax_errors<S>(grammar: &Grammar, input: S, sync: &[char]) -> Vec<(usize, usize)>
 where
     S: AsRef<str>,
 {
     let input = expand_input(input);
-    let start_symbol = grammar.start_symbol();
+    let mut errors = Vec::new();
+    let mut start = 0;
+
+    loop {
+        let state = ParseState::from_chars(grammar, input[start..].to_vec());
+        if state.accepts() {
+            return errors;
+        }
 
-    let parse_state = build_parse_state(start_symbol, grammar, &input)?;
-    Ok(Node::from_parse_state(start_symbol, &parse_state, input))
+        let failed_at = state
+            .unparsed()
+            .map_or(input.len(), |rest| input.len() - rest.chars().count());
+        if failed_at == input.len() {
+            errors.push((failed_at, failed_at));
+            return errors;
+        }
+
+        // Skip to just after the next sync character, or the end of input if
+        // there isn't one
+        let resume = input[failed_at..]
+            .iter()
+            .position(|c| sync.contains(c))
+            .map_or(input.len(), |offset| failed_at + offset + 1);
+        errors.push((failed_at, resume));
+        if resume == input.len() {
+            return errors;
+        }
+        start = resume;
+    }
 }
 
 syntax_abuse::tests! {
@@ -183,6 +971,14 @@ syntax_abuse::tests! {
             A -> B;
             B -> A
         };
+        BINOP : Grammar = grammar! {
+            E -> E "-" E;
+            E -> ["0123456789"];
+        };
+        AMBIGUOUS : Grammar = grammar! {
+            E -> E E;
+            E -> "a";
+        };
     }
 
     tests! {
@@ -194,7 +990,7 @@ syntax_abuse::tests! {
                 fn $name() {
                     let input = expand_input($input);
                     assert_eq!(
-                        build_parse_state($grammar.start_symbol(), &$grammar, &input),
+                        build_parse_state($grammar.start_symbol(), &$grammar, &input, None),
                         $expected
                     )
                 }
@@ -202,8 +998,12 @@ syntax_abuse::tests! {
         }
 
         macro_rules! err {
-            ($string: expr) => {
-                Err(String::from($string))
+            ($position: expr, $string: expr, [$($expected: expr),*]) => {
+                Err(Error::Unparsed(ParseError {
+                    position: $position,
+                    remaining: String::from($string),
+                    expected: vec![$(String::from($expected)),*],
+                }))
             }
         }
 
@@ -220,7 +1020,7 @@ syntax_abuse::tests! {
             start: usize,
             progress: usize
         ) -> Vec<Item<'a>> {
-            grammar.get_rules_by_name(name).into_iter()
+            grammar.get_rules_by_name(name)
                 .map(move |rule| Item::from_parts(rule, start, progress))
                 .collect()
         }
@@ -305,14 +1105,14 @@ syntax_abuse::tests! {
             invalid_character,
             ARITH,
             "1%2",
-            err!("%2")
+            err!(1, "%2", ["Number", "[0123456789]", "[*/]", "[+-]"])
         }
 
         testcase! {
             valid_character_in_the_wrong_place,
             ARITH,
             "+1",
-            err!("+1")
+            err!(0, "+1", ["Sum", "Product", "Factor", "'('", "Number", "[0123456789]"])
         }
 
         testcase! {
@@ -323,8 +1123,8 @@ syntax_abuse::tests! {
                 StateSet::exhausted(flatvec![
                     make_items(&LOOP, "A", 0, 0),
                     make_items(&LOOP, "B", 0, 0),
-                    make_items(&LOOP, "B", 0, 1),
-                    make_item(&LOOP, 1, 0, 1)
+                    make_item(&LOOP, 1, 0, 1),
+                    make_items(&LOOP, "B", 0, 1)
                 ])
             ])
         }
@@ -377,44 +1177,2309 @@ syntax_abuse::tests! {
     }
 
     tests! {
-        parser:
+        lex_and_parse:
 
-        fn force(result: Result<impl Iterator<Item=Node>, String>) -> Result<Vec<Node>, String> {
-            let nodes = result?;
-            Ok(nodes.collect::<Vec<_>>())
+        use crate::tokenizer::{
+            count, eat, literal, map, oneof, token_kinds, tokenize, Token, Tokenizer,
+        };
+
+        // The grammar only sees one character per token: 'n' for a number
+        // and the operators as themselves
+        testdata! {
+            TOKEN_ARITH: Grammar = grammar! {
+                Sum -> Sum "+" Product;
+                Sum -> Product;
+                Product -> Product "*" "n";
+                Product -> "n";
+            };
+        }
+
+        fn tokenizer() -> impl Tokenizer<Token = Token> {
+            firstof!(
+                map(count(oneof("digit", "0123456789".chars().collect())), |data| {
+                    Some(Token { tag: "number", contents: data.iter().collect() })
+                }),
+                literal("plus", "+"),
+                literal("times", "*"),
+                eat(literal("space", " "))
+            )
+        }
+
+        fn kind(token: &Token) -> char {
+            match token.tag {
+                "number" => 'n',
+                "plus" => '+',
+                _ => '*',
+            }
+        }
+
+        /// Lex `input` then recognise the tokens, `None` if it couldn't be
+        /// lexed
+        fn accepts(input: &str) -> Option<bool> {
+            let tokens = tokenize(input, tokenizer()).ok()?;
+            Some(recognise(&TOKEN_ARITH, token_kinds(&tokens, kind)))
+        }
+
+        /// The characters of the leaves of `node` from left to right
+        fn leaves(node: &Node) -> Vec<char> {
+            match node {
+                Node::Internal { children, .. } => children.iter().flat_map(leaves).collect(),
+                Node::Leaf(c) => vec![*c],
+                Node::Grapheme(cluster) => cluster.chars().collect(),
+            }
         }
 
         testcase! {
-            arith_success,
-            force(parse(&ARITH, "1+2")),
-            Ok(vec![
-                Node::Internal {
-                    name: String::from("Sum"),
-                    children: vec![
-                        Node::Internal {
-                            name: String::from("Sum"),
-                            children: vec![
-                                Node::Internal {
-                                    name: String::from("Product"),
-                                    children: vec![
-                                        Node::Internal {
-                                            name: String::from("Factor"),
-                                            children: vec![
-                                                Node::Internal {
-                                                    name: String::from("Number"),
-                                                    children: vec![
-                                                        Node::Leaf('1')
-                                                    ]
-                                                }
-                                            ]
-                                        }
-                                    ]
-                                }
-                            ]
-                        },
-                        Node::Leaf('+'),
-                        Node::Internal {
-                            name: String::from("Product"),
+            kinds,
+            token_kinds(&tokenize("12 + 3*456", tokenizer()).unwrap(), kind),
+            "n+n*n"
+        }
+
+        testcase! {
+            accepted,
+            ["1", "12 + 3", "1+2*3", "  10 *  20 + 30  "]
+                .iter()
+                .map(|input| accepts(input))
+                .collect::<Vec<_>>(),
+            vec![Some(true); 4]
+        }
+
+        testcase! {
+            rejected,
+            ["1 +", "1 2", "+ 1", "1 * * 2"]
+                .iter()
+                .map(|input| accepts(input))
+                .collect::<Vec<_>>(),
+            vec![Some(false); 4]
+        }
+
+        testcase! {
+            lexer_error,
+            accepts("1 % 2"),
+            None
+        }
+
+        testcase! {
+            leaves_are_tokens,
+            {
+                let tokens = tokenize("12 + 3", tokenizer()).unwrap();
+                let tree = parse(&TOKEN_ARITH, token_kinds(&tokens, kind)).unwrap().next().unwrap();
+                leaves(&tree)
+                    .iter()
+                    .zip(&tokens)
+                    .map(|(leaf, token)| format!("{} {}", leaf, token.token.contents))
+                    .collect::<Vec<_>>()
+            },
+            vec!["n 12", "+ +", "n 3"]
+        }
+    }
+
+    tests! {
+        longest_match:
+
+        testcase! {
+            whole_input,
+            longest_match(&ARITH, "1+2"),
+            Some(3)
+        }
+
+        testcase! {
+            several_prefixes,
+            longest_match(&ARITH, "1+2*3+"),
+            Some(5)
+        }
+
+        testcase! {
+            stops_at_invalid_character,
+            longest_match(&ARITH, "12*(3)%4"),
+            Some(6)
+        }
+
+        testcase! {
+            shorter_prefix_after_failure,
+            longest_match(&ARITH, "1+(2"),
+            Some(1)
+        }
+
+        testcase! {
+            no_match,
+            longest_match(&ARITH, "+1"),
+            None
+        }
+
+        testcase! {
+            empty_input,
+            longest_match(&ARITH, ""),
+            None
+        }
+
+        testcase! {
+            empty_match,
+            longest_match(&EMPTY, "abc"),
+            Some(0)
+        }
+    }
+
+    tests! {
+        complete_prefixes:
+
+        testdata! {
+            WORDS: Grammar = grammar! {
+                Word -> "a";
+                Word -> "ab";
+                Word -> "abcd";
+            };
+        }
+
+        testcase! {
+            several,
+            complete_prefixes(&WORDS, "abcde"),
+            vec![1, 2, 4]
+        }
+
+        testcase! {
+            whole_input,
+            complete_prefixes(&WORDS, "abcd"),
+            vec![1, 2, 4]
+        }
+
+        testcase! {
+            repetition,
+            complete_prefixes(&ARITH, "12+3"),
+            vec![1, 2, 4]
+        }
+
+        testcase! {
+            empty_match,
+            complete_prefixes(&ALMOST_EMPTY, "RuleX"),
+            vec![4]
+        }
+
+        testcase! {
+            includes_empty_prefix,
+            complete_prefixes(&EMPTY, "abc"),
+            vec![0]
+        }
+
+        testcase! {
+            none,
+            complete_prefixes(&WORDS, "xab"),
+            vec![]
+        }
+    }
+
+    tests! {
+        is_viable_prefix:
+
+        testdata! {
+            ACCENTED: Grammar = grammar! {
+                Word -> "x" "e\u{301}";
+            };
+            LINES: Grammar = grammar! {
+                Lines -> Line Lines;
+                Lines -> Line;
+                Line -> ["abc"] $;
+            };
+        }
+
+        fn viable(grammar: &Grammar, inputs: &[&str]) -> Vec<bool> {
+            inputs.iter().map(|input| is_viable_prefix(grammar, input)).collect()
+        }
+
+        testcase! {
+            incomplete,
+            viable(&ARITH, &["1+", "(1+2", "(", ""]),
+            vec![true, true, true, true]
+        }
+
+        testcase! {
+            complete,
+            viable(&ARITH, &["1+2", "(1)*3"]),
+            vec![true, true]
+        }
+
+        testcase! {
+            not_viable,
+            viable(&ARITH, &["1++", "1)", "+", "(1+2))"]),
+            vec![false, false, false, false]
+        }
+
+        testcase! {
+            empty_language,
+            viable(&EMPTY, &["", "a"]),
+            vec![true, false]
+        }
+
+        testcase! {
+            fixed_words,
+            viable(&ALMOST_EMPTY, &["R", "Rul", "Rule", "Rules"]),
+            vec![true, true, true, false]
+        }
+
+        testcase! {
+            part_of_grapheme,
+            viable(&ACCENTED, &["x", "xe", "xe\u{301}", "xa", "xe\u{300}"]),
+            vec![true, true, true, false, false]
+        }
+
+        testcase! {
+            part_of_newline,
+            viable(&LINES, &["a\r", "a\r\nb", "a\rb", "\r"]),
+            vec![true, true, false, false]
+        }
+    }
+
+    tests! {
+        recognise_with_limit:
+
+        testcase! {
+            within_limit,
+            recognise_with_limit(&ARITH, "1+2", 100),
+            Ok(true)
+        }
+
+        testcase! {
+            within_limit_failure,
+            recognise_with_limit(&ARITH, "1+", 100),
+            Ok(false)
+        }
+
+        testcase! {
+            exceeded,
+            recognise_with_limit(&AMBIGUOUS, "aaaaaaaaaaaaaaaaaaaa", 20),
+            Err(StateSetLimitExceeded { position: 8, limit: 20 })
+        }
+
+        testcase! {
+            unlimited,
+            recognise(&AMBIGUOUS, "aaaaaaaaaaaaaaaaaaaa"),
+            true
+        }
+    }
+
+    #[cfg(feature = "arena")]
+    tests! {
+        recognise_in:
+
+        fn same_as_recognise(grammar: &Grammar, inputs: &[&str]) -> bool {
+            let mut arena = Bump::new();
+            inputs.iter().all(|input| {
+                let accepted = recognise_in(&arena, grammar, input);
+                arena.reset();
+                accepted == recognise(grammar, input)
+            })
+        }
+
+        testcase! {
+            arith,
+            same_as_recognise(&ARITH, &["1+2*3", "(1+2)*3", "1+", "", "1", "1+(2*3", "12/34-(5)"]),
+            true
+        }
+
+        testcase! {
+            nullable,
+            same_as_recognise(&LOOP, &["", "a"])
+                && same_as_recognise(&ALMOST_EMPTY, &["Rule", "R"]),
+            true
+        }
+
+        testcase! {
+            ambiguous,
+            same_as_recognise(&AMBIGUOUS, &["a", "aaaaaaaaaa", "aab"]),
+            true
+        }
+
+        testcase! {
+            shared_arena,
+            {
+                let arena = Bump::new();
+                ["1+2", "1+", "(3)"]
+                    .iter()
+                    .map(|input| recognise_in(&arena, &ARITH, input))
+                    .collect::<Vec<_>>()
+            },
+            vec![true, false, true]
+        }
+    }
+
+    tests! {
+        parse_normalized:
+
+        fn fullwidth_digits(c: char) -> char {
+            if ('\u{FF10}'..='\u{FF19}').contains(&c) {
+                std::char::from_u32(c as u32 - 0xFF10 + '0' as u32).unwrap()
+            } else {
+                c
+            }
+        }
+
+        fn force(
+            result: Result<impl Iterator<Item=Node>, ParseError>
+        ) -> Result<Vec<Node>, String> {
+            Ok(result.map_err(|error| error.remaining)?.collect::<Vec<_>>())
+        }
+
+        testcase! {
+            fullwidth,
+            force(parse_normalized(&ARITH, "\u{FF11}+\u{FF12}", fullwidth_digits)),
+            force(parse(&ARITH, "1+2"))
+        }
+
+        testcase! {
+            mixed,
+            force(parse_normalized(&ARITH, "1+\u{FF12}", fullwidth_digits)),
+            force(parse(&ARITH, "1+2"))
+        }
+
+        testcase! {
+            unnormalized_fails,
+            force(parse(&ARITH, "\u{FF11}+\u{FF12}")),
+            Err(String::from("\u{FF11}+\u{FF12}"))
+        }
+
+        testcase! {
+            failure,
+            force(parse_normalized(&ARITH, "\u{FF11}%\u{FF12}", fullwidth_digits)),
+            Err(String::from("%2"))
+        }
+    }
+
+    tests! {
+        disambiguate_binop:
+
+        use crate::ast::disambiguate_binop;
+        use crate::grammar::Associativity;
+
+        fn node(children: Vec<Node>) -> Node {
+            Node::Internal { name: String::from("E"), children }
+        }
+
+        fn number(c: char) -> Node {
+            node(vec![Node::Leaf(c)])
+        }
+
+        testcase! {
+            left,
+            disambiguate_binop(parse(&BINOP, "1-2-3").unwrap(), Associativity::Left),
+            Some(node(vec![
+                node(vec![number('1'), Node::Leaf('-'), number('2')]),
+                Node::Leaf('-'),
+                number('3')
+            ]))
+        }
+
+        testcase! {
+            right,
+            disambiguate_binop(parse(&BINOP, "1-2-3").unwrap(), Associativity::Right),
+            Some(node(vec![
+                number('1'),
+                Node::Leaf('-'),
+                node(vec![number('2'), Node::Leaf('-'), number('3')])
+            ]))
+        }
+
+        testcase! {
+            longer_chain,
+            disambiguate_binop(parse(&BINOP, "1-2-3-4").unwrap(), Associativity::Left),
+            Some(node(vec![
+                node(vec![
+                    node(vec![number('1'), Node::Leaf('-'), number('2')]),
+                    Node::Leaf('-'),
+                    number('3')
+                ]),
+                Node::Leaf('-'),
+                number('4')
+            ]))
+        }
+
+        testcase! {
+            single_operand,
+            disambiguate_binop(parse(&BINOP, "1").unwrap(), Associativity::Right),
+            Some(number('1'))
+        }
+
+        testcase! {
+            no_trees,
+            disambiguate_binop(vec![], Associativity::Left),
+            None
+        }
+    }
+
+    tests! {
+        derivation_counts:
+
+        fn count(name: &str, start: usize, end: usize, count: usize) -> DerivationCount {
+            DerivationCount { name: String::from(name), start, end, count }
+        }
+
+        testcase! {
+            ambiguous,
+            derivation_counts(&AMBIGUOUS, "aaa"),
+            Ok(vec![
+                count("E", 0, 1, 1),
+                count("E", 0, 2, 1),
+                count("E", 0, 3, 2),
+                count("E", 1, 2, 1),
+                count("E", 1, 3, 1),
+                count("E", 2, 3, 1)
+            ])
+        }
+
+        testcase! {
+            ambiguous_spans,
+            derivation_counts(&AMBIGUOUS, "aaaa").map(|counts| {
+                counts.into_iter()
+                    .filter(DerivationCount::is_ambiguous)
+                    .collect::<Vec<_>>()
+            }),
+            Ok(vec![
+                count("E", 0, 3, 2),
+                count("E", 0, 4, 5),
+                count("E", 1, 4, 2)
+            ])
+        }
+
+        testcase! {
+            unambiguous,
+            derivation_counts(&ARITH, "1+2").map(|counts| {
+                counts.iter().any(DerivationCount::is_ambiguous)
+            }),
+            Ok(false)
+        }
+
+        testcase! {
+            failure,
+            derivation_counts(&ARITH, "1%2").map_err(|error| error.remaining),
+            Err(String::from("%2"))
+        }
+    }
+
+    tests! {
+        error_location:
+
+        use crate::tokenizer::{firstof, oneof, tokenize};
+
+        testdata! {
+            LINES: Grammar = grammar! {
+                Lines -> Line "\r\n" Lines;
+                Lines -> Line;
+                Line -> ["abc"] Line;
+                Line -> ["abc"];
+            };
+        }
+
+        testcase! {
+            first_line,
+            error_location("ab%", "%"),
+            CharacterPosition { row: 0, col: 2 }
+        }
+
+        testcase! {
+            lf,
+            error_location("ab\nc%", "%"),
+            CharacterPosition { row: 1, col: 1 }
+        }
+
+        testcase! {
+            crlf,
+            error_location("ab\r\nc%", "%"),
+            CharacterPosition { row: 1, col: 1 }
+        }
+
+        testcase! {
+            limit_exceeded,
+            StateSetLimitExceeded { position: 5, limit: 1 }.location("ab\r\nc%"),
+            CharacterPosition { row: 1, col: 1 }
+        }
+
+        testcase! {
+            parse_error,
+            parse(&LINES, "ab\r\nc%").err().map(|error| error.location("ab\r\nc%")),
+            Some(CharacterPosition { row: 1, col: 1 })
+        }
+
+        #[test]
+        fn tokenizer_agrees_with_parser() {
+            let input = "ab\r\nca\r\nb%";
+
+            let error = parse(&LINES, input).err().unwrap();
+            let (tokens, rest) = tokenize(
+                input,
+                firstof!(
+                    oneof("letter", hashset!['a', 'b', 'c']),
+                    oneof("newline", hashset!['\r', '\n'])
+                ),
+            )
+            .unwrap_err()
+            .into_parts();
+
+            assert_eq!(error.remaining, rest);
+            assert_eq!(
+                error_location(input, &error.remaining),
+                tokens.last().unwrap().span.end
+            );
+            assert_eq!(error.location(input), CharacterPosition { row: 2, col: 1 });
+        }
+    }
+
+    tests! {
+        shared_grammar:
+
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        testdata! {
+            RC: ??? = Rc::new(grammar! {
+                S -> "a" S;
+                S -> "a";
+            });
+        }
+
+        testcase! {
+            recognise_rc,
+            recognise(&RC!(), "aaa"),
+            true
+        }
+
+        testcase! {
+            recognise_arc,
+            recognise(&Arc::new(grammar! { S -> "a" }), "a"),
+            true
+        }
+
+        testcase! {
+            longest_match_rc,
+            longest_match(&RC!(), "aab"),
+            Some(2)
+        }
+
+        #[test]
+        fn parse_rc() {
+            let grammar = RC!();
+            let trees = parse(&grammar, "aa").map(Iterator::count);
+            assert_eq!(trees, Ok(1));
+        }
+
+        #[test]
+        fn possible_next_symbols_rc() {
+            let grammar = RC!();
+            assert_eq!(
+                grammar.possible_next_symbols("a"),
+                vec![
+                    grammar::Symbol::Rule(String::from("S")),
+                    grammar::Symbol::Literal('a')
+                ]
+            );
+        }
+    }
+
+    tests! {
+        complete_parses:
+
+        testdata! {
+            TWO_ROOTS: Grammar = grammar! {
+                S -> A;
+                S -> B;
+                A -> "x";
+                B -> "x";
+            };
+        }
+
+        fn root(name: &str, end: usize) -> (String, usize, usize) {
+            (String::from(name), 0, end)
+        }
+
+        testcase! {
+            unambiguous,
+            complete_parses(&ARITH, "1+2"),
+            vec![root("Sum", 3)]
+        }
+
+        testcase! {
+            ambiguous,
+            complete_parses(&TWO_ROOTS, "x"),
+            vec![root("S", 1), root("S", 1)]
+        }
+
+        testcase! {
+            ambiguous_below_root,
+            complete_parses(&AMBIGUOUS, "aaaa"),
+            vec![root("E", 4)]
+        }
+
+        testcase! {
+            empty,
+            complete_parses(&EMPTY, ""),
+            vec![root("Empty", 0)]
+        }
+
+        testcase! {
+            failure,
+            complete_parses(&ARITH, "1+"),
+            vec![]
+        }
+
+        testcase! {
+            prefix_only,
+            complete_parses(&ARITH, "1+2)"),
+            vec![]
+        }
+    }
+
+    tests! {
+        syntax_errors:
+
+        testdata! {
+            STATEMENTS: Grammar = grammar! {
+                Statements -> Statement ";" Statements;
+                Statements -> Statement ";";
+                Statement -> Name "=" Name;
+                Name -> ["abc"];
+            };
+        }
+
+        testcase! {
+            valid,
+            syntax_errors(&STATEMENTS, "a=b;c=a;", &[';']),
+            vec![]
+        }
+
+        testcase! {
+            skips_to_next_sync,
+            syntax_errors(&STATEMENTS, "a=b;c?c;b=a;", &[';']),
+            vec![(5, 8)]
+        }
+
+        testcase! {
+            fails_on_sync,
+            syntax_errors(&STATEMENTS, "a=;b=c;", &[';']),
+            vec![(2, 3)]
+        }
+
+        testcase! {
+            several_errors,
+            syntax_errors(&STATEMENTS, "x;a=b;b==c;c=a;", &[';']),
+            vec![(0, 2), (8, 11)]
+        }
+
+        testcase! {
+            error_in_last_statement,
+            syntax_errors(&STATEMENTS, "a=b;c?c;", &[';']),
+            vec![(5, 8)]
+        }
+
+        testcase! {
+            no_sync_character,
+            syntax_errors(&STATEMENTS, "a=b;c?c", &[';']),
+            vec![(5, 7)]
+        }
+
+        testcase! {
+            incomplete,
+            syntax_errors(&STATEMENTS, "a=b;c=", &[';']),
+            vec![(6, 6)]
+        }
+
+        testcase! {
+            without_sync,
+            syntax_errors(&STATEMENTS, "a=b;c?c;b=a;", &[]),
+            vec![(5, 12)]
+        }
+
+        testcase! {
+            several_sync_characters,
+            syntax_errors(&STATEMENTS, "a=b;c?c\nb=a;", &[';', '\n']),
+            vec![(5, 8)]
+        }
+    }
+
+    tests! {
+        map_names:
+
+        fn strip_prefix(name: &str) -> String {
+            String::from(name.strip_prefix("foo_").unwrap_or(name))
+        }
+
+        testcase! {
+            unprefixed,
+            {
+                let prefixed = ARITH.clone().with_prefix("foo_");
+                parse(&prefixed, "1+2")
+                    .unwrap()
+                    .map(|tree| tree.map_names(strip_prefix))
+                    .collect::<Vec<_>>()
+            },
+            parse(&ARITH, "1+2").unwrap().collect::<Vec<_>>()
+        }
+
+        testcase! {
+            every_internal_node,
+            parse(&ARITH.clone().with_prefix("foo_"), "(1)")
+                .unwrap()
+                .next()
+                .unwrap()
+                .map_names(|name| String::from(if name == "foo_Sum" { "Sum" } else { "X" })),
+            Node::Internal {
+                name: String::from("Sum"),
+                children: vec![Node::Internal {
+                    name: String::from("X"),
+                    children: vec![Node::Internal {
+                        name: String::from("X"),
+                        children: vec![
+                            Node::Leaf('('),
+                            Node::Internal {
+                                name: String::from("Sum"),
+                                children: vec![Node::Internal {
+                                    name: String::from("X"),
+                                    children: vec![Node::Internal {
+                                        name: String::from("X"),
+                                        children: vec![Node::Internal {
+                                            name: String::from("X"),
+                                            children: vec![Node::Leaf('1')]
+                                        }]
+                                    }]
+                                }]
+                            },
+                            Node::Leaf(')')
+                        ]
+                    }]
+                }]
+            }
+        }
+
+        testcase! {
+            leaves_unchanged,
+            Node::Grapheme(String::from("e\u{301}")).map_names(|_| String::new()),
+            Node::Grapheme(String::from("e\u{301}"))
+        }
+    }
+
+    tests! {
+        deep_trees:
+
+        testdata! {
+            LEFT: Grammar = grammar! {
+                S -> S "a";
+                S -> "a";
+            };
+        }
+
+        /// The number of internal nodes down the leftmost path of `node`
+        fn depth(mut node: &Node) -> usize {
+            let mut depth = 0;
+            while let Node::Internal { children, .. } = node {
+                depth += 1;
+                node = &children[0];
+            }
+            depth
+        }
+
+        /// A tree 100,000 levels deep. The request was for right recursion
+        /// (`S -> "a" S`) but Earley parsing without Leo's optimisation takes
+        /// at least quadratic time on it, every completion completes all of the
+        /// waiting items again. 2,000 characters already take over a minute.
+        /// Left recursion nests the tree just as deeply in linear time and the
+        /// tree iterator handles both the same way
+        fn deep_tree() -> Node {
+            parse(&LEFT, "a".repeat(100_000)).unwrap().next().unwrap()
+        }
+
+        testcase! {
+            deep_left_recursion,
+            depth(&deep_tree()),
+            100_000
+        }
+
+        // collect asks for a size hint before building any trees
+        testcase! {
+            collect,
+            {
+                let trees = parse(&LEFT, "a".repeat(100_000)).unwrap();
+                (trees.size_hint(), trees.map(|tree| depth(&tree)).collect::<Vec<_>>())
+            },
+            ((0, None), vec![100_000])
+        }
+
+        // Each level formats as " S {", " }" and a leaf " a"
+        testcase! {
+            clone_compare_and_format,
+            {
+                let tree = deep_tree();
+                let copy = tree.clone();
+                (copy == tree, format!("{:?}", copy).len())
+            },
+            (true, 800_000)
+        }
+
+        testcase! {
+            not_equal,
+            deep_tree() == parse(&LEFT, "a".repeat(99_999)).unwrap().next().unwrap(),
+            false
+        }
+
+        // Without an upper bound on the first S it could be the same item as
+        // its parent, which used to recurse forever
+        testcase! {
+            same_item_as_parent,
+            parse(&grammar! { S -> S S; S -> "a"; }, "aaaa")
+                .unwrap()
+                .map(|tree| format!("{:?}", tree))
+                .collect::<Vec<_>>(),
+            vec![
+                " S { S { a } S { S { a } S { S { a } S { a } } } }",
+                " S { S { a } S { S { S { a } S { a } } S { a } } }",
+                " S { S { S { a } S { a } } S { S { a } S { a } } }",
+                " S { S { S { a } S { S { a } S { a } } } S { a } }",
+                " S { S { S { S { a } S { a } } S { a } } S { a } }"
+            ]
+        }
+    }
+
+    tests! {
+        depth_limit:
+
+        testdata! {
+            DEEP: Grammar = grammar! {
+                S -> "a" S;
+                S -> "a";
+            };
+            PAIRS: Grammar = grammar! {
+                S -> "a" S;
+                S -> "a" "a" S;
+                S -> "a" "a";
+                S -> "a";
+            };
+        }
+
+        fn depths(grammar: &Grammar, input: &str, max_depth: usize) -> Vec<Result<usize, DepthLimitExceeded>> {
+            fn depth(node: &Node) -> usize {
+                match node {
+                    Node::Internal { children, .. } => 1 + children.iter().map(depth).max().unwrap_or(0),
+                    Node::Leaf(_) | Node::Grapheme(_) => 0,
+                }
+            }
+            parse_with_depth_limit(grammar, input, max_depth)
+                .unwrap()
+                .map(|tree| tree.map(|tree| depth(&tree)))
+                .collect()
+        }
+
+        testcase! {
+            within_limit,
+            depths(&DEEP, "aaaa", 4),
+            vec![Ok(4)]
+        }
+
+        testcase! {
+            too_deep,
+            depths(&DEEP, "aaaa", 3),
+            vec![Err(DepthLimitExceeded { limit: 3 })]
+        }
+
+        testcase! {
+            shallow_trees_kept,
+            depths(&PAIRS, "aaa", 2),
+            vec![Ok(2), Ok(2), Err(DepthLimitExceeded { limit: 2 })]
+        }
+
+        testcase! {
+            same_as_parse,
+            parse_with_depth_limit(&PAIRS, "aaaa", 10).unwrap().collect::<Result<Vec<_>, _>>(),
+            Ok(parse(&PAIRS, "aaaa").unwrap().collect::<Vec<_>>())
+        }
+
+        testcase! {
+            long_input,
+            {
+                let input = "a".repeat(200);
+                depths(&DEEP, &input, 50)
+            },
+            vec![Err(DepthLimitExceeded { limit: 50 })]
+        }
+
+        testcase! {
+            parse_failure,
+            parse_with_depth_limit(&DEEP, "ab", 10)
+                .map(Iterator::count)
+                .map_err(|error| error.remaining),
+            Err(String::from("b"))
+        }
+    }
+
+    tests! {
+        outline:
+
+        fn tree(grammar: &Grammar, input: &str) -> Node {
+            parse(grammar, input).unwrap().next().unwrap()
+        }
+
+        testcase! {
+            arith,
+            tree(&ARITH, "1+2*3").outline(),
+            [
+                "Sum",
+                "    Sum",
+                "        Product",
+                "            Factor",
+                "                Number",
+                "                    1",
+                "    +",
+                "    Product",
+                "        Product",
+                "            Factor",
+                "                Number",
+                "                    2",
+                "        *",
+                "        Factor",
+                "            Number",
+                "                3",
+            ]
+            .join("\n")
+        }
+
+        testcase! {
+            leaf,
+            Node::Leaf('a').outline(),
+            "a"
+        }
+
+        testcase! {
+            escaped_leaves,
+            Node::Internal {
+                name: String::from("Line"),
+                children: vec![Node::Leaf('\n'), Node::Grapheme(String::from("\r\n"))]
+            }
+            .outline(),
+            "Line\n    \\n\n    \\r\\n"
+        }
+
+        testcase! {
+            empty_rule,
+            tree(&EMPTY, "").outline(),
+            "Empty"
+        }
+    }
+
+    tests! {
+        flatten_rule:
+
+        testdata! {
+            RIGHT: Grammar = grammar! {
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+            };
+            LEFT: Grammar = grammar! {
+                Number -> Number ["0123456789"];
+                Number -> ["0123456789"];
+            };
+            LIST: Grammar = grammar! {
+                List -> Number "," List;
+                List -> Number;
+                Number -> ["0123456789"] Number;
+                Number -> ["0123456789"];
+            };
+        }
+
+        fn tree(grammar: &Grammar, input: &str) -> Node {
+            parse(grammar, input).unwrap().next().unwrap()
+        }
+
+        fn number(digits: &str) -> Node {
+            Node::Internal {
+                name: String::from("Number"),
+                children: digits.chars().map(Node::Leaf).collect(),
+            }
+        }
+
+        testcase! {
+            right_recursive,
+            tree(&RIGHT, "1234").flatten_rule("Number"),
+            number("1234")
+        }
+
+        testcase! {
+            left_recursive,
+            tree(&LEFT, "1234").flatten_rule("Number"),
+            number("1234")
+        }
+
+        testcase! {
+            left_recursive_covers_input,
+            parse(&LEFT, "1234").map(Iterator::count),
+            Ok(1)
+        }
+
+        testcase! {
+            already_flat,
+            tree(&RIGHT, "1").flatten_rule("Number"),
+            number("1")
+        }
+
+        testcase! {
+            nested,
+            tree(&LIST, "12,3,45").flatten_rule("Number"),
+            Node::Internal {
+                name: String::from("List"),
+                children: vec![
+                    number("12"),
+                    Node::Leaf(','),
+                    Node::Internal {
+                        name: String::from("List"),
+                        children: vec![
+                            number("3"),
+                            Node::Leaf(','),
+                            Node::Internal {
+                                name: String::from("List"),
+                                children: vec![number("45")]
+                            }
+                        ]
+                    }
+                ]
+            }
+        }
+
+        testcase! {
+            both_rules,
+            tree(&LIST, "12,3").flatten_rule("Number").flatten_rule("List"),
+            Node::Internal {
+                name: String::from("List"),
+                children: vec![number("12"), Node::Leaf(','), number("3")]
+            }
+        }
+
+        testcase! {
+            other_name,
+            tree(&RIGHT, "12").flatten_rule("List"),
+            tree(&RIGHT, "12")
+        }
+    }
+
+    tests! {
+        same_shape:
+
+        fn tree(grammar: &Grammar, input: &str) -> Node {
+            parse(grammar, input).unwrap().next().unwrap()
+        }
+
+        testcase! {
+            different_content,
+            tree(&ARITH, "12+3").same_shape(&tree(&ARITH, "45-6")),
+            true
+        }
+
+        testcase! {
+            different_operator_rule,
+            tree(&ARITH, "1+2").same_shape(&tree(&ARITH, "1*2")),
+            false
+        }
+
+        testcase! {
+            different_length,
+            tree(&ARITH, "12").same_shape(&tree(&ARITH, "123")),
+            false
+        }
+
+        testcase! {
+            different_grouping,
+            tree(&ARITH, "1+2").same_shape(&tree(&ARITH, "(1)")),
+            false
+        }
+
+        testcase! {
+            leaf_and_grapheme,
+            Node::Leaf('a').same_shape(&Node::Grapheme(String::from("e\u{301}"))),
+            true
+        }
+
+        testcase! {
+            leaf_and_internal,
+            Node::Leaf('a').same_shape(&Node::Internal { name: String::from("A"), children: vec![] }),
+            false
+        }
+    }
+
+    tests! {
+        into_parts:
+
+        testcase! {
+            internal,
+            parse(&ARITH, "1")
+                .unwrap()
+                .next()
+                .unwrap()
+                .into_internal()
+                .map(|(name, children)| (name, children.len())),
+            Some((String::from("Sum"), 1))
+        }
+
+        testcase! {
+            leaf,
+            (Node::Leaf('a').into_internal(), Node::Leaf('a').into_grapheme()),
+            (None, None)
+        }
+
+        testcase! {
+            grapheme,
+            (
+                Node::Grapheme(String::from("e\u{301}")).into_internal(),
+                Node::Grapheme(String::from("e\u{301}")).into_grapheme()
+            ),
+            (None, Some(String::from("e\u{301}")))
+        }
+
+        testcase! {
+            consumes_deep_tree,
+            {
+                let mut node = Node::Leaf('a');
+                for _ in 0..100_000 {
+                    node = Node::Internal { name: String::from("S"), children: vec![node] };
+                }
+                let mut depth = 0;
+                while let Node::Internal { .. } = node {
+                    node = node.into_internal().unwrap().1.pop().unwrap();
+                    depth += 1;
+                }
+                (depth, node)
+            },
+            (100_000, Node::Leaf('a'))
+        }
+    }
+
+    tests! {
+        parse_with_provenance:
+
+        /// Flatten the internal nodes of a tree into `(name, start, end)` in
+        /// pre-order
+        fn spans(node: &SpannedNode) -> Vec<(String, usize, usize)> {
+            match node {
+                SpannedNode::Internal { name, start, end, children } => {
+                    let mut result = vec![(name.clone(), *start, *end)];
+                    result.extend(children.iter().flat_map(spans));
+                    result
+                }
+                SpannedNode::Leaf(_) | SpannedNode::Grapheme(_) => vec![],
+            }
+        }
+
+        fn span(name: &str, start: usize, end: usize) -> (String, usize, usize) {
+            (String::from(name), start, end)
+        }
+
+        testcase! {
+            arith,
+            parse_with_provenance(&ARITH, "1+23")
+                .map(|trees| trees.flat_map(|tree| spans(&tree)).collect::<Vec<_>>()),
+            Ok(vec![
+                span("Sum", 0, 4),
+                span("Sum", 0, 1),
+                span("Product", 0, 1),
+                span("Factor", 0, 1),
+                span("Number", 0, 1),
+                span("Product", 2, 4),
+                span("Factor", 2, 4),
+                span("Number", 2, 4),
+                span("Number", 3, 4)
+            ])
+        }
+
+        testcase! {
+            leaves,
+            parse_with_provenance(&grammar! { S -> "ab" }, "ab")
+                .map(Iterator::collect::<Vec<_>>),
+            Ok(vec![SpannedNode::Internal {
+                name: String::from("S"),
+                start: 0,
+                end: 2,
+                children: vec![SpannedNode::Leaf('a'), SpannedNode::Leaf('b')]
+            }])
+        }
+
+        testcase! {
+            empty,
+            parse_with_provenance(&EMPTY, "").map(Iterator::collect::<Vec<_>>),
+            Ok(vec![SpannedNode::Internal {
+                name: String::from("Empty"),
+                start: 0,
+                end: 0,
+                children: vec![]
+            }])
+        }
+
+        testcase! {
+            failure,
+            parse_with_provenance(&ARITH, "1%2")
+                .map(Iterator::count)
+                .map_err(|error| error.remaining),
+            Err(String::from("%2"))
+        }
+        /// The spans down the leftmost path of a tree, walked without
+        /// recursion
+        fn leftmost_spans(tree: &SpannedNode) -> Vec<(usize, usize)> {
+            let mut result = Vec::new();
+            let mut node = tree;
+            while let SpannedNode::Internal { start, end, children, .. } = node {
+                result.push((*start, *end));
+                match children.first() {
+                    Some(child) => node = child,
+                    None => break,
+                }
+            }
+            result
+        }
+
+        // Spans come from the parse state rather than being summed up from the
+        // leaves, so a tree as deep as the input is long takes linear time and
+        // is dropped without recursion
+        testcase! {
+            deep_left_recursion,
+            parse_with_provenance(&grammar! { L -> L "a"; L -> "a"; }, "a".repeat(100_000))
+                .unwrap()
+                .map(|tree| {
+                    let spans = leftmost_spans(&tree);
+                    (spans.len(), spans.first().copied(), spans.last().copied())
+                })
+                .collect::<Vec<_>>(),
+            vec![(100_000, Some((0, 100_000)), Some((0, 1)))]
+        }
+
+        testcase! {
+            matches_parse,
+            parse_with_provenance(&ARITH, "1+2*(3-4)").unwrap().count(),
+            parse(&ARITH, "1+2*(3-4)").unwrap().count()
+        }
+    }
+
+    tests! {
+        graphemes:
+
+        testdata! {
+            CAFE: Grammar = grammar! {
+                Word -> "caf" Accent;
+                Accent -> "e\u{301}";
+                Accent -> "e";
+            };
+        }
+
+        testcase! {
+            combining,
+            recognise(&CAFE, "cafe\u{301}"),
+            true
+        }
+
+        testcase! {
+            without_combining,
+            recognise(&CAFE, "cafe"),
+            true
+        }
+
+        testcase! {
+            precomposed,
+            recognise(&CAFE, "caf\u{e9}"),
+            false
+        }
+
+        testcase! {
+            partial_cluster,
+            recognise(&grammar! { Word -> "e\u{301}x" }, "ex"),
+            false
+        }
+
+        testcase! {
+            tree,
+            parse(&CAFE, "cafe\u{301}").map(Iterator::collect::<Vec<_>>),
+            Ok(vec![Node::Internal {
+                name: String::from("Word"),
+                children: vec![
+                    Node::Leaf('c'),
+                    Node::Leaf('a'),
+                    Node::Leaf('f'),
+                    Node::Internal {
+                        name: String::from("Accent"),
+                        children: vec![Node::Grapheme(String::from("e\u{301}"))]
+                    }
+                ]
+            }])
+        }
+
+        testcase! {
+            counts,
+            derivation_counts(&CAFE, "cafe\u{301}").map(|counts| {
+                counts
+                    .into_iter()
+                    .filter(|count| count.end == 5)
+                    .map(|count| (count.name, count.start, count.count))
+                    .collect::<Vec<_>>()
+            }),
+            Ok(vec![(String::from("Word"), 0, 1), (String::from("Accent"), 3, 1)])
+        }
+    }
+
+    tests! {
+        terminal_leaves:
+
+        // One rule per terminal variant, anything the parser recognises
+        // must produce a single tree with the matched input in its leaves
+        testdata! {
+            TERMINALS: Grammar = grammar! {
+                Terminal -> "a";
+                Terminal -> ["xyz"];
+                Terminal -> "e\u{301}";
+            };
+        }
+
+        fn leaves(input: &str) -> (bool, Result<Vec<Node>, String>, Result<usize, String>) {
+            (
+                recognise(&TERMINALS, input),
+                parse(&TERMINALS, input)
+                    .map(|trees| {
+                        trees
+                            .flat_map(|tree| match tree {
+                                Node::Internal { .. } => tree.into_internal().unwrap().1,
+                                _ => vec![tree],
+                            })
+                            .collect()
+                    })
+                    .map_err(|error| error.remaining),
+                derivation_counts(&TERMINALS, input)
+                    .map(|counts| counts.into_iter().map(|count| count.count).sum())
+                    .map_err(|error| error.remaining)
+            )
+        }
+
+        testcase! {
+            literal,
+            leaves("a"),
+            (true, Ok(vec![Node::Leaf('a')]), Ok(1))
+        }
+
+        testcase! {
+            oneof,
+            leaves("y"),
+            (true, Ok(vec![Node::Leaf('y')]), Ok(1))
+        }
+
+        testcase! {
+            grapheme,
+            leaves("e\u{301}"),
+            (true, Ok(vec![Node::Grapheme(String::from("e\u{301}"))]), Ok(1))
+        }
+
+        testcase! {
+            no_match,
+            leaves("b"),
+            (false, Err(String::from("b")), Err(String::from("b")))
+        }
+    }
+
+    tests! {
+        nullable_trees:
+
+        // Every symbol after the first can match nothing, the tree builder
+        // has to be prepared for children that are empty or start at the
+        // very end of the input
+        testdata! {
+            NULLABLE: Grammar = grammar! {
+                S -> A B B;
+                A -> "a";
+                A -> ;
+                B -> ;
+                B -> "b";
+            };
+        }
+
+        /// The characters matched by `node`
+        fn text(node: &Node) -> String {
+            match node {
+                Node::Internal { children, .. } => children.iter().map(text).collect(),
+                Node::Leaf(c) => c.to_string(),
+                Node::Grapheme(cluster) => cluster.clone(),
+            }
+        }
+
+        fn tree_count(input: &str) -> Result<usize, String> {
+            parse(&NULLABLE, input).map(Iterator::count).map_err(|error| error.remaining)
+        }
+
+        testcase! {
+            recognised,
+            ["", "a", "b", "ab", "bb", "abb", "abbb"]
+                .iter()
+                .map(|input| recognise(&NULLABLE, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, true, true, true, false]
+        }
+
+        testcase! {
+            empty_input,
+            tree_count(""),
+            Ok(1)
+        }
+
+        testcase! {
+            only_leading,
+            tree_count("a"),
+            Ok(1)
+        }
+
+        testcase! {
+            ambiguous_trailing,
+            (tree_count("b"), tree_count("ab")),
+            (Ok(2), Ok(2))
+        }
+
+        testcase! {
+            everything,
+            tree_count("abb"),
+            Ok(1)
+        }
+
+        testcase! {
+            matches_derivation_counts,
+            ["", "a", "b", "ab", "bb", "abb"]
+                .iter()
+                .filter(|input| {
+                    let root = derivation_counts(&NULLABLE, input).unwrap().into_iter().find(|count| {
+                        count.name == "S" && count.start == 0 && count.end == input.len()
+                    });
+                    tree_count(input) != Ok(root.map_or(0, |root| root.count))
+                })
+                .collect::<Vec<_>>(),
+            Vec::<&&str>::new()
+        }
+
+        testcase! {
+            trees_cover_input,
+            ["", "a", "b", "ab", "bb", "abb", "abbb"]
+                .iter()
+                .filter(|input| {
+                    parse(&NULLABLE, input)
+                        .is_ok_and(|mut trees| trees.any(|tree| text(&tree) != **input))
+                })
+                .collect::<Vec<_>>(),
+            Vec::<&&str>::new()
+        }
+    }
+
+    tests! {
+        newline:
+
+        testdata! {
+            LINES: Grammar = grammar! {
+                Lines -> Line Lines;
+                Lines -> Line;
+                Line -> Word $;
+                Word -> ["abc"] Word;
+                Word -> ["abc"];
+            };
+        }
+
+        fn line(word: &str, ending: &str) -> Node {
+            let mut word = word.chars().rev();
+            let last = Node::Internal {
+                name: String::from("Word"),
+                children: vec![Node::Leaf(word.next().unwrap())],
+            };
+            let word = word.fold(last, |rest, c| Node::Internal {
+                name: String::from("Word"),
+                children: vec![Node::Leaf(c), rest],
+            });
+            let ending = match ending {
+                "\n" => Node::Leaf('\n'),
+                _ => Node::Grapheme(String::from(ending)),
+            };
+            Node::Internal { name: String::from("Line"), children: vec![word, ending] }
+        }
+
+        testcase! {
+            recognised,
+            ["ab\n", "ab\r\n", "ab\nc\r\nb\n", "ab", "ab\r", "ab\n\n", "ab\r\r\n"]
+                .iter()
+                .map(|input| recognise(&LINES, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, false, false, false, false]
+        }
+
+        testcase! {
+            mixed_endings,
+            parse(&LINES, "ab\r\nc\n").unwrap().collect::<Vec<_>>(),
+            vec![Node::Internal {
+                name: String::from("Lines"),
+                children: vec![
+                    line("ab", "\r\n"),
+                    Node::Internal { name: String::from("Lines"), children: vec![line("c", "\n")] }
+                ]
+            }]
+        }
+
+        testcase! {
+            ending_consumes_both_characters,
+            parse_forest(&LINES, "a\r\n").unwrap().tree_at(0),
+            Some(Node::Internal { name: String::from("Lines"), children: vec![line("a", "\r\n")] })
+        }
+
+        testcase! {
+            error_position,
+            parse(&LINES, "ab\r\nc!\n").map(Iterator::count).map_err(|error| error.remaining),
+            Err(String::from("!\n"))
+        }
+
+        testcase! {
+            from_bnf,
+            Grammar::from_bnf("Line -> \"a\" $; ").unwrap(),
+            grammar! {
+                Line -> "a" $;
+            }
+        }
+
+        // A can match the \r so $ scans \n, or be empty so $ scans \r\n. The
+        // item after $ used to be added to the same state set by both scans,
+        // doubling every tree
+        testcase! {
+            ending_scanned_from_two_positions,
+            {
+                let grammar = grammar! {
+                    S -> A $ "x";
+                    A -> "\r";
+                    A -> ;
+                };
+                let trees = parse(&grammar, "\r\nx").unwrap();
+                let hint = trees.size_hint();
+                let trees = trees.collect::<Vec<_>>();
+                let distinct = trees[0] != trees[1];
+                (hint, trees.len(), distinct, parse_unambiguous(&grammar, "\r\nx").is_err())
+            },
+            ((2, Some(2)), 2, true, true)
+        }
+
+        testcase! {
+            single_char_fast_path,
+            (
+                recognise(&grammar! { Break -> $; }, "\n"),
+                recognise(&grammar! { Break -> $; }, "\r"),
+                recognise(&grammar! { Break -> $; }, "\r\n")
+            ),
+            (true, false, true)
+        }
+    }
+
+    tests! {
+        char_range:
+
+        testdata! {
+            IDENTIFIER: Grammar = grammar! {
+                Identifier -> Letter Rest;
+                Rest -> Letter Rest;
+                Rest -> ['0'-'9'] Rest;
+                Rest -> ;
+                Letter -> ['a'-'z'];
+                Letter -> ['A'-'Z'];
+                Letter -> "_";
+            };
+        }
+
+        testcase! {
+            recognised,
+            ["x", "Foo_bar9", "_1a", "9lives", "a-b", "", "\u{e9}"]
+                .iter()
+                .map(|input| recognise(&IDENTIFIER, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, false, false, false, false]
+        }
+
+        testcase! {
+            leaf,
+            parse(&grammar! { Digit -> ['0'-'9']; }, "7").unwrap().collect::<Vec<_>>(),
+            vec![Node::Internal { name: String::from("Digit"), children: vec![Node::Leaf('7')] }]
+        }
+
+        testcase! {
+            error_expects_range,
+            parse(&IDENTIFIER, "9").map(Iterator::count).map_err(|error| error.expected),
+            Err(vec![
+                String::from("Letter"),
+                String::from("[a-z]"),
+                String::from("[A-Z]"),
+                String::from("'_'")
+            ])
+        }
+    }
+
+    tests! {
+        any_char:
+
+        testdata! {
+            STRING: Grammar = grammar! {
+                String -> "\"" Contents "\"";
+                Contents -> Char Contents;
+                Contents -> ;
+                Char -> ["abc "];
+                Char -> "\\" .;
+            };
+        }
+
+        testcase! {
+            recognised,
+            ["\"a b\"", "\"\\\"\"", "\"\\\\\"", "\"\\\n\"", "\"\\\"", "\"d\""]
+                .iter()
+                .map(|input| recognise(&STRING, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, true, false, false]
+        }
+
+        testcase! {
+            leaf,
+            parse(&grammar! { Any -> .; }, "\u{e9}").unwrap().collect::<Vec<_>>(),
+            vec![Node::Internal { name: String::from("Any"), children: vec![Node::Leaf('\u{e9}')] }]
+        }
+
+        testcase! {
+            one_character,
+            (recognise(&grammar! { Any -> .; }, ""), recognise(&grammar! { Any -> .; }, "ab")),
+            (false, false)
+        }
+    }
+
+    tests! {
+        not:
+
+        testdata! {
+            STRING: Grammar = grammar! {
+                String -> "\"" Contents "\"";
+                Contents -> Char Contents;
+                Contents -> ;
+                Char -> [^ "\"\\"];
+                Char -> "\\" .;
+            };
+        }
+
+        testcase! {
+            recognised,
+            ["\"\"", "\"a b\"", "\"\\\"\"", "\"\u{e9}\\\\\"", "\"a\"b\"", "\"\\\""]
+                .iter()
+                .map(|input| recognise(&STRING, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, true, false, false]
+        }
+
+        testcase! {
+            first_chars,
+            grammar! { Other -> [^ 'a'-'z']; }.first_chars("Other").contains(&'a'),
+            false
+        }
+    }
+
+    tests! {
+        furthest_failure:
+
+        testdata! {
+            NULLABLE: Grammar = grammar! {
+                S -> Opt Opt "a" Opt "b" "c";
+                S -> Opt "a" Opt Opt "d";
+                Opt -> ;
+                Opt -> "x";
+            };
+        }
+
+        testcase! {
+            nullable_rules_between_terminals,
+            parse(&NULLABLE, "axbq").map(Iterator::count).map_err(|error| error.remaining),
+            Err(String::from("q"))
+        }
+
+        testcase! {
+            shorter_alternative_fails_first,
+            // The second alternative gives up at 'b' but the first gets as
+            // far as 'q'
+            parse(&NULLABLE, "xabq").map(Iterator::count).map_err(|error| error.remaining),
+            Err(String::from("q"))
+        }
+
+        testcase! {
+            only_nullable_rules_left,
+            // Every Opt before "a" is skipped, then nothing can match 'q'
+            parse(&NULLABLE, "q").map(Iterator::count).map_err(|error| error.remaining),
+            Err(String::from("q"))
+        }
+
+        testcase! {
+            location,
+            error_location("xa\nbq", "q"),
+            CharacterPosition { row: 1, col: 1 }
+        }
+
+        testcase! {
+            after_multi_character_terminal,
+            parse(&grammar! { Line -> "a" $ "b"; }, "a\r\nc")
+                .map(Iterator::count)
+                .map_err(|error| error.remaining),
+            Err(String::from("c"))
+        }
+
+        testcase! {
+            inside_multi_character_terminal,
+            parse(&grammar! { Line -> "a" $ "b"; }, "a\rb")
+                .map(Iterator::count)
+                .map_err(|error| error.remaining),
+            Err(String::from("\rb"))
+        }
+    }
+
+    tests! {
+        groups:
+
+        testdata! {
+            FACTORED: Grammar = grammar! {
+                Statement -> Name ("=" | "+=" | "-=") Value;
+                Value -> Name;
+                Value -> ["0123456789"];
+                Name -> ["abc"] Name;
+                Name -> ["abc"];
+            };
+            EXPANDED: Grammar = grammar! {
+                Statement -> Name "=" Value;
+                Statement -> Name "+=" Value;
+                Statement -> Name "-=" Value;
+                Value -> Name;
+                Value -> ["0123456789"];
+                Name -> ["abc"] Name;
+                Name -> ["abc"];
+            };
+            NESTED: Grammar = grammar! {
+                Number -> ("-" | ) Digits ("." Digits ("e" Digits | ) | );
+                Digits -> ["0123456789"] Digits;
+                Digits -> ["0123456789"];
+            };
+            INPUTS: Vec<&'static str> = vec![
+                "a=1", "ab+=c", "c-=ba", "a==1", "a*=1", "=1", "a=", "a+=-1", "",
+            ];
+        }
+
+        fn rule_count(grammar: &Grammar, name: &str) -> usize {
+            grammar.get_rules_by_name(name).count()
+        }
+
+        testcase! {
+            recognition_matches_expansion,
+            INPUTS.iter().map(|input| recognise(&FACTORED, input)).collect::<Vec<_>>(),
+            INPUTS.iter().map(|input| recognise(&EXPANDED, input)).collect::<Vec<_>>()
+        }
+
+        testcase! {
+            one_helper_rule_per_alternative,
+            (
+                rule_count(&FACTORED, "Statement"),
+                rule_count(&FACTORED, "(\"=\" | \"+=\" | \"-=\")")
+            ),
+            (1, 3)
+        }
+
+        testcase! {
+            tree_shape,
+            parse(&FACTORED, "a+=1").unwrap().collect::<Vec<_>>(),
+            vec![Node::Internal {
+                name: String::from("Statement"),
+                children: vec![
+                    Node::Internal {
+                        name: String::from("Name"),
+                        children: vec![Node::Leaf('a')]
+                    },
+                    Node::Internal {
+                        name: String::from("(\"=\" | \"+=\" | \"-=\")"),
+                        children: vec![Node::Leaf('+'), Node::Leaf('=')]
+                    },
+                    Node::Internal {
+                        name: String::from("Value"),
+                        children: vec![Node::Leaf('1')]
+                    },
+                ]
+            }]
+        }
+
+        testcase! {
+            nested_and_optional,
+            ["1", "-1", "1.5", "-12.5e3", "1e3", "1.", "--1", "1.5e"]
+                .iter()
+                .map(|input| recognise(&NESTED, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, true, false, false, false, false]
+        }
+
+        testcase! {
+            shared_between_rules,
+            rule_count(
+                &grammar! {
+                    A -> "a" ("x" | "y");
+                    B -> "b" ("x" | "y");
+                },
+                "(\"x\" | \"y\")"
+            ),
+            2
+        }
+
+        testcase! {
+            without_groups_unchanged,
+            grammar! {
+                A -> B "c";
+                B -> ;
+            },
+            Grammar::new(vec![
+                rule!(A -> B "c"),
+                rule!(B -> ),
+            ])
+        }
+    }
+
+    tests! {
+        short_input:
+
+        testdata! {
+            CLASSIFIER: Grammar = grammar! {
+                Char -> Padding Class Padding;
+                Char -> Wrapped;
+                Padding -> ;
+                Padding -> Padding Nothing;
+                Nothing -> ;
+                Class -> Digit;
+                Class -> Letter;
+                Class -> "_";
+                Digit -> ["0123456789"];
+                Letter -> Upper;
+                Letter -> ["abcxyz"];
+                Upper -> Letter "'";
+                Wrapped -> Char;
+                Wrapped -> "!";
+            };
+            NULLABLE: Grammar = grammar! {
+                S -> A B;
+                A -> ;
+                A -> "a";
+                B -> A;
+            };
+            NOT_NULLABLE: Grammar = grammar! {
+                S -> A B;
+                A -> ;
+                B -> "b";
+            };
+        }
+
+        /// The inputs where the fast path disagrees with the parser
+        fn mismatches(grammar: &Grammar) -> Vec<String> {
+            std::iter::once(String::new())
+                .chain((' '..='~').map(String::from))
+                .filter(|input| recognise(grammar, input) != ParseState::build(grammar, input).accepts())
+                .collect()
+        }
+
+        testcase! {
+            matches_parser,
+            [&*CLASSIFIER, &*NULLABLE, &*NOT_NULLABLE, &*ARITH]
+                .iter()
+                .copied()
+                .flat_map(mismatches)
+                .collect::<Vec<_>>(),
+            Vec::<String>::new()
+        }
+
+        testcase! {
+            rebuilt_grammars,
+            [
+                CLASSIFIER.clone().with_prefix("x_"),
+                CLASSIFIER.clone().with_start_symbol("Class"),
+                CLASSIFIER.clone().minimize(),
+                CLASSIFIER.clone().merge_char_alternatives(),
+                NULLABLE.clone().with_layout("A", &[]),
+            ]
+            .iter()
+            .flat_map(mismatches)
+            .collect::<Vec<_>>(),
+            Vec::<String>::new()
+        }
+
+        testcase! {
+            single_chars,
+            ["1", "a", "_", "!", "A", "'", ""]
+                .iter()
+                .map(|input| recognise(&CLASSIFIER, input))
+                .collect::<Vec<_>>(),
+            vec![true, true, true, true, false, false, false]
+        }
+
+        testcase! {
+            empty,
+            (recognise(&NULLABLE, ""), recognise(&NOT_NULLABLE, "")),
+            (true, false)
+        }
+
+        testcase! {
+            predicates_respected,
+            recognise(
+                &grammar! {
+                    S -> A;
+                    A -> ["ab"];
+                }
+                .with_predicate("A", |matched| matched != ['a']),
+                "a"
+            ),
+            false
+        }
+    }
+
+    tests! {
+        forest:
+
+        testdata! {
+            AMBIGUOUS: Grammar = grammar! {
+                E -> E ["+*"] E;
+                E -> A;
+                E -> B;
+                A -> ["0123456789"];
+                B -> ["0123456789"];
+            };
+        }
+
+        fn indexed(input: &str) -> Vec<Node> {
+            let forest = parse_forest(&AMBIGUOUS, input).unwrap();
+            (0..forest.tree_count()).map(|index| forest.tree_at(index).unwrap()).collect()
+        }
+
+        testcase! {
+            tree_count,
+            parse_forest(&AMBIGUOUS, "1+2*3").unwrap().tree_count(),
+            parse(&AMBIGUOUS, "1+2*3").unwrap().count()
+        }
+
+        testcase! {
+            matches_sequential,
+            ["1", "1+2", "1+2*3", "1+2*3+4"]
+                .iter()
+                .filter(|input| indexed(input) != parse(&AMBIGUOUS, input).unwrap().collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            Vec::<&&str>::new()
+        }
+
+        testcase! {
+            single_index,
+            parse_forest(&AMBIGUOUS, "1+2*3+4").unwrap().tree_at(37),
+            parse(&AMBIGUOUS, "1+2*3+4").unwrap().nth(37)
+        }
+
+        testcase! {
+            out_of_range,
+            {
+                let forest = parse_forest(&AMBIGUOUS, "1+2").unwrap();
+                forest.tree_at(forest.tree_count())
+            },
+            None
+        }
+
+        testcase! {
+            failed_parse,
+            parse_forest(&AMBIGUOUS, "1+a")
+                .map(|forest| forest.tree_count())
+                .map_err(|error| error.remaining),
+            Err(String::from("a"))
+        }
+    }
+
+    tests! {
+        size_hint:
+
+        fn hints_match(grammar: &Grammar, input: &str) -> bool {
+            let total = parse(grammar, input).unwrap().count();
+            let mut trees = parse(grammar, input).unwrap();
+            (0..=total).all(|produced| {
+                let remaining = total - produced;
+                let matches = trees.size_hint() == (remaining, Some(remaining));
+                let _ = trees.next();
+                matches
+            })
+        }
+
+        testcase! {
+            exact,
+            parse(&AMBIGUOUS, "aaaaa").unwrap().size_hint(),
+            (14, Some(14))
+        }
+
+        testcase! {
+            matches_count,
+            ["a", "aa", "aaa", "aaaa", "aaaaa"]
+                .iter()
+                .all(|input| hints_match(&AMBIGUOUS, input)),
+            true
+        }
+
+        testcase! {
+            binop,
+            hints_match(&BINOP, "1-2-3-4"),
+            true
+        }
+
+        testcase! {
+            exhausted,
+            {
+                let mut trees = parse(&AMBIGUOUS, "aaa").unwrap();
+                trees.by_ref().for_each(drop);
+                (trees.size_hint(), trees.next(), trees.next())
+            },
+            ((0, Some(0)), None, None)
+        }
+
+        testcase! {
+            cyclic,
+            parse(&LOOP, "").unwrap().size_hint(),
+            (0, None)
+        }
+
+        // Counting every way to split 40 characters takes too long
+        testcase! {
+            large_parse,
+            parse(&AMBIGUOUS, "a".repeat(40)).unwrap().size_hint(),
+            (0, None)
+        }
+    }
+
+    tests! {
+        prefix_parse:
+
+        testdata! {
+            WORDS: Grammar = grammar! {
+                Words -> Words " " Word;
+                Words -> Word;
+                Word -> ["abc"] Word;
+                Word -> ["abc"];
+            };
+        }
+
+        fn coverage(input: &str) -> Option<(usize, usize, bool, usize)> {
+            parse_prefix(&WORDS, input).map(|prefix| {
+                (prefix.covered, prefix.input_len, prefix.is_complete(), prefix.trees.count())
+            })
+        }
+
+        testcase! {
+            complete,
+            coverage("ab c"),
+            Some((4, 4, true, 1))
+        }
+
+        testcase! {
+            partial,
+            coverage("ab c!d"),
+            Some((4, 6, false, 1))
+        }
+
+        testcase! {
+            trailing_separator,
+            coverage("ab "),
+            Some((2, 3, false, 1))
+        }
+
+        testcase! {
+            no_prefix,
+            coverage("!ab"),
+            None
+        }
+
+        testcase! {
+            trees_match_full_parse,
+            parse_prefix(&WORDS, "ab c!d").unwrap().trees.collect::<Vec<_>>(),
+            parse(&WORDS, "ab c").unwrap().collect::<Vec<_>>()
+        }
+    }
+
+    tests! {
+        rule_order:
+
+        // S -> Y completes before S -> X as it doesn't have to wait for Z,
+        // the first tree should still follow declaration order
+        testdata! {
+            INDIRECT: Grammar = grammar! {
+                S -> X;
+                S -> Y;
+                X -> Z;
+                Z -> "a";
+                Y -> "a";
+            };
+            SWAPPED: Grammar = grammar! {
+                S -> Y;
+                S -> X;
+                X -> Z;
+                Z -> "a";
+                Y -> "a";
+            };
+        }
+
+        fn unary(name: &str, child: Node) -> Node {
+            Node::Internal { name: String::from(name), children: vec![child] }
+        }
+
+        testcase! {
+            first_declared_first,
+            parse(&INDIRECT, "a").unwrap().collect::<Vec<_>>(),
+            vec![
+                unary("S", unary("X", unary("Z", Node::Leaf('a')))),
+                unary("S", unary("Y", Node::Leaf('a'))),
+            ]
+        }
+
+        testcase! {
+            swapped_declarations,
+            parse(&SWAPPED, "a").unwrap().collect::<Vec<_>>(),
+            vec![
+                unary("S", unary("Y", Node::Leaf('a'))),
+                unary("S", unary("X", unary("Z", Node::Leaf('a')))),
+            ]
+        }
+
+        testcase! {
+            nested_alternatives,
+            parse(
+                &grammar! {
+                    S -> A;
+                    A -> B;
+                    A -> "a";
+                    B -> "a";
+                },
+                "a"
+            ).unwrap().next(),
+            Some(unary("S", unary("A", unary("B", Node::Leaf('a')))))
+        }
+    }
+
+    tests! {
+        precedence:
+
+        testdata! {
+            DECLARED: Grammar = grammar! {
+                E -> E "+" E;
+                E -> E "*" E;
+                E -> ["0123456789"];
+            };
+            PRECEDENCE: Grammar = grammar! {
+                E -> E "+" E;
+                [1] E -> E "*" E;
+                E -> ["0123456789"];
+            };
+            NEGATIVE: Grammar = grammar! {
+                [-1] E -> E "+" E;
+                E -> E "*" E;
+                E -> ["0123456789"];
+            };
+        }
+
+        /// The operator at the root of each tree in order
+        fn roots(grammar: &Grammar, input: &str) -> Vec<Node> {
+            parse(grammar, input)
+                .unwrap()
+                .map(|tree| match &tree {
+                    Node::Internal { children, .. } => children[1].clone(),
+                    _ => tree,
+                })
+                .collect()
+        }
+
+        testcase! {
+            declaration_order,
+            roots(&DECLARED, "1+2*3"),
+            vec![Node::Leaf('+'), Node::Leaf('*')]
+        }
+
+        testcase! {
+            higher_first,
+            roots(&PRECEDENCE, "1+2*3"),
+            vec![Node::Leaf('*'), Node::Leaf('+')]
+        }
+
+        testcase! {
+            lower_last,
+            roots(&NEGATIVE, "1+2*3"),
+            vec![Node::Leaf('*'), Node::Leaf('+')]
+        }
+
+        testcase! {
+            same_trees,
+            {
+                let mut trees = parse(&PRECEDENCE, "1+2*3+4").unwrap().collect::<Vec<_>>();
+                trees.sort_by_key(|tree| format!("{:?}", tree));
+                trees
+            },
+            {
+                let mut trees = parse(&DECLARED, "1+2*3+4").unwrap().collect::<Vec<_>>();
+                trees.sort_by_key(|tree| format!("{:?}", tree));
+                trees
+            }
+        }
+
+        testcase! {
+            forest_agrees,
+            {
+                let forest = parse_forest(&PRECEDENCE, "1+2*3+4").unwrap();
+                (0..forest.tree_count())
+                    .map(|index| forest.tree_at(index).unwrap())
+                    .collect::<Vec<_>>()
+            },
+            parse(&PRECEDENCE, "1+2*3+4").unwrap().collect::<Vec<_>>()
+        }
+
+        testcase! {
+            survives_rename,
+            {
+                let mut grammar = PRECEDENCE.clone();
+                grammar.rename_rule("E", "Expr");
+                roots(&grammar, "1+2*3")
+            },
+            vec![Node::Leaf('*'), Node::Leaf('+')]
+        }
+    }
+
+    tests! {
+        parser:
+
+        fn force(
+            result: Result<impl Iterator<Item=Node>, ParseError>
+        ) -> Result<Vec<Node>, String> {
+            let nodes = result.map_err(|error| error.remaining)?;
+            Ok(nodes.collect::<Vec<_>>())
+        }
+
+        testcase! {
+            no_duplicate_trees,
+            parse(&ARITH, "1+3*4-6").map(Iterator::count),
+            Ok(1)
+        }
+
+        testcase! {
+            arith_success,
+            force(parse(&ARITH, "1+2")),
+            Ok(vec![
+                Node::Internal {
+                    name: String::from("Sum"),
+                    children: vec![
+                        Node::Internal {
+                            name: String::from("Sum"),
+                            children: vec![
+                                Node::Internal {
+                                    name: String::from("Product"),
+                                    children: vec![
+                                        Node::Internal {
+                                            name: String::from("Factor"),
+                                            children: vec![
+                                                Node::Internal {
+                                                    name: String::from("Number"),
+                                                    children: vec![
+                                                        Node::Leaf('1')
+                                                    ]
+                                                }
+                                            ]
+                                        }
+                                    ]
+                                }
+                            ]
+                        },
+                        Node::Leaf('+'),
+                        Node::Internal {
+                            name: String::from("Product"),
                             children: vec![
                                 Node::Internal {
                                     name: String::from("Factor"),
@@ -434,4 +3499,464 @@ syntax_abuse::tests! {
             ])
         }
     }
+
+    tests! {
+        repeat:
+
+        use crate::grammar::{Rule, Symbol};
+
+        /// `List` is `Item` repeated between `min` and `max` times separated
+        /// by commas
+        fn list(min: usize, max: Option<usize>) -> Grammar {
+            let item = Symbol::Rule(String::from("Item"));
+            Grammar::new(vec![
+                Rule::new(
+                    String::from("List"),
+                    vec![Symbol::repeat(item, Some(Symbol::Literal(',')), min, max)],
+                ),
+                Rule::new(String::from("Item"), symbol!(["abc"])),
+            ])
+        }
+
+        /// `List` is `Item` repeated at least `min` times with `Item`
+        /// matching one or two `a`s or nothing
+        fn ambiguous(min: usize) -> Grammar {
+            let item = Symbol::Rule(String::from("Item"));
+            Grammar::new(vec![
+                Rule::new(String::from("List"), vec![Symbol::repeat(item, None, min, None)]),
+                Rule::new(String::from("Item"), symbol!("a")),
+                Rule::new(String::from("Item"), symbol!("aa")),
+                Rule::new(String::from("Item"), Vec::new()),
+            ])
+        }
+
+        fn recognised(grammar: &Grammar, inputs: &[&str]) -> Vec<bool> {
+            inputs.iter().map(|input| recognise(grammar, input)).collect()
+        }
+
+        /// Every tree in order, checking the forest and the count agree
+        fn trees(grammar: &Grammar, input: &str) -> Vec<Node> {
+            let trees = parse(grammar, input).unwrap().collect::<Vec<_>>();
+            let forest = parse_forest(grammar, input).unwrap();
+            assert_eq!(forest.tree_count(), trees.len());
+            for (i, tree) in trees.iter().enumerate() {
+                assert_eq!(forest.tree_at(i).as_ref(), Some(tree));
+            }
+            trees
+        }
+
+        testcase! {
+            bounded,
+            recognised(&list(1, Some(3)), &["", "a", "a,b", "a,b,c", "a,b,c,a"]),
+            vec![false, true, true, true, false]
+        }
+
+        testcase! {
+            separators,
+            recognised(&list(1, Some(3)), &["a,", ",a", "ab", "a,,b"]),
+            vec![false, false, false, false]
+        }
+
+        testcase! {
+            unbounded,
+            recognised(&list(0, None), &["", "a", "a,b,c,a,b,c,a,b,c"]),
+            vec![true, true, true]
+        }
+
+        testcase! {
+            minimum,
+            recognised(&list(3, None), &["a,b", "a,b,c", "a,b,c,a"]),
+            vec![false, true, true]
+        }
+
+        testcase! {
+            exact,
+            recognised(&list(2, Some(2)), &["a", "a,b", "a,b,c"]),
+            vec![false, true, false]
+        }
+
+        testcase! {
+            tree,
+            parse(&list(1, None), "a,b").unwrap().collect::<Vec<_>>(),
+            vec![Node::Internal {
+                name: String::from("List"),
+                children: vec![
+                    Node::Internal {
+                        name: String::from("Item"),
+                        children: vec![Node::Leaf('a')],
+                    },
+                    Node::Leaf(','),
+                    Node::Internal {
+                        name: String::from("Item"),
+                        children: vec![Node::Leaf('b')],
+                    },
+                ],
+            }]
+        }
+
+        testcase! {
+            empty_tree,
+            trees(&list(0, Some(2)), ""),
+            vec![Node::Internal {
+                name: String::from("List"),
+                children: Vec::new(),
+            }]
+        }
+
+        // `aaa` split into one and two character items, each with or without
+        // an empty first repetition. Repetitions past the minimum can't be
+        // empty, there would be no end of trees that can't be told apart
+        testcase! {
+            splits,
+            trees(&ambiguous(1), "aaa").len(),
+            6
+        }
+
+        // The two required repetitions split `aa` between them three ways,
+        // then there are two ways for later repetitions to match it if they
+        // are both empty and one if only one of them is
+        testcase! {
+            required_repetitions,
+            trees(&ambiguous(2), "aa").len(),
+            7
+        }
+
+        testcase! {
+            long,
+            parse(&list(0, None), "a,b,c,".repeat(100).trim_end_matches(','))
+                .unwrap()
+                .count(),
+            1
+        }
+    }
+
+    tests! {
+        parse_unambiguous:
+
+        testcase! {
+            unambiguous,
+            parse_unambiguous(&BINOP, "1-2"),
+            Ok(parse(&BINOP, "1-2").unwrap().next().unwrap())
+        }
+
+        testcase! {
+            ambiguous,
+            {
+                let mut trees = parse(&BINOP, "1-2-3").unwrap();
+                Err(ParseOutcome::Ambiguous {
+                    count: Some(2),
+                    first: trees.next().unwrap(),
+                    second: trees.next().unwrap(),
+                })
+            },
+            parse_unambiguous(&BINOP, "1-2-3")
+        }
+
+        testcase! {
+            cyclic,
+            match parse_unambiguous(&LOOP, "") {
+                Err(ParseOutcome::Ambiguous { count, .. }) => count,
+                _ => Some(0),
+            },
+            None
+        }
+
+        testcase! {
+            too_many_to_count,
+            match parse_unambiguous(&BINOP, ["1"; 40].join("-")) {
+                Err(ParseOutcome::Ambiguous { count, .. }) => count,
+                _ => Some(0),
+            },
+            None
+        }
+
+        testcase! {
+            no_parse,
+            parse_unambiguous(&BINOP, "1-+2"),
+            Err(ParseOutcome::NoParse(ParseError {
+                position: 2,
+                remaining: String::from("+2"),
+                expected: vec![String::from("E"), String::from("[0123456789]")],
+            }))
+        }
+
+        testcase! {
+            incomplete,
+            parse_unambiguous(&BINOP, "1-"),
+            Err(ParseOutcome::NoParse(ParseError {
+                position: 2,
+                remaining: String::new(),
+                expected: vec![String::from("E"), String::from("[0123456789]")],
+            }))
+        }
+    }
+
+    tests! {
+        range:
+
+        fn trees(result: Result<impl Iterator<Item=Node>, ParseError>) -> Option<Vec<Node>> {
+            Some(result.ok()?.collect())
+        }
+
+        testcase! {
+            recognised,
+            ["x=1+2;", "x=1+%;", "ab12+3", "x=1+2"]
+                .iter()
+                .map(|input| recognise_range(&ARITH, input, 2..5))
+                .collect::<Vec<_>>(),
+            vec![true, false, false, true]
+        }
+
+        testcase! {
+            whole_input,
+            recognise_range(&ARITH, "x=1+2;", 0..6),
+            false
+        }
+
+        testcase! {
+            empty_range,
+            (recognise_range(&ARITH, "x=1+2;", 3..3), recognise_range(&EMPTY, "x=1+2;", 3..3)),
+            (false, true)
+        }
+
+        testcase! {
+            same_trees,
+            trees(parse_range(&ARITH, "x=1+2;", 2..5)),
+            trees(parse(&ARITH, "1+2"))
+        }
+
+        testcase! {
+            characters_not_bytes,
+            trees(parse_range(&ARITH, "\u{e9}=1+2;", 2..5)),
+            trees(parse(&ARITH, "1+2"))
+        }
+
+        testcase! {
+            error_position,
+            parse_range(&ARITH, "x=1+%2;", 2..6).err(),
+            Some(ParseError {
+                position: 4,
+                remaining: String::from("%2"),
+                expected: ["Product", "Factor", "'('", "Number", "[0123456789]"]
+                    .iter()
+                    .map(|&symbol| String::from(symbol))
+                    .collect(),
+            })
+        }
+
+        #[test]
+        #[should_panic(expected = "range end index 5 out of range for slice of length 3")]
+        fn out_of_bounds() {
+            let _ = recognise_range(&ARITH, "1+2", 2..5);
+        }
+
+        #[test]
+        #[should_panic(expected = "out of range for slice of length 3")]
+        fn parse_out_of_bounds() {
+            let _ = parse_range(&ARITH, "1+2", 2..5);
+        }
+    }
+
+    tests! {
+        empty_input:
+
+        /// The node for a nullable rule matching nothing
+        fn empty(name: &str) -> Node {
+            Node::Internal { name: String::from(name), children: vec![] }
+        }
+
+        /// The error for an empty input to [`ARITH`]
+        fn arith_error() -> ParseError {
+            ParseError {
+                position: 0,
+                remaining: String::new(),
+                expected: ["Sum", "Product", "Factor", "'('", "Number", "[0123456789]"]
+                    .iter()
+                    .map(|&symbol| String::from(symbol))
+                    .collect(),
+            }
+        }
+
+        testcase! {
+            recognised,
+            (recognise(&EMPTY, ""), recognise(&ALMOST_EMPTY, ""), recognise(&ARITH, "")),
+            (true, false, false)
+        }
+
+        testcase! {
+            one_tree,
+            parse(&EMPTY, "").map(Iterator::collect::<Vec<_>>),
+            Ok(vec![empty("Empty")])
+        }
+
+        testcase! {
+            not_nullable,
+            parse(&ARITH, "").map(Iterator::count),
+            Err(arith_error())
+        }
+
+        testcase! {
+            almost_empty,
+            parse(&ALMOST_EMPTY, "").map(Iterator::count),
+            Err(ParseError {
+                position: 0,
+                remaining: String::new(),
+                expected: vec![String::from("'R'")]
+            })
+        }
+
+        testcase! {
+            size_hint,
+            parse(&EMPTY, "").unwrap().size_hint(),
+            (1, Some(1))
+        }
+
+        testcase! {
+            unambiguous,
+            (parse_unambiguous(&EMPTY, ""), parse_unambiguous(&ARITH, "")),
+            (Ok(empty("Empty")), Err(ParseOutcome::NoParse(arith_error())))
+        }
+
+        testcase! {
+            empty_range,
+            (
+                parse_range(&EMPTY, "abc", 1..1).map(Iterator::collect::<Vec<_>>),
+                parse_range(&ARITH, "abc", 1..1)
+                    .map(Iterator::count)
+                    .map_err(|error| error.position)
+            ),
+            (Ok(vec![empty("Empty")]), Err(1))
+        }
+
+        testcase! {
+            forest,
+            (
+                parse_forest(&EMPTY, "").map(|forest| forest.tree_at(0)),
+                parse_forest(&ARITH, "").map(|forest| forest.tree_at(0))
+            ),
+            (Ok(Some(empty("Empty"))), Err(arith_error()))
+        }
+
+        testcase! {
+            depth_limit,
+            (
+                parse_with_depth_limit(&EMPTY, "", 1).map(Iterator::collect::<Vec<_>>),
+                parse_with_depth_limit(&ARITH, "", 1).map(Iterator::count)
+            ),
+            (Ok(vec![Ok(empty("Empty"))]), Err(arith_error()))
+        }
+
+        testcase! {
+            counts,
+            (
+                derivation_counts(&EMPTY, "").map(|counts| counts.len()),
+                derivation_counts(&ARITH, "")
+            ),
+            (Ok(1), Err(arith_error()))
+        }
+
+        testcase! {
+            prefix,
+            (
+                parse_prefix(&EMPTY, "").map(|prefix| (prefix.covered, prefix.trees.count())),
+                parse_prefix(&ARITH, "").map(|prefix| prefix.covered)
+            ),
+            (Some((0, 1)), None)
+        }
+
+        testcase! {
+            recovery,
+            (syntax_errors(&EMPTY, "", &[';']), syntax_errors(&ARITH, "", &[';'])),
+            (vec![], vec![(0, 0)])
+        }
+
+        testcase! {
+            tokens,
+            (
+                tokenizer::tokenize("", tokenizer::literal("a", "a"))
+                    .map_err(|error| error.rest().len()),
+                tokenizer::tokenize("", tokenizer::literal("empty", "")),
+                tokenizer::tokenize_partial("", tokenizer::literal("a", "a"))
+            ),
+            (Err(0), Ok(vec![]), (vec![], 0))
+        }
+    }
+
+    tests! {
+        trace:
+
+        testdata! {
+            PAIR: Grammar = grammar! {
+                S -> A "b";
+                A -> "a";
+            };
+        }
+
+        /// Every event traced while parsing `input`
+        fn events(grammar: &Grammar, input: &str) -> Vec<TraceEvent> {
+            let mut events = Vec::new();
+            let _ = ParseState::build_traced(grammar, input, |event| events.push(event));
+            events
+        }
+
+        /// The number of (predictions, scans, completions) traced while parsing
+        /// `input`
+        fn totals(grammar: &Grammar, input: &str) -> (usize, usize, usize) {
+            events(grammar, input).iter().fold((0, 0, 0), |(predict, scan, complete), event| {
+                match event {
+                    TraceEvent::Predict { .. } => (predict + 1, scan, complete),
+                    TraceEvent::Scan { .. } => (predict, scan + 1, complete),
+                    TraceEvent::Complete { .. } => (predict, scan, complete + 1),
+                }
+            })
+        }
+
+        testcase! {
+            steps,
+            events(&PAIR, "ab"),
+            vec![
+                TraceEvent::Predict {
+                    position: 0,
+                    item: String::from("S -> \u{25CF} A 'b' (0)"),
+                    rule: String::from("A"),
+                },
+                TraceEvent::Scan {
+                    position: 0,
+                    item: String::from("A -> \u{25CF} 'a' (0)"),
+                    width: 1,
+                },
+                TraceEvent::Complete {
+                    position: 1,
+                    item: String::from("A -> 'a' \u{25CF} (0)"),
+                },
+                TraceEvent::Scan {
+                    position: 1,
+                    item: String::from("S -> A \u{25CF} 'b' (0)"),
+                    width: 1,
+                },
+                TraceEvent::Complete {
+                    position: 2,
+                    item: String::from("S -> A 'b' \u{25CF} (0)"),
+                },
+            ]
+        }
+
+        testcase! {
+            failed_scans_not_traced,
+            totals(&PAIR, "ac"),
+            (1, 1, 1)
+        }
+
+        testcase! {
+            arith_totals,
+            totals(&ARITH, "1+2"),
+            (11, 5, 8)
+        }
+
+        testcase! {
+            same_trees,
+            parse_traced(&AMBIGUOUS, "aaa", |_| ()).map(Iterator::collect::<Vec<_>>),
+            parse(&AMBIGUOUS, "aaa").map(Iterator::collect::<Vec<_>>)
+        }
+    }
 }
@@ -21,12 +21,45 @@ macro_rules! symbol {
             $crate::NonEmptyHashSet::new($str.chars().collect::<::std::collections::HashSet<_>>()),
         )]
     };
+    // A ^ at the start of [] negates the class, as in a regex
+    ([ ^ $start:literal - $end:literal ]) => {
+        ::std::vec![$crate::grammar::Symbol::negate(
+            $crate::grammar::Symbol::Range($start, $end)
+        )]
+    };
+    ([ ^ $str:literal ]) => {
+        ::std::vec![$crate::grammar::Symbol::negate(
+            $crate::grammar::Symbol::OneOf($crate::NonEmptyHashSet::new(
+                $str.chars().collect::<::std::collections::HashSet<_>>()
+            ),)
+        )]
+    };
+    // Two character literals separated by - inside [] match the range of
+    // characters between them, again like a regex character class
+    ([ $start:literal - $end:literal ]) => {
+        ::std::vec![$crate::grammar::Symbol::Range($start, $end)]
+    };
     // A string literal without [] is a sequence of Literal matchers (one for
-    // each character in the string)
+    // each character in the string, grapheme clusters of several characters
+    // become a single Grapheme matcher)
     ($str:literal) => {
-        $str.chars()
-            .map($crate::grammar::Symbol::Literal)
-            .collect::<::std::vec::Vec<_>>()
+        $crate::grammar::Symbol::literals($str)
+    };
+    // A bareword surrounded by [] refers to a character set defined by a
+    // charset directive in grammar! which expands to a variable of the same
+    // name
+    ([ $charset:ident ]) => {
+        ::std::vec![$crate::grammar::Symbol::OneOf(::std::clone::Clone::clone(
+            &$charset
+        ))]
+    };
+    // $ matches a line terminator as in a regex
+    ($) => {
+        ::std::vec![$crate::grammar::Symbol::Newline]
+    };
+    // . matches any character, again as in a regex
+    (.) => {
+        ::std::vec![$crate::grammar::Symbol::AnyChar]
     };
 }
 
@@ -65,27 +98,126 @@ macro_rules! rule {
     }
 }
 
+/// Parses a single rule (without the trailing ;) which may contain `( | )`
+/// groups on behalf of grammar! { }. The result is the rule along with a Vec
+/// of the helper rules generated for its groups.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! group_rule {
+    // A precedence before the rule name is set on the rule, helpers for its
+    // groups keep the default
+    ([ $precedence:literal ] $name:ident -> $($symbols:tt)*) => {{
+        let (rule, helpers) = $crate::group_rule!($name -> $($symbols)*);
+        (rule.with_precedence($precedence), helpers)
+    }};
+    ($name:ident -> $($symbols:tt)*) => {
+        $crate::group_rule!(@body [::std::stringify!($name)] [] [] $($symbols)*)
+    };
+    // Base case: Every symbol in the body has been parsed. The first
+    // accumulator holds a Vec<Symbol> for each symbol (as in symbols!) and the
+    // second a Vec<Rule> of helper rules for each group
+    (@body [$name:expr] [$($body:expr),*] [$($helpers:expr),*]) => {{
+        let body: ::std::vec::Vec<::std::vec::Vec<$crate::grammar::Symbol>> =
+            ::std::vec![$($body),*];
+        let rule = $crate::grammar::Rule::new(
+            ::std::string::String::from($name),
+            body.into_iter().flatten().collect(),
+        );
+        let helpers: ::std::vec::Vec<::std::vec::Vec<$crate::grammar::Rule>> =
+            ::std::vec![$($helpers),*];
+        (rule, helpers.into_iter().flatten().collect::<::std::vec::Vec<_>>())
+    }};
+    // A group is replaced by a reference to a helper rule named after the
+    // text of the group, with one alternative of the helper for each
+    // alternative of the group
+    (@body [$name:expr] [$($body:expr),*] [$($helpers:expr),*] ( $($group:tt)* ) $($rest:tt)*) => {
+        $crate::group_rule!(
+            @body
+            [$name]
+            [$($body,)* ::std::vec![$crate::grammar::Symbol::Rule(
+                ::std::string::String::from(::std::concat!("(", ::std::stringify!($($group)*), ")"))
+            )]]
+            [$($helpers,)* $crate::group_rule!(
+                @alternatives
+                [::std::concat!("(", ::std::stringify!($($group)*), ")")]
+                []
+                []
+                $($group)*
+            )]
+            $($rest)*
+        )
+    };
+    // Any other symbol is parsed by symbol!
+    (@body [$name:expr] [$($body:expr),*] [$($helpers:expr),*] $first:tt $($rest:tt)*) => {
+        $crate::group_rule!(
+            @body [$name] [$($body,)* $crate::symbol!($first)] [$($helpers),*] $($rest)*
+        )
+    };
+    // Split the contents of a group on | and build a rule (plus helpers for
+    // any nested groups) from each alternative. The first accumulator holds
+    // the alternative currently being collected and the second the rules for
+    // the alternatives already found.
+    (@alternatives [$name:expr] [$($alternative:tt)*] [$($rules:expr),*]) => {{
+        let alternatives: ::std::vec::Vec<(
+            $crate::grammar::Rule,
+            ::std::vec::Vec<$crate::grammar::Rule>,
+        )> = ::std::vec![
+            $($rules,)*
+            $crate::group_rule!(@body [$name] [] [] $($alternative)*)
+        ];
+        alternatives
+            .into_iter()
+            .flat_map(|(rule, helpers)| ::std::iter::once(rule).chain(helpers))
+            .collect::<::std::vec::Vec<_>>()
+    }};
+    (@alternatives [$name:expr] [$($alternative:tt)*] [$($rules:expr),*] | $($rest:tt)*) => {
+        $crate::group_rule!(
+            @alternatives
+            [$name]
+            []
+            [$($rules,)* $crate::group_rule!(@body [$name] [] [] $($alternative)*)]
+            $($rest)*
+        )
+    };
+    (@alternatives [$name:expr] [$($alternative:tt)*] [$($rules:expr),*] $first:tt $($rest:tt)*) => {
+        $crate::group_rule!(
+            @alternatives [$name] [$($alternative)* $first] [$($rules),*] $($rest)*
+        )
+    };
+}
+
 /// Helper for grammar! { }. Collects rules by finding each ; then passing the
-/// preceding token trees to rule!().
+/// preceding token trees to group_rule!().
 #[macro_export]
 #[doc(hidden)]
 macro_rules! grammar_aux {
     // Base case: Found all of the rules and don't have any leftover tokens,
-    // construct a new grammar.
-    ([][$($rules:expr)*]) => {
-        $crate::grammar::Grammar::new(vec![$($rules),*])
-    };
+    // construct a new grammar. Helper rules go after the declared rules, a
+    // group which appears several times produces identical helpers which are
+    // only kept once so the group is shared.
+    ([][$($rules:expr)*]) => {{
+        let (mut rules, helpers): (
+            ::std::vec::Vec<$crate::grammar::Rule>,
+            ::std::vec::Vec<::std::vec::Vec<$crate::grammar::Rule>>,
+        ) = ::std::vec![$($rules),*].into_iter().unzip();
+        for helper in helpers.into_iter().flatten() {
+            if !rules.contains(&helper) {
+                rules.push(helper);
+            }
+        }
+        $crate::grammar::Grammar::new(rules)
+    }};
     // No more tokens in the input but there are still some in the
     // accumulator. Assume that they represent a rule (this is caused by missing
     // the ; from the last rule).
     ([$($rule:tt)+][$($rules:expr)*]) => {
-        $crate::grammar_aux!([][$($rules)* $crate::rule!($($rule)*)])
+        $crate::grammar_aux!([][$($rules)* $crate::group_rule!($($rule)*)])
     };
     // Found a ;. Assume everything preceding it (now in the first accumulator)
-    // is one rule. The rule is constructed with rule! then pushed onto the
-    // rules list (second accumulator)
+    // is one rule. The rule is constructed with group_rule! then pushed onto
+    // the rules list (second accumulator)
     ([$($rule:tt)*][$($rules:expr)*] ; $($rest:tt)*) => {
-        $crate::grammar_aux!([][$($rules)* $crate::rule!($($rule)*)] $($rest)*)
+        $crate::grammar_aux!([][$($rules)* $crate::group_rule!($($rule)*)] $($rest)*)
     };
     // Something other than a ;. Push it onto the first accumulator then recuse
     // on the remaining input.
@@ -105,6 +237,36 @@ macro_rules! grammar_aux {
 /// }
 /// ```
 ///
+/// The first rule is the start symbol unless the rules are preceded by a
+/// `start: <Rule Name>;` directive, see [`Grammar::with_start_symbol`]
+///
+/// A character class used in several rules can be named once with a
+/// `charset <Name> = ["<Characters>"];` directive before the rules, then
+/// referred to as `[<Name>]` in a rule body. The brackets distinguish it from
+/// a reference to a rule called `<Name>`.
+///
+/// Alternatives for part of a rule body can be grouped in parentheses,
+/// `A -> X (B | C);` matches the same input as `A -> X B; A -> X C;`. Rather
+/// than expanding into one rule per alternative the group becomes a helper rule
+/// named after the text of the group (here `(B | C)`) with an alternative for
+/// each branch, so the shared prefix `X` is only predicted and scanned once. An
+/// empty branch makes the group optional: `(B | )`. Groups can be nested and
+/// identical groups are shared between rules.
+///
+/// The tradeoff is the shape of the parse tree, each group adds an internal
+/// node named after the helper rule between the rule and the symbols matched by
+/// the chosen branch. Write the alternatives out as separate rules if the tree
+/// needs to be flat.
+///
+/// A rule can be given a precedence by writing it in `[]` before the rule
+/// name, `[1] E -> E "+" E;`. When the input is ambiguous alternatives with
+/// higher precedence are tried first, see [`Rule::with_precedence`].
+///
+/// Ordinary Rust comments can be used anywhere inside the macro, they are
+/// stripped by the compiler before the macro sees its input. For grammars
+/// loaded at runtime see [`Grammar::from_bnf`] which supports `#` line
+/// comments.
+///
 /// # Panics
 /// See [`Grammar::new`] and [`Rule::new`]
 ///
@@ -125,7 +287,9 @@ macro_rules! grammar_aux {
 ///     ])
 /// )
 /// ```
-/// A bare string becomes a sequence of [`Symbol::Literal`] (one for each character)
+/// A bare string becomes a sequence of [`Symbol::Literal`] (one for each
+/// character, except grapheme clusters made up of several characters which
+/// become a single [`Symbol::Grapheme`])
 /// ```
 /// # use parsey::grammar;
 /// # use parsey::grammar::{ Grammar, Rule, Symbol };
@@ -162,15 +326,148 @@ macro_rules! grammar_aux {
 ///     ])
 /// )
 /// ```
+/// Two characters separated by `-` in `[]` are [`Symbol::Range`], matching
+/// any character between them
+/// ```
+/// # use parsey::grammar;
+/// # use parsey::grammar::{ Grammar, Rule, Symbol };
+/// assert_eq!(
+///     grammar! {
+///         Letter -> ['a'-'z'];
+///     },
+///     Grammar::new(vec![
+///         Rule::new(String::from("Letter"), vec![Symbol::Range('a', 'z')])
+///     ])
+/// )
+/// ```
+/// A `^` at the start of `[]` negates it with [`Symbol::Not`], matching any
+/// character the class doesn't
+/// ```
+/// # use parsey::grammar;
+/// # use parsey::grammar::{ Grammar, Rule, Symbol };
+/// assert_eq!(
+///     grammar! {
+///         NotLetter -> [^ 'a'-'z'];
+///     },
+///     Grammar::new(vec![
+///         Rule::new(
+///             String::from("NotLetter"),
+///             vec![Symbol::negate(Symbol::Range('a', 'z'))]
+///         )
+///     ])
+/// )
+/// ```
+/// `$` is [`Symbol::Newline`], matching either `\n` or `\r\n`
+/// ```
+/// # use parsey::grammar;
+/// # use parsey::grammar::{ Grammar, Rule, Symbol };
+/// assert_eq!(
+///     grammar! {
+///         Line -> "a" $;
+///     },
+///     Grammar::new(vec![
+///         Rule::new(
+///             String::from("Line"),
+///             vec![Symbol::Literal('a'), Symbol::Newline]
+///         )
+///     ])
+/// )
+/// ```
+/// `.` is [`Symbol::AnyChar`], matching any one character
+/// ```
+/// # use parsey::grammar;
+/// # use parsey::grammar::{ Grammar, Rule, Symbol };
+/// assert_eq!(
+///     grammar! {
+///         Escaped -> "\\" .;
+///     },
+///     Grammar::new(vec![
+///         Rule::new(
+///             String::from("Escaped"),
+///             vec![Symbol::Literal('\\'), Symbol::AnyChar]
+///         )
+///     ])
+/// )
+/// ```
+/// A named character set in `[]` is also [`Symbol::OneOf`]
+/// ```
+/// # use parsey::grammar;
+/// assert_eq!(
+///     grammar! {
+///         charset Digit = ["0123456789"];
+///         Number -> [Digit] Number;
+///         Number -> [Digit];
+///     },
+///     grammar! {
+///         Number -> ["0123456789"] Number;
+///         Number -> ["0123456789"];
+///     }
+/// )
+/// ```
+///
+/// A parenthesised group of alternatives becomes a helper rule
+/// ```
+/// # use parsey::grammar;
+/// let mut expanded = grammar! {
+///     Assign -> Name Op Name;
+///     Name -> ["abc"];
+///     Op -> "=";
+///     Op -> "+=";
+/// };
+/// expanded.rename_rule("Op", "(\"=\" | \"+=\")");
+/// assert_eq!(
+///     grammar! {
+///         Assign -> Name ("=" | "+=") Name;
+///         Name -> ["abc"];
+///     },
+///     expanded
+/// )
+/// ```
+///
+/// A precedence before the rule name is passed to [`Rule::with_precedence`]
+/// ```
+/// # use parsey::grammar;
+/// # use parsey::grammar::{ Grammar, Rule, Symbol };
+/// assert_eq!(
+///     grammar! {
+///         [2] Rule -> "a";
+///     },
+///     Grammar::new(vec![
+///         Rule::new(String::from("Rule"), vec![Symbol::Literal('a')])
+///             .with_precedence(2)
+///     ])
+/// )
+/// ```
 ///
 /// [Grammar]: super::Grammar
 /// [Grammar::new]: super::Grammar::new
+/// [Grammar::from_bnf]: super::Grammar::from_bnf
+/// [Grammar::with_start_symbol]: super::Grammar::with_start_symbol
 /// [Rule::new]: super::Rule::new
+/// [Rule::with_precedence]: super::Rule::with_precedence
 /// [Symbol::OneOf]: super::Symbol::OneOf
+/// [Symbol::Range]: super::Symbol::Range
 /// [Symbol::Literal]: super::Symbol::Literal
+/// [Symbol::Grapheme]: super::Symbol::Grapheme
+/// [Symbol::Newline]: super::Symbol::Newline
+/// [Symbol::AnyChar]: super::Symbol::AnyChar
+/// [Symbol::Not]: super::Symbol::Not
 /// [Symbol::Rule]: super::Symbol::Rule
 #[macro_export]
 macro_rules! grammar {
+    // An optional start directive before the rules names the start symbol
+    (start : $start:ident ; $($rules:tt)+) => {
+        $crate::grammar!($($rules)+).with_start_symbol(::std::stringify!($start))
+    };
+    // A charset directive becomes a variable holding the set, references to
+    // it in rule bodies (see symbol! above) copy it into a OneOf
+    (charset $name:ident = [ $str:literal ] ; $($rules:tt)+) => {{
+        #[allow(non_snake_case)]
+        let $name = $crate::NonEmptyHashSet::new(
+            $str.chars().collect::<::std::collections::HashSet<_>>(),
+        );
+        $crate::grammar!($($rules)+)
+    }};
     ($($rules:tt)+) => {
         // Construct the new grammar with grammar_aux initialised with two empty
         // accumulators
@@ -208,6 +505,10 @@ macro_rules! tokenizers_untyped {
 
 /// Run several tokenizers in sequence then collect all of the characters into a
 /// single token
+///
+/// Each tokenizer is greedy, the next one only starts once the current one
+/// can't accept the next character and only if the current one completed on
+/// the character before (or can match the empty string)
 #[macro_export]
 macro_rules! chain {
     ($tag:literal $(, $tok:expr)* $(,)?) => {
@@ -215,6 +516,29 @@ macro_rules! chain {
     }
 }
 
+/// As [`chain!`] but keep the token produced by each tokenizer instead of
+/// merging their characters, the token is a `Vec` of the sub-tokens in order.
+/// The tokenizers must produce the same token type, use
+/// [`map`](crate::tokenizer::map) to bring them into line. Sub-tokens which
+/// aren't produced (e.g. from [`eat`](crate::tokenizer::eat)) are left out.
+/// ```
+/// # use parsey::chain_typed;
+/// # use parsey::tokenizer::{keywords, literal, tokenize};
+/// let tokens = tokenize(
+///     "if(",
+///     chain_typed!(keywords(&[("if", "if"), ("while", "while")]), literal("open", "(")),
+/// )
+/// .unwrap();
+/// let tags = tokens[0].token.iter().map(|token| token.tag).collect::<Vec<_>>();
+/// assert_eq!(tags, vec!["if", "open"]);
+/// ```
+#[macro_export]
+macro_rules! chain_typed {
+    ($($tok:expr),* $(,)?) => {
+        $crate::tokenizer::chain_typed($crate::tokenizers![$($tok),*])
+    }
+}
+
 /// Tokenize the using the first of a set of tokenizers to match
 ///
 /// The first character of the input is fed to each tokenizer in turn, the first
@@ -0,0 +1,435 @@
+use std::iter::{self, FusedIterator};
+
+use crate::ast::{self, DerivationCount, Forest, Node, SpannedNode};
+use crate::grammar::{Grammar, Symbol};
+use crate::state::{Item, StateSet};
+use crate::{
+    build_partial_parse_state, expand_input, is_complete_parse, parse_error, root_items, unparsed,
+    DepthLimitExceeded, ParseError, PrefixParse, TraceEvent,
+};
+
+/// The result of running the Earley algorithm over some input. Build it once
+/// with [`ParseState::build`] then run as many queries against it as needed
+/// without parsing the input again. If the parse fails part way through the
+/// input the state built up to that point is kept, so queries about prefixes
+/// of the input still work.
+#[derive(Debug)]
+pub struct ParseState<'a> {
+    grammar: &'a Grammar,
+    input: Vec<char>,
+    state_sets: Vec<StateSet<'a>>,
+}
+
+impl<'a> ParseState<'a> {
+    /// Parse `input` according to `grammar`
+    #[must_use]
+    pub fn build<S>(grammar: &'a Grammar, input: S) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self::from_chars(grammar, expand_input(input))
+    }
+
+    /// As [`ParseState::build`] but every prediction, scan and completion made
+    /// while parsing is passed to `trace`, see [`TraceEvent`]
+    /// ```
+    /// # use parsey::{grammar, ParseState, TraceEvent};
+    /// let grammar = grammar! {
+    ///     Sum -> Sum "+" Number;
+    ///     Sum -> Number;
+    ///     Number -> ["0123456789"];
+    /// };
+    /// let mut scans = 0;
+    /// let state = ParseState::build_traced(&grammar, "1+2", |event| {
+    ///     if let TraceEvent::Scan { .. } = event {
+    ///         scans += 1;
+    ///     }
+    /// });
+    /// assert!(state.accepts());
+    /// assert_eq!(scans, 3);
+    /// ```
+    #[must_use]
+    pub fn build_traced<S>(
+        grammar: &'a Grammar,
+        input: S,
+        mut trace: impl FnMut(TraceEvent),
+    ) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self::from_chars_traced(grammar, expand_input(input), Some(&mut trace))
+    }
+
+    pub(crate) fn from_chars(grammar: &'a Grammar, input: Vec<char>) -> Self {
+        Self::from_chars_traced(grammar, input, None)
+    }
+
+    fn from_chars_traced(
+        grammar: &'a Grammar,
+        input: Vec<char>,
+        trace: Option<&mut dyn FnMut(TraceEvent)>,
+    ) -> Self {
+        // Can't fail without a limit
+        let state_sets =
+            build_partial_parse_state(grammar.start_symbol(), grammar, &input, None, trace)
+                .unwrap();
+        ParseState {
+            grammar,
+            input,
+            state_sets,
+        }
+    }
+
+    /// The input left over when the parse ran out of state, `None` if every
+    /// character was consumed. Consuming every character doesn't mean the
+    /// input is in the language, see [`ParseState::accepts`]
+    #[must_use]
+    pub fn unparsed(&self) -> Option<String> {
+        unparsed(&self.input, &self.state_sets)
+    }
+
+    /// True if the input is in the language described by the grammar, see
+    /// [`recognise`](crate::recognise)
+    #[must_use]
+    pub fn accepts(&self) -> bool {
+        self.consumed_everything() && is_complete_parse(self.start_symbol(), self.last())
+    }
+
+    /// The length of the longest prefix of the input in the language, see
+    /// [`longest_match`](crate::longest_match)
+    #[must_use]
+    pub fn longest_match(&self) -> Option<usize> {
+        // The state set at index n holds the items produced after consuming n
+        // characters, search backwards for the longest complete parse
+        self.state_sets
+            .iter()
+            .rposition(|state_set| is_complete_parse(self.start_symbol(), state_set))
+    }
+
+    /// The length of every prefix of the input in the language, see
+    /// [`complete_prefixes`](crate::complete_prefixes)
+    #[must_use]
+    pub fn complete_prefixes(&self) -> Vec<usize> {
+        self.state_sets
+            .iter()
+            .enumerate()
+            .filter(|(_, state_set)| is_complete_parse(self.start_symbol(), state_set))
+            .map(|(k, _)| k)
+            .collect()
+    }
+
+    /// The root completions spanning the whole input, see
+    /// [`complete_parses`](crate::complete_parses)
+    #[must_use]
+    pub fn complete_parses(&self) -> Vec<(String, usize, usize)> {
+        if !self.consumed_everything() {
+            return Vec::new();
+        }
+        root_items(self.start_symbol(), self.last())
+            .map(|item| (String::from(item.rule_name()), 0, self.input.len()))
+            .collect()
+    }
+
+    /// True if the input could be extended into a sentence of the language,
+    /// see [`is_viable_prefix`](crate::is_viable_prefix)
+    #[must_use]
+    pub fn is_viable_prefix(&self) -> bool {
+        if self.consumed_everything() {
+            // Each item in the last state set is a parse of the input which
+            // hasn't failed yet
+            return !self.last().items().is_empty();
+        }
+
+        // The input may stop part way through a terminal matching several
+        // characters, which couldn't be scanned yet
+        self.state_sets
+            .iter()
+            .enumerate()
+            .any(|(position, state_set)| {
+                state_set
+                    .items()
+                    .iter()
+                    .filter_map(Item::next_symbol)
+                    .any(|symbol| symbol.is_partial_match(&self.input[position..]))
+            })
+    }
+
+    /// The symbols which could come next after the input, see
+    /// [`Grammar::possible_next_symbols`]
+    #[must_use]
+    pub fn possible_next_symbols(&self) -> Vec<Symbol> {
+        if !self.consumed_everything() {
+            return Vec::new();
+        }
+
+        let mut result = Vec::new();
+        for item in self.last().items() {
+            if let Some(symbol) = item.next_symbol() {
+                if !result.contains(symbol) {
+                    result.push(symbol.clone());
+                }
+            }
+        }
+        result
+    }
+
+    /// The possible parse trees, see [`parse`](crate::parse)
+    ///
+    /// # Errors
+    /// In case of parse failure a [`ParseError`] is returned.
+    pub fn trees(&self) -> Result<impl FusedIterator<Item = Node> + 'a, ParseError> {
+        self.check_consumed()?;
+        Ok(Node::from_parse_state(
+            self.grammar,
+            &self.state_sets,
+            self.input.clone(),
+        ))
+    }
+
+    /// The parse trees with the span of each internal node, see
+    /// [`parse_with_provenance`](crate::parse_with_provenance)
+    ///
+    /// # Errors
+    /// In case of parse failure a [`ParseError`] is returned.
+    pub fn trees_with_provenance(
+        &self,
+    ) -> Result<impl FusedIterator<Item = SpannedNode> + 'a, ParseError> {
+        self.check_consumed()?;
+        Ok(SpannedNode::from_parse_state(
+            self.grammar,
+            &self.state_sets,
+            self.input.clone(),
+        ))
+    }
+
+    /// The parse trees of the longest prefix of the input in the language, see
+    /// [`parse_prefix`](crate::parse_prefix)
+    #[must_use]
+    pub fn prefix_trees(&self) -> Option<PrefixParse<impl FusedIterator<Item = Node> + 'a>> {
+        let covered = self.longest_match()?;
+        // Items in the state sets up to `covered` never end after it, so the
+        // trees can be built from those sets alone
+        let trees = Node::from_parse_state(
+            self.grammar,
+            &self.state_sets[..=covered],
+            self.input[..covered].to_vec(),
+        );
+        Some(PrefixParse {
+            trees,
+            covered,
+            input_len: self.input.len(),
+        })
+    }
+
+    /// The possible parse trees no deeper than `max_depth`, see
+    /// [`parse_with_depth_limit`](crate::parse_with_depth_limit)
+    ///
+    /// # Errors
+    /// In case of parse failure a [`ParseError`] is returned.
+    pub fn trees_with_depth_limit(
+        &self,
+        max_depth: usize,
+    ) -> Result<impl Iterator<Item = Result<Node, DepthLimitExceeded>> + 'a, ParseError> {
+        self.check_consumed()?;
+        let (trees, exceeded) = Node::from_parse_state_with_depth_limit(
+            self.grammar,
+            &self.state_sets,
+            self.input.clone(),
+            max_depth,
+        );
+        // The flag is only final once every tree has been tried
+        let limit = iter::from_fn(move || {
+            exceeded
+                .take()
+                .then_some(Err(DepthLimitExceeded { limit: max_depth }))
+        });
+        Ok(trees.map(Ok).chain(limit))
+    }
+
+    /// Every parse tree addressable by index, see
+    /// [`parse_forest`](crate::parse_forest)
+    ///
+    /// # Errors
+    /// In case of parse failure a [`ParseError`] is returned.
+    pub fn forest(&self) -> Result<Forest<'a>, ParseError> {
+        self.check_consumed()?;
+        Ok(Forest::from_parse_state(
+            self.grammar,
+            &self.state_sets,
+            self.input.clone(),
+        ))
+    }
+
+    /// The number of derivations of each rule completed during the parse, see
+    /// [`derivation_counts`](crate::derivation_counts)
+    ///
+    /// # Errors
+    /// In case of parse failure a [`ParseError`] is returned.
+    pub fn derivation_counts(&self) -> Result<Vec<DerivationCount>, ParseError> {
+        self.check_consumed()?;
+        Ok(DerivationCount::from_parse_state(
+            &self.state_sets,
+            &self.input,
+        ))
+    }
+
+    /// Every rule completed during the parse as `(rule, start, end)`, the
+    /// outer `Vec` is indexed by start position. This is the chart trees are
+    /// built from, useful for extracting information from the parse without
+    /// enumerating whole trees. Available even if the parse failed, in that
+    /// case it only covers the input before the failure.
+    #[must_use]
+    pub fn completions_by_start(&self) -> Vec<Vec<(String, usize, usize)>> {
+        ast::completions_by_start(&self.state_sets)
+    }
+
+    fn start_symbol(&self) -> &'a str {
+        self.grammar.start_symbol()
+    }
+
+    /// The last state set, there is always at least one
+    fn last(&self) -> &StateSet<'a> {
+        self.state_sets.last().unwrap()
+    }
+
+    fn consumed_everything(&self) -> bool {
+        self.state_sets.len() > self.input.len()
+    }
+
+    /// An error if the parse stopped before the end of the input, or if the
+    /// input ran out part way through a sentence. An empty input is no
+    /// different, it is an error unless the start symbol is nullable
+    fn check_consumed(&self) -> Result<(), ParseError> {
+        if let Some(error) = parse_error(&self.input, &self.state_sets) {
+            return Err(error);
+        }
+        if self.accepts() {
+            return Ok(());
+        }
+        Err(ParseError {
+            position: self.input.len(),
+            remaining: String::new(),
+            expected: self
+                .possible_next_symbols()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        })
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::grammar;
+
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+        BINOP: Grammar = grammar! {
+            E -> E "-" E;
+            E -> ["0123456789"];
+        };
+    }
+
+    #[test]
+    fn several_queries() {
+        let state = ParseState::build(&ARITH, "1+2");
+        assert!(state.accepts());
+        assert_eq!(state.unparsed(), None);
+        assert_eq!(state.longest_match(), Some(3));
+        assert_eq!(state.complete_prefixes(), vec![1, 3]);
+        assert_eq!(state.complete_parses(), vec![(String::from("Sum"), 0, 3)]);
+        assert_eq!(state.trees().map(Iterator::count), Ok(1));
+        assert!(state
+            .derivation_counts()
+            .unwrap()
+            .iter()
+            .all(|count| !count.is_ambiguous()));
+        assert!(!state.possible_next_symbols().contains(&Symbol::Literal(')')));
+    }
+
+    #[test]
+    fn completions_by_start() {
+        let completion = |name: &str, start, end| (String::from(name), start, end);
+        assert_eq!(
+            ParseState::build(&ARITH, "1+2").completions_by_start(),
+            vec![
+                vec![
+                    completion("Number", 0, 1),
+                    completion("Factor", 0, 1),
+                    completion("Product", 0, 1),
+                    completion("Sum", 0, 1),
+                    completion("Sum", 0, 3)
+                ],
+                vec![],
+                vec![
+                    completion("Number", 2, 3),
+                    completion("Factor", 2, 3),
+                    completion("Product", 2, 3)
+                ],
+                vec![]
+            ]
+        );
+    }
+
+    #[test]
+    fn ambiguous() {
+        let state = ParseState::build(&BINOP, "1-2-3");
+        assert!(state.accepts());
+        assert_eq!(state.trees().map(Iterator::count), Ok(2));
+        assert_eq!(
+            state
+                .derivation_counts()
+                .unwrap()
+                .into_iter()
+                .filter(DerivationCount::is_ambiguous)
+                .count(),
+            1
+        );
+        assert_eq!(state.complete_parses().len(), 1);
+    }
+
+    #[test]
+    fn incomplete_input() {
+        let state = ParseState::build(&ARITH, "1+");
+        assert!(!state.accepts());
+        assert_eq!(state.unparsed(), None);
+        assert_eq!(state.longest_match(), Some(1));
+        assert_eq!(state.complete_parses(), vec![]);
+        assert_eq!(
+            state.trees().map(Iterator::count).map_err(|error| (error.position, error.remaining)),
+            Err((2, String::new()))
+        );
+        assert!(state.possible_next_symbols().contains(&Symbol::Literal('(')));
+        assert!(state.is_viable_prefix());
+    }
+
+    #[test]
+    fn failed_parse() {
+        let state = ParseState::build(&ARITH, "12%3");
+        assert!(!state.accepts());
+        assert_eq!(state.unparsed(), Some(String::from("%3")));
+        assert_eq!(state.longest_match(), Some(2));
+        assert_eq!(state.complete_prefixes(), vec![1, 2]);
+        assert_eq!(state.complete_parses(), vec![]);
+        let error = ParseError {
+            position: 2,
+            remaining: String::from("%3"),
+            expected: ["Number", "[0123456789]", "[*/]", "[+-]"]
+                .iter()
+                .map(|&symbol| String::from(symbol))
+                .collect(),
+        };
+        assert_eq!(state.trees().map(Iterator::count), Err(error.clone()));
+        assert_eq!(state.derivation_counts(), Err(error));
+        assert_eq!(state.possible_next_symbols(), vec![]);
+        assert!(!state.is_viable_prefix());
+    }
+}
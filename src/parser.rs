@@ -0,0 +1,163 @@
+use crate::grammar::Grammar;
+use crate::state::StateSet;
+use crate::{accepts, fill_parse_state, unparsed, Scratch};
+
+/// Recognise many inputs in a row without reallocating the buffers used by
+/// the parser each time. [`recognise`](crate::recognise) builds the characters
+/// of the input and every state set from scratch, when parsing lots of small
+/// inputs (e.g. in a server) those allocations add up. A `Parser` keeps them
+/// between calls. The state sets refer to the rules of the grammar, so the
+/// grammars used must outlive the parser.
+/// ```
+/// # use parsey::{grammar, Parser};
+/// let grammar = grammar! {
+///     Sum -> Sum "+" Number;
+///     Sum -> Number;
+///     Number -> ["0123456789"];
+/// };
+/// let mut parser = Parser::new();
+/// assert!(parser.recognise(&grammar, "1+2"));
+/// assert!(!parser.recognise(&grammar, "1+"));
+/// ```
+#[derive(Debug, Default)]
+pub struct Parser<'a> {
+    input: Vec<char>,
+    parse_state: Vec<StateSet<'a>>,
+    scratch: Scratch<'a>,
+}
+
+impl<'a> Parser<'a> {
+    /// A parser with no buffers allocated yet
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// As [`recognise`](crate::recognise) but reusing the buffers from
+    /// previous calls
+    pub fn recognise<S>(&mut self, grammar: &'a Grammar, input: S) -> bool
+    where
+        S: AsRef<str>,
+    {
+        self.input.clear();
+        self.input.extend(input.as_ref().chars());
+        if let Some(accepted) = grammar.recognise_short(&self.input) {
+            return accepted;
+        }
+
+        self.scratch.recycle(&mut self.parse_state);
+        let start_symbol = grammar.start_symbol();
+        // Can't fail without a limit
+        let Scratch { spare, to_add } = &mut self.scratch;
+        let built = fill_parse_state(
+            start_symbol,
+            grammar,
+            &self.input,
+            None,
+            &mut self.parse_state,
+            to_add,
+            || spare.pop().unwrap_or_default(),
+            None,
+        );
+        built.is_ok()
+            && unparsed(&self.input, &self.parse_state).is_none()
+            && accepts(start_symbol, &self.parse_state)
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::recognise;
+
+    testdata! {
+        ARITH: Grammar = grammar! {
+            Sum -> Sum ["+-"] Product;
+            Sum -> Product;
+            Product -> Product ["*/"] Factor;
+            Product -> Factor;
+            Factor -> "(" Sum ")";
+            Factor -> Number;
+            Number -> ["0123456789"] Number;
+            Number -> ["0123456789"];
+        };
+        KEYWORDS: Grammar = grammar! {
+            Keyword -> "let";
+            Keyword -> "letter";
+            Keyword -> "in";
+        };
+        INPUTS: Vec<&'static str> = vec![
+            "1+(2*3)-45",
+            "",
+            "1",
+            "1+",
+            "(((1)))",
+            "1+(2*3",
+            "12*34/56",
+            "1+)",
+            "let",
+            "letter",
+            "lett",
+        ];
+    }
+
+    testcase! {
+        same_as_recognise,
+        INPUTS
+            .iter()
+            .map(|input| recognise(&ARITH, input))
+            .collect::<Vec<_>>(),
+        {
+            let mut parser = Parser::new();
+            INPUTS
+                .iter()
+                .map(|input| parser.recognise(&ARITH, input))
+                .collect::<Vec<_>>()
+        }
+    }
+
+    testcase! {
+        reversed_order,
+        INPUTS
+            .iter()
+            .rev()
+            .map(|input| recognise(&ARITH, input))
+            .collect::<Vec<_>>(),
+        {
+            let mut parser = Parser::new();
+            INPUTS
+                .iter()
+                .rev()
+                .map(|input| parser.recognise(&ARITH, input))
+                .collect::<Vec<_>>()
+        }
+    }
+
+    testcase! {
+        several_grammars,
+        INPUTS
+            .iter()
+            .flat_map(|input| vec![recognise(&ARITH, input), recognise(&KEYWORDS, input)])
+            .collect::<Vec<_>>(),
+        {
+            let mut parser = Parser::new();
+            INPUTS
+                .iter()
+                .flat_map(|input| {
+                    vec![parser.recognise(&ARITH, input), parser.recognise(&KEYWORDS, input)]
+                })
+                .collect::<Vec<_>>()
+        }
+    }
+
+    testcase! {
+        buffers_kept,
+        {
+            let mut parser = Parser::new();
+            let _ = parser.recognise(&ARITH, "1+2*3");
+            let _ = parser.recognise(&ARITH, "1+");
+            // The second parse reuses three of the six item vectors from the
+            // first, the rest are kept for later
+            (parser.parse_state.len(), parser.scratch.spare.len())
+        },
+        (3, 3)
+    }
+}
@@ -0,0 +1,30 @@
+//! The commonly used parts of the API in one place
+//!
+//! ```
+//! use parsey::prelude::*;
+//!
+//! let grammar = grammar! {
+//!     Sum -> Sum "+" Number;
+//!     Sum -> Number;
+//!     Number -> ["0123456789"];
+//! };
+//! assert!(recognise(&grammar, "1+2"));
+//!
+//! let tree = parse(&grammar, "1+2").unwrap().next().unwrap();
+//! assert!(tree.same_shape(&parse(&grammar, "3+4").unwrap().next().unwrap()));
+//!
+//! let tokens = tokenize("ab", firstof!(literal("a", "a"), oneof("b", ['b'].into())));
+//! assert_eq!(tokens.unwrap().len(), 2);
+//! ```
+
+pub use crate::ast::Node;
+pub use crate::grammar::{Grammar, Rule, Symbol};
+pub use crate::tokenizer::{
+    any_char, at_eof, categorize, count, eat, empty, from_fn, keywords, literal, map, oneof,
+    oneof_str, punctuation, spans, tokenize, tokenize_partial, State, Token, TokenAndSpan,
+    TokenizeError, Tokenizer, TokenizerExt,
+};
+pub use crate::{
+    chain, chain_typed, firstof, grammar, longestof, rule, tokenizers, tokenizers_untyped,
+};
+pub use crate::{parse, parse_traced, recognise, ParseError, ParseState, TraceEvent};
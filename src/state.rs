@@ -1,5 +1,5 @@
 pub(crate) use item::Item;
-pub(crate) use stateset::StateSet;
+pub(crate) use stateset::{Items, StateSet};
 
 mod item;
 mod stateset;
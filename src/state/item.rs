@@ -3,27 +3,33 @@ use std::fmt;
 use syntax_abuse as syntax;
 
 use super::{
-    super::grammar::{Grammar, Rule, Symbol},
-    stateset::StateSet,
+    super::grammar::{Grammar, Repetition, Rule, Symbol},
+    stateset::{Items, StateSet},
 };
+use crate::TraceEvent;
 
 #[derive(PartialEq, Copy, Clone)]
 pub(crate) struct Item<'a> {
     rule: &'a Rule,
     start: usize,
     progress: usize,
+    /// How far matching has got through the symbol at `progress`, which only
+    /// changes for a [`Symbol::Repeat`]
+    repetition: Repetition,
 }
 
 impl<'a> Item<'a> {
-    /// Construct a vector of items from a vector of rules, each item starts
-    /// at the given start position and has its progress marker set to 0.
-    pub(crate) fn from_rules(rules: Vec<&'a Rule>, start: usize) -> Vec<Self> {
+    /// Construct a vector of items from a collection of rules, each item
+    /// starts at the given start position and has its progress marker set to
+    /// 0.
+    pub(crate) fn from_rules(rules: impl IntoIterator<Item = &'a Rule>, start: usize) -> Vec<Self> {
         rules
             .into_iter()
             .map(|rule| Item {
                 rule,
                 start,
                 progress: 0,
+                repetition: Repetition::default(),
             })
             .collect::<Vec<_>>()
     }
@@ -34,6 +40,7 @@ impl<'a> Item<'a> {
             rule,
             start,
             progress,
+            repetition: Repetition::default(),
         }
     }
 
@@ -52,72 +59,107 @@ impl<'a> Item<'a> {
 
     /// Perform the relevant step from the earley algorithm for the current
     /// item. Predictions and Completions mutate `current_state` directly,
-    /// Scans return the rule to be added to the next set (if applicable)
-    /// for the caller to make use of.
+    /// Scans return the rule to be added to a later set (if applicable) for
+    /// the caller to make use of, along with the number of characters scanned
+    /// (the item belongs in the state set that many positions ahead). The step
+    /// taken is passed to `trace` if there is one.
     #[allow(clippy::option_if_let_else)]
-    pub(crate) fn parse(
+    pub(crate) fn parse<I: Items<'a>>(
         &self,
         grammar: &'a Grammar,
-        current_state: &mut StateSet<'a>,
-        prev_state: &[StateSet<'a>],
+        current_state: &mut StateSet<'a, I>,
+        prev_state: &[StateSet<'a, I>],
         input: &[char],
         current_position: usize,
-    ) -> Option<Item<'a>> {
-        if let Some(matcher) = self.rule.get(self.progress) {
-            match matcher {
+        trace: Option<&mut dyn FnMut(TraceEvent)>,
+    ) -> Option<(usize, Item<'a>)> {
+        if let Some(symbol) = self.rule.get(self.progress) {
+            // A repeat which has matched enough times can be left behind
+            // without waiting for anything else
+            if symbol.can_stop(self.repetition) {
+                current_state.add(vec![self.advanced()]);
+            }
+
+            match symbol.expected(self.repetition)? {
                 Symbol::Rule(name) => {
                     // Prediction: Add all rules that can produce the
                     // required non-terminal to the current state set,
                     // starting from the current position
+                    if let Some(trace) = trace {
+                        trace(TraceEvent::Predict {
+                            position: current_position,
+                            item: self.to_string(),
+                            rule: name.clone(),
+                        });
+                    }
                     current_state.add(Item::from_rules(
                         grammar.get_rules_by_name(name),
                         current_position,
                     ));
 
-                    // If the rule we just predicted is nullable complete it
-                    // immediately
-                    if grammar.rule_is_nullable(name) {
-                        self.complete(current_state, prev_state);
+                    // If the rule we just predicted is nullable it can be
+                    // skipped, advance over it immediately. Waiting for the
+                    // empty completion isn't enough, it may have already been
+                    // processed before this item was added. Most grammars have
+                    // no nullable rules at all, skip hashing the name in that
+                    // case
+                    if grammar.has_nullable_rules()
+                        && grammar.rule_is_nullable(name)
+                        && grammar.allows_completion(name, &[])
+                    {
+                        current_state.add(vec![self.stepped()]);
                     }
                     None
                 }
-                // Scan: If the current character matches the current
-                // non-terminal (criteria differs per terminal) then return
-                // the current item advanced by one place (over the
-                // terminal), this will be added to the next state set by
-                // the caller when it is created.
-                Symbol::Literal(c) => self.scan(input, current_position, |next| next == c),
-                Symbol::OneOf(cs) => self.scan(input, current_position, |next| cs.contains(next)),
+                // Scan: If the upcoming characters match the current
+                // terminal then return the current item advanced by one
+                // place (over the terminal) along with the number of
+                // characters matched, this will be added to the matching
+                // state set by the caller when it is created.
+                terminal => {
+                    let width = terminal.scan(&input[current_position..])?;
+                    if let Some(trace) = trace {
+                        trace(TraceEvent::Scan {
+                            position: current_position,
+                            item: self.to_string(),
+                            width,
+                        });
+                    }
+                    Some((width, self.stepped()))
+                }
             }
-        } else {
+        } else if grammar.allows_completion(self.rule.name(), &input[self.start..current_position])
+        {
             // Completion: See below
+            if let Some(trace) = trace {
+                trace(TraceEvent::Complete {
+                    position: current_position,
+                    item: self.to_string(),
+                });
+            }
             self.complete(current_state, prev_state);
             None
+        } else {
+            // A predicate rejected the completion, remove the item so nothing
+            // built from the parse state (trees, derivation counts) uses it
+            current_state.retain(|item| item != self);
+            None
         }
     }
 
-    /// Common Scan implementation. Unconditionally returns None if `pos` is
-    /// past the end of `input`, otherwise the character is passed to pred. If
-    /// pred succeeds the item is returned advanced by one place (see the Scan
-    /// branch of `Item::parse`), if it fails None is returned.
-    fn scan(&self, input: &[char], pos: usize, pred: impl FnOnce(&char) -> bool) -> Option<Self> {
-        input
-            .get(pos)
-            .copied()
-            .filter(pred)
-            .map(|_| self.advanced())
-    }
-
-    /// Completion step, called when a real completion is encountered and if a
-    /// nullable rule is predicted. Find all rules in the state set this item started
-    /// in that need the non-terminal produced by this rule to
-    /// complete and add them to this state set advanced by one place
-    /// (over the non-terminal)
-    fn complete(&self, current_state: &mut StateSet<'a>, prev_state: &[StateSet<'a>]) {
+    /// Completion step, called when a completion is encountered. Find all
+    /// rules in the state set this item started in that need the non-terminal
+    /// produced by this rule to complete and add them to this state set
+    /// advanced by one place (over the non-terminal)
+    fn complete<I: Items<'a>>(
+        &self,
+        current_state: &mut StateSet<'a, I>,
+        prev_state: &[StateSet<'a, I>],
+    ) {
         // Find the state set the completed rule started in (will usually be
         // a previous state set but completions caused by matching the empty
         // string will start in the current state set)
-        let target_state_set: &StateSet<'_>;
+        let target_state_set: &StateSet<'_, I>;
         if self.start == prev_state.len() {
             target_state_set = current_state;
         } else {
@@ -133,17 +175,42 @@ impl<'a> Item<'a> {
             .filter_map(|item| {
                 item.next_name()
                     .filter(|name| *name == completed)
-                    .map(|_| item.advanced())
+                    .map(|_| item.stepped())
             })
             .collect::<Vec<Item<'_>>>();
 
         current_state.add(items);
     }
 
+    /// The next symbol to be matched, None if the item is complete or has
+    /// matched a [`Symbol::Repeat`] as many times as it can and has to move on
+    pub(crate) fn next_symbol(&self) -> Option<&'a Symbol> {
+        self.rule.get(self.progress)?.expected(self.repetition)
+    }
+
+    /// True if the item is part way through a [`Symbol::Repeat`]
+    pub(crate) fn is_repeating(&self) -> bool {
+        self.repetition != Repetition::default()
+    }
+
+    /// True if this item, produced by a scan, could also be produced by a scan
+    /// from a different state set into the same one. Either it is part way
+    /// through a [`Symbol::Repeat`] (different numbers of repetitions so far
+    /// can lead to the same item) or the terminal it just scanned has a
+    /// variable width (`\r\n` from one state set and `\n` from the next)
+    pub(crate) fn may_be_scanned_twice(&self) -> bool {
+        self.is_repeating()
+            || self
+                .progress
+                .checked_sub(1)
+                .and_then(|scanned| self.rule.get(scanned))
+                .is_some_and(Symbol::has_variable_width)
+    }
+
     /// If the next symbol to be processed is a rule this returns the name of
     /// that rule, otherwise it returns None.
     fn next_name(&self) -> Option<&str> {
-        self.rule.get(self.progress).and_then(Symbol::rule_name)
+        self.next_symbol().and_then(Symbol::rule_name)
     }
 
     /// Returns a copy of the current item with its progress marker advanced
@@ -151,8 +218,26 @@ impl<'a> Item<'a> {
     fn advanced(&self) -> Self {
         let mut new = *self;
         new.progress += 1;
+        new.repetition = Repetition::default();
         new
     }
+
+    /// Returns a copy of the current item after `next_symbol` has matched.
+    /// The progress marker stays on a [`Symbol::Repeat`] which can match
+    /// again, see [`Item::parse`] for moving past it
+    fn stepped(&self) -> Self {
+        let repetition = self
+            .rule
+            .get(self.progress)
+            .and_then(|symbol| symbol.matched(self.repetition));
+        match repetition {
+            Some(repetition) => Item {
+                repetition,
+                ..*self
+            },
+            None => self.advanced(),
+        }
+    }
 }
 
 impl fmt::Display for Item<'_> {
@@ -167,7 +252,11 @@ impl fmt::Display for Item<'_> {
             .collect::<Vec<_>>()
             .join(" ");
 
-        write!(f, "{} -> {} ({})", self.rule.name(), body, self.start)
+        write!(f, "{} -> {} ({})", self.rule.name(), body, self.start)?;
+        if self.is_repeating() {
+            write!(f, " {:?}", self.repetition)?;
+        }
+        Ok(())
     }
 }
 
@@ -191,60 +280,60 @@ syntax::tests! {
 
     testcase! {
         from_rules,
-        Item::from_rules(vec![&RULE, &RULE2, &RULE3], 1),
+        Item::from_rules(vec![&*RULE, &*RULE2, &*RULE3], 1),
         vec![
-            Item { rule: &RULE,  start: 1, progress: 0 },
-            Item { rule: &RULE2, start: 1, progress: 0 },
-            Item { rule: &RULE3, start: 1, progress: 0 }
+            Item::from_parts(&RULE, 1, 0),
+            Item::from_parts(&RULE2, 1, 0),
+            Item::from_parts(&RULE3, 1, 0)
         ]
     }
 
     testcase! {
         rule_name,
-        Item { rule: &RULE, start: 0, progress: 0 }.rule_name(),
+        Item::from_parts(&RULE, 0, 0).rule_name(),
         "Rule"
     }
 
     testcase! {
         is_complete_completed,
-        Item { rule: &RULE, start: 0, progress: 4 }.is_complete(),
+        Item::from_parts(&RULE, 0, 4).is_complete(),
         true
     }
 
     testcase! {
         is_complete_overshoot,
-        Item { rule: &RULE, start: 0, progress: 300 }.is_complete(),
+        Item::from_parts(&RULE, 0, 300).is_complete(),
         true
     }
 
     testcase! {
         is_complete_incomplete,
-        Item { rule: &RULE, start: 0, progress: 0 }.is_complete(),
+        Item::from_parts(&RULE, 0, 0).is_complete(),
         false
     }
 
     testcase! {
         next_name_literal,
-        Item { rule: &RULE, start: 0, progress: 0 }.next_name(),
+        Item::from_parts(&RULE, 0, 0).next_name(),
         None
     }
 
     testcase! {
         next_name_oneof,
-        Item { rule: &RULE4, start: 0, progress: 0 }.next_name(),
+        Item::from_parts(&RULE4, 0, 0).next_name(),
         None
     }
 
     testcase! {
         next_name_rule,
-        Item { rule: &RULE5, start: 0, progress: 0 }.next_name(),
+        Item::from_parts(&RULE5, 0, 0).next_name(),
         Some("Rule")
     }
 
     testcase! {
         advanced,
-        Item { rule: &RULE, start: 0, progress: 0 }.advanced(),
-        Item { rule: &RULE, start: 0, progress: 1 }
+        Item::from_parts(&RULE, 0, 0).advanced(),
+        Item::from_parts(&RULE, 0, 1)
     }
 
     #[test]
@@ -256,12 +345,13 @@ syntax::tests! {
         let prev = Vec::new();
         let input = Vec::new();
         assert_eq!(
-            Item { rule: &rule, start: 0, progress: 0 }.parse(
+            Item::from_parts(&rule, 0, 0).parse(
                 &grammar,
                 &mut state,
                 &prev,
                 &input,
-                0
+                0,
+                None
             ),
             None
         );
@@ -276,14 +366,15 @@ syntax::tests! {
         let prev = Vec::new();
         let input = vec!['x'];
         assert_eq!(
-            Item { rule: &rule, start: 0, progress: 0 }.parse(
+            Item::from_parts(&rule, 0, 0).parse(
                 &grammar,
                 &mut state,
                 &prev,
                 &input,
-                0
+                0,
+                None
             ),
-            Some(Item { rule: &rule, start: 0, progress: 1 })
+            Some((1, Item::from_parts(&rule, 0, 1)))
         );
         assert_eq!(state.items(), vec![]);
     }
@@ -296,12 +387,13 @@ syntax::tests! {
         let prev = Vec::new();
         let input = vec!['x'];
         assert_eq!(
-            Item { rule: &rule, start: 0, progress: 0 }.parse(
+            Item::from_parts(&rule, 0, 0).parse(
                 &grammar,
                 &mut state,
                 &prev,
                 &input,
-                0
+                0,
+                None
             ),
             None
         );
@@ -317,18 +409,61 @@ syntax::tests! {
         let prev = vec![StateSet::new(Item::from_rules(vec![&rule], 0))];
         let input = Vec::new();
         assert_eq!(
-            Item { rule: &rule2, start: 0, progress: 5 }.parse(
+            Item::from_parts(&rule2, 0, 5).parse(
                 &grammar,
                 &mut state,
                 &prev,
                 &input,
-                0
+                0,
+                None
             ),
             None
         );
         assert_eq!(
             state.items(),
-            vec![Item { rule: &rule, start: 0, progress: 1 }]
+            vec![Item::from_parts(&rule, 0, 1)]
         );
     }
+
+    #[test]
+    fn parse_grapheme_success() {
+        let rule = rule! { Rule -> "e\u{301}" };
+        let grammar = Grammar::new(vec![rule.clone()]);
+        let mut state = StateSet::new(vec![]);
+        let prev = Vec::new();
+        let input = vec!['e', '\u{301}'];
+        assert_eq!(
+            Item::from_parts(&rule, 0, 0).parse(
+                &grammar,
+                &mut state,
+                &prev,
+                &input,
+                0,
+                None
+            ),
+            Some((2, Item::from_parts(&rule, 0, 1)))
+        );
+        assert_eq!(state.items(), vec![]);
+    }
+
+    #[test]
+    fn parse_grapheme_failure() {
+        let rule = rule! { Rule -> "e\u{301}" };
+        let grammar = Grammar::new(vec![rule.clone()]);
+        let mut state = StateSet::new(vec![]);
+        let prev = Vec::new();
+        let input = vec!['e'];
+        assert_eq!(
+            Item::from_parts(&rule, 0, 0).parse(
+                &grammar,
+                &mut state,
+                &prev,
+                &input,
+                0,
+                None
+            ),
+            None
+        );
+        assert_eq!(state.items(), vec![]);
+    }
 }
@@ -1,33 +1,80 @@
 use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
 
 use syntax_abuse as syntax;
 
 use super::item::Item;
 
+/// Storage for the items of a state set. Usually a `Vec`, with the `arena`
+/// feature the items can be kept in a bump arena instead (see
+/// [`recognise_in`](crate::recognise_in))
+pub(crate) trait Items<'a>: Deref<Target = [Item<'a>]> {
+    fn push(&mut self, item: Item<'a>);
+    fn retain(&mut self, pred: impl FnMut(&Item<'a>) -> bool);
+}
+
+impl<'a> Items<'a> for Vec<Item<'a>> {
+    fn push(&mut self, item: Item<'a>) {
+        Vec::push(self, item);
+    }
+
+    fn retain(&mut self, pred: impl FnMut(&Item<'a>) -> bool) {
+        Vec::retain(self, pred);
+    }
+}
+
+#[cfg(feature = "arena")]
+impl<'a> Items<'a> for bumpalo::collections::Vec<'_, Item<'a>> {
+    fn push(&mut self, item: Item<'a>) {
+        bumpalo::collections::Vec::push(self, item);
+    }
+
+    fn retain(&mut self, pred: impl FnMut(&Item<'a>) -> bool) {
+        bumpalo::collections::Vec::retain(self, pred);
+    }
+}
+
 /// The set of Earley items produced from one step of the algorithm
 #[derive(PartialEq, Clone, Debug)]
-pub(crate) struct StateSet<'a> {
-    items: Vec<Item<'a>>,
+pub(crate) struct StateSet<'a, I = Vec<Item<'a>>> {
+    items: I,
     next: usize,
+    /// The items are `Item<'a>` whatever `I` is
+    item: PhantomData<Item<'a>>,
 }
 
-impl<'a> StateSet<'a> {
+impl<'a, I: Items<'a>> StateSet<'a, I> {
     /// Constructs a new state set from a vector of items. Note: This function
     /// assumes that each item in the vector is unique, though it's probably
     /// harmless if that isn't true the parser will do redundant work if there
     /// are duplicates.
-    pub(crate) fn new(items: Vec<Item<'a>>) -> Self {
-        StateSet { items, next: 0 }
+    pub(crate) fn new(items: I) -> Self {
+        StateSet {
+            items,
+            next: 0,
+            item: PhantomData,
+        }
     }
 
     #[cfg(test)]
-    pub(crate) fn exhausted(items: Vec<Item<'a>>) -> Self {
+    pub(crate) fn exhausted(items: I) -> Self {
         let next = items.len() + 1;
-        StateSet { items, next }
+        StateSet {
+            items,
+            next,
+            item: PhantomData,
+        }
     }
 
     syntax::get! { pub items : [Item<'a>] }
 
+    /// The items in the state set, for reuse of its storage once the state set
+    /// is no longer needed
+    pub(crate) fn into_items(self) -> I {
+        self.items
+    }
+
     /// Effectively `Iterator::next`. No point using an actual iterator because
     /// a for loop won't work while building the state set (as we have to mutate
     /// it while iterating) and the rest of the time we operate on the whole set
@@ -47,9 +94,27 @@ impl<'a> StateSet<'a> {
             }
         }
     }
+
+    /// Remove all items for which `pred` returns false. Items that have
+    /// already been returned by `next` may be removed, the cursor is adjusted
+    /// so that it still points at the first item not yet returned.
+    pub(crate) fn retain(&mut self, mut pred: impl FnMut(&Item<'a>) -> bool) {
+        let next = self.next;
+        let mut index = 0;
+        let mut removed_before_cursor = 0;
+        self.items.retain(|item| {
+            let keep = pred(item);
+            if !keep && index < next {
+                removed_before_cursor += 1;
+            }
+            index += 1;
+            keep
+        });
+        self.next -= removed_before_cursor;
+    }
 }
 
-impl fmt::Display for StateSet<'_> {
+impl<'a, I: Items<'a>> fmt::Display for StateSet<'a, I> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -75,15 +140,15 @@ syntax::tests! {
 
     testcase! {
         new_doesnt_check_for_duplicates,
-        StateSet::new(Item::from_rules(vec![&RULE, &RULE], 0)),
-        StateSet { items: Item::from_rules(vec![&RULE, &RULE], 0), next: 0 }
+        StateSet::new(Item::from_rules(vec![&*RULE, &*RULE], 0)),
+        StateSet { items: Item::from_rules(vec![&*RULE, &*RULE], 0), next: 0, item: PhantomData }
     }
 
     #[test]
     fn add_does_check_for_duplicates() {
-        let mut state = StateSet::new(Item::from_rules(vec![&RULE], 0));
+        let mut state = StateSet::new(Item::from_rules(vec![&*RULE], 0));
         let orig_state = state.clone();
-        state.add(Item::from_rules(vec![&RULE], 0));
+        state.add(Item::from_rules(vec![&*RULE], 0));
         assert_eq!(state, orig_state);
     }
 
@@ -101,4 +166,54 @@ syntax::tests! {
         assert_eq!(state.next(), Some(items2[1]));
         assert_eq!(state.next(), Some(items2[2]));
     }
+
+    tests! {
+        retain:
+
+        testdata! {
+            RULES: Vec<Rule> = vec![
+                rule! { Rule1 -> "Rule1" },
+                rule! { Rule2 -> "Rule2" },
+                rule! { Rule3 -> "Rule3" }
+            ];
+        }
+
+        fn items() -> Vec<Item<'static>> {
+            Item::from_rules(RULES.iter().collect::<Vec<_>>(), 0)
+        }
+
+        #[test]
+        fn removes_items() {
+            let mut state = StateSet::new(items());
+            state.retain(|item| item.rule_name() != "Rule2");
+            assert_eq!(state.items(), vec![items()[0], items()[2]]);
+        }
+
+        #[test]
+        fn exhausted() {
+            let mut state = StateSet::exhausted(items());
+            state.retain(|item| item.rule_name() == "Rule1");
+            assert_eq!(state, StateSet::exhausted(vec![items()[0]]));
+            assert_eq!(state.next(), None);
+        }
+
+        #[test]
+        fn cursor_stays_valid() {
+            let mut state = StateSet::new(items());
+            assert_eq!(state.next(), Some(items()[0]));
+            assert_eq!(state.next(), Some(items()[1]));
+            state.retain(|item| item.rule_name() != "Rule1");
+            assert_eq!(state.next(), Some(items()[2]));
+            assert_eq!(state.next(), None);
+        }
+
+        #[test]
+        fn remove_unvisited() {
+            let mut state = StateSet::new(items());
+            assert_eq!(state.next(), Some(items()[0]));
+            state.retain(|item| item.rule_name() != "Rule2");
+            assert_eq!(state.next(), Some(items()[2]));
+            assert_eq!(state.next(), None);
+        }
+    }
 }
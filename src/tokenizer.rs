@@ -1,15 +1,16 @@
 //! Tokenizer
-use std::cell::RefCell;
-use std::rc::Rc;
 
-pub use builtins::{chain, eat, firstof, literal, longestof, map, oneof, Token, empty};
+pub use builtins::{any_char, at_eof, categorize, chain, chain_typed, count, eat, firstof, from_fn, keywords, literal, longestof, map, oneof, oneof_str, punctuation, spans, Token, empty};
+pub use indentation::{Indentation, IndentationTokenizer};
 pub use span::{CharacterPosition, Span};
 
 mod builtins;
+mod indentation;
 mod span;
 
 type Tokens<T> = Vec<TokenAndSpan<T>>;
-type Result<T> = std::result::Result<Tokens<T>, (Tokens<T>, String)>;
+type Result<T> = std::result::Result<Tokens<T>, TokenizeError<T>>;
+type Limited<T> = std::result::Result<Result<T>, BacktrackLimitExceeded>;
 
 /// The token and source span information
 #[derive(Debug, Clone, PartialEq)]
@@ -18,6 +19,141 @@ pub struct TokenAndSpan<T> {
     pub token: T,
     /// The span
     pub span: Span,
+    /// The number of characters consumed by the token, the same as the
+    /// length of the data passed to `make_token`
+    pub len: usize,
+}
+
+/// The ways [`tokenize`] can fail. Every variant holds the tokens found before
+/// the failure and the input left over, starting at the beginning of the token
+/// that couldn't be produced
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeError<T> {
+    /// The tokenizer rejected a character before completing a token
+    Failed {
+        /// The tokens found before the failure
+        tokens: Vec<TokenAndSpan<T>>,
+        /// The input position of the rejected character
+        position: usize,
+        /// The unconsumed input
+        rest: String,
+    },
+    /// The input ran out part way through a token the tokenizer never
+    /// completed
+    IncompleteAtEof {
+        /// The tokens found before the incomplete one
+        tokens: Vec<TokenAndSpan<T>>,
+        /// The characters of the incomplete token
+        rest: String,
+    },
+    /// The tokenizer completed a token then read to the end of the input
+    /// without completing again, the input after the last completion is left
+    /// over
+    TrailingInput {
+        /// The tokens found, including the one at the last completion
+        tokens: Vec<TokenAndSpan<T>>,
+        /// The input after the last completion
+        rest: String,
+    },
+    /// Only produced by [`IndentationTokenizer`], a line was dedented to a
+    /// level that doesn't match any enclosing line
+    InconsistentDedent {
+        /// The tokens found before the line
+        tokens: Vec<TokenAndSpan<T>>,
+        /// The input position of the start of the line
+        position: usize,
+        /// The input from the start of the line
+        rest: String,
+    },
+}
+
+impl<T> TokenizeError<T> {
+    /// The tokens found before the failure
+    #[must_use]
+    pub fn tokens(&self) -> &[TokenAndSpan<T>] {
+        match self {
+            TokenizeError::Failed { tokens, .. }
+            | TokenizeError::IncompleteAtEof { tokens, .. }
+            | TokenizeError::TrailingInput { tokens, .. }
+            | TokenizeError::InconsistentDedent { tokens, .. } => tokens,
+        }
+    }
+
+    /// The unconsumed input
+    #[must_use]
+    pub fn rest(&self) -> &str {
+        match self {
+            TokenizeError::Failed { rest, .. }
+            | TokenizeError::IncompleteAtEof { rest, .. }
+            | TokenizeError::TrailingInput { rest, .. }
+            | TokenizeError::InconsistentDedent { rest, .. } => rest,
+        }
+    }
+
+    /// The tokens found and the unconsumed input, discarding the reason for
+    /// the failure
+    #[must_use]
+    pub fn into_parts(self) -> (Vec<TokenAndSpan<T>>, String) {
+        match self {
+            TokenizeError::Failed { tokens, rest, .. }
+            | TokenizeError::IncompleteAtEof { tokens, rest }
+            | TokenizeError::TrailingInput { tokens, rest }
+            | TokenizeError::InconsistentDedent { tokens, rest, .. } => (tokens, rest),
+        }
+    }
+
+    /// The same failure holding `tokens` instead, along with the tokens it
+    /// held
+    fn replace_tokens<U>(
+        self,
+        tokens: Vec<TokenAndSpan<U>>,
+    ) -> (Vec<TokenAndSpan<T>>, TokenizeError<U>) {
+        match self {
+            TokenizeError::Failed {
+                tokens: found,
+                position,
+                rest,
+            } => (
+                found,
+                TokenizeError::Failed {
+                    tokens,
+                    position,
+                    rest,
+                },
+            ),
+            TokenizeError::IncompleteAtEof {
+                tokens: found,
+                rest,
+            } => (found, TokenizeError::IncompleteAtEof { tokens, rest }),
+            TokenizeError::TrailingInput {
+                tokens: found,
+                rest,
+            } => (found, TokenizeError::TrailingInput { tokens, rest }),
+            TokenizeError::InconsistentDedent {
+                tokens: found,
+                position,
+                rest,
+            } => (
+                found,
+                TokenizeError::InconsistentDedent {
+                    tokens,
+                    position,
+                    rest,
+                },
+            ),
+        }
+    }
+}
+
+/// Error produced by [`tokenize_with_backtrack_limit`] when the tokenizer
+/// reads further past its last completion than the limit allows
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BacktrackLimitExceeded {
+    /// The input position of the character that took the tokenizer over the
+    /// limit
+    pub position: usize,
+    /// The limit that was exceeded
+    pub limit: usize,
 }
 
 /// Tokenization States
@@ -59,12 +195,84 @@ pub trait Tokenizer {
     /// May return `None` to avoid producing a token, in this case the input is
     /// still consumed
     fn make_token(&self, data: &[char]) -> Option<Self::Token>;
+
+    /// As `make_token` but also given the source span of the token. This is
+    /// what tokenization actually calls, the default forwards to `make_token`
+    /// ignoring the span
+    fn make_token_spanned(&self, data: &[char], span: Span) -> Option<Self::Token> {
+        let _ = span;
+        self.make_token(data)
+    }
+
+    /// Called if the input runs out while the tokenizer is `Pending`
+    ///
+    /// Returning true completes the tokenizer anyway, consuming the rest of the
+    /// input. This is how tokenizers anchored to the end of input (see
+    /// `at_eof`) complete, since they report `Pending` instead of `Completed`
+    /// until they know there is nothing left. The default never completes
+    fn complete_at_eof(&mut self) -> bool {
+        false
+    }
 }
 
+/// Combinators for building tokenizers by chaining method calls instead of
+/// nesting the builtins, implemented for every [`Tokenizer`]. They live here
+/// rather than on `Tokenizer` so the trait can still be used as
+/// `Box<dyn Tokenizer>`
+/// ```
+/// # use parsey::tokenizer::{literal, oneof_str, tokenize, TokenizerExt};
+/// let tokenizer = literal("let", "let")
+///     .or(oneof_str("digit", "0123456789"))
+///     .map(|chars| Some(chars.iter().collect::<String>()));
+/// let tokens = tokenize("let1", tokenizer).unwrap();
+/// assert_eq!(tokens[0].token, "let");
+/// assert_eq!(tokens[1].token, "1");
+/// ```
+pub trait TokenizerExt: Tokenizer + Sized {
+    /// Replace the tokens produced by this tokenizer, see [`map`]
+    fn map<S, F: Fn(&[char]) -> Option<S>>(self, make_token: F) -> impl Tokenizer<Token = S> {
+        map(self, make_token)
+    }
+
+    /// Produce the source span of each match instead of a token, see
+    /// [`spans`]
+    fn spans(self) -> impl Tokenizer<Token = Span> {
+        spans(self)
+    }
+
+    /// Try this tokenizer then `other` if this one fails on the first
+    /// character, the same as [`firstof!`](crate::firstof) with two tokenizers
+    fn or(
+        self,
+        other: impl Tokenizer<Token = Self::Token> + 'static,
+    ) -> impl Tokenizer<Token = Self::Token>
+    where
+        Self: 'static,
+    {
+        firstof(vec![Box::new(self), Box::new(other)])
+    }
+
+    /// Match this tokenizer followed immediately by `other`, producing both
+    /// tokens. The same as [`chain_typed!`](crate::chain_typed) with two
+    /// tokenizers, use that directly for longer sequences as chaining `then`
+    /// nests the token vectors
+    fn then(
+        self,
+        other: impl Tokenizer<Token = Self::Token> + 'static,
+    ) -> impl Tokenizer<Token = Vec<Self::Token>>
+    where
+        Self: 'static,
+    {
+        chain_typed(vec![Box::new(self), Box::new(other)])
+    }
+}
+
+impl<T: Tokenizer> TokenizerExt for T {}
+
 /// Persistent tokenization state
 struct TokenizationState<T: Tokenizer> {
-    tokenizer: Rc<RefCell<T>>,
-    chars: Rc<Vec<char>>,
+    tokenizer: T,
+    chars: Vec<char>,
     progress: usize,
     token_start: usize,
     start_line: usize,
@@ -72,44 +280,72 @@ struct TokenizationState<T: Tokenizer> {
     start_char: usize,
     end_char: usize,
     last_result: State,
+    /// The furthest the tokenizer may read past its last completion
+    backtrack_limit: Option<usize>,
 }
 
-impl<T: Tokenizer> Clone for TokenizationState<T> {
-    fn clone(&self) -> Self {
+/// The point to return to once the tokenizer fails, the position just after
+/// the last character it completed on. Only the end of the token changes
+/// between completions so this is all that needs to be saved
+#[derive(Copy, Clone)]
+struct Restart {
+    progress: usize,
+    end_line: usize,
+    end_char: usize,
+}
+
+impl<T: Tokenizer> TokenizationState<T> {
+    fn new(input: &str, tokenizer: T, backtrack_limit: Option<usize>) -> Self {
+        let already_completed = tokenizer.can_match_empty();
         TokenizationState {
-            tokenizer: self.tokenizer.clone(),
-            chars: self.chars.clone(),
-            progress: self.progress,
-            token_start: self.token_start,
-            start_line: self.start_line,
-            end_line: self.end_line,
-            start_char: self.start_char,
-            end_char: self.end_char,
-            last_result: self.last_result,
+            tokenizer,
+            chars: input.chars().collect(),
+            progress: 0,
+            token_start: 0,
+            start_line: 0,
+            end_line: 0,
+            start_char: 0,
+            end_char: 0,
+            last_result: if already_completed {
+                State::Completed
+            } else {
+                State::Pending
+            },
+            backtrack_limit,
         }
     }
-}
 
-impl<T: Tokenizer> TokenizationState<T> {
-    fn tokenize(mut self) -> Result<T::Token> {
+    fn tokenize(mut self) -> Limited<T::Token> {
         // The tokens found so far
         let mut result = Vec::new();
-        // A copy of the state from the last time the tokenizer completed
-        let mut candidate = None;
+        // Where the tokenizer last completed
+        let mut candidate: Option<Restart> = None;
 
-        self.tokenizer.borrow_mut().reset();
+        self.tokenizer.reset();
         while !self.eof() {
-            self.last_result = self.tokenizer.borrow_mut().feed(self.chars[self.progress]);
+            self.last_result = self.tokenizer.feed(self.chars[self.progress]);
             match self.last_result {
-                // Nothing to do until the tokenizer yields something or fails
-                State::Pending => self.advance(),
+                // Nothing to do until the tokenizer yields something or fails,
+                // unless it has gone further past its last completion than
+                // it is allowed to backtrack
+                State::Pending => {
+                    if let (Some(limit), Some(candidate)) = (self.backtrack_limit, &candidate) {
+                        if self.progress - candidate.progress >= limit {
+                            return Err(BacktrackLimitExceeded {
+                                position: self.progress,
+                                limit,
+                            });
+                        }
+                    }
+                    self.advance();
+                }
                 // The tokenizer could produce a token at this position. Don't
                 // actually produce a token yet (we need to check it is the
                 // longest possible token) but save the state
                 State::Completed => {
                     // Need to advance across the current character first
                     self.advance();
-                    candidate = Some(self.clone());
+                    candidate = Some(self.restart_point());
                 }
                 // The tokenizer can't accept any more input. Work out if it
                 // has a token
@@ -122,7 +358,7 @@ impl<T: Tokenizer> TokenizationState<T> {
                         // Reset the state to the point the tokenizer last
                         // completed. Resets candidate to None in the process so
                         // the next Failed will take the other branch
-                        self = candidate.take().unwrap();
+                        self.restart(candidate.take().unwrap());
                         // Add a token to result without moving the progress
                         // marker forward (the current character will be fed to
                         // the tokenizer again in the next loop iteration after
@@ -131,18 +367,32 @@ impl<T: Tokenizer> TokenizationState<T> {
                     } else {
                         // The tokenizer failed without ever completing, fail
                         // immediately
-                        return self.make_error(result);
+                        return Ok(Err(TokenizeError::Failed {
+                            position: self.progress,
+                            rest: self.rest(),
+                            tokens: result,
+                        }));
                     }
                 }
             }
         }
 
+        // A tokenizer anchored to the end of input will still be Pending here,
+        // give it the chance to complete now that there is nothing left. This
+        // takes priority over any earlier candidate as it covers more input
+        if let State::Pending = self.last_result {
+            if self.progress > self.token_start && self.tokenizer.complete_at_eof() {
+                self.last_result = State::Completed;
+                candidate = Some(self.restart_point());
+            }
+        }
+
         // If the tokenizer completes on the last character of the input or only
         // produces Pending after the last completion the loop above can exit
         // without using the candidate. If there is one still around restore to
         // it and produce a token
         if let Some(candidate) = candidate {
-            self = candidate;
+            self.restart(candidate);
             self.complete(&mut result);
         }
 
@@ -151,35 +401,59 @@ impl<T: Tokenizer> TokenizationState<T> {
         // current position and the end of input so anything left over is
         // unconsumed
         if !self.eof() {
-            return self.make_error(result);
+            return Ok(Err(TokenizeError::TrailingInput {
+                rest: self.rest(),
+                tokens: result,
+            }));
         }
 
         // If there were no completions we will reach this point with
         // last_result == Pending and want to produce an error. Failed is
         // impossible as the main loop either falls back to the last completion
         // or bails out when it encounters a failure.
-        match self.last_result {
+        Ok(match self.last_result {
             State::Completed => Ok(result),
-            State::Pending => self.make_error(result),
+            State::Pending => Err(TokenizeError::IncompleteAtEof {
+                rest: self.rest(),
+                tokens: result,
+            }),
             State::Failed => unreachable!(),
+        })
+    }
+
+    /// The point to restart from if the tokenizer fails later
+    fn restart_point(&self) -> Restart {
+        Restart {
+            progress: self.progress,
+            end_line: self.end_line,
+            end_char: self.end_char,
         }
     }
 
+    /// Return to the point the tokenizer last completed
+    fn restart(&mut self, restart: Restart) {
+        self.progress = restart.progress;
+        self.end_line = restart.end_line;
+        self.end_char = restart.end_char;
+        self.last_result = State::Completed;
+    }
+
     /// Update tokenization state based on the current character
     fn advance(&mut self) {
-        if self.chars[self.progress] == '\n' {
-            self.end_line += 1;
-            self.end_char = 0;
-        } else {
-            self.end_char += 1;
-        }
+        let mut position = CharacterPosition {
+            row: self.end_line,
+            col: self.end_char,
+        };
+        position.advance(&self.chars, self.progress);
+        self.end_line = position.row;
+        self.end_char = position.col;
         self.progress += 1;
     }
 
-    /// Produce the error result, errors contain all of the tokens found and any
-    /// input left over
-    fn make_error(self, result: Tokens<T::Token>) -> Result<T::Token> {
-        Err((result, self.chars[self.token_start..].iter().collect()))
+    /// The input left over for an error, everything from the start of the
+    /// current token
+    fn rest(&self) -> String {
+        self.chars[self.token_start..].iter().collect()
     }
 
     /// True if the tokenizer has reached end of input, false otherwise
@@ -190,26 +464,27 @@ impl<T: Tokenizer> TokenizationState<T> {
     /// If the tokenizer produces a token add it to result then update
     /// tokenization state
     fn complete(&mut self, result: &mut Tokens<T::Token>) {
+        let span = Span::new(
+            self.start_line,
+            self.end_line,
+            self.start_char,
+            self.end_char,
+        );
         // Tokenizers can return None from make token to consume the input but
         // not add a token to the result (e.g whitespace or comments)
         if let Some(token) = self
             .tokenizer
-            .borrow()
-            .make_token(&self.chars[self.token_start..self.progress])
+            .make_token_spanned(&self.chars[self.token_start..self.progress], span)
         {
             result.push(TokenAndSpan {
                 token,
-                span: Span::new(
-                    self.start_line,
-                    self.end_line,
-                    self.start_char,
-                    self.end_char,
-                ),
+                span,
+                len: self.progress - self.token_start,
             });
         }
 
         // Reset the tokenizer for the next token
-        self.tokenizer.borrow_mut().reset();
+        self.tokenizer.reset();
 
         // Update the variables tracking the beginning of the new token
         self.token_start = self.progress;
@@ -218,31 +493,446 @@ impl<T: Tokenizer> TokenizationState<T> {
     }
 }
 
+/// The kind of each token in `tokens`, one character per token as chosen by
+/// `kind`. A grammar written over these characters rather than the source
+/// text can then recognise or parse the tokens, so lexing and parsing can be
+/// done by different tools: the tokenizer deals with whitespace, multi
+/// character tokens and the like, the grammar only sees one terminal per
+/// token. Position `i` in the result is `tokens[i]`, so the leaves of a parse
+/// tree can be matched back up with the tokens.
+/// ```
+/// # use parsey::{grammar, recognise};
+/// # use parsey::tokenizer::{eat, literal, oneof, token_kinds, tokenize, Tokenizer};
+/// # use parsey::{firstof, tokenizers};
+/// let tokenizer = firstof!(
+///     oneof("digit", "0123456789".chars().collect()),
+///     literal("plus", "+"),
+///     eat(literal("space", " "))
+/// );
+/// let grammar = grammar! {
+///     Sum -> Sum "+" "n";
+///     Sum -> "n";
+/// };
+/// let tokens = tokenize("1 + 2", tokenizer).unwrap();
+/// let kinds = token_kinds(&tokens, |token| if token.tag == "digit" { 'n' } else { '+' });
+/// assert_eq!(kinds, "n+n");
+/// assert!(recognise(&grammar, kinds));
+/// ```
+pub fn token_kinds<T>(tokens: &[TokenAndSpan<T>], kind: impl Fn(&T) -> char) -> String {
+    tokens.iter().map(|token| kind(&token.token)).collect()
+}
+
 /// Tokenize a string
 ///
 /// # Errors
-/// If the tokenizer fails or consumes the whole input without completing it
-/// returns all of the tokens found and the remaining input if any
+/// If the tokenizer fails or consumes the whole input without completing,
+/// [`TokenizeError`] says which along with all of the tokens found and the
+/// remaining input
 pub fn tokenize<T, S: AsRef<str>>(input: S, tokenizer: impl Tokenizer<Token = T>) -> Result<T> {
-    let already_completed = tokenizer.can_match_empty();
-    TokenizationState {
-        tokenizer: Rc::new(RefCell::new(tokenizer)),
-        chars: Rc::new(input.as_ref().chars().collect()),
-        progress: 0,
-        token_start: 0,
-        start_line: 0,
-        end_line: 0,
-        start_char: 0,
-        end_char: 0,
-        last_result: if already_completed {
-            State::Completed
-        } else {
-            State::Pending
-        },
-    }
-    .tokenize()
+    match TokenizationState::new(input.as_ref(), tokenizer, None).tokenize() {
+        Ok(result) => result,
+        Err(_) => unreachable!("Tokenizing without a backtrack limit can't exceed it"),
+    }
+}
+
+/// As [`tokenize`] but fail if the tokenizer reads more than `limit`
+/// characters past the last point it could have produced a token. Finding the
+/// longest match means feeding those characters again after backtracking, on
+/// untrusted input a tokenizer that keeps going without completing (e.g. an
+/// unterminated string after a shorter match) can make this expensive. The
+/// limit bounds the work per token.
+///
+/// # Errors
+/// If the tokenizer goes over the limit. Otherwise the result of tokenizing
+/// as for [`tokenize`]
+pub fn tokenize_with_backtrack_limit<T, S: AsRef<str>>(
+    input: S,
+    tokenizer: impl Tokenizer<Token = T>,
+    limit: usize,
+) -> std::result::Result<Result<T>, BacktrackLimitExceeded> {
+    TokenizationState::new(input.as_ref(), tokenizer, Some(limit)).tokenize()
+}
+
+/// Tokenize as much of a string as possible, returning the tokens found and
+/// the number of characters consumed. Unlike [`tokenize`] leftover input isn't
+/// an error, tokenizing can be resumed from the returned position once more
+/// input is available (e.g. the next chunk of a stream or line of a REPL)
+#[must_use]
+pub fn tokenize_partial<T, S: AsRef<str>>(
+    input: S,
+    tokenizer: impl Tokenizer<Token = T>,
+) -> (Vec<TokenAndSpan<T>>, usize) {
+    let len = input.as_ref().chars().count();
+    match tokenize(input, tokenizer) {
+        Ok(tokens) => (tokens, len),
+        // The error holds everything from the start of the token that failed
+        Err(error) => {
+            let (tokens, rest) = error.into_parts();
+            (tokens, len - rest.chars().count())
+        }
+    }
 }
 
 // fn repeated<T, D>(token: impl Tokenizer<Token = T>, delimeter: Option<impl Tokenizer<Token = D>>, min: usize, max: usize) -> impl Tokenizer<Token = T> {
 //     todo!()
 // }
+
+syntax_abuse::tests! {
+    /// Produces its own span as the token
+    struct Located<T>(T);
+
+    impl<T: Tokenizer> Tokenizer for Located<T> {
+        type Token = Span;
+
+        fn reset(&mut self) {
+            self.0.reset();
+        }
+
+        fn can_match_empty(&self) -> bool {
+            self.0.can_match_empty()
+        }
+
+        fn feed(&mut self, c: char) -> State {
+            self.0.feed(c)
+        }
+
+        fn make_token(&self, _: &[char]) -> Option<Self::Token> {
+            None
+        }
+
+        fn make_token_spanned(&self, _: &[char], span: Span) -> Option<Self::Token> {
+            Some(span)
+        }
+    }
+
+    tests! {
+        make_token_spanned:
+
+        testcase! {
+            stores_span,
+            tokenize(
+                "ab\ncd",
+                Located(firstof!(
+                    literal("ab", "ab"),
+                    literal("newline", "\n"),
+                    literal("cd", "cd")
+                ))
+            )
+            .map(|tokens| tokens.into_iter().map(|token| token.token).collect::<Vec<_>>()),
+            Ok(vec![
+                Span::new(0, 0, 0, 2),
+                Span::new(0, 1, 2, 0),
+                Span::new(1, 1, 0, 2)
+            ])
+        }
+
+        testcase! {
+            nested,
+            tokenize(
+                "a b",
+                firstof!(
+                    Located(literal("a", "a")),
+                    eat(literal("space", " ")),
+                    Located(literal("b", "b"))
+                )
+            )
+            .map(|tokens| tokens.into_iter().map(|token| token.token).collect::<Vec<_>>()),
+            Ok(vec![Span::new(0, 0, 0, 1), Span::new(0, 0, 2, 3)])
+        }
+
+        testcase! {
+            default_ignores_span,
+            literal("a", "a").make_token_spanned(&['a'], Span::new(1, 1, 5, 6)),
+            literal("a", "a").make_token(&['a'])
+        }
+    }
+
+    tests! {
+        backtrack_limit:
+
+        // Either a single `a` or a run of `a`s closed by `b`, every `a` after
+        // the first is read past a completion
+        testdata! {
+            RUN: ??? = from_fn("run", || (0, false), |(count, closed): &mut (usize, bool), c| {
+                match c {
+                    _ if *closed => State::Failed,
+                    'a' => {
+                        *count += 1;
+                        if *count == 1 { State::Completed } else { State::Pending }
+                    }
+                    'b' if *count > 0 => {
+                        *closed = true;
+                        State::Completed
+                    }
+                    _ => State::Failed,
+                }
+            });
+        }
+
+        fn tags(input: &str, limit: usize) -> std::result::Result<Vec<&'static str>, BacktrackLimitExceeded> {
+            tokenize_with_backtrack_limit(input, RUN!(), limit)
+                .map(|tokens| tokens.unwrap().into_iter().map(|token| token.token.tag).collect())
+        }
+
+        testcase! {
+            within_limit,
+            tags("aaab", 3),
+            Ok(vec!["run"])
+        }
+
+        testcase! {
+            exceeded,
+            tags(&"a".repeat(1000), 10),
+            Err(BacktrackLimitExceeded { position: 11, limit: 10 })
+        }
+
+        testcase! {
+            no_completion_unlimited,
+            tokenize_with_backtrack_limit("aaaa", literal("long", "aaaa"), 0)
+                .map(|tokens| tokens.map(|tokens| tokens.len())),
+            Ok(Ok(1))
+        }
+
+        testcase! {
+            same_as_tokenize,
+            tokenize_with_backtrack_limit("aaa", RUN!(), 5),
+            Ok(tokenize("aaa", RUN!()))
+        }
+    }
+
+    tests! {
+        tokenize_partial:
+
+        fn partial(input: &str) -> (Vec<&'static str>, usize) {
+            let (tokens, consumed) = tokenize_partial(
+                input,
+                firstof!(literal("let", "let"), eat(literal("space", " ")), literal("x", "x"))
+            );
+            (tokens.into_iter().map(|token| token.token.tag).collect(), consumed)
+        }
+
+        testcase! {
+            everything,
+            partial("let x"),
+            (vec!["let", "x"], 5)
+        }
+
+        testcase! {
+            stops_at_failure,
+            partial("let x?"),
+            (vec!["let", "x"], 5)
+        }
+
+        testcase! {
+            incomplete_token,
+            partial("let le"),
+            (vec!["let"], 4)
+        }
+
+        testcase! {
+            nothing,
+            partial("?"),
+            (vec![], 0)
+        }
+
+        testcase! {
+            resume,
+            {
+                let input = "let x let x";
+                let (first, consumed) = partial(&input[..6]);
+                let (second, _) = partial(&input.chars().skip(consumed).collect::<String>());
+                [first, second].concat()
+            },
+            vec!["let", "x", "let", "x"]
+        }
+
+        testcase! {
+            counts_characters,
+            tokenize_partial("\u{e9}\u{e9}x", literal("e", "\u{e9}\u{e9}")).1,
+            2
+        }
+    }
+
+    tests! {
+        len:
+
+        fn lengths<T: std::fmt::Debug>(tokens: Result<T>) -> Vec<usize> {
+            tokens.unwrap().into_iter().map(|token| token.len).collect()
+        }
+
+        testcase! {
+            multiple_characters,
+            lengths(tokenize(
+                "let x",
+                firstof!(literal("let", "let"), literal("space", " "), literal("x", "x"))
+            )),
+            vec![3, 1, 1]
+        }
+
+        testcase! {
+            spans_lines,
+            lengths(tokenize("ab\ncd", literal("multiline", "ab\ncd"))),
+            vec![5]
+        }
+
+        testcase! {
+            counts_characters_not_bytes,
+            lengths(tokenize("\u{e9}t\u{e9}", literal("summer", "\u{e9}t\u{e9}"))),
+            vec![3]
+        }
+
+        testcase! {
+            eaten_input_not_counted,
+            lengths(tokenize(
+                "a   b",
+                firstof!(literal("a", "a"), eat(literal("space", "   ")), literal("b", "b"))
+            )),
+            vec![1, 1]
+        }
+    }
+
+    tests! {
+        tokenize_error:
+
+        fn kind<T>(result: Result<T>) -> (&'static str, Vec<usize>, String) {
+            let error = result.err().unwrap();
+            let kind = match error {
+                TokenizeError::Failed { .. } => "failed",
+                TokenizeError::IncompleteAtEof { .. } => "incomplete",
+                TokenizeError::TrailingInput { .. } => "trailing",
+                TokenizeError::InconsistentDedent { .. } => "dedent",
+            };
+            let lengths = error.tokens().iter().map(|token| token.len).collect();
+            (kind, lengths, String::from(error.rest()))
+        }
+
+        testcase! {
+            failed,
+            tokenize("ab?", firstof!(literal("a", "a"), literal("b", "b"))),
+            Err(TokenizeError::Failed {
+                tokens: vec![
+                    TokenAndSpan {
+                        token: Token { tag: "a", contents: String::from("a") },
+                        span: Span::new(0, 0, 0, 1),
+                        len: 1
+                    },
+                    TokenAndSpan {
+                        token: Token { tag: "b", contents: String::from("b") },
+                        span: Span::new(0, 0, 1, 2),
+                        len: 1
+                    }
+                ],
+                position: 2,
+                rest: String::from("?")
+            })
+        }
+
+        testcase! {
+            failed_part_way_through_token,
+            kind(tokenize(
+                "x abd",
+                firstof!(literal("x", "x"), literal("space", " "), literal("abc", "abc"))
+            )),
+            ("failed", vec![1, 1], String::from("abd"))
+        }
+
+        testcase! {
+            incomplete_at_eof,
+            kind(tokenize(
+                "x ab",
+                firstof!(literal("x", "x"), literal("space", " "), literal("abc", "abc"))
+            )),
+            ("incomplete", vec![1, 1], String::from("ab"))
+        }
+
+        testcase! {
+            trailing_input,
+            kind(tokenize("aab", longestof!(literal("a", "a"), literal("abc", "abc")))),
+            ("trailing", vec![1, 1], String::from("b"))
+        }
+
+        testcase! {
+            into_parts,
+            tokenize("a?", literal("a", "a")).err().unwrap().into_parts(),
+            (
+                vec![TokenAndSpan {
+                    token: Token { tag: "a", contents: String::from("a") },
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
+                }],
+                String::from("?")
+            )
+        }
+    }
+
+    tests! {
+        tokenizer_ext:
+
+        fn tokens<T: std::fmt::Debug>(result: Result<T>) -> Vec<T> {
+            result.unwrap().into_iter().map(|token| token.token).collect()
+        }
+
+        fn token(tag: &'static str, contents: &str) -> Token {
+            Token { tag, contents: String::from(contents) }
+        }
+
+        testcase! {
+            map,
+            tokens(tokenize("aaa", literal("a", "a").map(|chars| Some(chars.len())))),
+            vec![1, 1, 1]
+        }
+
+        testcase! {
+            or,
+            tokens(tokenize("ab", literal("a", "a").or(literal("b", "b")))),
+            vec![token("a", "a"), token("b", "b")]
+        }
+
+        testcase! {
+            or_same_as_firstof,
+            tokenize(
+                "a b?",
+                literal("a", "a").or(eat(literal("space", " "))).or(literal("b", "b"))
+            ),
+            tokenize(
+                "a b?",
+                firstof!(literal("a", "a"), eat(literal("space", " ")), literal("b", "b"))
+            )
+        }
+
+        testcase! {
+            then,
+            tokens(tokenize("abab", literal("a", "a").then(literal("b", "b")))),
+            vec![
+                vec![token("a", "a"), token("b", "b")],
+                vec![token("a", "a"), token("b", "b")]
+            ]
+        }
+
+        testcase! {
+            then_needs_both,
+            tokenize("aa", literal("a", "a").then(literal("b", "b"))).is_err(),
+            true
+        }
+
+        testcase! {
+            boxed,
+            {
+                let boxed: Box<dyn Tokenizer<Token = Token>> = Box::new(literal("a", "a"));
+                tokens(tokenize("ba", literal("b", "b").or(boxed)))
+            },
+            vec![token("b", "b"), token("a", "a")]
+        }
+
+        testcase! {
+            combined,
+            tokens(tokenize(
+                "1+23",
+                oneof_str("digit", "0123456789")
+                    .or(literal("plus", "+"))
+                    .map(|chars| Some(chars.iter().collect::<String>()))
+            )),
+            vec!["1", "+", "2", "3"]
+        }
+    }
+}
@@ -1,29 +1,53 @@
-use super::{State, Tokenizer};
+use super::{Span, State, Tokenizer};
 
 #[allow(unreachable_pub)]
-pub use chain::chain;
+pub use anychar::any_char;
+#[allow(unreachable_pub)]
+pub use ateof::at_eof;
+#[allow(unreachable_pub)]
+pub use categorize::categorize;
+#[allow(unreachable_pub)]
+pub use chain::{chain, chain_typed};
+#[allow(unreachable_pub)]
+pub use count::count;
 #[allow(unreachable_pub)]
 pub use eater::eat;
 #[allow(unreachable_pub)]
 pub use firstof::firstof;
 #[allow(unreachable_pub)]
+pub use fromfn::from_fn;
+#[allow(unreachable_pub)]
+pub use keywords::keywords;
+#[allow(unreachable_pub)]
 pub use literal::literal;
 #[allow(unreachable_pub)]
 pub use longestof::longestof;
 #[allow(unreachable_pub)]
 pub use map::map;
 #[allow(unreachable_pub)]
-pub use oneof::oneof;
+pub use oneof::{oneof, oneof_str};
+#[allow(unreachable_pub)]
+pub use punctuation::punctuation;
+#[allow(unreachable_pub)]
+pub use spans::spans;
 #[allow(unreachable_pub)]
 pub use empty::empty;
 
+mod anychar;
+mod ateof;
+mod categorize;
 mod chain;
+mod count;
 mod eater;
 mod firstof;
+mod fromfn;
+mod keywords;
 mod literal;
 mod longestof;
 mod map;
 mod oneof;
+mod punctuation;
+mod spans;
 mod empty;
 
 /// Default token type for builtin tokenizers
@@ -41,6 +65,9 @@ trait StateMachine {
     fn reset(&mut self);
     fn can_match_empty(&self) -> bool;
     fn feed(&mut self, c: char) -> State;
+    fn complete_at_eof(&mut self) -> bool {
+        false
+    }
 }
 
 struct BasicTokenizer<S: StateMachine> {
@@ -63,6 +90,10 @@ impl<S: StateMachine> Tokenizer for BasicTokenizer<S> {
         self.state.feed(c)
     }
 
+    fn complete_at_eof(&mut self) -> bool {
+        self.state.complete_at_eof()
+    }
+
     fn make_token(&self, data: &[char]) -> Option<Self::Token> {
         Some(Token {
             tag: self.tag,
@@ -0,0 +1,128 @@
+use super::{BasicTokenizer, State, StateMachine, Token, Tokenizer};
+
+struct AnyChar {
+    done: bool,
+}
+
+impl StateMachine for AnyChar {
+    fn reset(&mut self) {
+        self.done = false;
+    }
+
+    fn can_match_empty(&self) -> bool {
+        false
+    }
+
+    fn feed(&mut self, _: char) -> State {
+        if self.done {
+            return State::Failed;
+        }
+        self.done = true;
+        State::Completed
+    }
+}
+
+/// Match exactly one arbitrary character
+#[must_use]
+pub fn any_char(tag: &'static str) -> impl Tokenizer<Token = Token> {
+    BasicTokenizer {
+        tag,
+        state: AnyChar { done: false },
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{ tokenize, TokenAndSpan, Span, TokenizeError };
+
+    testcase! {
+        single,
+        tokenize("a", any_char("char")),
+        Ok(
+            vec![
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("a")
+                    },
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
+                }
+            ]
+        )
+    }
+
+    testcase! {
+        several,
+        tokenize("abc", any_char("char")),
+        Ok(
+            vec![
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("a")
+                    },
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
+                },
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("b")
+                    },
+                    span: Span::new(0, 0, 1, 2),
+                    len: 1
+                },
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("c")
+                    },
+                    span: Span::new(0, 0, 2, 3),
+                    len: 1
+                }
+            ]
+        )
+    }
+
+    testcase! {
+        newline,
+        tokenize("a\nb", any_char("char")),
+        Ok(
+            vec![
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("a")
+                    },
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
+                },
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("\n")
+                    },
+                    span: Span::new(0, 1, 1, 0),
+                    len: 1
+                },
+                TokenAndSpan {
+                    token: Token {
+                        tag: "char",
+                        contents: String::from("b")
+                    },
+                    span: Span::new(1, 1, 0, 1),
+                    len: 1
+                }
+            ]
+        )
+    }
+
+    testcase! {
+        empty,
+        tokenize("", any_char("char")),
+        Err(TokenizeError::IncompleteAtEof {
+            tokens: vec![],
+            rest: String::new()
+        })
+    }
+}
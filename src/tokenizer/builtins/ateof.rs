@@ -0,0 +1,192 @@
+use super::{Span, State, Tokenizer};
+
+struct AtEof<T> {
+    tokenizer: T,
+    completed: bool,
+}
+
+impl<T: Tokenizer> Tokenizer for AtEof<T> {
+    type Token = T::Token;
+
+    fn reset(&mut self) {
+        self.completed = false;
+        self.tokenizer.reset();
+    }
+
+    fn can_match_empty(&self) -> bool {
+        false
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        // Completing here would let the main tokenizer loop produce a token in
+        // the middle of the input, instead remember the completion and wait to
+        // find out if there is any more input
+        match self.tokenizer.feed(c) {
+            State::Completed => {
+                self.completed = true;
+                State::Pending
+            }
+            State::Pending => {
+                self.completed = false;
+                State::Pending
+            }
+            State::Failed => State::Failed,
+        }
+    }
+
+    fn make_token(&self, data: &[char]) -> Option<Self::Token> {
+        self.tokenizer.make_token(data)
+    }
+
+    fn make_token_spanned(&self, data: &[char], span: Span) -> Option<Self::Token> {
+        self.tokenizer.make_token_spanned(data, span)
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        self.completed || self.tokenizer.complete_at_eof()
+    }
+}
+
+/// Anchor a tokenizer to the end of input
+///
+/// The sub-tokenizer only completes if the token it matches runs to the end of
+/// the input, otherwise it fails. `tokenize` already fails if there is input
+/// left over once tokenization stops, this is useful when the tokenizer is
+/// combined with others (e.g. a trailing comment which must be last):
+/// ```
+/// # use parsey::{firstof, tokenizers, tokenizer::{at_eof, literal, tokenize}};
+/// let tokenizer = || firstof!(literal("a", "a"), at_eof(literal("end", "end")));
+/// assert!(tokenize("aaend", tokenizer()).is_ok());
+/// assert!(tokenize("aenda", tokenizer()).is_err());
+/// ```
+///
+/// Empty matches are never anchored to the end of input
+#[must_use]
+pub fn at_eof<T>(tokenizer: impl Tokenizer<Token = T>) -> impl Tokenizer<Token = T> {
+    AtEof {
+        tokenizer,
+        completed: false,
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{literal, map, oneof, tokenize, Span, Token, TokenAndSpan, TokenizeError};
+
+    testdata! {
+        PREFIX: ??? = firstof!(
+            literal("a", "a"),
+            literal("b", "b")
+        );
+
+        ANCHORED: ??? = firstof!(
+            literal("a", "a"),
+            at_eof(literal("b", "b"))
+        );
+    }
+
+    fn token(tag: &'static str, contents: &str, start: usize) -> TokenAndSpan<Token> {
+        TokenAndSpan {
+            token: Token {
+                tag,
+                contents: String::from(contents),
+            },
+            span: Span::new(0, 0, start, start + contents.len()),
+            len: contents.len(),
+        }
+    }
+
+    tests! {
+        prefix:
+
+        testcase! {
+            at_end,
+            tokenize("ab", PREFIX!()),
+            Ok(vec![token("a", "a", 0), token("b", "b", 1)])
+        }
+
+        testcase! {
+            in_the_middle,
+            tokenize("ba", PREFIX!()),
+            Ok(vec![token("b", "b", 0), token("a", "a", 1)])
+        }
+    }
+
+    tests! {
+        anchored:
+
+        testcase! {
+            at_end,
+            tokenize("ab", ANCHORED!()),
+            Ok(vec![token("a", "a", 0), token("b", "b", 1)])
+        }
+
+        testcase! {
+            in_the_middle,
+            tokenize("ba", ANCHORED!()),
+            Err(TokenizeError::Failed {
+                tokens: vec![],
+                position: 1,
+                rest: String::from("ba")
+            })
+        }
+
+        testcase! {
+            whole_input,
+            tokenize("b", at_eof(literal("b", "b"))),
+            Ok(vec![token("b", "b", 0)])
+        }
+
+        testcase! {
+            empty,
+            tokenize("", at_eof(literal("b", "b"))),
+            Err(TokenizeError::IncompleteAtEof {
+                tokens: vec![],
+                rest: String::new()
+            })
+        }
+
+        testcase! {
+            can_match_empty,
+            at_eof(literal("e", "")).can_match_empty(),
+            false
+        }
+    }
+
+    tests! {
+        nested:
+
+        testcase! {
+            in_chain,
+            tokenize(
+                "xyz",
+                chain!("chain", literal("x", "x"), at_eof(oneof("yz", hashset!['y', 'z'])))
+            ),
+            Err(TokenizeError::Failed {
+                tokens: vec![],
+                position: 2,
+                rest: String::from("xyz")
+            })
+        }
+
+        testcase! {
+            in_chain_at_end,
+            tokenize(
+                "xy",
+                chain!("chain", literal("x", "x"), at_eof(oneof("yz", hashset!['y', 'z'])))
+            ),
+            Ok(vec![token("chain", "xy", 0)])
+        }
+
+        testcase! {
+            beats_shorter_match,
+            tokenize("ab", longestof!(literal("a", "a"), at_eof(literal("ab", "ab")))),
+            Ok(vec![token("ab", "ab", 0)])
+        }
+
+        testcase! {
+            mapped,
+            tokenize("b", map(at_eof(literal("b", "b")), |_| Some(0))),
+            Ok(vec![TokenAndSpan { token: 0, span: Span::new(0, 0, 0, 1) , len: 1}])
+        }
+    }
+}
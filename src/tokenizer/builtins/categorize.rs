@@ -0,0 +1,154 @@
+use super::{Span, State, Token, Tokenizer};
+
+struct Categorize<T, F> {
+    tokenizer: T,
+    category: F,
+}
+
+impl<T, F> Categorize<T, F>
+where
+    F: Fn(&Token) -> &'static str,
+{
+    fn retag(&self, token: Option<Token>) -> Option<Token> {
+        token.map(|token| Token {
+            tag: (self.category)(&token),
+            ..token
+        })
+    }
+}
+
+impl<T, F> Tokenizer for Categorize<T, F>
+where
+    T: Tokenizer<Token = Token>,
+    F: Fn(&Token) -> &'static str,
+{
+    type Token = Token;
+
+    fn reset(&mut self) {
+        self.tokenizer.reset();
+    }
+
+    fn can_match_empty(&self) -> bool {
+        self.tokenizer.can_match_empty()
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        self.tokenizer.feed(c)
+    }
+
+    fn make_token(&self, data: &[char]) -> Option<Self::Token> {
+        self.retag(self.tokenizer.make_token(data))
+    }
+
+    fn make_token_spanned(&self, data: &[char], span: Span) -> Option<Self::Token> {
+        self.retag(self.tokenizer.make_token_spanned(data, span))
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        self.tokenizer.complete_at_eof()
+    }
+}
+
+/// Run a tokenizer then choose the tag of each token it produces from the
+/// token itself. `category` is given the token with the tag from `tokenizer`
+/// and returns the tag to use instead, e.g. to pick out keywords after
+/// matching every word with a single identifier tokenizer rather than listing
+/// the keywords in the tokenizer
+/// ```
+/// # use parsey::tokenizer::{categorize, from_fn, tokenize, State};
+/// let words = categorize(
+///     from_fn(
+///         "identifier",
+///         || (),
+///         |(), c| if c.is_ascii_lowercase() { State::Completed } else { State::Failed },
+///     ),
+///     |token| if token.contents == "if" { "keyword" } else { token.tag },
+/// );
+/// assert_eq!(tokenize("if", words).unwrap()[0].token.tag, "keyword");
+/// ```
+#[must_use]
+pub fn categorize<T, F>(tokenizer: T, category: F) -> impl Tokenizer<Token = Token>
+where
+    T: Tokenizer<Token = Token>,
+    F: Fn(&Token) -> &'static str,
+{
+    Categorize {
+        tokenizer,
+        category,
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{eat, from_fn, literal, oneof, tokenize, Span, TokenAndSpan};
+
+    testdata! {
+        WORDS: ??? = firstof!(
+            categorize(
+                from_fn(
+                    "identifier",
+                    || (),
+                    |(), c| if c.is_ascii_lowercase() { State::Completed } else { State::Failed }
+                ),
+                |token| match token.contents.as_str() {
+                    "if" | "while" => "keyword",
+                    _ => token.tag,
+                }
+            ),
+            eat(oneof("space", hashset![' ']))
+        );
+    }
+
+    fn tags(input: &str) -> Vec<(&'static str, String)> {
+        tokenize(input, WORDS!())
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.token.tag, token.token.contents))
+            .collect()
+    }
+
+    testcase! {
+        keywords,
+        tags("if x while y"),
+        vec![
+            ("keyword", String::from("if")),
+            ("identifier", String::from("x")),
+            ("keyword", String::from("while")),
+            ("identifier", String::from("y"))
+        ]
+    }
+
+    testcase! {
+        longest_match_first,
+        // Categorized after the whole word is matched, so words starting with
+        // a keyword stay identifiers
+        tags("iffy whiled"),
+        vec![
+            ("identifier", String::from("iffy")),
+            ("identifier", String::from("whiled"))
+        ]
+    }
+
+    testcase! {
+        keeps_span,
+        tokenize("ab while", WORDS!()).unwrap()[1].clone(),
+        TokenAndSpan {
+            token: Token { tag: "keyword", contents: String::from("while") },
+            span: Span::new(0, 0, 3, 8),
+            len: 5
+        }
+    }
+
+    testcase! {
+        sees_original_tag,
+        tokenize(
+            "ab",
+            categorize(literal("original", "ab"), |token| {
+                if token.tag == "original" { "seen" } else { "unseen" }
+            })
+        )
+        .unwrap()[0]
+        .token
+        .tag,
+        "seen"
+    }
+}
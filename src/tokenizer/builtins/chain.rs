@@ -4,11 +4,53 @@ struct Chain<T> {
     tokenizers: Vec<Box<dyn Tokenizer<Token = T>>>,
     failed: bool,
     progress: usize,
+    /// Characters fed since the last reset
+    fed: usize,
+    /// True if the current sub-tokenizer completed on the last character
+    just_completed: bool,
+    /// The number of characters fed when each sub-tokenizer before the
+    /// current one finished, i.e. where its part of the token ends
+    ends: Vec<usize>,
 }
 
 impl<T> Chain<T> {
+    fn new(tokenizers: Vec<Box<dyn Tokenizer<Token = T>>>) -> Self {
+        Chain {
+            tokenizers,
+            failed: false,
+            progress: 0,
+            fed: 0,
+            just_completed: false,
+            ends: Vec::new(),
+        }
+    }
+
     fn the_rest_are_empty(&mut self) -> bool {
-        self.tokenizers[self.progress..].iter().all(Tokenizer::can_match_empty)
+        self.tokenizers[self.progress + 1..].iter().all(Tokenizer::can_match_empty)
+    }
+
+    /// Move on to the next sub-tokenizer, the current one ends before the
+    /// character being fed
+    fn next_tokenizer(&mut self) {
+        self.ends.push(self.fed - 1);
+        self.progress += 1;
+        self.just_completed = false;
+    }
+
+    /// Split the characters of a token produced by the chain into the parts
+    /// matched by each sub-tokenizer. The main tokenizer loop may have settled
+    /// on an earlier completion than the last one seen so anything past the end
+    /// of the token is cut off
+    fn parts<'a>(&self, data: &'a [char]) -> Vec<&'a [char]> {
+        let mut start = 0;
+        (0..self.tokenizers.len())
+            .map(|idx| {
+                let end = self.ends.get(idx).map_or(data.len(), |end| (*end).min(data.len()));
+                let part = &data[start..end];
+                start = end;
+                part
+            })
+            .collect()
     }
 }
 
@@ -16,6 +58,9 @@ impl<T> StateMachine for Chain<T> {
     fn reset(&mut self) {
         self.failed = false;
         self.progress = 0;
+        self.fed = 0;
+        self.just_completed = false;
+        self.ends.clear();
         for tokenizer in &mut self.tokenizers {
             tokenizer.reset();
         }
@@ -29,31 +74,38 @@ impl<T> StateMachine for Chain<T> {
         if self.failed || self.progress == self.tokenizers.len() {
             return State::Failed;
         }
+        self.fed += 1;
 
         // The loop allows the match arms to jump back to the top if the match
         // by not returning
         loop {
             match self.tokenizers[self.progress].feed(c) {
-                State::Pending => return State::Pending,
+                State::Pending => {
+                    self.just_completed = false;
+                    return State::Pending;
+                }
                 State::Failed => {
-                    // The current tokenizer can't accept the current character
-                    // but if it can match the empty string we can skip it
-                    if self.tokenizers[self.progress].can_match_empty() {
-                        self.progress += 1;
+                    // Sub-tokenizers are greedy as in count, once the current
+                    // one can't accept the character move on to the next if
+                    // the current one completed on the character before. If
+                    // it can match the empty string we can skip it too
+                    if self.just_completed || self.tokenizers[self.progress].can_match_empty() {
+                        self.next_tokenizer();
+                        if self.progress == self.tokenizers.len() {
+                            return State::Failed;
+                        }
                     } else {
                         return State::Failed;
                     }
                 }
                 State::Completed => {
-                    //TODO: Need to implement the Completed, Failed sequence
-                    // here before advancing progress
-                    self.progress += 1;
+                    self.just_completed = true;
 
                     // If the last tokenizer just completed or the remaining
                     // tokenizers can match the empty string then complete. The
                     // main tokenizer loop will still feed more characters if
                     // there are any
-                    return if self.progress == self.tokenizers.len() || self.the_rest_are_empty() {
+                    return if self.the_rest_are_empty() {
                         State::Completed
                     } else {
                         State::Pending
@@ -62,6 +114,48 @@ impl<T> StateMachine for Chain<T> {
             }
         }
     }
+
+    fn complete_at_eof(&mut self) -> bool {
+        self.progress < self.tokenizers.len()
+            && self.tokenizers[self.progress].complete_at_eof()
+            && self.tokenizers[self.progress + 1..].iter().all(Tokenizer::can_match_empty)
+    }
+}
+
+/// Chain which keeps the token produced by each sub-tokenizer
+struct TypedChain<T> {
+    chain: Chain<T>,
+}
+
+impl<T> Tokenizer for TypedChain<T> {
+    type Token = Vec<T>;
+
+    fn reset(&mut self) {
+        self.chain.reset();
+    }
+
+    fn can_match_empty(&self) -> bool {
+        self.chain.can_match_empty()
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        self.chain.feed(c)
+    }
+
+    fn make_token(&self, data: &[char]) -> Option<Self::Token> {
+        Some(
+            self.chain
+                .tokenizers
+                .iter()
+                .zip(self.chain.parts(data))
+                .filter_map(|(tokenizer, part)| tokenizer.make_token(part))
+                .collect(),
+        )
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        self.chain.complete_at_eof()
+    }
 }
 
 /// Implementation of the chain! macro
@@ -73,16 +167,38 @@ pub fn chain<T>(
 ) -> impl Tokenizer<Token = Token> {
     BasicTokenizer {
         tag,
-        state: Chain {
-            tokenizers,
-            progress: 0,
-            failed: false,
-        },
+        state: Chain::new(tokenizers),
+    }
+}
+
+/// Implementation of the chain_typed! macro
+#[doc(hidden)]
+#[must_use]
+pub fn chain_typed<T>(
+    tokenizers: Vec<Box<dyn Tokenizer<Token = T>>>,
+) -> impl Tokenizer<Token = Vec<T>> {
+    TypedChain {
+        chain: Chain::new(tokenizers),
     }
 }
 
 syntax_abuse::tests! {
-    use crate::tokenizer::{tokenize, literal, TokenAndSpan, Span};
+    use crate::tokenizer::{
+        eat, from_fn, keywords, literal, map, tokenize, Span, State, TokenAndSpan, TokenizeError
+    };
+
+    testdata! {
+        NUMBER: ??? = from_fn(
+            "number",
+            || (),
+            |(), c| if c.is_ascii_digit() { State::Completed } else { State::Failed }
+        );
+        KEYWORDS: ??? = keywords(&[("in", "in"), ("int", "int"), ("if", "if")]);
+    }
+
+    fn token(tag: &'static str, contents: &str) -> Token {
+        Token { tag, contents: String::from(contents) }
+    }
 
     testcase! {
         simple,
@@ -102,7 +218,8 @@ syntax_abuse::tests! {
                         tag: "chain",
                         contents: String::from("ABC")
                     },
-                    span: Span::new(0, 0, 0, 3)
+                    span: Span::new(0, 0, 0, 3),
+                    len: 3
                 }
             ]
         )
@@ -127,7 +244,8 @@ syntax_abuse::tests! {
                             tag: "chain",
                             contents: String::from("AB")
                         },
-                        span: Span::new(0, 0, 0, 2)
+                        span: Span::new(0, 0, 0, 2),
+                        len: 2
                     }
                 ]
             )
@@ -143,7 +261,8 @@ syntax_abuse::tests! {
                             tag: "chain",
                             contents: String::from("AB")
                         },
-                        span: Span::new(0, 0, 0, 2)
+                        span: Span::new(0, 0, 0, 2),
+                        len: 2
                     }
                 ]
             )
@@ -159,7 +278,8 @@ syntax_abuse::tests! {
                             tag: "chain",
                             contents: String::from("AB")
                         },
-                        span: Span::new(0, 0, 0, 2)
+                        span: Span::new(0, 0, 0, 2),
+                        len: 2
                     }
                 ]
             )
@@ -172,18 +292,20 @@ syntax_abuse::tests! {
             "ABCD",
             chain!("chain", literal("", "A"), literal("", "B"))
         ),
-        Err((
-            vec![
+        Err(TokenizeError::Failed {
+            tokens: vec![
                 TokenAndSpan {
                     token: Token {
                         tag: "chain",
                         contents: String::from("AB")
                     },
-                    span: Span::new(0, 0, 0, 2)
+                    span: Span::new(0, 0, 0, 2),
+                    len: 2
                 }
             ],
-            String::from("CD")
-        ))
+            position: 2,
+            rest: String::from("CD")
+        })
     }
 
     tests! {
@@ -199,10 +321,11 @@ syntax_abuse::tests! {
                     literal("", "123")
                 )
             ),
-            Err((
-                vec![],
-                String::from("Test123")
-            ))
+            Err(TokenizeError::Failed {
+                tokens: vec![],
+                position: 2,
+                rest: String::from("Test123")
+            })
         }
 
         testcase! {
@@ -215,10 +338,112 @@ syntax_abuse::tests! {
                     literal("", "13")
                 )
             ),
-            Err((
-                vec![],
-                String::from("Test123")
-            ))
+            Err(TokenizeError::Failed {
+                tokens: vec![],
+                position: 5,
+                rest: String::from("Test123")
+            })
+        }
+    }
+
+    testcase! {
+        greedy,
+        tokenize("123int", chain!("chain", NUMBER!(), KEYWORDS!())),
+        Ok(vec![TokenAndSpan {
+            token: token("chain", "123int"),
+            span: Span::new(0, 0, 0, 6),
+            len: 6
+        }])
+    }
+
+    tests! {
+        typed:
+
+        testcase! {
+            keeps_sub_tokens,
+            tokenize("123int", chain_typed!(NUMBER!(), KEYWORDS!())),
+            Ok(vec![TokenAndSpan {
+                token: vec![token("number", "123"), token("int", "int")],
+                span: Span::new(0, 0, 0, 6),
+                len: 6
+            }])
+        }
+
+        testcase! {
+            several_tokens,
+            tokenize("1in22if", chain_typed!(NUMBER!(), KEYWORDS!()))
+                .unwrap()
+                .into_iter()
+                .map(|token| token.token)
+                .collect::<Vec<_>>(),
+            vec![
+                vec![token("number", "1"), token("in", "in")],
+                vec![token("number", "22"), token("if", "if")]
+            ]
+        }
+
+        testcase! {
+            mapped_to_one_type,
+            tokenize(
+                "42if",
+                chain_typed!(
+                    map(NUMBER!(), |data| Some(data.iter().collect::<String>().parse().unwrap())),
+                    map(KEYWORDS!(), |data| Some(data.len()))
+                )
+            )
+            .unwrap()[0]
+            .token
+            .clone(),
+            vec![42, 2]
+        }
+
+        testcase! {
+            eaten_sub_token_left_out,
+            tokenize("1 if", chain_typed!(NUMBER!(), eat(literal("space", " ")), KEYWORDS!()))
+                .unwrap()[0]
+                .token
+                .clone(),
+            vec![token("number", "1"), token("if", "if")]
+        }
+
+        testcase! {
+            empty_in_the_middle,
+            tokenize("1if", chain_typed!(NUMBER!(), literal("nothing", ""), KEYWORDS!()))
+                .unwrap()[0]
+                .token
+                .clone(),
+            vec![token("number", "1"), token("nothing", ""), token("if", "if")]
+        }
+
+        testcase! {
+            settles_on_earlier_completion,
+            // The optional "ab" is still pending when the input runs out so
+            // the token is cut back to where the number ended
+            tokenize(
+                "12a",
+                chain_typed!(
+                    map(NUMBER!(), |data| Some(data.len())),
+                    map(longestof!(literal("", ""), literal("ab", "ab")), |data| Some(data.len()))
+                )
+            ),
+            Err(TokenizeError::TrailingInput {
+                tokens: vec![TokenAndSpan {
+                    token: vec![2, 0],
+                    span: Span::new(0, 0, 0, 2),
+                    len: 2
+                }],
+                rest: String::from("a")
+            })
+        }
+
+        testcase! {
+            failure,
+            tokenize("12x", chain_typed!(NUMBER!(), KEYWORDS!())),
+            Err(TokenizeError::Failed {
+                tokens: vec![],
+                position: 2,
+                rest: String::from("12x")
+            })
         }
     }
 }
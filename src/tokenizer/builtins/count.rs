@@ -0,0 +1,152 @@
+use super::{State, Tokenizer};
+
+struct Count<T> {
+    tokenizer: T,
+    /// Repetitions completed before the current one
+    repetitions: usize,
+    /// Characters fed since the last reset
+    fed: usize,
+    /// True if the sub-tokenizer completed on the last character
+    just_completed: bool,
+    /// The number of characters and repetitions at each completion, used to
+    /// work out the count for the token the main tokenizer loop settles on
+    completions: Vec<(usize, usize)>,
+}
+
+impl<T: Tokenizer> Count<T> {
+    fn feed_repetition(&mut self, c: char) -> State {
+        let state = self.tokenizer.feed(c);
+        self.just_completed = false;
+        if let State::Completed = state {
+            self.just_completed = true;
+            self.completions.push((self.fed, self.repetitions + 1));
+        }
+        state
+    }
+}
+
+impl<T: Tokenizer> Tokenizer for Count<T> {
+    type Token = usize;
+
+    fn reset(&mut self) {
+        self.tokenizer.reset();
+        self.repetitions = 0;
+        self.fed = 0;
+        self.just_completed = false;
+        self.completions.clear();
+    }
+
+    fn can_match_empty(&self) -> bool {
+        false
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        self.fed += 1;
+        let just_completed = self.just_completed;
+        match self.feed_repetition(c) {
+            // If the sub-tokenizer can't continue straight after completing
+            // start the next repetition with the current character
+            State::Failed if just_completed => {
+                self.repetitions += 1;
+                self.tokenizer.reset();
+                self.feed_repetition(c)
+            }
+            state => state,
+        }
+    }
+
+    fn make_token(&self, data: &[char]) -> Option<Self::Token> {
+        self.completions
+            .iter()
+            .find(|(fed, _)| *fed == data.len())
+            .map(|(_, repetitions)| *repetitions)
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        let completed = self.tokenizer.complete_at_eof();
+        if completed {
+            self.completions.push((self.fed, self.repetitions + 1));
+        }
+        completed
+    }
+}
+
+/// Match one or more consecutive repetitions of a tokenizer, the token is the
+/// number of repetitions
+///
+/// Repetitions are greedy, a new repetition only starts once the previous one
+/// can't accept the next character and only if the previous one completed on
+/// the character before
+#[must_use]
+pub fn count(tokenizer: impl Tokenizer) -> impl Tokenizer<Token = usize> {
+    Count {
+        tokenizer,
+        repetitions: 0,
+        fed: 0,
+        just_completed: false,
+        completions: Vec::new(),
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{eat, literal, tokenize, Span, TokenAndSpan, TokenizeError};
+
+    testdata! {
+        HASHES: ??? = count(literal("hash", "#"));
+    }
+
+    testcase! {
+        single,
+        tokenize("#", HASHES!()),
+        Ok(vec![TokenAndSpan { token: 1, span: Span::new(0, 0, 0, 1) , len: 1}])
+    }
+
+    testcase! {
+        run,
+        tokenize("###", HASHES!()),
+        Ok(vec![TokenAndSpan { token: 3, span: Span::new(0, 0, 0, 3) , len: 3}])
+    }
+
+    testcase! {
+        several_runs,
+        tokenize("#### ##", firstof!(HASHES!(), eat::<usize, _>(literal("space", " ")))),
+        Ok(vec![
+            TokenAndSpan { token: 4, span: Span::new(0, 0, 0, 4) , len: 4},
+            TokenAndSpan { token: 2, span: Span::new(0, 0, 5, 7) , len: 2}
+        ])
+    }
+
+    testcase! {
+        trailing_input,
+        tokenize("##a", HASHES!()),
+        Err(TokenizeError::Failed {
+            tokens: vec![TokenAndSpan { token: 2, span: Span::new(0, 0, 0, 2) , len: 2}],
+            position: 2,
+            rest: String::from("a")
+        })
+    }
+
+    testcase! {
+        multiple_characters,
+        tokenize("ababab", count(literal("ab", "ab"))),
+        Ok(vec![TokenAndSpan { token: 3, span: Span::new(0, 0, 0, 6) , len: 6}])
+    }
+
+    testcase! {
+        incomplete_repetition,
+        tokenize("aba", count(literal("ab", "ab"))),
+        Err(TokenizeError::TrailingInput {
+            tokens: vec![TokenAndSpan { token: 1, span: Span::new(0, 0, 0, 2) , len: 2}],
+            rest: String::from("a")
+        })
+    }
+
+    testcase! {
+        empty,
+        tokenize("", HASHES!()),
+        Err(TokenizeError::IncompleteAtEof {
+            tokens: vec![],
+            rest: String::new()
+        })
+    }
+}
@@ -11,7 +11,7 @@ pub fn eat<S, T: Tokenizer>(tokenizer: T) -> impl Tokenizer<Token = S> {
 }
 
 syntax_abuse::tests! {
-    use crate::tokenizer::{ tokenize, literal };
+    use crate::tokenizer::{ tokenize, literal, TokenizeError };
 
     testdata! {
         EATER: ??? = eat::<(), _>(literal("eaten", "test"));
@@ -26,18 +26,20 @@ syntax_abuse::tests! {
     testcase! {
         extra,
         tokenize("test extra", EATER!()),
-        Err((
-            vec![],
-            String::from(" extra")
-        ))
+        Err(TokenizeError::Failed {
+            tokens: vec![],
+            position: 4,
+            rest: String::from(" extra")
+        })
     }
 
     testcase! {
         failure,
         tokenize("text", EATER!()),
-        Err((
-            vec![],
-            String::from("text")
-        ))
+        Err(TokenizeError::Failed {
+            tokens: vec![],
+            position: 2,
+            rest: String::from("text")
+        })
     }
 }
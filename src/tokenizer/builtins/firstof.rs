@@ -1,4 +1,4 @@
-use super::{State, Tokenizer};
+use super::{Span, State, Tokenizer};
 
 struct FirstOf<T> {
     chosen_tokenizer: Option<usize>,
@@ -39,6 +39,17 @@ impl<T> Tokenizer for FirstOf<T> {
     fn make_token(&self, data: &[char]) -> Option<Self::Token> {
         self.tokenizers[self.chosen_tokenizer.unwrap()].make_token(data)
     }
+
+    fn make_token_spanned(&self, data: &[char], span: Span) -> Option<Self::Token> {
+        self.tokenizers[self.chosen_tokenizer.unwrap()].make_token_spanned(data, span)
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        match self.chosen_tokenizer {
+            Some(i) => self.tokenizers[i].complete_at_eof(),
+            None => false,
+        }
+    }
 }
 
 #[doc(hidden)]
@@ -51,7 +62,7 @@ pub fn firstof<T>(tokenizers: Vec<Box<dyn Tokenizer<Token = T>>>) -> impl Tokeni
 }
 
 syntax_abuse::tests! {
-    use crate::tokenizer::{literal, tokenize, Span, Token, TokenAndSpan};
+    use crate::tokenizer::{literal, tokenize, Span, Token, TokenAndSpan, TokenizeError};
 
     testdata! {
         TEST_OR_ABC: ??? = firstof!(
@@ -73,7 +84,8 @@ syntax_abuse::tests! {
                             tag: "Test",
                             contents: String::from("Test")
                         },
-                        span: Span::new(0, 0, 0, 4)
+                        span: Span::new(0, 0, 0, 4),
+                        len: 4
                     }
                 ]
             )
@@ -89,7 +101,8 @@ syntax_abuse::tests! {
                             tag: "abc",
                             contents: String::from("abc")
                         },
-                        span: Span::new(0, 0, 0, 3)
+                        span: Span::new(0, 0, 0, 3),
+                        len: 3
                     }
                 ]
             )
@@ -99,10 +112,11 @@ syntax_abuse::tests! {
     testcase! {
         failure,
         tokenize("123", TEST_OR_ABC!()),
-        Err((
-            vec![],
-            String::from("123")
-        ))
+        Err(TokenizeError::Failed {
+            tokens: vec![],
+            position: 0,
+            rest: String::from("123")
+        })
     }
 
     testcase! {
@@ -111,17 +125,84 @@ syntax_abuse::tests! {
             "This is a test",
             firstof!(literal("short", "This"), literal("long", "This is a test"))
         ),
-        Err((
-            vec![
+        Err(TokenizeError::Failed {
+            tokens: vec![
                 TokenAndSpan {
                     token: Token {
                         tag: "short",
                         contents: String::from("This")
                     },
-                    span: Span::new(0, 0, 0, 4)
+                    span: Span::new(0, 0, 0, 4),
+                    len: 4
                 }
             ],
-            String::from(" is a test")
-        ))
+            position: 4,
+            rest: String::from(" is a test")
+        })
+    }
+
+    tests! {
+        empty:
+
+        testdata! {
+            EMPTY_OR_A: ??? = firstof!(
+                literal("e", ""),
+                literal("a", "a")
+            );
+        }
+
+        testcase! {
+            can_match_empty,
+            EMPTY_OR_A!().can_match_empty(),
+            true
+        }
+
+        testcase! {
+            empty_input,
+            tokenize("", EMPTY_OR_A!()),
+            Ok(vec![])
+        }
+
+        testcase! {
+            falls_through_to_non_empty,
+            tokenize("a", EMPTY_OR_A!()),
+            Ok(
+                vec![
+                    TokenAndSpan {
+                        token: Token {
+                            tag: "a",
+                            contents: String::from("a")
+                        },
+                        span: Span::new(0, 0, 0, 1),
+                        len: 1
+                    }
+                ]
+            )
+        }
+
+        testcase! {
+            after_reset,
+            tokenize("aa", EMPTY_OR_A!()),
+            Ok(
+                vec![
+                    TokenAndSpan {
+                        token: Token {
+                            tag: "a",
+                            contents: String::from("a")
+                        },
+                        span: Span::new(0, 0, 0, 1),
+                        len: 1
+                    },
+                    TokenAndSpan {
+                        token: Token {
+                            tag: "a",
+                            contents: String::from("a")
+                        },
+                        span: Span::new(0, 0, 1, 2),
+                        len: 1
+                    }
+                ]
+            )
+        }
     }
 }
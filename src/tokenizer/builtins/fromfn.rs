@@ -0,0 +1,120 @@
+use super::{BasicTokenizer, State, StateMachine, Token, Tokenizer};
+
+struct FromFn<S, R, F> {
+    state: S,
+    reset: R,
+    feed: F,
+}
+
+impl<S, R, F> StateMachine for FromFn<S, R, F>
+where
+    R: Fn() -> S,
+    F: FnMut(&mut S, char) -> State,
+{
+    fn reset(&mut self) {
+        self.state = (self.reset)();
+    }
+
+    fn can_match_empty(&self) -> bool {
+        false
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        (self.feed)(&mut self.state, c)
+    }
+}
+
+/// Build a tokenizer from closures instead of a type implementing
+/// [`Tokenizer`]. `reset` produces the state the tokenizer starts from and is
+/// called again after every token, `feed` updates the state for the next
+/// character and reports the transition. Tokenizers built this way can't
+/// match empty input
+/// ```
+/// # use parsey::tokenizer::{from_fn, tokenize, State};
+/// // One or more digits
+/// let digits = from_fn(
+///     "digits",
+///     || (),
+///     |(), c| if c.is_ascii_digit() { State::Completed } else { State::Failed },
+/// );
+/// assert_eq!(tokenize("123", digits).unwrap()[0].token.contents, "123");
+/// ```
+#[must_use]
+pub fn from_fn<S, R, F>(tag: &'static str, reset: R, feed: F) -> impl Tokenizer<Token = Token>
+where
+    R: Fn() -> S,
+    F: FnMut(&mut S, char) -> State,
+{
+    BasicTokenizer {
+        tag,
+        state: FromFn {
+            state: reset(),
+            reset,
+            feed,
+        },
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{tokenize, Span, TokenAndSpan, TokenizeError};
+
+    // Exactly two identical characters
+    testdata! {
+        DOUBLE: ??? = from_fn("double", Vec::new, |seen: &mut Vec<char>, c| {
+            seen.push(c);
+            match seen.as_slice() {
+                [_] => State::Pending,
+                [a, b] if a == b => State::Completed,
+                _ => State::Failed,
+            }
+        });
+    }
+
+    fn token(contents: &str, start: usize) -> TokenAndSpan<Token> {
+        TokenAndSpan {
+            token: Token { tag: "double", contents: String::from(contents) },
+            span: Span::new(0, 0, start, start + 2),
+            len: 2,
+        }
+    }
+
+    testcase! {
+        single,
+        tokenize("aa", DOUBLE!()),
+        Ok(vec![token("aa", 0)])
+    }
+
+    testcase! {
+        state_reset_between_tokens,
+        tokenize("aabbcc", DOUBLE!()),
+        Ok(vec![token("aa", 0), token("bb", 2), token("cc", 4)])
+    }
+
+    testcase! {
+        mismatch,
+        tokenize("aabc", DOUBLE!()),
+        Err(TokenizeError::Failed {
+            tokens: vec![token("aa", 0)],
+            position: 3,
+            rest: String::from("bc")
+        })
+    }
+
+    testcase! {
+        incomplete,
+        tokenize("aab", DOUBLE!()),
+        Err(TokenizeError::IncompleteAtEof {
+            tokens: vec![token("aa", 0)],
+            rest: String::from("b")
+        })
+    }
+
+    testcase! {
+        empty,
+        tokenize("", DOUBLE!()),
+        Err(TokenizeError::IncompleteAtEof {
+            tokens: vec![],
+            rest: String::new()
+        })
+    }
+}
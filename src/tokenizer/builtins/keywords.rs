@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use super::{State, Token, Tokenizer};
+
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    /// Set if a keyword ends at this node
+    tag: Option<&'static str>,
+}
+
+struct Keywords {
+    /// The root is always at index 0
+    nodes: Vec<TrieNode>,
+    /// `None` once the input has left the trie
+    current: Option<usize>,
+}
+
+impl Keywords {
+    fn step(&self, node: usize, c: char) -> Option<usize> {
+        self.nodes[node].children.get(&c).copied()
+    }
+}
+
+impl Tokenizer for Keywords {
+    type Token = Token;
+
+    fn reset(&mut self) {
+        self.current = Some(0);
+    }
+
+    fn can_match_empty(&self) -> bool {
+        self.nodes[0].tag.is_some()
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        self.current = self.current.and_then(|node| self.step(node, c));
+        match self.current {
+            None => State::Failed,
+            Some(node) if self.nodes[node].tag.is_some() => State::Completed,
+            Some(_) => State::Pending,
+        }
+    }
+
+    fn make_token(&self, data: &[char]) -> Option<Self::Token> {
+        // The main tokenizer loop may have fed more characters since the
+        // keyword completed, find it again from the data
+        let node = data.iter().try_fold(0, |node, c| self.step(node, *c))?;
+        Some(Token {
+            tag: self.nodes[node].tag?,
+            contents: data.iter().collect(),
+        })
+    }
+}
+
+/// Match one of several literal keywords, given as `(tag, keyword)` pairs. The
+/// token is tagged with the tag of the matched keyword
+///
+/// Equivalent to `longestof!` over a `literal` for each keyword but the
+/// keywords are stored in a trie, so the work done per character doesn't
+/// depend on the number of keywords. If a keyword appears more than once the
+/// first tag is used
+#[must_use]
+pub fn keywords(pairs: &[(&'static str, &'static str)]) -> impl Tokenizer<Token = Token> {
+    let mut nodes = vec![TrieNode::default()];
+    for (tag, keyword) in pairs {
+        let mut node = 0;
+        for c in keyword.chars() {
+            node = if let Some(next) = nodes[node].children.get(&c) {
+                *next
+            } else {
+                nodes.push(TrieNode::default());
+                let next = nodes.len() - 1;
+                let _ = nodes[node].children.insert(c, next);
+                next
+            };
+        }
+        let _ = nodes[node].tag.get_or_insert(tag);
+    }
+
+    Keywords {
+        nodes,
+        current: Some(0),
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{eat, oneof, tokenize, Span, TokenAndSpan};
+
+    const KEYWORDS: [(&str, &str); 50] = [
+        ("abstract", "abstract"),
+        ("as", "as"),
+        ("assert", "assert"),
+        ("async", "async"),
+        ("await", "await"),
+        ("break", "break"),
+        ("case", "case"),
+        ("catch", "catch"),
+        ("class", "class"),
+        ("const", "const"),
+        ("continue", "continue"),
+        ("default", "default"),
+        ("do", "do"),
+        ("double", "double"),
+        ("else", "else"),
+        ("enum", "enum"),
+        ("extends", "extends"),
+        ("false", "false"),
+        ("final", "final"),
+        ("finally", "finally"),
+        ("float", "float"),
+        ("for", "for"),
+        ("fn", "fn"),
+        ("if", "if"),
+        ("impl", "impl"),
+        ("implements", "implements"),
+        ("import", "import"),
+        ("in", "in"),
+        ("int", "int"),
+        ("interface", "interface"),
+        ("internal", "internal"),
+        ("let", "let"),
+        ("long", "long"),
+        ("loop", "loop"),
+        ("match", "match"),
+        ("mod", "mod"),
+        ("new", "new"),
+        ("null", "null"),
+        ("private", "private"),
+        ("protected", "protected"),
+        ("public", "public"),
+        ("return", "return"),
+        ("static", "static"),
+        ("struct", "struct"),
+        ("super", "super"),
+        ("switch", "switch"),
+        ("this", "this"),
+        ("true", "true"),
+        ("while", "while"),
+        ("yield", "yield"),
+    ];
+
+    testdata! {
+        WORDS: ??? = firstof!(
+            keywords(&KEYWORDS),
+            eat::<Token, _>(oneof("space", hashset![' ']))
+        );
+    }
+
+    fn tags(input: &str) -> Result<Vec<&'static str>, String> {
+        tokenize(input, WORDS!())
+            .map(|tokens| tokens.into_iter().map(|token| token.token.tag).collect())
+            .map_err(|error| error.into_parts().1)
+    }
+
+    testcase! {
+        single,
+        tokenize("while", keywords(&KEYWORDS)),
+        Ok(vec![TokenAndSpan {
+            token: Token { tag: "while", contents: String::from("while") },
+            span: Span::new(0, 0, 0, 5),
+            len: 5
+        }])
+    }
+
+    testcase! {
+        shared_prefixes,
+        tags("in int interface internal"),
+        Ok(vec!["in", "int", "interface", "internal"])
+    }
+
+    testcase! {
+        longest_wins,
+        tags("implements impl import"),
+        Ok(vec!["implements", "impl", "import"])
+    }
+
+    testcase! {
+        all,
+        tags(&KEYWORDS.iter().map(|(_, keyword)| *keyword).collect::<Vec<_>>().join(" ")),
+        Ok(KEYWORDS.iter().map(|(tag, _)| *tag).collect())
+    }
+
+    testcase! {
+        falls_back_to_prefix,
+        tags("inte"),
+        Err(String::from("e"))
+    }
+
+    testcase! {
+        not_a_keyword,
+        tags("if whale"),
+        Err(String::from("whale"))
+    }
+
+    testcase! {
+        adjacent,
+        tokenize("intin", keywords(&KEYWORDS)).map(|tokens| {
+            tokens.into_iter().map(|token| token.token.tag).collect::<Vec<_>>()
+        }),
+        Ok(vec!["int", "in"])
+    }
+
+    testcase! {
+        duplicate_keyword,
+        tokenize("x", keywords(&[("first", "x"), ("second", "x")]))
+            .map(|tokens| tokens[0].token.tag),
+        Ok("first")
+    }
+
+    testcase! {
+        can_match_empty,
+        (
+            keywords(&KEYWORDS).can_match_empty(),
+            keywords(&[("empty", "")]).can_match_empty()
+        ),
+        (false, true)
+    }
+}
@@ -42,7 +42,7 @@ pub fn literal<S: AsRef<str>>(tag: &'static str, lit: S) -> impl Tokenizer<Token
 }
 
 syntax_abuse::tests! {
-    use crate::tokenizer::{ tokenize, TokenAndSpan, Span };
+    use crate::tokenizer::{ tokenize, TokenAndSpan, Span, TokenizeError };
 
     testcase! {
         simple,
@@ -54,7 +54,8 @@ syntax_abuse::tests! {
                         tag: "simple",
                         contents: String::from("test")
                     },
-                    span: Span::new(0, 0, 0, 4)
+                    span: Span::new(0, 0, 0, 4),
+                    len: 4
                 }
             ]
         )
@@ -73,7 +74,8 @@ syntax_abuse::tests! {
                         tag: "newline",
                         contents: String::from("First Line\nSecond Line")
                     },
-                    span: Span::new(0, 1, 0, 11)
+                    span: Span::new(0, 1, 0, 11),
+                    len: 22
                 }
             ]
         )
@@ -92,7 +94,8 @@ syntax_abuse::tests! {
                         tag: "newline",
                         contents: String::from("Test\n")
                     },
-                    span: Span::new(0, 1, 0, 0)
+                    span: Span::new(0, 1, 0, 0),
+                    len: 5
                 }
             ]
         )
@@ -107,35 +110,38 @@ syntax_abuse::tests! {
     testcase! {
         extra,
         tokenize("Text More Text", literal("extra", "Text")),
-        Err((
-            vec![
+        Err(TokenizeError::Failed {
+            tokens: vec![
                 TokenAndSpan {
                     token: Token {
                         tag: "extra",
                         contents: String::from("Text")
                     },
-                    span: Span::new(0, 0, 0, 4)
+                    span: Span::new(0, 0, 0, 4),
+                    len: 4
                 }
             ],
-            String::from(" More Text")
-        ))
+            position: 4,
+            rest: String::from(" More Text")
+        })
     }
 
     testcase! {
         not_enough,
         tokenize("1234", literal("not-enough", "12345")),
-        Err((
-            vec![],
-            String::from("1234")
-        ))
+        Err(TokenizeError::IncompleteAtEof {
+            tokens: vec![],
+            rest: String::from("1234")
+        })
     }
 
     testcase! {
         failure,
         tokenize("Text", literal("failure", "Test")),
-        Err((
-            vec![],
-            String::from("Text")
-        ))
+        Err(TokenizeError::Failed {
+            tokens: vec![],
+            position: 2,
+            rest: String::from("Text")
+        })
     }
 }
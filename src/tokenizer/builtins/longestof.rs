@@ -1,4 +1,4 @@
-use super::{State, Tokenizer};
+use super::{Span, State, Tokenizer};
 
 struct LongestOf<T> {
     tokenizers: Vec<Box<dyn Tokenizer<Token = T>>>,
@@ -49,6 +49,20 @@ impl<T> Tokenizer for LongestOf<T> {
     fn make_token(&self, data: &[char]) -> Option<Self::Token> {
         self.tokenizers[self.last_completed.unwrap()].make_token(data)
     }
+
+    fn make_token_spanned(&self, data: &[char], span: Span) -> Option<Self::Token> {
+        self.tokenizers[self.last_completed.unwrap()].make_token_spanned(data, span)
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        for tokenizer_idx in self.in_progress.iter().copied() {
+            if self.tokenizers[tokenizer_idx].complete_at_eof() {
+                self.last_completed = Some(tokenizer_idx);
+                return true;
+            }
+        }
+        false
+    }
 }
 
 #[doc(hidden)]
@@ -63,7 +77,7 @@ pub fn longestof<T>(tokenizers: Vec<Box<dyn Tokenizer<Token = T>>>) -> impl Toke
 }
 
 syntax_abuse::tests! {
-    use crate::tokenizer::{literal, tokenize, Span, Token, TokenAndSpan};
+    use crate::tokenizer::{literal, tokenize, Span, Token, TokenAndSpan, TokenizeError};
 
     tests! {
         successes:
@@ -86,7 +100,8 @@ syntax_abuse::tests! {
                             tag: "4",
                             contents: String::from("This is a test")
                         },
-                        span: Span::new(0, 0, 0, 14)
+                        span: Span::new(0, 0, 0, 14),
+                        len: 14
                     }
                 ]
             )
@@ -110,7 +125,8 @@ syntax_abuse::tests! {
                             tag: "1",
                             contents: String::from("This is a test")
                         },
-                        span: Span::new(0, 0, 0, 14)
+                        span: Span::new(0, 0, 0, 14),
+                        len: 14
                     }
                 ]
             )
@@ -134,7 +150,8 @@ syntax_abuse::tests! {
                             tag: "2",
                             contents: String::from("This is a test")
                         },
-                        span: Span::new(0, 0, 0, 14)
+                        span: Span::new(0, 0, 0, 14),
+                        len: 14
                     }
                 ]
             )
@@ -157,7 +174,8 @@ syntax_abuse::tests! {
                             tag: "1",
                             contents: String::from("abcd")
                         },
-                        span: Span::new(0, 0, 0, 4)
+                        span: Span::new(0, 0, 0, 4),
+                        len: 4
                     }
                 ]
             )
@@ -180,14 +198,16 @@ syntax_abuse::tests! {
                             tag: "2",
                             contents: String::from("abcd")
                         },
-                        span: Span::new(0, 0, 0, 4)
+                        span: Span::new(0, 0, 0, 4),
+                        len: 4
                     },
                     TokenAndSpan {
                         token: Token {
                             tag: "2",
                             contents: String::from("abcd")
                         },
-                        span: Span::new(0, 0, 4, 8)
+                        span: Span::new(0, 0, 4, 8),
+                        len: 4
                     }
                 ]
             )
@@ -207,18 +227,20 @@ syntax_abuse::tests! {
                     literal("3", "a")
                 )
             ),
-            Err((
-                vec![
+            Err(TokenizeError::Failed {
+                tokens: vec![
                     TokenAndSpan {
                         token: Token {
                             tag: "1",
                             contents: String::from("abc")
                         },
-                        span: Span::new(0, 0, 0, 3)
+                        span: Span::new(0, 0, 0, 3),
+                        len: 3
                     }
                 ],
-                String::from("d")
-            ))
+                position: 3,
+                rest: String::from("d")
+            })
         }
 
         testcase! {
@@ -231,18 +253,20 @@ syntax_abuse::tests! {
                     literal("3", "abc")
                 )
             ),
-            Err((
-                vec![
+            Err(TokenizeError::Failed {
+                tokens: vec![
                     TokenAndSpan {
                         token: Token {
                             tag: "3",
                             contents: String::from("abc")
                         },
-                        span: Span::new(0, 0, 0, 3)
+                        span: Span::new(0, 0, 0, 3),
+                        len: 3
                     }
                 ],
-                String::from("d")
-            ))
+                position: 3,
+                rest: String::from("d")
+            })
         }
 
         testcase! {
@@ -255,10 +279,10 @@ syntax_abuse::tests! {
                     literal("3", "abcdefg")
                 )
             ),
-            Err((
-                vec![],
-                String::from("abcd")
-            ))
+            Err(TokenizeError::IncompleteAtEof {
+                tokens: vec![],
+                rest: String::from("abcd")
+            })
         }
 
         testcase! {
@@ -270,18 +294,19 @@ syntax_abuse::tests! {
                     literal("2", "abcde")
                 )
             ),
-            Err((
-                vec![
+            Err(TokenizeError::TrailingInput {
+                tokens: vec![
                     TokenAndSpan {
                         token: Token {
                             tag: "1",
                             contents: String::from("abc")
                         },
-                        span: Span::new(0, 0, 0, 3)
+                        span: Span::new(0, 0, 0, 3),
+                        len: 3
                     }
                 ],
-                String::from("d")
-            ))
+                rest: String::from("d")
+            })
         }
 
         testcase! {
@@ -294,18 +319,67 @@ syntax_abuse::tests! {
                     literal("3", "abcde")
                 )
             ),
-            Err((
-                vec![
+            Err(TokenizeError::TrailingInput {
+                tokens: vec![
                     TokenAndSpan {
                         token: Token {
                             tag: "1",
                             contents: String::from("abc")
                         },
-                        span: Span::new(0, 0, 0, 3)
+                        span: Span::new(0, 0, 0, 3),
+                        len: 3
                     }
                 ],
-                String::from("d")
-            ))
+                rest: String::from("d")
+            })
+        }
+    }
+
+    tests! {
+        empty:
+
+        testdata! {
+            EMPTY_OR_A: ??? = longestof!(
+                literal("e", ""),
+                literal("a", "a")
+            );
+        }
+
+        testcase! {
+            can_match_empty,
+            EMPTY_OR_A!().can_match_empty(),
+            true
+        }
+
+        testcase! {
+            empty_input,
+            tokenize("", EMPTY_OR_A!()),
+            Ok(vec![])
+        }
+
+        testcase! {
+            prefers_longer_match,
+            tokenize("aa", EMPTY_OR_A!()),
+            Ok(
+                vec![
+                    TokenAndSpan {
+                        token: Token {
+                            tag: "a",
+                            contents: String::from("a")
+                        },
+                        span: Span::new(0, 0, 0, 1),
+                        len: 1
+                    },
+                    TokenAndSpan {
+                        token: Token {
+                            tag: "a",
+                            contents: String::from("a")
+                        },
+                        span: Span::new(0, 0, 1, 2),
+                        len: 1
+                    }
+                ]
+            )
         }
     }
 }
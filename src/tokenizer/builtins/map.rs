@@ -23,6 +23,10 @@ impl<S, T: Tokenizer, F: Fn(&[char]) -> Option<S>> Tokenizer for Mapper<S, T, F>
     fn make_token(&self, data: &[char]) -> Option<Self::Token> {
         (self.make_token)(data)
     }
+
+    fn complete_at_eof(&mut self) -> bool {
+        self.tokenizer.complete_at_eof()
+    }
 }
 
 /// Override the `make_token` method of a tokenizer
@@ -41,7 +45,7 @@ pub fn map<S, T, F: Fn(&[char]) -> Option<T>>(
 }
 
 syntax_abuse::tests! {
-    use crate::tokenizer::{ tokenize, literal, TokenAndSpan, Span };
+    use crate::tokenizer::{ tokenize, literal, TokenAndSpan, Span, TokenizeError };
 
     testdata! {
         MAPPER: ??? = map(literal("map", "test"), |chars| Some(chars.iter().collect::<String>()));
@@ -54,7 +58,8 @@ syntax_abuse::tests! {
             vec![
                 TokenAndSpan {
                     token: String::from("test"),
-                    span: Span::new(0, 0, 0, 4)
+                    span: Span::new(0, 0, 0, 4),
+                    len: 4
                 }
             ]
         )
@@ -63,23 +68,26 @@ syntax_abuse::tests! {
     testcase! {
         extra,
         tokenize("test extra", MAPPER!()),
-        Err((
-            vec![
+        Err(TokenizeError::Failed {
+            tokens: vec![
                 TokenAndSpan {
                     token: String::from("test"),
-                    span: Span::new(0, 0, 0, 4)
+                    span: Span::new(0, 0, 0, 4),
+                    len: 4
                 }
             ],
-            String::from(" extra")
-        ))
+            position: 4,
+            rest: String::from(" extra")
+        })
     }
 
     testcase! {
         failure,
         tokenize("text", MAPPER!()),
-        Err((
-            vec![],
-            String::from("text")
-        ))
+        Err(TokenizeError::Failed {
+            tokens: vec![],
+            position: 2,
+            rest: String::from("text")
+        })
     }
 }
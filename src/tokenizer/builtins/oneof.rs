@@ -38,8 +38,19 @@ pub fn oneof(tag: &'static str, chars: HashSet<char>) -> impl Tokenizer<Token =
     }
 }
 
+/// Match a single character from the characters of `chars`, like the `[]`
+/// syntax of the [`grammar!`](crate::grammar!) macro
+///
+/// # Panics
+/// If `chars` is empty
+#[must_use]
+pub fn oneof_str(tag: &'static str, chars: &str) -> impl Tokenizer<Token = Token> {
+    assert!(!chars.is_empty(), "oneof_str needs at least one character");
+    oneof(tag, chars.chars().collect())
+}
+
 syntax_abuse::tests! {
-    use crate::tokenizer::{ tokenize, TokenAndSpan, Span };
+    use crate::tokenizer::{ tokenize, TokenAndSpan, Span, TokenizeError };
 
     testdata! {
         SIMPLE: ??? = oneof("simple", hashset!['A', 'B']);
@@ -55,7 +66,8 @@ syntax_abuse::tests! {
                         tag: "simple",
                         contents: String::from("A")
                     },
-                    span: Span::new(0, 0, 0, 1)
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
                 }
             ]
         )
@@ -71,7 +83,8 @@ syntax_abuse::tests! {
                         tag: "simple",
                         contents: String::from("B")
                     },
-                    span: Span::new(0, 0, 0, 1)
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
                 }
             ]
         )
@@ -80,33 +93,99 @@ syntax_abuse::tests! {
     testcase! {
         extra,
         tokenize("ABC", SIMPLE!()),
-        Err((
-            vec![
+        Err(TokenizeError::Failed {
+            tokens: vec![
                 TokenAndSpan {
                     token: Token {
                         tag: "simple",
                         contents: String::from("A")
                     },
-                    span: Span::new(0, 0, 0, 1)
+                    span: Span::new(0, 0, 0, 1),
+                    len: 1
                 },
                 TokenAndSpan {
                     token: Token {
                         tag: "simple",
                         contents: String::from("B")
                     },
-                    span: Span::new(0, 0, 1, 2)
+                    span: Span::new(0, 0, 1, 2),
+                    len: 1
                 }
             ],
-            String::from("C")
-        ))
+            position: 2,
+            rest: String::from("C")
+        })
     }
 
     testcase! {
         failure,
         tokenize("C", SIMPLE!()),
-        Err((
-            vec![],
-            String::from("C")
-        ))
+        Err(TokenizeError::Failed {
+            tokens: vec![],
+            position: 0,
+            rest: String::from("C")
+        })
+    }
+
+    tests! {
+        from_str:
+
+        testdata! {
+            VOWELS: ??? = oneof_str("vowels", "aeiou");
+        }
+
+        fn contents(input: &str) -> Result<Vec<String>, TokenizeError<Token>> {
+            tokenize(input, VOWELS!())
+                .map(|tokens| tokens.into_iter().map(|token| token.token.contents).collect())
+        }
+
+        testcase! {
+            vowels,
+            contents("aeiou"),
+            Ok(vec![
+                String::from("a"),
+                String::from("e"),
+                String::from("i"),
+                String::from("o"),
+                String::from("u")
+            ])
+        }
+
+        testcase! {
+            tag,
+            tokenize("e", VOWELS!()).map(|tokens| tokens[0].token.tag),
+            Ok("vowels")
+        }
+
+        testcase! {
+            consonant,
+            contents("ab"),
+            Err(TokenizeError::Failed {
+                tokens: vec![
+                    TokenAndSpan {
+                        token: Token {
+                            tag: "vowels",
+                            contents: String::from("a")
+                        },
+                        span: Span::new(0, 0, 0, 1),
+                        len: 1
+                    }
+                ],
+                position: 1,
+                rest: String::from("b")
+            })
+        }
+
+        testcase! {
+            repeated_characters,
+            contents("aa").is_ok() && tokenize("a", oneof_str("a", "aaa")).is_ok(),
+            true
+        }
+
+        #[test]
+        #[should_panic(expected = "oneof_str needs at least one character")]
+        fn empty() {
+            drop(oneof_str("empty", ""));
+        }
     }
 }
@@ -0,0 +1,74 @@
+use super::{keywords, Token, Tokenizer};
+
+/// Match one of several operators or punctuation marks, given as `(tag,
+/// operator)` pairs. The longest operator wins so `<=` is one token rather
+/// than `<` followed by `=`
+///
+/// This is the same trie as [`keywords`] which has no word boundary check
+/// either, the separate name is for readability when a lexer has both
+#[must_use]
+pub fn punctuation(table: &[(&'static str, &'static str)]) -> impl Tokenizer<Token = Token> {
+    keywords(table)
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{eat, oneof, tokenize, Span, TokenAndSpan};
+
+    const OPERATORS: [(&str, &str); 8] = [
+        ("lt", "<"),
+        ("le", "<="),
+        ("shl", "<<"),
+        ("shl_assign", "<<="),
+        ("gt", ">"),
+        ("ge", ">="),
+        ("assign", "="),
+        ("eq", "=="),
+    ];
+
+    testdata! {
+        SPACED: ??? = firstof!(
+            punctuation(&OPERATORS),
+            eat::<Token, _>(oneof("space", hashset![' ']))
+        );
+    }
+
+    fn tags(input: &str) -> Result<Vec<&'static str>, String> {
+        tokenize(input, SPACED!())
+            .map(|tokens| tokens.into_iter().map(|token| token.token.tag).collect())
+            .map_err(|error| error.into_parts().1)
+    }
+
+    testcase! {
+        single,
+        tokenize("<<=", punctuation(&OPERATORS)),
+        Ok(vec![TokenAndSpan {
+            token: Token { tag: "shl_assign", contents: String::from("<<=") },
+            span: Span::new(0, 0, 0, 3),
+            len: 3
+        }])
+    }
+
+    testcase! {
+        overlapping,
+        tags("< <= << <<="),
+        Ok(vec!["lt", "le", "shl", "shl_assign"])
+    }
+
+    testcase! {
+        no_separator_needed,
+        tags("<<=<=<"),
+        Ok(vec!["shl_assign", "le", "lt"])
+    }
+
+    testcase! {
+        longest_then_rest,
+        tags("<===>="),
+        Ok(vec!["le", "eq", "ge"])
+    }
+
+    testcase! {
+        unknown,
+        tags("<= !"),
+        Err(String::from("!"))
+    }
+}
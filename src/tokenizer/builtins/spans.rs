@@ -0,0 +1,109 @@
+use super::{Span, State, Tokenizer};
+
+struct Spans<T> {
+    tokenizer: T,
+}
+
+impl<T: Tokenizer> Tokenizer for Spans<T> {
+    type Token = Span;
+
+    fn reset(&mut self) {
+        self.tokenizer.reset();
+    }
+
+    fn can_match_empty(&self) -> bool {
+        self.tokenizer.can_match_empty()
+    }
+
+    fn feed(&mut self, c: char) -> State {
+        self.tokenizer.feed(c)
+    }
+
+    // The span is only known when tokenizing calls `make_token_spanned`
+    fn make_token(&self, _: &[char]) -> Option<Self::Token> {
+        None
+    }
+
+    fn make_token_spanned(&self, _: &[char], span: Span) -> Option<Self::Token> {
+        Some(span)
+    }
+
+    fn complete_at_eof(&mut self) -> bool {
+        self.tokenizer.complete_at_eof()
+    }
+}
+
+/// Produce the source span of each match of a sub-tokenizer instead of a token
+///
+/// The sub-tokenizer's `make_token` is never called so nothing is built from
+/// the matched characters, which makes finding where things are (e.g. for
+/// highlighting) cheaper than tokenizing them. Every match produces a span,
+/// even if the sub-tokenizer wouldn't have produced a token
+/// ```
+/// # use parsey::{firstof, tokenizers};
+/// # use parsey::tokenizer::{any_char, count, eat, oneof_str, spans, tokenize};
+/// let numbers = firstof!(spans(count(oneof_str("digit", "0123456789"))), eat(any_char("other")));
+/// let found = tokenize("x=12+3", numbers).unwrap();
+/// let columns = found
+///     .iter()
+///     .map(|found| (found.token.start.col, found.token.end.col))
+///     .collect::<Vec<_>>();
+/// assert_eq!(columns, vec![(2, 4), (5, 6)]);
+/// ```
+#[must_use]
+pub fn spans(tokenizer: impl Tokenizer) -> impl Tokenizer<Token = Span> {
+    Spans { tokenizer }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{
+        any_char, count, eat, literal, map, oneof_str, tokenize, TokenAndSpan, TokenizeError,
+    };
+
+    /// Matches runs of digits but panics if asked to build a token
+    fn number() -> impl Tokenizer<Token = String> {
+        map(count(oneof_str("digit", "0123456789")), |_| panic!("Built a token"))
+    }
+
+    /// The span of every number in `input`
+    fn numbers(input: &str) -> Vec<Span> {
+        tokenize(input, firstof!(spans(number()), eat(any_char("other"))))
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token)
+            .collect()
+    }
+
+    testcase! {
+        every_number,
+        numbers("12 apples, 3 pears\nand 456 plums"),
+        vec![Span::new(0, 0, 0, 2), Span::new(0, 0, 11, 12), Span::new(1, 1, 4, 7)]
+    }
+
+    testcase! {
+        no_numbers,
+        numbers("none"),
+        vec![]
+    }
+
+    testcase! {
+        lengths,
+        tokenize("ab12", firstof!(spans(literal("ab", "ab")), spans(number())))
+            .map(|tokens| tokens.into_iter().map(|token| token.len).collect::<Vec<_>>()),
+        Ok(vec![2, 2])
+    }
+
+    testcase! {
+        failure,
+        tokenize("12a", spans(number())),
+        Err(TokenizeError::Failed {
+            tokens: vec![TokenAndSpan {
+                token: Span::new(0, 0, 0, 2),
+                span: Span::new(0, 0, 0, 2),
+                len: 2
+            }],
+            position: 2,
+            rest: String::from("a")
+        })
+    }
+}
@@ -0,0 +1,247 @@
+use super::{tokenize, CharacterPosition, Span, TokenAndSpan, TokenizeError, Tokenizer, Tokens};
+
+type Result<T> = std::result::Result<Tokens<T>, TokenizeError<T>>;
+
+/// Tokens produced by [`IndentationTokenizer`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Indentation<T> {
+    /// The line is indented further than the one before it
+    Indent,
+    /// The line closes one level of indentation, a single line can close
+    /// several levels at once
+    Dedent,
+    /// A token produced by the wrapped tokenizer
+    Token(T),
+}
+
+/// Tokenizer for indentation sensitive (Python like) languages
+///
+/// `feed` only sees one character at a time and `make_token` only produces one
+/// token, neither of which can express an indentation stack spanning several
+/// lines. Instead this wraps a tokenizer for the rest of the language (which
+/// must consume whitespace and newlines itself, e.g. with `eat`), runs it over
+/// the whole input and injects `Indent` and `Dedent` tokens wherever the
+/// indentation of the first token on a line changes. Lines without any tokens
+/// (blank lines, comments) don't affect indentation and any open levels are
+/// closed at the end of input. Indentation is measured in leading spaces and
+/// tabs, each counting as one column.
+#[derive(Debug)]
+pub struct IndentationTokenizer<T> {
+    tokenizer: T,
+}
+
+impl<T: Tokenizer> IndentationTokenizer<T> {
+    /// Wrap `tokenizer`
+    #[must_use]
+    pub fn new(tokenizer: T) -> Self {
+        IndentationTokenizer { tokenizer }
+    }
+
+    /// Tokenize a string
+    ///
+    /// `Indent` and `Dedent` tokens have an empty span at the start of the
+    /// first token on the line (or the end of input for the final `Dedent`s)
+    ///
+    /// # Errors
+    /// As [`tokenize`], tokens found before the wrapped tokenizer failed still
+    /// have indentation tokens injected. Also fails with
+    /// [`TokenizeError::InconsistentDedent`] if a line is dedented to a level
+    /// that doesn't match any enclosing line
+    #[allow(clippy::missing_panics_doc)]
+    pub fn tokenize<S: AsRef<str>>(self, input: S) -> Result<Indentation<T::Token>> {
+        let input = input.as_ref();
+        let lines = input.split('\n').collect::<Vec<_>>();
+
+        let (tokens, error) = match tokenize(input, self.tokenizer) {
+            Ok(tokens) => (tokens, None),
+            Err(error) => {
+                // Swapped back for the tokens with indentation injected below
+                let (tokens, error) = error.replace_tokens::<Indentation<T::Token>>(Vec::new());
+                (tokens, Some(error))
+            }
+        };
+
+        let mut result = Vec::new();
+        // Always contains at least the outermost level (0)
+        let mut levels = vec![0];
+        let mut last_row = None;
+        for TokenAndSpan { token, span, len } in tokens {
+            // Only the first token on a line can change the indentation, a
+            // token starting on the row a multi-line token ended on doesn't
+            // count
+            if last_row.is_none_or(|row| span.start.row > row) {
+                let indent = lines[span.start.row]
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .count();
+                let marker = Span {
+                    start: span.start,
+                    end: span.start,
+                };
+
+                if indent > *levels.last().unwrap() {
+                    levels.push(indent);
+                    result.push(TokenAndSpan {
+                        token: Indentation::Indent,
+                        span: marker,
+                        len: 0,
+                    });
+                } else {
+                    while indent < *levels.last().unwrap() {
+                        let _ = levels.pop();
+                        result.push(TokenAndSpan {
+                            token: Indentation::Dedent,
+                            span: marker,
+                            len: 0,
+                        });
+                    }
+                    if indent != *levels.last().unwrap() {
+                        let rest = lines[span.start.row..].join("\n");
+                        return Err(TokenizeError::InconsistentDedent {
+                            tokens: result,
+                            position: input.chars().count() - rest.chars().count(),
+                            rest,
+                        });
+                    }
+                }
+            }
+
+            last_row = Some(span.end.row);
+            result.push(TokenAndSpan {
+                token: Indentation::Token(token),
+                span,
+                len,
+            });
+        }
+
+        if let Some(error) = error {
+            return Err(error.replace_tokens(result).1);
+        }
+
+        // Close anything still open at the end of input
+        let end = CharacterPosition::from_offset(
+            &input.chars().collect::<Vec<_>>(),
+            input.chars().count(),
+        );
+        for _ in 1..levels.len() {
+            result.push(TokenAndSpan {
+                token: Indentation::Dedent,
+                span: Span { start: end, end },
+                len: 0,
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+syntax_abuse::tests! {
+    use crate::tokenizer::{any_char, eat, oneof, Token};
+
+    testdata! {
+        LETTERS: ??? = IndentationTokenizer::new(firstof!(
+            eat::<Token, _>(oneof("whitespace", hashset![' ', '\t', '\r', '\n'])),
+            any_char("letter")
+        ));
+    }
+
+    /// Summarise the tokens as a string, `>` for `Indent` and `<` for `Dedent`
+    fn summary(tokens: Vec<TokenAndSpan<Indentation<Token>>>) -> String {
+        tokens
+            .into_iter()
+            .map(|token| match token.token {
+                Indentation::Indent => String::from(">"),
+                Indentation::Dedent => String::from("<"),
+                Indentation::Token(token) => token.contents,
+            })
+            .collect()
+    }
+
+    testcase! {
+        flat,
+        LETTERS!().tokenize("a\nb\nc").map(summary),
+        Ok(String::from("abc"))
+    }
+
+    testcase! {
+        block,
+        LETTERS!().tokenize("a\n  b\n  c\nd").map(summary),
+        Ok(String::from("a>bc<d"))
+    }
+
+    testcase! {
+        nested_blocks,
+        LETTERS!().tokenize("a\n  b\n    c\n    d\ne").map(summary),
+        Ok(String::from("a>b>cd<<e"))
+    }
+
+    testcase! {
+        partial_dedent,
+        LETTERS!().tokenize("a\n  b\n    c\n  d").map(summary),
+        Ok(String::from("a>b>c<d<"))
+    }
+
+    testcase! {
+        closed_at_end_of_input,
+        LETTERS!().tokenize("a\n  b\n    c\n").map(summary),
+        Ok(String::from("a>b>c<<"))
+    }
+
+    testcase! {
+        blank_lines_ignored,
+        LETTERS!().tokenize("a\n\n  b\n \n  c").map(summary),
+        Ok(String::from("a>bc<"))
+    }
+
+    testcase! {
+        same_line,
+        LETTERS!().tokenize("a b\n  c d").map(summary),
+        Ok(String::from("ab>cd<"))
+    }
+
+    testcase! {
+        crlf,
+        LETTERS!().tokenize("a\r\n  b\r\nc").map(summary),
+        Ok(String::from("a>b<c"))
+    }
+
+    testcase! {
+        spans,
+        LETTERS!().tokenize("a\n  b").map(|tokens| {
+            tokens.into_iter().map(|token| token.span).collect::<Vec<_>>()
+        }),
+        Ok(vec![
+            Span::new(0, 0, 0, 1),
+            Span::new(1, 1, 2, 2),
+            Span::new(1, 1, 2, 3),
+            Span::new(1, 1, 3, 3)
+        ])
+    }
+
+    testcase! {
+        inconsistent_dedent,
+        LETTERS!()
+            .tokenize("a\n    b\n  c\nd")
+            .map_err(|error| match error {
+                TokenizeError::InconsistentDedent { tokens, position, rest } => {
+                    (summary(tokens), position, rest)
+                }
+                error => panic!("Unexpected error {:?}", error),
+            }),
+        Err((String::from("a>b<"), 8, String::from("  c\nd")))
+    }
+
+    testcase! {
+        tokenizer_failure,
+        IndentationTokenizer::new(firstof!(
+            eat::<Token, _>(oneof("whitespace", hashset![' ', '\n'])),
+            oneof("letter", hashset!['a', 'b'])
+        ))
+        .tokenize("a\n  b\n  c")
+        .map_err(|error| match error {
+            TokenizeError::Failed { tokens, position, rest } => (summary(tokens), position, rest),
+            error => panic!("Unexpected error {:?}", error),
+        }),
+        Err((String::from("a>b"), 8, String::from("c")))
+    }
+}
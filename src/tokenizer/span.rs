@@ -1,5 +1,11 @@
-/// The position of a character in a file
-#[derive(Debug, Copy, Clone, PartialEq)]
+use std::fmt;
+
+/// The position of a character in a file. The default is the first character
+/// of the file.
+///
+/// Displays as `line:column`, one indexed for humans, so `row: 0, col: 4` is
+/// shown as `1:5`
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct CharacterPosition {
     /// The zero indexed line number
     pub row: usize,
@@ -7,7 +13,40 @@ pub struct CharacterPosition {
     pub col: usize,
 }
 
-/// Source span of a token
+impl CharacterPosition {
+    /// The position of `chars[offset]`
+    pub(crate) fn from_offset(chars: &[char], offset: usize) -> Self {
+        let mut position = CharacterPosition { row: 0, col: 0 };
+        for idx in 0..offset {
+            position.advance(chars, idx);
+        }
+        position
+    }
+
+    /// Move past `chars[idx]`. This is the only place newlines are handled so
+    /// the tokenizer and parser always agree on positions. A `\r` directly
+    /// before a `\n` is part of the same line break, so `\r\n` and `\n` both
+    /// count as one
+    pub(crate) fn advance(&mut self, chars: &[char], idx: usize) {
+        match chars[idx] {
+            '\n' => {
+                self.row += 1;
+                self.col = 0;
+            }
+            '\r' if chars.get(idx + 1) == Some(&'\n') => (),
+            _ => self.col += 1,
+        }
+    }
+}
+
+impl fmt::Display for CharacterPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.row + 1, self.col + 1)
+    }
+}
+
+/// Source span of a token. Displays as `start-end` with each position shown as
+/// by [`CharacterPosition`]'s `Display`
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Span {
     /// The location of the first character of the token
@@ -35,3 +74,55 @@ impl Span {
         }
     }
 }
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+syntax_abuse::tests! {
+
+    testcase! {
+        default_position,
+        CharacterPosition::default(),
+        CharacterPosition { row: 0, col: 0 }
+    }
+
+    testcase! {
+        display_position,
+        CharacterPosition { row: 0, col: 4 }.to_string(),
+        "1:5"
+    }
+
+    testcase! {
+        display_default_position,
+        CharacterPosition::default().to_string(),
+        "1:1"
+    }
+
+    testcase! {
+        display_span,
+        Span::new(0, 0, 2, 7).to_string(),
+        "1:3-1:8"
+    }
+
+    testcase! {
+        display_multi_line_span,
+        Span::new(1, 3, 4, 0).to_string(),
+        "2:5-4:1"
+    }
+
+    testcase! {
+        display_span_from_offsets,
+        {
+            let input = "ab\r\ncd\nef".chars().collect::<Vec<_>>();
+            Span {
+                start: CharacterPosition::from_offset(&input, 1),
+                end: CharacterPosition::from_offset(&input, 9),
+            }
+            .to_string()
+        },
+        "1:2-3:3"
+    }
+}
@@ -26,29 +26,236 @@ where
         assert!(!contents.is_empty(), "NonEmptyHashSet must not be empty");
         Self { contents }
     }
+
+    /// The items in either set. Always non-empty as `self` is
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            contents: self.contents.union(&other.contents).cloned().collect(),
+        }
+    }
+
+    /// The items in both sets, `None` if the sets have nothing in common
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Option<Self>
+    where
+        T: Clone,
+    {
+        let contents = self
+            .contents
+            .intersection(&other.contents)
+            .cloned()
+            .collect::<HashSet<_>>();
+        (!contents.is_empty()).then(|| Self { contents })
+    }
+
+    /// Apply `f` to every item. Items that map to the same value are merged
+    /// but at least one item is always left
+    #[must_use]
+    pub fn map<U, F>(&self, f: F) -> NonEmptyHashSet<U>
+    where
+        U: Hash + Eq,
+        F: FnMut(&T) -> U,
+    {
+        NonEmptyHashSet {
+            contents: self.contents.iter().map(f).collect(),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub(crate) enum Uncertain {
+/// A position or length which may only be known approximately
+///
+/// The tree builder uses this to track where a node must end. Once all of the
+/// children before a symbol have been found its start is exact, however its
+/// end depends on how much input the symbols after it consume, which is only
+/// bounded. `Unknown` therefore always carries a bound rather than an exact
+/// value: for positions it is an upper bound (the true position is at most the
+/// value), for lengths it is a lower bound (the true length is at least the
+/// value). Subtracting a lower bound length from a position gives an upper
+/// bound position, so any subtraction involving `Unknown` produces `Unknown`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Uncertain {
+    /// The value is exact
     Known(usize),
+    /// The value is a bound on the true value
     Unknown(usize),
 }
 
+impl Uncertain {
+    /// The contained value, either exact or a bound
+    #[must_use]
+    pub fn value(self) -> usize {
+        match self {
+            Uncertain::Known(n) | Uncertain::Unknown(n) => n,
+        }
+    }
+
+    /// True if the value is exact
+    #[must_use]
+    pub fn is_known(self) -> bool {
+        matches!(self, Uncertain::Known(_))
+    }
+
+    /// True if `position` is consistent with this value when it is treated as
+    /// a position, i.e. equal to a known position or no greater than an
+    /// unknown one
+    #[must_use]
+    pub fn admits(self, position: usize) -> bool {
+        match self {
+            Uncertain::Known(n) => position == n,
+            Uncertain::Unknown(n) => position <= n,
+        }
+    }
+}
+
+/// Subtraction saturates at 0 rather than panicking, an estimate that would
+/// underflow is clamped to the start of the input
 impl Sub for Uncertain {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
         match self {
             Uncertain::Known(a) => match rhs {
-                Uncertain::Known(b) => Uncertain::Known(a - b),
-                Uncertain::Unknown(b) => Uncertain::Unknown(a - b),
+                Uncertain::Known(b) => Uncertain::Known(a.saturating_sub(b)),
+                Uncertain::Unknown(b) => Uncertain::Unknown(a.saturating_sub(b)),
             },
             Uncertain::Unknown(a) => {
                 let b = match rhs {
                     Uncertain::Known(b) | Uncertain::Unknown(b) => b,
                 };
-                Uncertain::Unknown(a - b)
+                Uncertain::Unknown(a.saturating_sub(b))
             }
         }
     }
 }
+
+syntax_abuse::tests! {
+    tests! {
+        non_empty_hash_set:
+
+        testcase! {
+            union,
+            nonempty_hashset!['a', 'b'].union(&nonempty_hashset!['b', 'c']),
+            nonempty_hashset!['a', 'b', 'c']
+        }
+
+        testcase! {
+            union_with_self,
+            nonempty_hashset!['a'].union(&nonempty_hashset!['a']),
+            nonempty_hashset!['a']
+        }
+
+        testcase! {
+            intersection,
+            nonempty_hashset!['a', 'b'].intersection(&nonempty_hashset!['b', 'c']),
+            Some(nonempty_hashset!['b'])
+        }
+
+        testcase! {
+            intersection_empty,
+            nonempty_hashset!['a', 'b'].intersection(&nonempty_hashset!['c', 'd']),
+            None
+        }
+
+        testcase! {
+            map,
+            nonempty_hashset!['a', 'b'].map(char::to_ascii_uppercase),
+            nonempty_hashset!['A', 'B']
+        }
+
+        testcase! {
+            map_merges,
+            nonempty_hashset![1, 2, 3, 4].map(|n| n % 2),
+            nonempty_hashset![0, 1]
+        }
+    }
+
+    tests! {
+        sub:
+
+        testcase! {
+            known_known,
+            Uncertain::Known(5) - Uncertain::Known(3),
+            Uncertain::Known(2)
+        }
+
+        testcase! {
+            known_unknown,
+            Uncertain::Known(5) - Uncertain::Unknown(3),
+            Uncertain::Unknown(2)
+        }
+
+        testcase! {
+            unknown_known,
+            Uncertain::Unknown(5) - Uncertain::Known(3),
+            Uncertain::Unknown(2)
+        }
+
+        testcase! {
+            unknown_unknown,
+            Uncertain::Unknown(5) - Uncertain::Unknown(3),
+            Uncertain::Unknown(2)
+        }
+
+        testcase! {
+            known_underflow,
+            Uncertain::Known(1) - Uncertain::Unknown(3),
+            Uncertain::Unknown(0)
+        }
+
+        testcase! {
+            unknown_underflow,
+            Uncertain::Unknown(1) - Uncertain::Known(3),
+            Uncertain::Unknown(0)
+        }
+    }
+
+    tests! {
+        admits:
+
+        testcase! {
+            known_exact,
+            Uncertain::Known(3).admits(3),
+            true
+        }
+
+        testcase! {
+            known_smaller,
+            Uncertain::Known(3).admits(2),
+            false
+        }
+
+        testcase! {
+            unknown_smaller,
+            Uncertain::Unknown(3).admits(2),
+            true
+        }
+
+        testcase! {
+            unknown_equal,
+            Uncertain::Unknown(3).admits(3),
+            true
+        }
+
+        testcase! {
+            unknown_larger,
+            Uncertain::Unknown(3).admits(4),
+            false
+        }
+    }
+
+    testcase! {
+        value,
+        (Uncertain::Known(1).value(), Uncertain::Unknown(2).value()),
+        (1, 2)
+    }
+
+    testcase! {
+        is_known,
+        (Uncertain::Known(1).is_known(), Uncertain::Unknown(1).is_known()),
+        (true, false)
+    }
+}